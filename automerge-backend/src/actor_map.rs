@@ -1,30 +1,64 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, collections::HashMap};
 
 use automerge_protocol as amp;
+use fxhash::FxBuildHasher;
 use smol_str::SmolStr;
 
 use crate::{
     expanded_op::ExpandedOp,
-    internal::{ActorId, ElementId, InternalOp, Key, ObjectId, OpId},
+    internal::{ActorId, ElementId, InternalOp, Key, KeyId, ObjectId, OpId},
+    memory::{hash_map_size, vec_size},
 };
 
+/// Interns the actor IDs and map keys used in the op set so that internal ops can refer to them
+/// by index.
 #[derive(PartialEq, Debug, Clone, Default)]
-pub(crate) struct ActorMap(Vec<amp::ActorId>);
+pub(crate) struct ActorMap {
+    actors: Vec<amp::ActorId>,
+    keys: Vec<SmolStr>,
+    key_ids: HashMap<SmolStr, KeyId, FxBuildHasher>,
+}
 
 impl ActorMap {
     pub fn import_key(&mut self, key: &amp::Key) -> Key {
         match key {
-            amp::Key::Map(string) => Key::Map(string.clone()),
+            amp::Key::Map(string) => Key::Map(self.intern_key(string)),
             amp::Key::Seq(eid) => Key::Seq(self.import_element_id(eid)),
         }
     }
 
+    fn intern_key(&mut self, key: &SmolStr) -> KeyId {
+        if let Some(id) = self.key_ids.get(key) {
+            return *id;
+        }
+        let id = KeyId(self.keys.len() as u32);
+        self.keys.push(key.clone());
+        self.key_ids.insert(key.clone(), id);
+        id
+    }
+
+    /// The interned map key for `key`, or `None` if no op has used it
+    pub fn lookup_key(&self, key: &str) -> Option<Key> {
+        self.key_ids.get(key).map(|id| Key::Map(*id))
+    }
+
+    /// The heap space used by the interned actors and keys
+    pub fn heap_size(&self) -> usize {
+        let long_keys: usize = self
+            .keys
+            .iter()
+            .filter(|key| key.is_heap_allocated())
+            .map(SmolStr::len)
+            .sum();
+        vec_size(&self.actors) + vec_size(&self.keys) + hash_map_size(&self.key_ids) + long_keys
+    }
+
     pub fn import_actor(&mut self, actor: &amp::ActorId) -> ActorId {
-        if let Some(idx) = self.0.iter().position(|a| a == actor) {
+        if let Some(idx) = self.actors.iter().position(|a| a == actor) {
             ActorId(idx)
         } else {
-            self.0.push(actor.clone());
-            ActorId(self.0.len() - 1)
+            self.actors.push(actor.clone());
+            ActorId(self.actors.len() - 1)
         }
     }
 
@@ -57,8 +91,19 @@ impl ActorMap {
         }
     }
 
+    /// Like `import_obj` but returns `None` rather than adding unknown actors to the map
+    pub fn lookup_obj(&self, obj: &amp::ObjectId) -> Option<ObjectId> {
+        match obj {
+            amp::ObjectId::Root => Some(ObjectId::Root),
+            amp::ObjectId::Id(opid) => {
+                let actor = self.actors.iter().position(|a| a == &opid.1)?;
+                Some(ObjectId::Id(OpId(opid.0, ActorId(actor))))
+            }
+        }
+    }
+
     pub fn export_actor(&self, actor: ActorId) -> amp::ActorId {
-        self.0[actor.0].clone()
+        self.actors[actor.0].clone()
     }
 
     pub fn export_opid(&self, opid: &OpId) -> amp::OpId {
@@ -74,16 +119,16 @@ impl ActorMap {
 
     #[allow(dead_code)]
     pub fn index_of(&mut self, actor: &amp::ActorId) -> usize {
-        if let Some(index) = self.0.iter().position(|a| a == actor) {
+        if let Some(index) = self.actors.iter().position(|a| a == actor) {
             return index;
         }
-        self.0.push(actor.clone());
-        self.0.len() - 1
+        self.actors.push(actor.clone());
+        self.actors.len() - 1
     }
 
     #[allow(dead_code)]
     pub fn actor_for(&self, index: usize) -> Option<&amp::ActorId> {
-        self.0.get(index)
+        self.actors.get(index)
     }
 
     pub fn cmp(&self, eid1: &ElementId, eid2: &ElementId) -> Ordering {
@@ -112,15 +157,15 @@ impl ActorMap {
 
     pub fn key_to_string(&self, key: &Key) -> SmolStr {
         match &key {
-            Key::Map(s) => s.clone(),
+            Key::Map(id) => self.keys[id.0 as usize].clone(),
             Key::Seq(eid) => self.elementid_to_string(eid),
         }
     }
 
     fn cmp_opid(&self, op1: &OpId, op2: &OpId) -> Ordering {
         if op1.0 == op2.0 {
-            let actor1 = &self.0[(op1.1).0];
-            let actor2 = &self.0[(op2.1).0];
+            let actor1 = &self.actors[(op1.1).0];
+            let actor2 = &self.actors[(op2.1).0];
             actor1.cmp(actor2)
             //op1.1.cmp(&op2.1)
         } else {
@@ -1,10 +1,12 @@
+mod blame;
+mod queue;
+mod read;
 mod traversal;
 
 use core::cmp::max;
 use std::{
     collections::{HashMap, HashSet},
     fmt::Debug,
-    sync::{Arc, Mutex},
 };
 
 use amp::ChangeHash;
@@ -15,23 +17,29 @@ use crate::{
     change::encode_document,
     error::AutomergeError,
     event_handlers::{EventHandlerId, EventHandlers},
+    memory::{hash_map_size, vec_size},
     op_handle::OpHandle,
     op_set::OpSet,
     patches::{generate_from_scratch_diff, IncrementalPatch},
-    vector_clock::VectorClock,
-    Change, EventHandler,
+    vector_clock::ClockIndex,
+    Change, CompressionPolicy, EventHandler, MemoryUsage,
 };
 
+pub use blame::{Authorship, Blame, BlameValue};
+use queue::ChangeQueue;
+pub use queue::QueuedChange;
+pub use read::{Prop, Value};
+
 #[derive(Debug, Default, Clone)]
 pub struct Backend {
-    queue: Vec<Change>,
+    queue: ChangeQueue,
     op_set: OpSet,
     states: HashMap<amp::ActorId, Vec<usize>>,
     actors: ActorMap,
     history: Vec<Change>,
     history_index: HashMap<amp::ChangeHash, usize>,
-    /// A cache of vector clocks for speeding up sync operations.
-    clocks_cache: Arc<Mutex<HashMap<amp::ChangeHash, VectorClock>>>,
+    /// The vector clock of each change in `history`, for finding the changes a peer is missing
+    clocks: ClockIndex,
     event_handlers: EventHandlers,
 }
 
@@ -73,6 +81,23 @@ impl Backend {
         })
     }
 
+    /// Estimate how much memory this backend is using, see [`MemoryUsage`]
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage {
+            history: vec_size(&self.history)
+                + self.history.iter().map(Change::heap_size).sum::<usize>()
+                + hash_map_size(&self.history_index)
+                + hash_map_size(&self.states)
+                + self.states.values().map(vec_size).sum::<usize>()
+                + self.clocks.heap_size(),
+            queue: self.queue.heap_size(),
+            interned: self.actors.heap_size(),
+            ..MemoryUsage::default()
+        };
+        self.op_set.add_memory_usage(&mut usage);
+        usage
+    }
+
     pub fn load_changes(&mut self, changes: Vec<Change>) -> Result<(), AutomergeError> {
         self.apply_without_patch(changes)?;
         Ok(())
@@ -201,13 +226,15 @@ impl Backend {
         if local {
             self.apply_change(change, diffs)
         } else {
-            self.queue.push(change);
+            let history_index = &self.history_index;
+            self.queue
+                .push(change, |dep| history_index.contains_key(dep));
             self.apply_queued_ops(diffs)
         }
     }
 
     fn apply_queued_ops(&mut self, diffs: &mut IncrementalPatch) -> Result<(), AutomergeError> {
-        while let Some(next_change) = self.pop_next_causally_ready_change() {
+        while let Some(next_change) = self.queue.pop_ready() {
             self.apply_change(next_change, diffs)?;
         }
         Ok(())
@@ -225,6 +252,7 @@ impl Backend {
         self.event_handlers.before_apply_change(&change);
 
         let change_index = self.update_history(change);
+        self.queue.mark_applied(&self.history[change_index].hash);
 
         // SAFETY: change_index is the index for the change we've just added so this can't (and
         // shouldn't) panic. This is to get around the borrow checker.
@@ -258,28 +286,16 @@ impl Backend {
             .or_default()
             .push(history_index);
 
+        let index = &self.history_index;
+        let deps = change.deps.iter().filter_map(|dep| index.get(dep).copied());
+        self.clocks.push(change.actor_id(), change.seq, deps);
+
         self.history_index.insert(change.hash, history_index);
         self.history.push(change);
 
         history_index
     }
 
-    fn pop_next_causally_ready_change(&mut self) -> Option<Change> {
-        let mut index = 0;
-        while index < self.queue.len() {
-            let change = self.queue.get(index).unwrap();
-            if change
-                .deps
-                .iter()
-                .all(|d| self.history_index.contains_key(d))
-            {
-                return Some(self.queue.swap_remove(index));
-            }
-            index += 1;
-        }
-        None
-    }
-
     pub fn get_patch(&self) -> Result<amp::Patch, AutomergeError> {
         let workshop = self.op_set.patch_workshop(&self.actors);
         let diffs = generate_from_scratch_diff(&workshop);
@@ -302,56 +318,66 @@ impl Backend {
     /// `have_deps` represents the heads of a graph and this function computes the changes that
     /// exist in our graph but not in one with heads `have_deps`.
     pub fn get_changes(&self, have_deps: &[amp::ChangeHash]) -> Vec<&Change> {
-        if let Some(changes) = self.get_changes_fast(have_deps) {
-            changes
+        if have_deps.is_empty() {
+            self.history.iter().collect()
         } else {
             self.get_changes_vector_clock(have_deps)
         }
     }
 
     pub fn save(&self) -> Result<Vec<u8>, AutomergeError> {
+        self.save_with(&CompressionPolicy::default())
+    }
+
+    /// Like [`save`](Self::save) but compresses the columns of the document according to `policy`
+    pub fn save_with(&self, policy: &CompressionPolicy) -> Result<Vec<u8>, AutomergeError> {
         let changes: Vec<amp::Change> = self.history.iter().map(Change::decode).collect();
-        //self.history.iter().map(|change| change.decode()).collect();
-        Ok(encode_document(&changes)?)
+        Ok(encode_document(&changes, policy)?)
     }
 
+    /// Load a document saved with [`save`](Self::save).
+    ///
+    /// The objects in the document are not built until they are first read or changed, so
+    /// loading a document and only looking at part of it does not pay for the rest.
     // allow this for API reasons
     #[allow(clippy::needless_pass_by_value)]
     pub fn load(data: Vec<u8>) -> Result<Self, AutomergeError> {
-        let changes = Change::load_document(&data)?;
-        let mut backend = Self::new();
-        backend.load_changes(changes)?;
-        Ok(backend)
+        Self::load_lazily(&data, true)
     }
 
     pub fn load_without_hash_verification(data: &[u8]) -> Result<Self, AutomergeError> {
-        let changes = Change::load_document_without_hash_verification(data)?;
+        Self::load_lazily(data, false)
+    }
+
+    fn load_lazily(data: &[u8], validate_hashes: bool) -> Result<Self, AutomergeError> {
+        let (document, changes) = Change::load_indexed_document(data, validate_hashes)?;
         let mut backend = Self::new();
+        if let Some(document) = document {
+            for change in document.changes {
+                backend.op_set.update_deps(&change);
+                backend.op_set.max_op = max(backend.op_set.max_op, change.max_op());
+                backend.update_history(change);
+            }
+            backend.op_set.load_document(
+                document.objects,
+                &document.actors,
+                &mut backend.actors,
+            )?;
+        }
         backend.load_changes(changes)?;
         Ok(backend)
     }
 
     pub fn get_missing_deps(&self, heads: &[ChangeHash]) -> Vec<amp::ChangeHash> {
-        let in_queue: HashSet<_> = self.queue.iter().map(|change| change.hash).collect();
-        let mut missing = HashSet::new();
-
-        for head in self.queue.iter().flat_map(|change| &change.deps) {
-            if !self.history_index.contains_key(head) {
-                missing.insert(head);
-            }
-        }
+        let mut missing: HashSet<_> = self.queue.missing_deps().collect();
 
         for head in heads {
-            if !self.history_index.contains_key(head) {
+            if !self.history_index.contains_key(head) && !self.queue.contains(head) {
                 missing.insert(head);
             }
         }
 
-        let mut missing = missing
-            .into_iter()
-            .filter(|hash| !in_queue.contains(hash))
-            .copied()
-            .collect::<Vec<_>>();
+        let mut missing = missing.into_iter().copied().collect::<Vec<_>>();
         missing.sort();
         missing
     }
@@ -0,0 +1,276 @@
+use std::collections::HashMap;
+
+use automerge_protocol as amp;
+use smol_str::SmolStr;
+
+use super::Backend;
+use crate::{
+    concurrent_operations::ConcurrentOperations, error::AutomergeError, internal::ObjectId,
+    op_handle::OpHandle, Change,
+};
+
+/// Which operation, and which change, wrote a particular value in the document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Authorship {
+    /// The operation which set the value
+    pub opid: amp::OpId,
+    /// The hash of the change containing `opid`
+    pub change_hash: amp::ChangeHash,
+    pub actor: amp::ActorId,
+    pub seq: u64,
+    pub time: i64,
+    pub message: Option<String>,
+}
+
+/// A visible value in the document along with the authorship of the operation which wrote it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Blame {
+    pub author: Authorship,
+    pub value: BlameValue,
+}
+
+/// The blamed state of an object or a primitive.
+///
+/// This mirrors the shape of the document, each key of a map or table and each element of a list
+/// or text object is annotated with the [`Authorship`] of the winning operation for that key or
+/// element. Conflicting values which do not win are not included.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlameValue {
+    Map(HashMap<SmolStr, Blame>),
+    Table(HashMap<SmolStr, Blame>),
+    List(Vec<Blame>),
+    Text(Vec<Blame>),
+    /// The members of a set, ordered by [`amp::ScalarValue::set_key`]
+    Set(Vec<Blame>),
+    Primitive(amp::ScalarValue),
+}
+
+impl Backend {
+    /// Annotate every visible value in the document with the operation and change that wrote it.
+    ///
+    /// The returned value is always a `BlameValue::Map` representing the root object.
+    pub fn blame(&self) -> Result<BlameValue, AutomergeError> {
+        self.blame_object(&ObjectId::Root)
+    }
+
+    /// Find the change which contains the operation with the given ID.
+    pub fn get_change_for_opid(&self, opid: &amp::OpId) -> Option<&Change> {
+        let indices = self.states.get(opid.actor())?;
+        // The changes for a single actor have strictly increasing start ops so we can find the
+        // last change which starts before `opid`
+        let position = indices.partition_point(|&i| self.history[i].start_op <= opid.counter());
+        let change = &self.history[*indices.get(position.checked_sub(1)?)?];
+        if opid.counter() <= change.max_op() {
+            Some(change)
+        } else {
+            None
+        }
+    }
+
+    fn blame_object(&self, object_id: &ObjectId) -> Result<BlameValue, AutomergeError> {
+        let object = self.op_set.get_obj(object_id, &self.actors)?;
+        match object.obj_type {
+            amp::ObjType::Map | amp::ObjType::Table => {
+                let mut props = HashMap::new();
+                for (key, ops) in &object.props {
+                    if let Some(op) = self.winning_op(ops) {
+                        props.insert(self.actors.key_to_string(key), self.blame_op(op)?);
+                    }
+                }
+                if object.obj_type == amp::ObjType::Map {
+                    Ok(BlameValue::Map(props))
+                } else {
+                    Ok(BlameValue::Table(props))
+                }
+            }
+            amp::ObjType::List | amp::ObjType::Text => {
+                let mut elems = Vec::with_capacity(object.seq.len);
+                for opid in &object.seq {
+                    if let Some(op) = object
+                        .props
+                        .get(&(*opid).into())
+                        .and_then(|ops| self.winning_op(ops))
+                    {
+                        elems.push(self.blame_op(op)?);
+                    }
+                }
+                if object.obj_type == amp::ObjType::List {
+                    Ok(BlameValue::List(elems))
+                } else {
+                    Ok(BlameValue::Text(elems))
+                }
+            }
+            amp::ObjType::Set => {
+                let mut members = Vec::new();
+                for (key, ops) in &object.props {
+                    if let Some(op) = self.winning_op(ops) {
+                        members.push((self.actors.key_to_string(key), self.blame_op(op)?));
+                    }
+                }
+                members.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+                Ok(BlameValue::Set(
+                    members.into_iter().map(|(_, blame)| blame).collect(),
+                ))
+            }
+        }
+    }
+
+    fn blame_op(&self, op: &OpHandle) -> Result<Blame, AutomergeError> {
+        let opid = self.actors.export_opid(&op.id);
+        let change = self
+            .get_change_for_opid(&opid)
+            .ok_or_else(|| AutomergeError::InvalidOpId(opid.to_string()))?;
+        let value = if let Some(child) = op.child() {
+            self.blame_object(&child)?
+        } else {
+            BlameValue::Primitive(op.adjusted_value())
+        };
+        Ok(Blame {
+            author: Authorship {
+                change_hash: change.hash,
+                actor: change.actor_id().clone(),
+                seq: change.seq,
+                time: change.time,
+                message: change.message(),
+                opid,
+            },
+            value,
+        })
+    }
+
+    /// The frontend resolves conflicts by picking the value with the highest op ID, we do the same
+    pub(super) fn winning_op<'a>(&self, ops: &'a ConcurrentOperations) -> Option<&'a OpHandle> {
+        ops.iter()
+            .max_by(|a, b| self.actors.cmp(&a.id.into(), &b.id.into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use amp::SortedVec;
+    use automerge_protocol::{ActorId, ElementId, ObjectId, Op, OpType};
+    use maplit::hashmap;
+
+    use super::*;
+
+    #[test]
+    fn test_blame_attributes_values_to_changes() {
+        let actor_a: ActorId = "7b7723afd9e6480397a4d467b7693156".try_into().unwrap();
+        let actor_b: ActorId = "37704788917a499cb0206fa8519ac4d9".try_into().unwrap();
+        let change_a1: Change = amp::Change {
+            actor_id: actor_a.clone(),
+            seq: 1,
+            start_op: 1,
+            time: 10,
+            message: Some("make text".into()),
+            hash: None,
+            deps: Vec::new(),
+            operations: vec![
+                Op {
+                    obj: ObjectId::Root,
+                    action: OpType::Make(amp::ObjType::Text),
+                    key: "text".into(),
+                    insert: false,
+                    pred: SortedVec::new(),
+                },
+                Op {
+                    obj: actor_a.op_id_at(1).into(),
+                    action: OpType::Set("a".into()),
+                    key: ElementId::Head.into(),
+                    insert: true,
+                    pred: SortedVec::new(),
+                },
+                Op {
+                    obj: ObjectId::Root,
+                    action: OpType::Set("magpie".into()),
+                    key: "bird".into(),
+                    insert: false,
+                    pred: SortedVec::new(),
+                },
+            ],
+            extra_bytes: Vec::new(),
+        }
+        .try_into()
+        .unwrap();
+        let change_b1: Change = amp::Change {
+            actor_id: actor_b.clone(),
+            seq: 1,
+            start_op: 4,
+            time: 20,
+            message: None,
+            hash: None,
+            deps: vec![change_a1.hash],
+            operations: vec![
+                Op {
+                    obj: actor_a.op_id_at(1).into(),
+                    action: OpType::Set("b".into()),
+                    key: actor_a.op_id_at(2).into(),
+                    insert: true,
+                    pred: SortedVec::new(),
+                },
+                Op {
+                    obj: ObjectId::Root,
+                    action: OpType::Set("dove".into()),
+                    key: "bird".into(),
+                    insert: false,
+                    pred: vec![actor_a.op_id_at(3)].into(),
+                },
+            ],
+            extra_bytes: Vec::new(),
+        }
+        .try_into()
+        .unwrap();
+
+        let mut backend = Backend::new();
+        backend
+            .apply_changes(vec![change_a1.clone(), change_b1.clone()])
+            .unwrap();
+
+        let author_a = |opid: u64| Authorship {
+            opid: actor_a.op_id_at(opid),
+            change_hash: change_a1.hash,
+            actor: actor_a.clone(),
+            seq: 1,
+            time: 10,
+            message: Some("make text".into()),
+        };
+        let author_b = |opid: u64| Authorship {
+            opid: actor_b.op_id_at(opid),
+            change_hash: change_b1.hash,
+            actor: actor_b.clone(),
+            seq: 1,
+            time: 20,
+            message: None,
+        };
+
+        assert_eq!(
+            backend.blame().unwrap(),
+            BlameValue::Map(hashmap! {
+                "text".into() => Blame {
+                    author: author_a(1),
+                    value: BlameValue::Text(vec![
+                        Blame {
+                            author: author_a(2),
+                            value: BlameValue::Primitive("a".into()),
+                        },
+                        Blame {
+                            author: author_b(4),
+                            value: BlameValue::Primitive("b".into()),
+                        },
+                    ]),
+                },
+                "bird".into() => Blame {
+                    author: author_b(5),
+                    value: BlameValue::Primitive("dove".into()),
+                },
+            })
+        );
+        assert_eq!(
+            backend.get_change_for_opid(&actor_a.op_id_at(3)),
+            Some(&change_a1)
+        );
+        assert_eq!(backend.get_change_for_opid(&actor_a.op_id_at(4)), None);
+    }
+}
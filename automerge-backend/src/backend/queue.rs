@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use automerge_protocol as amp;
+
+use super::Backend;
+use crate::{
+    memory::{hash_map_size, hash_set_size},
+    Change,
+};
+
+/// A change which is waiting in the backend's queue for some of its dependencies to be applied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedChange<'a> {
+    pub change: &'a Change,
+    /// The dependencies of `change` which have not been applied yet, these may themselves be in
+    /// the queue
+    pub missing_deps: Vec<amp::ChangeHash>,
+    /// The position of `change` in the order changes were queued in, the first change a backend
+    /// queues is at 0. Changes which have been in the queue longer have lower numbers.
+    pub queued_at: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Waiting {
+    change: Change,
+    missing_deps: HashSet<amp::ChangeHash>,
+    queued_at: u64,
+}
+
+/// Changes which have been received but not applied yet.
+///
+/// Each waiting change is indexed by the dependencies it is missing, so when a change is applied
+/// the changes it makes ready can be found without scanning the whole queue.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ChangeQueue {
+    waiting: HashMap<amp::ChangeHash, Waiting>,
+    /// For each missing dependency, the waiting changes which depend on it
+    dependents: HashMap<amp::ChangeHash, Vec<amp::ChangeHash>>,
+    /// Waiting changes in the order they were queued in
+    by_age: BTreeMap<u64, amp::ChangeHash>,
+    /// Changes whose dependencies have all been applied
+    ready: VecDeque<Change>,
+    next_queued_at: u64,
+    max_len: Option<usize>,
+}
+
+impl ChangeQueue {
+    pub(crate) fn len(&self) -> usize {
+        self.waiting.len() + self.ready.len()
+    }
+
+    /// Add `change` to the queue, `is_applied` says whether a dependency has been applied.
+    ///
+    /// If this takes the queue past its maximum length the oldest waiting changes are dropped.
+    pub(crate) fn push<F>(&mut self, change: Change, is_applied: F)
+    where
+        F: Fn(&amp::ChangeHash) -> bool,
+    {
+        if self.waiting.contains_key(&change.hash) {
+            return;
+        }
+        let missing_deps: HashSet<_> = change
+            .deps
+            .iter()
+            .filter(|dep| !is_applied(dep))
+            .copied()
+            .collect();
+        if missing_deps.is_empty() {
+            self.ready.push_back(change);
+            return;
+        }
+        for dep in &missing_deps {
+            self.dependents.entry(*dep).or_default().push(change.hash);
+        }
+        let queued_at = self.next_queued_at;
+        self.next_queued_at += 1;
+        self.by_age.insert(queued_at, change.hash);
+        self.waiting.insert(
+            change.hash,
+            Waiting {
+                change,
+                missing_deps,
+                queued_at,
+            },
+        );
+        self.enforce_max_len();
+    }
+
+    pub(crate) fn contains(&self, hash: &amp::ChangeHash) -> bool {
+        self.waiting.contains_key(hash) || self.ready.iter().any(|c| &c.hash == hash)
+    }
+
+    /// The next change which is ready to be applied
+    pub(crate) fn pop_ready(&mut self) -> Option<Change> {
+        self.ready.pop_front()
+    }
+
+    /// Record that `hash` has been applied, making ready any changes which were only waiting for
+    /// it
+    pub(crate) fn mark_applied(&mut self, hash: &amp::ChangeHash) {
+        self.remove(hash);
+        for dependent in self.dependents.remove(hash).unwrap_or_default() {
+            if let Some(waiting) = self.waiting.get_mut(&dependent) {
+                waiting.missing_deps.remove(hash);
+                if waiting.missing_deps.is_empty() {
+                    let waiting = self.waiting.remove(&dependent).unwrap();
+                    self.by_age.remove(&waiting.queued_at);
+                    self.ready.push_back(waiting.change);
+                }
+            }
+        }
+    }
+
+    /// The hashes which some waiting change depends on but which are neither applied nor queued
+    pub(crate) fn missing_deps(&self) -> impl Iterator<Item = &amp::ChangeHash> {
+        self.dependents
+            .keys()
+            .filter(move |hash| !self.waiting.contains_key(hash))
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = QueuedChange<'_>> {
+        self.by_age.values().map(move |hash| {
+            let waiting = &self.waiting[hash];
+            let mut missing_deps: Vec<_> = waiting.missing_deps.iter().copied().collect();
+            missing_deps.sort_unstable();
+            QueuedChange {
+                change: &waiting.change,
+                missing_deps,
+                queued_at: waiting.queued_at,
+            }
+        })
+    }
+
+    pub(crate) fn remove(&mut self, hash: &amp::ChangeHash) -> Option<Change> {
+        let waiting = self.waiting.remove(hash)?;
+        self.by_age.remove(&waiting.queued_at);
+        for dep in &waiting.missing_deps {
+            if let Some(dependents) = self.dependents.get_mut(dep) {
+                dependents.retain(|dependent| dependent != hash);
+                if dependents.is_empty() {
+                    self.dependents.remove(dep);
+                }
+            }
+        }
+        Some(waiting.change)
+    }
+
+    pub(crate) fn remove_queued_before(&mut self, queued_at: u64) -> Vec<Change> {
+        let old: Vec<_> = self
+            .by_age
+            .range(..queued_at)
+            .map(|(_, hash)| *hash)
+            .collect();
+        old.iter().filter_map(|hash| self.remove(hash)).collect()
+    }
+
+    pub(crate) fn set_max_len(&mut self, max_len: Option<usize>) {
+        self.max_len = max_len;
+        self.enforce_max_len();
+    }
+
+    fn enforce_max_len(&mut self) {
+        if let Some(max_len) = self.max_len {
+            while self.waiting.len() > max_len {
+                let (_, oldest) = self.by_age.iter().next().unwrap();
+                let oldest = *oldest;
+                self.remove(&oldest);
+            }
+        }
+    }
+
+    pub(crate) fn heap_size(&self) -> usize {
+        hash_map_size(&self.waiting)
+            + self
+                .waiting
+                .values()
+                .map(|w| w.change.heap_size() + hash_set_size(&w.missing_deps))
+                .sum::<usize>()
+            + hash_map_size(&self.dependents)
+            + self
+                .dependents
+                .values()
+                .map(|d| d.capacity() * std::mem::size_of::<amp::ChangeHash>())
+                .sum::<usize>()
+            + self.by_age.len()
+                * (std::mem::size_of::<u64>() + std::mem::size_of::<amp::ChangeHash>())
+            + self.ready.capacity() * std::mem::size_of::<Change>()
+            + self.ready.iter().map(Change::heap_size).sum::<usize>()
+    }
+}
+
+impl Backend {
+    /// The changes which are waiting for their dependencies to be applied, oldest first
+    pub fn queued_changes(&self) -> Vec<QueuedChange<'_>> {
+        self.queue.iter().collect()
+    }
+
+    /// Remove the change with `hash` from the queue of changes waiting for their dependencies,
+    /// returning it if it was queued.
+    ///
+    /// Changes which depend on the removed change stay in the queue.
+    pub fn evict_queued_change(&mut self, hash: &amp::ChangeHash) -> Option<Change> {
+        self.queue.remove(hash)
+    }
+
+    /// Remove the changes which were queued before `queued_at`, see [`QueuedChange::queued_at`]
+    pub fn evict_queued_changes_before(&mut self, queued_at: u64) -> Vec<Change> {
+        self.queue.remove_queued_before(queued_at)
+    }
+
+    /// Limit the number of changes which can wait for their dependencies, when a new change
+    /// would take the queue past `max_len` the oldest queued changes are dropped. `None` removes
+    /// the limit, which is the default.
+    pub fn set_max_queue_len(&mut self, max_len: Option<usize>) {
+        self.queue.set_max_len(max_len);
+    }
+}
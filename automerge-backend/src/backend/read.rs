@@ -0,0 +1,404 @@
+use std::{collections::HashMap, fmt};
+
+use automerge_protocol as amp;
+use smol_str::SmolStr;
+
+use super::Backend;
+use crate::{
+    concurrent_operations::ConcurrentOperations, error::AutomergeError, internal::ObjectId,
+    object_store::ObjState, op_handle::OpHandle,
+};
+
+/// A property of an object, either a key in a map or table or an index in a list or text object.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Prop {
+    Key(SmolStr),
+    Index(usize),
+}
+
+impl fmt::Display for Prop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Prop::Key(key) => write!(f, "key {key:?}"),
+            Prop::Index(index) => write!(f, "index {index}"),
+        }
+    }
+}
+
+impl From<&str> for Prop {
+    fn from(key: &str) -> Self {
+        Prop::Key(key.into())
+    }
+}
+
+impl From<String> for Prop {
+    fn from(key: String) -> Self {
+        Prop::Key(key.into())
+    }
+}
+
+impl From<SmolStr> for Prop {
+    fn from(key: SmolStr) -> Self {
+        Prop::Key(key)
+    }
+}
+
+impl From<usize> for Prop {
+    fn from(index: usize) -> Self {
+        Prop::Index(index)
+    }
+}
+
+/// A value read directly from the backend.
+///
+/// Unlike the frontend's values, objects are not expanded. They are returned by ID and can be read
+/// with further calls to [`Backend::get`] and friends.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Object {
+        id: amp::ObjectId,
+        obj_type: amp::ObjType,
+    },
+    Primitive(amp::ScalarValue),
+}
+
+impl Backend {
+    /// The value of `prop` in the object `object_id`, if there are conflicting values this is
+    /// the one the frontend would pick.
+    pub fn get<P: Into<Prop>>(
+        &self,
+        object_id: &amp::ObjectId,
+        prop: P,
+    ) -> Result<Option<Value>, AutomergeError> {
+        let object_id = self.internal_object_id(object_id)?;
+        let object = self.op_set.get_obj(&object_id, &self.actors)?;
+        Ok(self
+            .ops_for_prop(&object_id, object, prop.into())?
+            .and_then(|ops| self.winning_op(ops))
+            .map(|op| self.read_value(op)))
+    }
+
+    /// All of the conflicting values of `prop` in the object `object_id`, keyed by the ID of the
+    /// operation which set them. This is empty if there is no value.
+    pub fn get_conflicts<P: Into<Prop>>(
+        &self,
+        object_id: &amp::ObjectId,
+        prop: P,
+    ) -> Result<HashMap<amp::OpId, Value>, AutomergeError> {
+        let object_id = self.internal_object_id(object_id)?;
+        let object = self.op_set.get_obj(&object_id, &self.actors)?;
+        Ok(self
+            .ops_for_prop(&object_id, object, prop.into())?
+            .into_iter()
+            .flat_map(|ops| ops.iter())
+            .map(|op| (self.actors.export_opid(&op.id), self.read_value(op)))
+            .collect())
+    }
+
+    /// The value at `path` from the root of the document, or `None` if there is nothing there.
+    pub fn get_at_path(&self, path: &[Prop]) -> Result<Option<Value>, AutomergeError> {
+        match path.split_last() {
+            None => Ok(Some(Value::Object {
+                id: amp::ObjectId::Root,
+                obj_type: amp::ObjType::Map,
+            })),
+            Some((prop, parent)) => match self.get_object_at_path(parent)? {
+                Some(object_id) => self.get(&object_id, prop.clone()),
+                None => Ok(None),
+            },
+        }
+    }
+
+    /// The conflicting values at `path` from the root of the document, see
+    /// [`Backend::get_conflicts`]
+    pub fn get_conflicts_at_path(
+        &self,
+        path: &[Prop],
+    ) -> Result<HashMap<amp::OpId, Value>, AutomergeError> {
+        match path.split_last() {
+            None => Ok(HashMap::new()),
+            Some((prop, parent)) => match self.get_object_at_path(parent)? {
+                Some(object_id) => self.get_conflicts(&object_id, prop.clone()),
+                None => Ok(HashMap::new()),
+            },
+        }
+    }
+
+    /// The number of elements in a list or text object, or the number of keys in a map or table
+    pub fn length(&self, object_id: &amp::ObjectId) -> Result<usize, AutomergeError> {
+        let object_id = self.internal_object_id(object_id)?;
+        Ok(self.op_set.get_obj(&object_id, &self.actors)?.len())
+    }
+
+    /// The keys of a map or table in sorted order. This is empty for lists and text.
+    pub fn keys(
+        &self,
+        object_id: &amp::ObjectId,
+    ) -> Result<impl Iterator<Item = SmolStr>, AutomergeError> {
+        let object_id = self.internal_object_id(object_id)?;
+        let object = self.op_set.get_obj(&object_id, &self.actors)?;
+        let mut keys: Vec<SmolStr> = if object.is_seq() {
+            Vec::new()
+        } else {
+            object
+                .visible_keys()
+                .map(|key| self.actors.key_to_string(key))
+                .collect()
+        };
+        keys.sort_unstable();
+        Ok(keys.into_iter())
+    }
+
+    /// The contents of a text object as a string. Elements which are not strings are skipped.
+    pub fn text(&self, object_id: &amp::ObjectId) -> Result<String, AutomergeError> {
+        let internal_id = self.internal_object_id(object_id)?;
+        let object = self.op_set.get_obj(&internal_id, &self.actors)?;
+        if object.obj_type != amp::ObjType::Text {
+            return Err(AutomergeError::NotText(object_id.clone()));
+        }
+        let mut text = String::new();
+        for opid in &object.seq {
+            if let Some(op) = object
+                .props
+                .get(&(*opid).into())
+                .and_then(|ops| self.winning_op(ops))
+            {
+                if let amp::ScalarValue::Str(s) = op.adjusted_value() {
+                    text.push_str(&s);
+                }
+            }
+        }
+        Ok(text)
+    }
+
+    fn get_object_at_path(&self, path: &[Prop]) -> Result<Option<amp::ObjectId>, AutomergeError> {
+        let mut object_id = amp::ObjectId::Root;
+        for prop in path {
+            match self.get(&object_id, prop.clone())? {
+                Some(Value::Object { id, .. }) => object_id = id,
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(object_id))
+    }
+
+    fn internal_object_id(&self, object_id: &amp::ObjectId) -> Result<ObjectId, AutomergeError> {
+        self.actors
+            .lookup_obj(object_id)
+            .ok_or(AutomergeError::MissingObjectError)
+    }
+
+    fn ops_for_prop<'a>(
+        &self,
+        object_id: &ObjectId,
+        object: &'a ObjState,
+        prop: Prop,
+    ) -> Result<Option<&'a ConcurrentOperations>, AutomergeError> {
+        let key = match (object.is_seq(), prop) {
+            (false, Prop::Key(key)) => match self.actors.lookup_key(&key) {
+                Some(key) => key,
+                None => return Ok(None),
+            },
+            (true, Prop::Index(index)) => match object.key_at(index) {
+                Some(key) => key,
+                None => return Ok(None),
+            },
+            (_, prop) => {
+                return Err(AutomergeError::InvalidProp {
+                    object_id: self.actors.export_obj(object_id),
+                    obj_type: object.obj_type,
+                    prop,
+                })
+            }
+        };
+        Ok(object.props.get(&key))
+    }
+
+    fn read_value(&self, op: &OpHandle) -> Value {
+        match (op.child(), op.obj_type()) {
+            (Some(child), Some(obj_type)) => Value::Object {
+                id: self.actors.export_obj(&child),
+                obj_type,
+            },
+            _ => Value::Primitive(op.adjusted_value()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryInto;
+
+    use amp::SortedVec;
+    use automerge_protocol::{ActorId, ElementId, ObjectId, Op, OpType};
+    use maplit::hashmap;
+
+    use super::*;
+    use crate::Change;
+
+    #[test]
+    fn test_read_values_without_a_frontend() {
+        let actor_a: ActorId = "7b7723afd9e6480397a4d467b7693156".try_into().unwrap();
+        let actor_b: ActorId = "37704788917a499cb0206fa8519ac4d9".try_into().unwrap();
+        let text_id: ObjectId = actor_a.op_id_at(1).into();
+        let list_id: ObjectId = actor_a.op_id_at(4).into();
+        let change_a1: Change = amp::Change {
+            actor_id: actor_a.clone(),
+            seq: 1,
+            start_op: 1,
+            time: 0,
+            message: None,
+            hash: None,
+            deps: Vec::new(),
+            operations: vec![
+                Op {
+                    obj: ObjectId::Root,
+                    action: OpType::Make(amp::ObjType::Text),
+                    key: "text".into(),
+                    insert: false,
+                    pred: SortedVec::new(),
+                },
+                Op {
+                    obj: text_id.clone(),
+                    action: OpType::Set("h".into()),
+                    key: ElementId::Head.into(),
+                    insert: true,
+                    pred: SortedVec::new(),
+                },
+                Op {
+                    obj: text_id.clone(),
+                    action: OpType::Set("i".into()),
+                    key: actor_a.op_id_at(2).into(),
+                    insert: true,
+                    pred: SortedVec::new(),
+                },
+                Op {
+                    obj: ObjectId::Root,
+                    action: OpType::Make(amp::ObjType::List),
+                    key: "birds".into(),
+                    insert: false,
+                    pred: SortedVec::new(),
+                },
+                Op {
+                    obj: list_id.clone(),
+                    action: OpType::Set("wren".into()),
+                    key: ElementId::Head.into(),
+                    insert: true,
+                    pred: SortedVec::new(),
+                },
+                Op {
+                    obj: list_id.clone(),
+                    action: OpType::Set(amp::ScalarValue::Counter(1)),
+                    key: actor_a.op_id_at(5).into(),
+                    insert: true,
+                    pred: SortedVec::new(),
+                },
+                Op {
+                    obj: ObjectId::Root,
+                    action: OpType::Set("magpie".into()),
+                    key: "bird".into(),
+                    insert: false,
+                    pred: SortedVec::new(),
+                },
+            ],
+            extra_bytes: Vec::new(),
+        }
+        .try_into()
+        .unwrap();
+        let change_b1: Change = amp::Change {
+            actor_id: actor_b.clone(),
+            seq: 1,
+            start_op: 1,
+            time: 0,
+            message: None,
+            hash: None,
+            deps: Vec::new(),
+            operations: vec![Op {
+                obj: ObjectId::Root,
+                action: OpType::Set("dove".into()),
+                key: "bird".into(),
+                insert: false,
+                pred: SortedVec::new(),
+            }],
+            extra_bytes: Vec::new(),
+        }
+        .try_into()
+        .unwrap();
+        let change_a2: Change = amp::Change {
+            actor_id: actor_a.clone(),
+            seq: 2,
+            start_op: 8,
+            time: 0,
+            message: None,
+            hash: None,
+            deps: vec![change_a1.hash],
+            operations: vec![Op {
+                obj: list_id.clone(),
+                action: OpType::Inc(2),
+                key: actor_a.op_id_at(6).into(),
+                insert: false,
+                pred: vec![actor_a.op_id_at(6)].into(),
+            }],
+            extra_bytes: Vec::new(),
+        }
+        .try_into()
+        .unwrap();
+
+        let mut backend = Backend::new();
+        backend
+            .apply_changes(vec![change_a1, change_b1, change_a2])
+            .unwrap();
+
+        assert_eq!(
+            backend.keys(&ObjectId::Root).unwrap().collect::<Vec<_>>(),
+            vec!["bird", "birds", "text"]
+        );
+        assert_eq!(backend.length(&ObjectId::Root).unwrap(), 3);
+        assert_eq!(
+            backend.get(&ObjectId::Root, "bird").unwrap(),
+            Some(Value::Primitive("magpie".into()))
+        );
+        assert_eq!(
+            backend.get_conflicts(&ObjectId::Root, "bird").unwrap(),
+            hashmap! {
+                actor_a.op_id_at(7) => Value::Primitive("magpie".into()),
+                actor_b.op_id_at(1) => Value::Primitive("dove".into()),
+            }
+        );
+        assert_eq!(backend.get(&ObjectId::Root, "owl").unwrap(), None);
+
+        assert_eq!(backend.text(&text_id).unwrap(), "hi");
+        assert!(matches!(
+            backend.text(&list_id),
+            Err(AutomergeError::NotText(_))
+        ));
+
+        assert_eq!(backend.length(&list_id).unwrap(), 2);
+        assert_eq!(
+            backend.get_at_path(&["birds".into(), 1.into()]).unwrap(),
+            Some(Value::Primitive(amp::ScalarValue::Counter(3)))
+        );
+        assert_eq!(backend.get(&list_id, 2).unwrap(), None);
+        assert_eq!(
+            backend.get_at_path(&["birds".into()]).unwrap(),
+            Some(Value::Object {
+                id: list_id.clone(),
+                obj_type: amp::ObjType::List
+            })
+        );
+        assert_eq!(
+            backend
+                .get_conflicts_at_path(&["bird".into(), "x".into()])
+                .unwrap(),
+            HashMap::new()
+        );
+        assert!(matches!(
+            backend.get(&list_id, "wren"),
+            Err(AutomergeError::InvalidProp { .. })
+        ));
+        assert!(matches!(
+            backend.get(&actor_b.op_id_at(9).into(), "x"),
+            Err(AutomergeError::MissingObjectError)
+        ));
+    }
+}
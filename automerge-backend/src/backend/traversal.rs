@@ -1,4 +1,4 @@
-use std::collections::{HashSet, VecDeque};
+use std::collections::HashSet;
 
 use amp::ChangeHash;
 use automerge_protocol as amp;
@@ -7,52 +7,11 @@ use super::Backend;
 use crate::{vector_clock::VectorClock, Change};
 
 impl Backend {
-    /// Get the list of changes not covered by `have_deps`.
-    ///
-    /// This _fast_ variant makes use of the topological sort of the graph to handle cases where
-    /// there are either no concurrent changes since the `have_deps` or all of the concurrent
-    /// changes are to the right of them in the sort.
-    ///
-    /// This strategy avoids us having to do lots of work when we may be playing catchup (no
-    /// changes ourelves but just streaming them from someone else).
-    pub(super) fn get_changes_fast(&self, have_deps: &[amp::ChangeHash]) -> Option<Vec<&Change>> {
-        if have_deps.is_empty() {
-            return Some(self.history.iter().collect());
-        }
-
-        let lowest_idx = have_deps
-            .iter()
-            .filter_map(|h| self.history_index.get(h))
-            .min()?
-            + 1;
-
-        let mut missing_changes = vec![];
-        let mut has_seen: HashSet<_> = have_deps.iter().collect();
-        for change in &self.history[lowest_idx..] {
-            let deps_seen = change.deps.iter().filter(|h| has_seen.contains(h)).count();
-            if deps_seen > 0 {
-                if deps_seen != change.deps.len() {
-                    // future change depends on something we haven't seen - fast path cant work
-                    return None;
-                }
-                missing_changes.push(change);
-                has_seen.insert(&change.hash);
-            }
-        }
-
-        // if we get to the end and there is a head we haven't seen then fast path cant work
-        if self.get_heads().iter().all(|h| has_seen.contains(h)) {
-            Some(missing_changes)
-        } else {
-            None
-        }
-    }
-
     /// Get the list of changes that are not transitive dependencies of `heads` using a vector
     /// clock.
     ///
-    /// This aims to be more predictable than versions that operate on the hash graph which
-    /// sometimes need to do big graph traversals.
+    /// The clock of each change is kept in `clocks`, so this takes time proportional to the
+    /// number of actors and the number of changes returned rather than the size of the history.
     pub(super) fn get_changes_vector_clock(&self, heads: &[amp::ChangeHash]) -> Vec<&Change> {
         // get the vector clock representing the state of the graph with the given heads
         //
@@ -79,68 +38,14 @@ impl Backend {
     }
 
     /// Get the vector clock for the state of the graph with these heads.
+    ///
+    /// Heads which are not in our graph do not contribute to the clock.
     pub(super) fn get_vector_clock_at(&self, heads: &[amp::ChangeHash]) -> VectorClock {
-        let mut clock = VectorClock::default();
-
-        // get the clock for each head individually and combine them
-        for hash in heads {
-            let found_clock = self.get_vector_clock_for_hash(hash);
-            clock += found_clock;
-        }
-
-        clock
-    }
-
-    /// Get the vector clock for the given hash.
-    pub(super) fn get_vector_clock_for_hash(&self, hash: &amp::ChangeHash) -> VectorClock {
-        // we need to do a BFS through the hash graph to ensure we get nodes at the highest seq for
-        // each actor id
-        let mut queue = VecDeque::new();
-        queue.push_back(hash);
-
-        let mut has_seen = HashSet::new();
-
-        let mut clock = VectorClock::default();
-
-        // we'll be needing this for the duration so just lock it once
-        let mut clocks_cache = self.clocks_cache.lock().unwrap();
-
-        while let Some(hash) = queue.pop_front() {
-            // since the graph is immutable the vector clock will not be changing so we can use the
-            // cached clock of a hash to skip having to traverse its dependencies
-            if let Some(cached_clock) = clocks_cache.get(hash) {
-                clock += cached_clock;
-                // don't add the changes dependencies to the queue as we already have the clock
-                // contribution from this subgraph
-                continue;
-            } else if has_seen.contains(&hash) {
-                continue;
-            }
-
-            if let Some(change) = self
-                .history_index
-                .get(hash)
-                .and_then(|i| self.history.get(*i))
-            {
-                queue.extend(change.deps.iter());
-                clock.update(change.actor_id(), change.seq);
-            }
-
-            if clock.len() == self.states.len() {
-                // we've found all the actors so can stop
-                //
-                // This prevents us from going down all the way to the root of the graph
-                // unnecessarily when we have clock entries for everyone
-                break;
-            }
-
-            has_seen.insert(hash);
-        }
-
-        // cache the clock for this hash
-        clocks_cache.insert(*hash, clock.clone());
-
-        clock
+        self.clocks.clock_at(
+            heads
+                .iter()
+                .filter_map(|hash| self.history_index.get(hash).copied()),
+        )
     }
 
     /// Filter the changes down to those that are not transitive dependencies of the heads.
@@ -197,7 +102,7 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_get_changes_fast_behavior() {
+    fn test_get_changes_behavior() {
         let actor_a: ActorId = "7b7723afd9e6480397a4d467b7693156".try_into().unwrap();
         let actor_b: ActorId = "37704788917a499cb0206fa8519ac4d9".try_into().unwrap();
         let change_a1: Change = amp::Change {
@@ -301,14 +206,8 @@ mod tests {
             .apply_changes(vec![change_a1.clone(), change_a2.clone()])
             .unwrap();
 
-        assert_eq!(
-            backend.get_changes_fast(&[]),
-            Some(vec![&change_a1, &change_a2])
-        );
-        assert_eq!(
-            backend.get_changes_fast(&[change_a1.hash]),
-            Some(vec![&change_a2])
-        );
+        assert_eq!(backend.get_changes(&[]), vec![&change_a1, &change_a2]);
+        assert_eq!(backend.get_changes(&[change_a1.hash]), vec![&change_a2]);
         assert_eq!(backend.get_heads(), vec![change_a2.hash]);
 
         backend
@@ -316,18 +215,24 @@ mod tests {
             .unwrap();
 
         assert_eq!(
-            backend.get_changes_fast(&[]),
-            Some(vec![&change_a1, &change_a2, &change_b1, &change_b2])
+            backend.get_changes(&[]),
+            vec![&change_a1, &change_a2, &change_b1, &change_b2]
         );
-        assert_eq!(backend.get_changes_fast(&[change_a1.hash]), None);
-        assert_eq!(backend.get_changes_fast(&[change_a2.hash]), None);
         assert_eq!(
-            backend.get_changes_fast(&[change_a1.hash, change_b1.hash]),
-            Some(vec![&change_a2, &change_b2])
+            backend.get_changes(&[change_a1.hash]),
+            vec![&change_a2, &change_b1, &change_b2]
         );
         assert_eq!(
-            backend.get_changes_fast(&[change_a2.hash, change_b1.hash]),
-            Some(vec![&change_b2])
+            backend.get_changes(&[change_a2.hash]),
+            vec![&change_b1, &change_b2]
+        );
+        assert_eq!(
+            backend.get_changes(&[change_a1.hash, change_b1.hash]),
+            vec![&change_a2, &change_b2]
+        );
+        assert_eq!(
+            backend.get_changes(&[change_a2.hash, change_b1.hash]),
+            vec![&change_b2]
         );
         assert_eq!(backend.get_heads(), vec![change_b2.hash, change_a2.hash]);
 
@@ -335,23 +240,33 @@ mod tests {
 
         assert_eq!(backend.get_heads(), vec![change_b3.hash]);
         assert_eq!(
-            backend.get_changes_fast(&[]),
-            Some(vec![
-                &change_a1, &change_a2, &change_b1, &change_b2, &change_b3
-            ])
+            backend.get_changes(&[]),
+            vec![&change_a1, &change_a2, &change_b1, &change_b2, &change_b3]
+        );
+        assert_eq!(
+            backend.get_changes(&[change_a1.hash]),
+            vec![&change_a2, &change_b1, &change_b2, &change_b3]
+        );
+        assert_eq!(
+            backend.get_changes(&[change_a2.hash]),
+            vec![&change_b1, &change_b2, &change_b3]
+        );
+        assert_eq!(
+            backend.get_changes(&[change_b1.hash]),
+            vec![&change_a1, &change_a2, &change_b2, &change_b3]
+        );
+        assert_eq!(
+            backend.get_changes(&[change_b2.hash]),
+            vec![&change_a1, &change_a2, &change_b3]
         );
-        assert_eq!(backend.get_changes_fast(&[change_a1.hash]), None);
-        assert_eq!(backend.get_changes_fast(&[change_a2.hash]), None);
-        assert_eq!(backend.get_changes_fast(&[change_b1.hash]), None);
-        assert_eq!(backend.get_changes_fast(&[change_b2.hash]), None);
         assert_eq!(
-            backend.get_changes_fast(&[change_a1.hash, change_b1.hash]),
-            Some(vec![&change_a2, &change_b2, &change_b3])
+            backend.get_changes(&[change_a1.hash, change_b1.hash]),
+            vec![&change_a2, &change_b2, &change_b3]
         );
         assert_eq!(
-            backend.get_changes_fast(&[change_a2.hash, change_b1.hash]),
-            Some(vec![&change_b2, &change_b3])
+            backend.get_changes(&[change_a2.hash, change_b1.hash]),
+            vec![&change_b2, &change_b3]
         );
-        assert_eq!(backend.get_changes_fast(&[change_b3.hash]), Some(vec![]));
+        assert!(backend.get_changes(&[change_b3.hash]).is_empty());
     }
 }
@@ -4,40 +4,44 @@ use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
     convert::{TryFrom, TryInto},
-    io::{Read, Write},
+    io::Write,
     ops::Range,
     str,
 };
 
 use amp::{OpType, SortedVec};
 use automerge_protocol as amp;
-use flate2::{
-    bufread::{DeflateDecoder, DeflateEncoder},
-    Compression,
-};
 use itertools::Itertools;
 use nonzero_ext::nonzero;
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use sha2::{Digest, Sha256};
 use tracing::instrument;
 
 use crate::{
     columnar::{
         ChangeEncoder, ChangeIterator, ColumnEncoder, DepsIterator, DocChange, DocOp, DocOpEncoder,
-        DocOpIterator, OperationIterator, COLUMN_TYPE_DEFLATE,
+        DocOpIterator, ObjectIndex, OperationIterator, COLUMN_TYPE_DEFLATE,
     },
+    compression::{decompress_zstd, inflate, CompressionPolicy},
     decoding,
     decoding::{Decodable, InvalidChangeError},
     encoding,
-    encoding::{Encodable, DEFLATE_MIN_SIZE},
+    encoding::Encodable,
     error::AutomergeError,
     expanded_op::ExpandedOpIterator,
     internal::InternalOpType,
+    memory::{hash_map_size, vec_size},
 };
 
 const HASH_BYTES: usize = 32;
 const BLOCK_TYPE_DOC: u8 = 0;
 const BLOCK_TYPE_CHANGE: u8 = 1;
 const BLOCK_TYPE_DEFLATE: u8 = 2;
+/// A change chunk compressed with zstd
+const BLOCK_TYPE_ZSTD: u8 = 3;
+/// A document chunk whose compressed columns are compressed with zstd rather than DEFLATE
+const BLOCK_TYPE_DOC_ZSTD: u8 = 4;
 const CHUNK_START: usize = 8;
 const HASH_RANGE: Range<usize> = 4..8;
 
@@ -54,10 +58,14 @@ impl From<&amp::Change> for Change {
 }
 
 fn encode(change: &amp::Change) -> Change {
+    encode_with_ops(change, EncodedOps::new(change))
+}
+
+fn encode_with_ops(change: &amp::Change, ops: EncodedOps) -> Change {
     let mut deps = change.deps.clone();
     deps.sort_unstable();
 
-    let mut chunk = encode_chunk(change, &deps);
+    let mut chunk = encode_chunk(change, &deps, ops);
 
     let mut bytes = Vec::with_capacity(MAGIC_BYTES.len() + 4 + chunk.bytes.len());
 
@@ -116,7 +124,33 @@ struct ChunkIntermediate {
     extra_bytes: Range<usize>,
 }
 
-fn encode_chunk(change: &amp::Change, deps: &[amp::ChangeHash]) -> ChunkIntermediate {
+/// The op columns of a change. Unlike the rest of the change they don't depend on the hashes of
+/// its dependencies, so they can be encoded before those are known.
+struct EncodedOps {
+    actors: Vec<amp::ActorId>,
+    bytes: Vec<u8>,
+    columns: HashMap<u32, Range<usize>>,
+}
+
+impl EncodedOps {
+    fn new(change: &amp::Change) -> Self {
+        let actors = actor_ids_in_change(change);
+        let expanded_ops =
+            ExpandedOpIterator::new(&change.operations, change.start_op, change.actor_id.clone());
+        let (bytes, columns) = ColumnEncoder::encode_ops(expanded_ops, &actors);
+        Self {
+            actors,
+            bytes,
+            columns,
+        }
+    }
+}
+
+fn encode_chunk(
+    change: &amp::Change,
+    deps: &[amp::ChangeHash],
+    encoded_ops: EncodedOps,
+) -> ChunkIntermediate {
     let mut bytes = Vec::new();
 
     // All these unwraps are okay because we're writing to an in memory buffer so io erros should
@@ -131,7 +165,11 @@ fn encode_chunk(change: &amp::Change, deps: &[amp::ChangeHash]) -> ChunkIntermed
     // encode first actor
     change.actor_id.to_bytes().encode(&mut bytes).unwrap();
 
-    let actors = actor_ids_in_change(change);
+    let EncodedOps {
+        actors,
+        bytes: ops_buf,
+        columns: mut ops,
+    } = encoded_ops;
 
     // encode seq, start_op, time, message
     change.seq.encode(&mut bytes).unwrap();
@@ -141,12 +179,6 @@ fn encode_chunk(change: &amp::Change, deps: &[amp::ChangeHash]) -> ChunkIntermed
     change.message.encode(&mut bytes).unwrap();
     let message = message..bytes.len();
 
-    let expanded_ops =
-        ExpandedOpIterator::new(&change.operations, change.start_op, change.actor_id.clone());
-
-    // encode ops into a side buffer - collect all other actors
-    let (ops_buf, mut ops) = ColumnEncoder::encode_ops(expanded_ops, &actors);
-
     // encode all other actors
     actors[1..].encode(&mut bytes).unwrap();
 
@@ -230,20 +262,20 @@ impl ChangeBytes {
         }
     }
 
-    fn compress(&mut self, body_start: usize) {
+    fn compress(&mut self, body_start: usize, policy: &CompressionPolicy) {
         match self {
             ChangeBytes::Compressed { .. } => {}
             ChangeBytes::Uncompressed(uncompressed) => {
-                if uncompressed.len() > DEFLATE_MIN_SIZE {
+                if let Some(compressed_body) = policy.compress(&uncompressed[body_start..]) {
                     let mut result = Vec::with_capacity(uncompressed.len());
                     result.extend(&uncompressed[0..8]);
-                    result.push(BLOCK_TYPE_DEFLATE);
-                    let mut deflater =
-                        DeflateEncoder::new(&uncompressed[body_start..], Compression::default());
-                    let mut deflated = Vec::new();
-                    let deflated_len = deflater.read_to_end(&mut deflated).unwrap();
-                    leb128::write::unsigned(&mut result, deflated_len as u64).unwrap();
-                    result.extend(&deflated[..]);
+                    result.push(if policy.is_zstd() {
+                        BLOCK_TYPE_ZSTD
+                    } else {
+                        BLOCK_TYPE_DEFLATE
+                    });
+                    leb128::write::unsigned(&mut result, compressed_body.len() as u64).unwrap();
+                    result.extend(&compressed_body[..]);
                     *self = ChangeBytes::Compressed {
                         compressed: result,
                         uncompressed: std::mem::take(uncompressed),
@@ -292,6 +324,43 @@ impl Change {
         load_blocks(bytes, false)
     }
 
+    /// Like [`load_document`](Self::load_document) but if the first block is a document chunk
+    /// it is returned separately, with its ops indexed by object. The second element of the
+    /// result holds the changes in the remaining blocks.
+    pub(crate) fn load_indexed_document(
+        bytes: &[u8],
+        validate_hashes: bool,
+    ) -> Result<(Option<IndexedDocument>, Vec<Change>), AutomergeError> {
+        let blocks = split_blocks(bytes)?;
+        match blocks.split_first() {
+            Some((first, rest))
+                if first[PREAMBLE_BYTES] == BLOCK_TYPE_DOC
+                    || first[PREAMBLE_BYTES] == BLOCK_TYPE_DOC_ZSTD =>
+            {
+                let document = decode_indexed_document(first, validate_hashes, true)?;
+                let changes = decode_blocks(rest.to_vec(), validate_hashes)?;
+                Ok((Some(document), changes))
+            }
+            _ => Ok((None, decode_blocks(blocks, validate_hashes)?)),
+        }
+    }
+
+    /// Decode each block of a document separately so that one corrupt block does not prevent
+    /// the changes in the other blocks from being loaded.
+    ///
+    /// The second element of the result is the offset of the end of the last block which could
+    /// be split off, any bytes after it are truncated or corrupt.
+    pub fn load_document_blocks(bytes: &[u8]) -> (Vec<DocumentBlock>, usize) {
+        let mut blocks = Vec::new();
+        let mut offset = 0;
+        while let Ok(Some(block)) = pop_block(&bytes[offset..]) {
+            let range = offset..(offset + block.end);
+            blocks.push(DocumentBlock::decode(&bytes[range.clone()], range.clone()));
+            offset = range.end;
+        }
+        (blocks, offset)
+    }
+
     pub fn from_bytes(bytes: Vec<u8>) -> Result<Change, decoding::Error> {
         decode_change(bytes)
     }
@@ -302,7 +371,7 @@ impl Change {
         self.start_op + (len as u64) - 1
     }
 
-    fn message(&self) -> Option<String> {
+    pub fn message(&self) -> Option<String> {
         let m = &self.bytes.uncompressed()[self.message.clone()];
         if m.is_empty() {
             None
@@ -347,8 +416,34 @@ impl Change {
         &self.bytes.uncompressed()[self.extra_bytes.clone()]
     }
 
+    /// Compress this change with the default [`CompressionPolicy`], see
+    /// [`compress_with`](Self::compress_with)
     pub fn compress(&mut self) {
-        self.bytes.compress(self.body_start);
+        self.bytes
+            .compress(self.body_start, &CompressionPolicy::default());
+    }
+
+    /// Compress the bytes returned by [`raw_bytes`](Self::raw_bytes). This does nothing if the
+    /// change has already been compressed or the policy says it is too small to compress.
+    ///
+    /// Fails if the policy's level is out of range for its codec, or if it uses zstd without the
+    /// `zstd` feature.
+    pub fn compress_with(&mut self, policy: &CompressionPolicy) -> Result<(), encoding::Error> {
+        policy.check()?;
+        self.bytes.compress(self.body_start, policy);
+        Ok(())
+    }
+
+    /// The heap space used by this change, see [`crate::MemoryUsage`]
+    pub(crate) fn heap_size(&self) -> usize {
+        let bytes = match &self.bytes {
+            ChangeBytes::Compressed {
+                compressed,
+                uncompressed,
+            } => compressed.capacity() + uncompressed.capacity(),
+            ChangeBytes::Uncompressed(bytes) => bytes.capacity(),
+        };
+        bytes + vec_size(&self.actors) + vec_size(&self.deps) + hash_map_size(&self.ops)
     }
 
     pub fn raw_bytes(&self) -> &[u8] {
@@ -402,6 +497,49 @@ fn increment_range_map(ranges: &mut HashMap<u32, Range<usize>>, len: usize) {
     }
 }
 
+/// A single block of a document, as returned by [`Change::load_document_blocks`]
+#[derive(Debug)]
+pub struct DocumentBlock {
+    /// Where this block is in the document
+    pub range: Range<usize>,
+    /// The changes which could be decoded from this block
+    pub changes: Vec<Change>,
+    /// Why this block could not be decoded. If the only problem is that the heads of a compressed
+    /// document do not match its changes then `changes` will still be populated.
+    pub error: Option<decoding::Error>,
+}
+
+impl DocumentBlock {
+    fn decode(bytes: &[u8], range: Range<usize>) -> DocumentBlock {
+        match decode_block(bytes, true) {
+            Ok(changes) => DocumentBlock {
+                range,
+                changes,
+                error: None,
+            },
+            Err(e @ decoding::Error::MismatchedHeads { .. }) => DocumentBlock {
+                range,
+                changes: decode_document(bytes, false).unwrap_or_default(),
+                error: Some(e),
+            },
+            Err(e) => DocumentBlock {
+                range,
+                changes: Vec::new(),
+                error: Some(e),
+            },
+        }
+    }
+}
+
+/// The changes in a document chunk along with its ops, indexed by object
+pub(crate) struct IndexedDocument {
+    pub changes: Vec<Change>,
+    /// The actors which the actor indices in `objects` refer to
+    pub actors: Vec<amp::ActorId>,
+    /// Empty unless the index was asked for
+    pub objects: ObjectIndex,
+}
+
 #[allow(dead_code)]
 pub(crate) struct Document {
     pub bytes: Vec<u8>,
@@ -526,36 +664,79 @@ fn decode_column_info(
     Ok(columns)
 }
 
+/// Where each column, keyed by column id, lies in the bytes of a chunk
+type ColumnRanges = HashMap<u32, Range<usize>>;
+
 fn decode_columns(
     cursor: &mut Range<usize>,
     columns: &[(u32, usize)],
-) -> HashMap<u32, Range<usize>> {
+) -> Result<ColumnRanges, decoding::Error> {
     let mut ops = HashMap::new();
     for (id, length) in columns {
         let start = cursor.start;
-        let end = start + length;
+        let end = start
+            .checked_add(*length)
+            .ok_or(decoding::Error::Overflow)?;
+        if end > cursor.end {
+            return Err(decoding::Error::NotEnoughBytes);
+        }
         *cursor = end..cursor.end;
         ops.insert(*id, start..end);
     }
-    ops
+    Ok(ops)
 }
 
-fn decode_block(
+/// Decompress the compressed columns of a document, so that iterating over them later can't fail.
+///
+/// DEFLATE and zstd columns are both decompressed once, here, into the returned buffer, and the
+/// returned column ranges point into that buffer with the compressed flag cleared. If no column is
+/// compressed `bytes` and `columns` are returned as they are.
+fn decompress_columns(
     bytes: &[u8],
-    changes: &mut Vec<Change>,
-    validate_hashes: bool,
-) -> Result<(), decoding::Error> {
-    match bytes[PREAMBLE_BYTES] {
-        BLOCK_TYPE_DOC => {
-            changes.extend(decode_document(bytes, validate_hashes)?);
-            Ok(())
+    columns: ColumnRanges,
+    chunktype: u8,
+) -> Result<(Cow<'_, [u8]>, ColumnRanges), decoding::Error> {
+    let column_error = |id: u32, e: decoding::Error| {
+        decoding::Error::ChangeDecompressFailed(format!("column {id}: {e}"))
+    };
+    if columns.keys().all(|id| id & COLUMN_TYPE_DEFLATE == 0) {
+        return Ok((Cow::Borrowed(bytes), columns));
+    }
+    let decompress = if chunktype == BLOCK_TYPE_DOC_ZSTD {
+        decompress_zstd
+    } else {
+        inflate
+    };
+    // the columns are decompressed once here and the ranges returned point into the
+    // decompressed bytes, with the compressed flag cleared so they aren't decompressed again
+    let mut decompressed = Vec::new();
+    let mut decompressed_columns = HashMap::new();
+    for (id, range) in columns {
+        let start = decompressed.len();
+        if id & COLUMN_TYPE_DEFLATE == 0 {
+            decompressed.extend(&bytes[range]);
+        } else {
+            decompressed.extend(decompress(&bytes[range]).map_err(|e| column_error(id, e))?);
         }
-        BLOCK_TYPE_CHANGE | BLOCK_TYPE_DEFLATE => {
-            changes.push(decode_change(bytes.to_vec())?);
-            Ok(())
+        decompressed_columns.insert(id & !COLUMN_TYPE_DEFLATE, start..decompressed.len());
+    }
+    Ok((Cow::Owned(decompressed), decompressed_columns))
+}
+
+fn decode_block(bytes: &[u8], validate_hashes: bool) -> Result<Vec<Change>, decoding::Error> {
+    match bytes[PREAMBLE_BYTES] {
+        BLOCK_TYPE_DOC | BLOCK_TYPE_DOC_ZSTD => decode_document(bytes, validate_hashes),
+        BLOCK_TYPE_CHANGE | BLOCK_TYPE_DEFLATE | BLOCK_TYPE_ZSTD => {
+            Ok(vec![decode_change(bytes.to_vec())?])
         }
         found => Err(decoding::Error::WrongType {
-            expected_one_of: vec![BLOCK_TYPE_DOC, BLOCK_TYPE_CHANGE, BLOCK_TYPE_DEFLATE],
+            expected_one_of: vec![
+                BLOCK_TYPE_DOC,
+                BLOCK_TYPE_CHANGE,
+                BLOCK_TYPE_DEFLATE,
+                BLOCK_TYPE_ZSTD,
+                BLOCK_TYPE_DOC_ZSTD,
+            ],
             found,
         }),
     }
@@ -563,8 +744,8 @@ fn decode_block(
 
 fn decode_change(bytes: Vec<u8>) -> Result<Change, decoding::Error> {
     let (chunktype, body) = decode_header_without_hash(&bytes)?;
-    let bytes = if chunktype == BLOCK_TYPE_DEFLATE {
-        decompress_chunk(0..PREAMBLE_BYTES, body, bytes)?
+    let bytes = if chunktype == BLOCK_TYPE_DEFLATE || chunktype == BLOCK_TYPE_ZSTD {
+        decompress_chunk(0..PREAMBLE_BYTES, body, bytes, chunktype)?
     } else {
         ChangeBytes::Uncompressed(bytes)
     };
@@ -593,7 +774,7 @@ fn decode_change(bytes: Vec<u8>) -> Result<Change, decoding::Error> {
     let actors = decode_actors(bytes.uncompressed(), &mut cursor, Some(actor))?;
 
     let ops_info = decode_column_info(bytes.uncompressed(), &mut cursor, false)?;
-    let ops = decode_columns(&mut cursor, &ops_info);
+    let ops = decode_columns(&mut cursor, &ops_info)?;
 
     Ok(Change {
         bytes,
@@ -614,10 +795,13 @@ fn decompress_chunk(
     preamble: Range<usize>,
     body: Range<usize>,
     compressed: Vec<u8>,
+    chunktype: u8,
 ) -> Result<ChangeBytes, decoding::Error> {
-    let mut decoder = DeflateDecoder::new(&compressed[body]);
-    let mut decompressed = Vec::new();
-    decoder.read_to_end(&mut decompressed)?;
+    let decompressed = if chunktype == BLOCK_TYPE_ZSTD {
+        decompress_zstd(&compressed[body])?
+    } else {
+        inflate(&compressed[body])?
+    };
     let mut result = Vec::with_capacity(decompressed.len() + preamble.len());
     result.extend(&compressed[preamble]);
     result.push(BLOCK_TYPE_CHANGE);
@@ -630,32 +814,14 @@ fn decompress_chunk(
 }
 
 //
-// group all the ops together with the appropriate change and reconstitute the del ops
-// mutates the arguments - returns nothing
+// fill in the pred of each op from the succ of the ops it overwrote and reconstitute the del ops,
+// which the document format only stores as successors
 //
 
-fn group_doc_change_and_doc_ops(
-    changes: &mut [DocChange],
-    mut ops: Vec<DocOp>,
+fn reconstitute_preds_and_dels(
+    ops: &mut Vec<DocOp>,
     actors: &[amp::ActorId],
 ) -> Result<(), decoding::Error> {
-    let mut changes_by_actor: HashMap<usize, Vec<usize>> = HashMap::new();
-
-    for (i, change) in changes.iter().enumerate() {
-        let actor_change_index = changes_by_actor.entry(change.actor).or_default();
-        if change.seq != (actor_change_index.len() + 1) as u64 {
-            return Err(decoding::Error::ChangeDecompressFailed(
-                "Doc Seq Invalid".into(),
-            ));
-        }
-        if change.actor >= actors.len() {
-            return Err(decoding::Error::ChangeDecompressFailed(
-                "Doc Actor Invalid".into(),
-            ));
-        }
-        actor_change_index.push(i);
-    }
-
     let mut op_by_id = HashMap::new();
     ops.iter().enumerate().for_each(|(i, op)| {
         op_by_id.insert((op.ctr, op.actor), i);
@@ -663,14 +829,15 @@ fn group_doc_change_and_doc_ops(
 
     for i in 0..ops.len() {
         let op = ops[i].clone(); // this is safe - avoid borrow checker issues
-                                 //let id = (op.ctr, op.actor);
-                                 //op_by_id.insert(id, i);
         for succ in &op.succ {
             if let Some(index) = op_by_id.get(succ) {
                 ops[*index].pred.push((op.ctr, op.actor));
             } else {
                 let key = if op.insert {
-                    amp::OpId(op.ctr, actors[op.actor].clone()).into()
+                    let actor = actors.get(op.actor).ok_or_else(|| {
+                        decoding::Error::ChangeDecompressFailed("Doc Actor Invalid".into())
+                    })?;
+                    amp::OpId(op.ctr, actor.clone()).into()
                 } else {
                     op.key.clone()
                 };
@@ -689,6 +856,35 @@ fn group_doc_change_and_doc_ops(
             }
         }
     }
+    Ok(())
+}
+
+//
+// group all the ops together with the appropriate change
+// mutates the arguments - returns nothing
+//
+
+fn group_doc_change_and_doc_ops(
+    changes: &mut [DocChange],
+    ops: Vec<DocOp>,
+    actors: &[amp::ActorId],
+) -> Result<(), decoding::Error> {
+    let mut changes_by_actor: HashMap<usize, Vec<usize>> = HashMap::new();
+
+    for (i, change) in changes.iter().enumerate() {
+        let actor_change_index = changes_by_actor.entry(change.actor).or_default();
+        if change.seq != (actor_change_index.len() + 1) as u64 {
+            return Err(decoding::Error::ChangeDecompressFailed(
+                "Doc Seq Invalid".into(),
+            ));
+        }
+        if change.actor >= actors.len() {
+            return Err(decoding::Error::ChangeDecompressFailed(
+                "Doc Actor Invalid".into(),
+            ));
+        }
+        actor_change_index.push(i);
+    }
 
     for op in ops {
         // binary search for our change
@@ -755,10 +951,26 @@ fn doc_changes_to_uncompressed_changes<'a>(
     })
 }
 
+/// Decode every block in `bytes`. With the `rayon` feature the blocks are decoded, decompressed
+/// and hashed in parallel, the changes are still returned in the order they appear in `bytes`.
 fn load_blocks(bytes: &[u8], validate_hashes: bool) -> Result<Vec<Change>, AutomergeError> {
+    decode_blocks(split_blocks(bytes)?, validate_hashes)
+}
+
+fn decode_blocks(blocks: Vec<&[u8]>, validate_hashes: bool) -> Result<Vec<Change>, AutomergeError> {
+    #[cfg(feature = "rayon")]
+    let decoded: Vec<_> = blocks
+        .into_par_iter()
+        .map(|slice| decode_block(slice, validate_hashes))
+        .collect();
+    #[cfg(not(feature = "rayon"))]
+    let decoded = blocks
+        .into_iter()
+        .map(|slice| decode_block(slice, validate_hashes));
+
     let mut changes = Vec::new();
-    for slice in split_blocks(bytes)? {
-        decode_block(slice, &mut changes, validate_hashes)?;
+    for block in decoded {
+        changes.extend(block?);
     }
     Ok(changes)
 }
@@ -799,12 +1011,19 @@ fn pop_block(bytes: &[u8]) -> Result<Option<Range<usize>>, decoding::Error> {
 }
 
 fn decode_document(bytes: &[u8], validate_hashes: bool) -> Result<Vec<Change>, decoding::Error> {
+    decode_indexed_document(bytes, validate_hashes, false).map(|document| document.changes)
+}
+
+fn decode_indexed_document(
+    bytes: &[u8],
+    validate_hashes: bool,
+    build_index: bool,
+) -> Result<IndexedDocument, decoding::Error> {
     let (chunktype, _hash, mut cursor) = decode_header(bytes)?;
 
-    // chunktype == 0 is a document, chunktype = 1 is a change
-    if chunktype > 0 {
+    if chunktype != BLOCK_TYPE_DOC && chunktype != BLOCK_TYPE_DOC_ZSTD {
         return Err(decoding::Error::WrongType {
-            expected_one_of: vec![0],
+            expected_one_of: vec![BLOCK_TYPE_DOC, BLOCK_TYPE_DOC_ZSTD],
             found: chunktype,
         });
     }
@@ -816,14 +1035,23 @@ fn decode_document(bytes: &[u8], validate_hashes: bool) -> Result<Vec<Change>, d
     let changes_info = decode_column_info(bytes, &mut cursor, true)?;
     let ops_info = decode_column_info(bytes, &mut cursor, true)?;
 
-    let changes_data = decode_columns(&mut cursor, &changes_info);
-    let mut doc_changes = ChangeIterator::new(bytes, &changes_data).collect::<Vec<_>>();
-    let doc_changes_deps = DepsIterator::new(bytes, &changes_data);
+    let changes_data = decode_columns(&mut cursor, &changes_info)?;
+    let (changes_bytes, changes_data) = decompress_columns(bytes, changes_data, chunktype)?;
+    let mut doc_changes = ChangeIterator::new(&changes_bytes, &changes_data).collect::<Vec<_>>();
+    let doc_changes_deps = DepsIterator::new(&changes_bytes, &changes_data);
 
     let doc_changes_len = doc_changes.len();
 
-    let ops_data = decode_columns(&mut cursor, &ops_info);
-    let doc_ops: Vec<_> = DocOpIterator::new(bytes, &actors, &ops_data).collect();
+    let ops_data = decode_columns(&mut cursor, &ops_info)?;
+    let (ops_bytes, ops_data) = decompress_columns(bytes, ops_data, chunktype)?;
+    let mut doc_ops: Vec<_> = DocOpIterator::new(&ops_bytes, &actors, &ops_data).collect();
+
+    reconstitute_preds_and_dels(&mut doc_ops, &actors)?;
+    let objects = if build_index {
+        ObjectIndex::new(&doc_ops)
+    } else {
+        ObjectIndex::default()
+    };
 
     group_doc_change_and_doc_ops(&mut doc_changes, doc_ops, &actors)?;
 
@@ -851,22 +1079,46 @@ fn decode_document(bytes: &[u8], validate_hashes: bool) -> Result<Vec<Change>, d
         }
     }
 
-    Ok(changes)
+    Ok(IndexedDocument {
+        changes,
+        actors,
+        objects,
+    })
 }
 
+/// The hash of a change covers the hashes of its dependencies, so the changes are hashed in order.
+/// Their op columns don't depend on the hashes though, so with the `rayon` feature those are
+/// encoded in parallel first.
 fn compress_doc_changes(
     uncompressed_changes: impl Iterator<Item = amp::Change>,
     doc_changes_deps: impl Iterator<Item = Vec<usize>>,
     num_changes: usize,
 ) -> Option<Vec<Change>> {
+    let (deps, uncompressed_changes): (Vec<Vec<usize>>, Vec<amp::Change>) =
+        doc_changes_deps.zip_eq(uncompressed_changes).unzip();
+
+    #[cfg(feature = "rayon")]
+    let with_ops: Vec<_> = uncompressed_changes
+        .into_par_iter()
+        .map(|change| {
+            let ops = EncodedOps::new(&change);
+            (change, ops)
+        })
+        .collect();
+    #[cfg(not(feature = "rayon"))]
+    let with_ops = uncompressed_changes.into_iter().map(|change| {
+        let ops = EncodedOps::new(&change);
+        (change, ops)
+    });
+
     let mut changes: Vec<Change> = Vec::with_capacity(num_changes);
 
     // fill out the hashes as we go
-    for (deps, mut uncompressed_change) in doc_changes_deps.zip_eq(uncompressed_changes) {
-        for idx in deps {
+    for (change_deps, (mut uncompressed_change, ops)) in deps.into_iter().zip(with_ops) {
+        for idx in change_deps {
             uncompressed_change.deps.push(changes.get(idx)?.hash);
         }
-        changes.push(uncompressed_change.into());
+        changes.push(encode_with_ops(&uncompressed_change, ops));
     }
 
     Some(changes)
@@ -992,7 +1244,11 @@ fn get_heads(changes: &[amp::Change]) -> HashSet<amp::ChangeHash> {
 }
 
 #[instrument(level = "debug", skip(changes))]
-pub(crate) fn encode_document(changes: &[amp::Change]) -> Result<Vec<u8>, encoding::Error> {
+pub(crate) fn encode_document(
+    changes: &[amp::Change],
+    policy: &CompressionPolicy,
+) -> Result<Vec<u8>, encoding::Error> {
+    policy.check()?;
     let mut bytes: Vec<u8> = Vec::new();
 
     let heads = get_heads(changes);
@@ -1007,15 +1263,19 @@ pub(crate) fn encode_document(changes: &[amp::Change]) -> Result<Vec<u8>, encodi
         .cloned()
         .collect();
 
-    let (change_bytes, change_info) = ChangeEncoder::encode_changes(changes, &actors);
+    let (change_bytes, change_info) = ChangeEncoder::encode_changes(changes, &actors, policy);
 
     let doc_ops = group_doc_ops(changes, &actors);
 
-    let (ops_bytes, ops_info) = DocOpEncoder::encode_doc_ops(doc_ops, &actors);
+    let (ops_bytes, ops_info) = DocOpEncoder::encode_doc_ops(doc_ops, &actors, policy);
 
     bytes.extend(&MAGIC_BYTES);
     bytes.extend(vec![0, 0, 0, 0]); // we dont know the hash yet so fill in a fake
-    bytes.push(BLOCK_TYPE_DOC);
+    bytes.push(if policy.is_zstd() {
+        BLOCK_TYPE_DOC_ZSTD
+    } else {
+        BLOCK_TYPE_DOC
+    });
 
     let mut chunk = Vec::new();
 
@@ -1328,11 +1588,10 @@ mod tests {
         assert_eq!(loaded_changes[1].max_op(), 8);
     }
 
-    #[test_env_log::test]
-    fn test_encode_decode_document_large_enough_for_compression() {
-        let actor = amp::ActorId::random();
-        let mut backend = crate::Backend::new();
-        let mut change1 = amp::Change {
+    /// A change which makes a list and inserts 256 strings into it, which is big enough to be
+    /// compressed
+    fn large_list_change(actor: &amp::ActorId) -> amp::Change {
+        let mut change = amp::Change {
             start_op: 1,
             seq: 1,
             time: 0,
@@ -1351,7 +1610,7 @@ mod tests {
         };
         let mut last_elem_id: amp::Key = amp::ElementId::Head.into();
         for i in 0..256 {
-            change1.operations.push(amp::Op {
+            change.operations.push(amp::Op {
                 action: amp::OpType::Set(format!("value {}", i).as_str().into()),
                 obj: actor.op_id_at(1).into(),
                 key: last_elem_id,
@@ -1360,7 +1619,14 @@ mod tests {
             });
             last_elem_id = actor.op_id_at(i + 2).into();
         }
-        let binchange1: Change = Change::try_from(change1).unwrap();
+        change
+    }
+
+    #[test_env_log::test]
+    fn test_encode_decode_document_large_enough_for_compression() {
+        let mut backend = crate::Backend::new();
+        let binchange1: Change =
+            Change::try_from(large_list_change(&amp::ActorId::random())).unwrap();
         backend.apply_changes(vec![binchange1]).unwrap();
 
         let changes = backend.get_changes(&[]);
@@ -1381,6 +1647,55 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_compression_policies_roundtrip() {
+        let change: Change = large_list_change(&amp::ActorId::random()).into();
+        let mut backend = crate::Backend::new();
+        backend.apply_changes(vec![change.clone()]).unwrap();
+        let uncompressed_len = backend.save_with(&CompressionPolicy::none()).unwrap().len();
+
+        #[allow(unused_mut)]
+        let mut policies = vec![
+            CompressionPolicy::none(),
+            CompressionPolicy::deflate(1),
+            CompressionPolicy::deflate(9),
+        ];
+        #[cfg(feature = "zstd")]
+        policies.push(CompressionPolicy::zstd(3));
+
+        for policy in policies {
+            let saved = backend.save_with(&policy).unwrap();
+            if policy.compression != crate::Compression::None {
+                assert!(saved.len() < uncompressed_len);
+            }
+            assert_eq!(Change::load_document(&saved).unwrap(), vec![change.clone()]);
+
+            let mut compressed = change.clone();
+            compressed.compress_with(&policy).unwrap();
+            let loaded = Change::from_bytes(compressed.raw_bytes().to_vec()).unwrap();
+            assert_eq!(loaded.hash, change.hash);
+            assert_eq!(loaded.decode(), change.decode());
+        }
+    }
+
+    #[test]
+    fn test_invalid_compression_policies() {
+        let mut change: Change = large_list_change(&amp::ActorId::random()).into();
+        let backend = crate::Backend::new();
+        let invalid = vec![
+            CompressionPolicy::deflate(10),
+            CompressionPolicy::zstd(0),
+            CompressionPolicy::zstd(23),
+        ];
+        for policy in invalid {
+            assert!(backend.save_with(&policy).is_err());
+            assert!(change.compress_with(&policy).is_err());
+        }
+
+        let zstd = CompressionPolicy::zstd(3);
+        assert_eq!(change.compress_with(&zstd).is_ok(), cfg!(feature = "zstd"),);
+    }
+
     #[test]
     fn test_invalid_document_checksum() {
         let change = amp::Change {
@@ -1394,7 +1709,7 @@ mod tests {
             deps: Vec::new(),
             extra_bytes: Vec::new(),
         };
-        let mut doc = encode_document(&[change]).unwrap();
+        let mut doc = encode_document(&[change], &CompressionPolicy::default()).unwrap();
         let hash: [u8; 4] = doc[4..8].try_into().unwrap();
         doc[4] = 0;
         doc[5] = 0;
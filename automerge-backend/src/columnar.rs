@@ -11,11 +11,11 @@ use std::{
 
 use amp::SortedVec;
 use automerge_protocol as amp;
-use flate2::bufread::DeflateDecoder;
 use smol_str::SmolStr;
 use tracing::instrument;
 
 use crate::{
+    compression::{inflate, CompressionPolicy},
     decoding::{BooleanDecoder, Decodable, Decoder, DeltaDecoder, RleDecoder},
     encoding::{BooleanEncoder, ColData, DeltaEncoder, Encodable, RleEncoder},
     expanded_op::ExpandedOp,
@@ -133,6 +133,7 @@ impl<'a> Iterator for OperationIterator<'a> {
             Action::MakeText => InternalOpType::Make(amp::ObjType::Text),
             Action::MakeMap => InternalOpType::Make(amp::ObjType::Map),
             Action::MakeTable => InternalOpType::Make(amp::ObjType::Table),
+            Action::MakeSet => InternalOpType::Make(amp::ObjType::Set),
             Action::Del => InternalOpType::Del,
             Action::Inc => InternalOpType::Inc(value.to_i64()?),
         };
@@ -174,6 +175,7 @@ impl<'a> Iterator for DocOpIterator<'a> {
             Action::MakeText => InternalOpType::Make(amp::ObjType::Text),
             Action::MakeMap => InternalOpType::Make(amp::ObjType::Map),
             Action::MakeTable => InternalOpType::Make(amp::ObjType::Table),
+            Action::MakeSet => InternalOpType::Make(amp::ObjType::Set),
             Action::Del => InternalOpType::Del,
             Action::Inc => InternalOpType::Inc(value.to_i64()?),
         };
@@ -435,11 +437,30 @@ impl<'a> Iterator for ValueIterator<'a> {
                 let data = self.val_raw.read_bytes(len).ok()?;
                 Some(amp::ScalarValue::Bytes(data.to_vec()))
             }
+            v if v % 16 == VALUE_TYPE_UUID => {
+                let len = v >> 4;
+                let data = self.val_raw.read_bytes(len).ok()?;
+                Some(amp::ScalarValue::Uuid(uuid::Uuid::from_slice(data).ok()?))
+            }
+            v if v % 16 == VALUE_TYPE_DECIMAL => {
+                let len = v >> 4;
+                let mut data = self.val_raw.read_bytes(len).ok()?;
+                let scale = u32::decode(&mut data)?;
+                let mantissa = i64::decode(&mut data)?;
+                if !data.is_empty() {
+                    return None;
+                }
+                // rejects scales too large for the mantissa
+                amp::Decimal::new(mantissa, scale)
+                    .ok()
+                    .map(amp::ScalarValue::Decimal)
+            }
             v if v % 16 >= VALUE_TYPE_MIN_UNKNOWN && v % 16 <= VALUE_TYPE_MAX_UNKNOWN => {
                 let len = v >> 4;
-                let _data = self.val_raw.read_bytes(len).ok()?;
-                unimplemented!()
-                //Some((amp::Value::Bytes(data))
+                let data = self.val_raw.read_bytes(len).ok()?;
+                amp::UnknownValue::new((v % 16) as u8, data.to_vec())
+                    .ok()
+                    .map(amp::ScalarValue::Unknown)
             }
             v if v % 16 == VALUE_TYPE_IEEE754 => {
                 let len = v >> 4;
@@ -538,6 +559,30 @@ impl PartialEq for DocOp {
 
 impl Eq for DocOp {}
 
+/// The rows of a document's op columns grouped by the object they belong to, so that the state
+/// of each object can be built without looking at the ops of any other object.
+///
+/// The ops are expected to have had their `pred` and the deletions implied by their `succ`
+/// filled in already.
+#[derive(Debug, Default)]
+pub(crate) struct ObjectIndex {
+    objects: HashMap<amp::ObjectId, Vec<DocOp>>,
+}
+
+impl ObjectIndex {
+    pub(crate) fn new(ops: &[DocOp]) -> ObjectIndex {
+        let mut objects: HashMap<amp::ObjectId, Vec<DocOp>> = HashMap::new();
+        for op in ops {
+            objects.entry(op.obj.clone()).or_default().push(op.clone());
+        }
+        ObjectIndex { objects }
+    }
+
+    pub(crate) fn into_objects(self) -> impl Iterator<Item = (amp::ObjectId, Vec<DocOp>)> {
+        self.objects.into_iter()
+    }
+}
+
 struct ValEncoder {
     len: RleEncoder<usize>,
     ref_actor: RleEncoder<usize>,
@@ -607,6 +652,23 @@ impl ValEncoder {
                 self.ref_actor.append_value(actor_index);
                 self.ref_counter.append_value(opid.0);
             }
+            amp::ScalarValue::Uuid(u) => {
+                let bytes = u.as_bytes();
+                self.raw.extend(bytes);
+                self.len.append_value(bytes.len() << 4 | VALUE_TYPE_UUID);
+            }
+            amp::ScalarValue::Decimal(d) => {
+                let len = d.scale().encode(&mut self.raw).unwrap()
+                    + d.mantissa().encode(&mut self.raw).unwrap();
+                self.len.append_value(len << 4 | VALUE_TYPE_DECIMAL);
+            }
+            amp::ScalarValue::Unknown(u) => {
+                // `UnknownValue` only allows the reserved type codes, so this can't be read back
+                // as a different type or spill into the length
+                self.raw.extend(u.bytes());
+                self.len
+                    .append_value(u.bytes().len() << 4 | usize::from(u.type_code()));
+            }
         }
     }
 
@@ -788,13 +850,17 @@ pub(crate) struct ChangeEncoder {
 
 impl ChangeEncoder {
     #[instrument(level = "debug", skip(changes, actors))]
-    pub fn encode_changes<'a, 'b, I>(changes: I, actors: &'a [amp::ActorId]) -> (Vec<u8>, Vec<u8>)
+    pub fn encode_changes<'a, 'b, I>(
+        changes: I,
+        actors: &'a [amp::ActorId],
+        policy: &CompressionPolicy,
+    ) -> (Vec<u8>, Vec<u8>)
     where
         I: IntoIterator<Item = &'b amp::Change>,
     {
         let mut e = Self::new();
         e.encode(changes, actors);
-        e.finish()
+        e.finish(policy)
     }
 
     fn new() -> ChangeEncoder {
@@ -845,7 +911,7 @@ impl ChangeEncoder {
         }
     }
 
-    fn finish(self) -> (Vec<u8>, Vec<u8>) {
+    fn finish(self, policy: &CompressionPolicy) -> (Vec<u8>, Vec<u8>) {
         let mut coldata = vec![
             self.actor.finish(DOC_ACTOR),
             self.seq.finish(DOC_SEQ),
@@ -868,7 +934,7 @@ impl ChangeEncoder {
             .encode(&mut info)
             .ok();
         for d in &mut coldata {
-            d.deflate();
+            d.compress(policy);
             d.encode_col_len(&mut info).ok();
         }
         for d in &coldata {
@@ -891,13 +957,17 @@ pub(crate) struct DocOpEncoder {
 
 impl DocOpEncoder {
     #[instrument(level = "debug", skip(ops, actors))]
-    pub(crate) fn encode_doc_ops<'a, I>(ops: I, actors: &'a [amp::ActorId]) -> (Vec<u8>, Vec<u8>)
+    pub(crate) fn encode_doc_ops<'a, I>(
+        ops: I,
+        actors: &'a [amp::ActorId],
+        policy: &CompressionPolicy,
+    ) -> (Vec<u8>, Vec<u8>)
     where
         I: IntoIterator<Item = DocOp>,
     {
         let mut e = Self::new();
         e.encode(ops, actors);
-        e.finish()
+        e.finish(policy)
     }
 
     fn new() -> DocOpEncoder {
@@ -945,6 +1015,7 @@ impl DocOpEncoder {
                         amp::ObjType::Table => Action::MakeTable,
                         amp::ObjType::List => Action::MakeList,
                         amp::ObjType::Text => Action::MakeText,
+                        amp::ObjType::Set => Action::MakeSet,
                     }
                 }
             };
@@ -952,7 +1023,7 @@ impl DocOpEncoder {
         }
     }
 
-    fn finish(self) -> (Vec<u8>, Vec<u8>) {
+    fn finish(self, policy: &CompressionPolicy) -> (Vec<u8>, Vec<u8>) {
         let mut coldata = vec![
             self.actor.finish(COL_ID_ACTOR),
             self.ctr.finish(COL_ID_CTR),
@@ -974,7 +1045,7 @@ impl DocOpEncoder {
             .encode(&mut info)
             .ok();
         for d in &mut coldata {
-            d.deflate();
+            d.compress(policy);
             d.encode_col_len(&mut info).ok();
         }
         for d in &coldata {
@@ -1054,6 +1125,7 @@ impl ColumnEncoder {
                     amp::ObjType::Table => Action::MakeTable,
                     amp::ObjType::List => Action::MakeList,
                     amp::ObjType::Text => Action::MakeText,
+                    amp::ObjType::Set => Action::MakeSet,
                 }
             }
         };
@@ -1106,12 +1178,9 @@ where
     let bytes = if let Some(r) = ops.get(&col_id) {
         Cow::Borrowed(&bytes[r.clone()])
     } else if let Some(r) = ops.get(&(col_id | COLUMN_TYPE_DEFLATE)) {
-        let mut decoder = DeflateDecoder::new(&bytes[r.clone()]);
-        let mut inflated = Vec::new();
-        //TODO this could throw if the compression is corrupt, we should propagate the error rather
-        //than unwrapping
-        decoder.read_to_end(&mut inflated).unwrap();
-        Cow::Owned(inflated)
+        // unreachable for decoded chunks: change chunks reject compressed columns and
+        // `decompress_columns` decompresses document columns and clears their compressed flag
+        Cow::Owned(inflate(&bytes[r.clone()]).unwrap())
     } else {
         Cow::from(&[] as &[u8])
     };
@@ -1129,7 +1198,9 @@ const VALUE_TYPE_BYTES: usize = 7;
 const VALUE_TYPE_COUNTER: usize = 8;
 const VALUE_TYPE_TIMESTAMP: usize = 9;
 const VALUE_TYPE_CURSOR: usize = 10;
-const VALUE_TYPE_MIN_UNKNOWN: usize = 11;
+const VALUE_TYPE_UUID: usize = 11;
+const VALUE_TYPE_DECIMAL: usize = 12;
+const VALUE_TYPE_MIN_UNKNOWN: usize = 13;
 const VALUE_TYPE_MAX_UNKNOWN: usize = 15;
 
 pub(crate) const COLUMN_TYPE_GROUP_CARD: u32 = 0;
@@ -1140,6 +1211,7 @@ pub(crate) const COLUMN_TYPE_BOOLEAN: u32 = 4;
 pub(crate) const COLUMN_TYPE_STRING_RLE: u32 = 5;
 pub(crate) const COLUMN_TYPE_VALUE_LEN: u32 = 6;
 pub(crate) const COLUMN_TYPE_VALUE_RAW: u32 = 7;
+/// The column is compressed, with DEFLATE or with zstd depending on the type of the chunk
 pub(crate) const COLUMN_TYPE_DEFLATE: u32 = 8;
 
 #[derive(PartialEq, Debug, Clone, Copy)]
@@ -1152,8 +1224,9 @@ pub(crate) enum Action {
     MakeText,
     Inc,
     MakeTable,
+    MakeSet,
 }
-const ACTIONS: [Action; 7] = [
+const ACTIONS: [Action; 8] = [
     Action::MakeMap,
     Action::Set,
     Action::MakeList,
@@ -1161,6 +1234,7 @@ const ACTIONS: [Action; 7] = [
     Action::MakeText,
     Action::Inc,
     Action::MakeTable,
+    Action::MakeSet,
 ];
 
 impl Decodable for Action {
@@ -1313,6 +1387,31 @@ mod tests {
         assert_eq!(decoded, ops);
     }
 
+    #[test]
+    fn decimals_with_too_large_a_scale_are_not_decoded() {
+        let decode = |scale: u32| {
+            let mut raw = Vec::new();
+            let len = scale.encode(&mut raw).unwrap() + 5i64.encode(&mut raw).unwrap();
+            let mut val_len = RleEncoder::new();
+            val_len.append_value(len << 4 | VALUE_TYPE_DECIMAL);
+            let val_len = val_len.finish(COL_VAL_LEN).data;
+            let mut values = ValueIterator {
+                actors: &[],
+                val_len: RleDecoder::from(Cow::from(&val_len[..])),
+                val_raw: Decoder::from(Cow::from(&raw[..])),
+                actor: RleDecoder::from(Cow::from(&[] as &[u8])),
+                ctr: RleDecoder::from(Cow::from(&[] as &[u8])),
+            };
+            values.next()
+        };
+        assert_eq!(
+            decode(18),
+            Some(ScalarValue::Decimal(amp::Decimal::new(5, 18).unwrap()))
+        );
+        assert_eq!(decode(19), None);
+        assert_eq!(decode(u32::MAX), None);
+    }
+
     #[test]
     fn pred_sorted() {
         let actor = ActorId::random();
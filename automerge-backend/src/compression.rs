@@ -0,0 +1,127 @@
+use std::io::Read;
+
+use flate2::bufread::{DeflateDecoder, DeflateEncoder};
+
+use crate::{decoding, encoding};
+
+/// How change chunks and the columns of documents are compressed when they are encoded.
+///
+/// Readers find out which codec was used from the chunk type, so changes and documents written
+/// with any policy can be loaded, except that zstd compressed data can only be written or read
+/// with the `zstd` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionPolicy {
+    pub compression: Compression,
+    /// Chunks and columns of this many bytes or fewer are stored uncompressed, compressing small
+    /// inputs usually makes them bigger
+    pub min_size: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    /// DEFLATE, with a level from 0 (fastest) to 9 (smallest)
+    Deflate {
+        level: u32,
+    },
+    /// zstd, with a level from 1 (fastest) to 22 (smallest)
+    Zstd {
+        level: i32,
+    },
+}
+
+/// The size below which the default policy does not compress
+pub(crate) const DEFAULT_MIN_SIZE: usize = 256;
+
+impl CompressionPolicy {
+    pub fn none() -> CompressionPolicy {
+        CompressionPolicy {
+            compression: Compression::None,
+            min_size: DEFAULT_MIN_SIZE,
+        }
+    }
+
+    pub fn deflate(level: u32) -> CompressionPolicy {
+        CompressionPolicy {
+            compression: Compression::Deflate { level },
+            min_size: DEFAULT_MIN_SIZE,
+        }
+    }
+
+    pub fn zstd(level: i32) -> CompressionPolicy {
+        CompressionPolicy {
+            compression: Compression::Zstd { level },
+            min_size: DEFAULT_MIN_SIZE,
+        }
+    }
+
+    #[must_use]
+    pub fn with_min_size(self, min_size: usize) -> CompressionPolicy {
+        CompressionPolicy { min_size, ..self }
+    }
+
+    /// Check that the level is in range for the codec and that the codec is available
+    pub(crate) fn check(&self) -> Result<(), encoding::Error> {
+        match self.compression {
+            Compression::Deflate { level } if level > 9 => {
+                Err(encoding::Error::InvalidDeflateLevel(level))
+            }
+            Compression::Zstd { level } if !(1..=22).contains(&level) => {
+                Err(encoding::Error::InvalidZstdLevel(level))
+            }
+            Compression::Zstd { .. } if !cfg!(feature = "zstd") => {
+                Err(encoding::Error::ZstdNotSupported)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Compress `data` if this policy says it should be, returning `None` if it should be stored
+    /// as it is. The policy must have passed [`check`](Self::check).
+    pub(crate) fn compress(&self, data: &[u8]) -> Option<Vec<u8>> {
+        if data.len() <= self.min_size {
+            return None;
+        }
+        match self.compression {
+            Compression::None => None,
+            Compression::Deflate { level } => {
+                let mut deflated = Vec::new();
+                // reading from and writing to in memory buffers can't fail
+                DeflateEncoder::new(data, flate2::Compression::new(level))
+                    .read_to_end(&mut deflated)
+                    .unwrap();
+                Some(deflated)
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd { level } => Some(zstd::bulk::compress(data, level).unwrap()),
+            #[cfg(not(feature = "zstd"))]
+            Compression::Zstd { .. } => unreachable!("zstd policies are rejected by check"),
+        }
+    }
+
+    pub(crate) fn is_zstd(&self) -> bool {
+        matches!(self.compression, Compression::Zstd { .. })
+    }
+}
+
+impl Default for CompressionPolicy {
+    fn default() -> Self {
+        CompressionPolicy::deflate(flate2::Compression::default().level())
+    }
+}
+
+pub(crate) fn inflate(data: &[u8]) -> Result<Vec<u8>, decoding::Error> {
+    let mut inflated = Vec::new();
+    DeflateDecoder::new(data).read_to_end(&mut inflated)?;
+    Ok(inflated)
+}
+
+#[cfg(feature = "zstd")]
+pub(crate) fn decompress_zstd(data: &[u8]) -> Result<Vec<u8>, decoding::Error> {
+    Ok(zstd::stream::decode_all(data)?)
+}
+
+#[cfg(not(feature = "zstd"))]
+pub(crate) fn decompress_zstd(_data: &[u8]) -> Result<Vec<u8>, decoding::Error> {
+    Err(decoding::Error::ZstdNotSupported)
+}
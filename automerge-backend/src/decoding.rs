@@ -37,10 +37,14 @@ pub enum Error {
     InvalidChange(#[from] InvalidChangeError),
     #[error("Change decompression error: {0}")]
     ChangeDecompressFailed(String),
+    #[error("Found data compressed with zstd, which needs the `zstd` feature")]
+    ZstdNotSupported,
     #[error("No doc changes found")]
     NoDocChanges,
     #[error("An overflow would have occurred, the data may be corrupt")]
     Overflow,
+    #[error("Found unexpected bytes after the end of the message")]
+    TrailingBytes,
     #[error("Calculated heads differed from actual heads. Calculated: {derived_heads:?} != stated {stated_heads:?}")]
     MismatchedHeads {
         derived_heads: HashSet<amp::ChangeHash>,
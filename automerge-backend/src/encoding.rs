@@ -1,23 +1,22 @@
 use core::fmt::Debug;
-use std::{
-    io,
-    io::{Read, Write},
-    mem,
-};
+use std::{io, io::Write, mem};
 
 use automerge_protocol as amp;
-use flate2::{bufread::DeflateEncoder, Compression};
 use smol_str::SmolStr;
 
-use crate::columnar::COLUMN_TYPE_DEFLATE;
-
-pub(crate) const DEFLATE_MIN_SIZE: usize = 256;
+use crate::{columnar::COLUMN_TYPE_DEFLATE, compression::CompressionPolicy};
 
 /// The error type for encoding operations.
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error(transparent)]
     Io(#[from] io::Error),
+    #[error("DEFLATE compression levels are from 0 to 9 but found {0}")]
+    InvalidDeflateLevel(u32),
+    #[error("zstd compression levels are from 1 to 22 but found {0}")]
+    InvalidZstdLevel(i32),
+    #[error("Compressing with zstd needs the `zstd` feature")]
+    ZstdNotSupported,
 }
 
 /// Encodes booleans by storing the count of the same value.
@@ -336,7 +335,7 @@ pub(crate) struct ColData {
     pub col: u32,
     pub data: Vec<u8>,
     #[cfg(debug_assertions)]
-    has_been_deflated: bool,
+    has_been_compressed: bool,
 }
 
 impl ColData {
@@ -345,7 +344,7 @@ impl ColData {
             col: col_id,
             data,
             #[cfg(debug_assertions)]
-            has_been_deflated: false,
+            has_been_compressed: false,
         }
     }
 
@@ -358,19 +357,15 @@ impl ColData {
         Ok(len)
     }
 
-    pub fn deflate(&mut self) {
+    pub fn compress(&mut self, policy: &CompressionPolicy) {
         #[cfg(debug_assertions)]
         {
-            debug_assert!(!self.has_been_deflated);
-            self.has_been_deflated = true;
+            debug_assert!(!self.has_been_compressed);
+            self.has_been_compressed = true;
         }
-        if self.data.len() > DEFLATE_MIN_SIZE {
-            let mut deflated = Vec::new();
-            let mut deflater = DeflateEncoder::new(&self.data[..], Compression::default());
-            //This unwrap should be okay as we're reading and writing to in memory buffers
-            deflater.read_to_end(&mut deflated).unwrap();
+        if let Some(compressed) = policy.compress(&self.data) {
             self.col |= COLUMN_TYPE_DEFLATE;
-            self.data = deflated;
+            self.data = compressed;
         }
     }
 }
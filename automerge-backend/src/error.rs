@@ -58,6 +58,19 @@ pub enum AutomergeError {
     DecodingError(#[from] decoding::Error),
     #[error("Attempted to create a cursor for opid {opid} which was not an element in a sequence")]
     InvalidCursor { opid: amp::OpId },
+    #[error("Cannot read {prop} of {obj_type:?} object {object_id}")]
+    InvalidProp {
+        object_id: amp::ObjectId,
+        obj_type: amp::ObjType,
+        prop: crate::Prop,
+    },
+    #[error("Operation {opid} on set {object_id} is not adding or removing a scalar member")]
+    InvalidSetOp {
+        object_id: amp::ObjectId,
+        opid: amp::OpId,
+    },
+    #[error("Object {0} is not a text object")]
+    NotText(amp::ObjectId),
     #[error("A compressed chunk could not be decompressed")]
     BadCompressedChunk,
 }
@@ -1,10 +1,13 @@
 use automerge_protocol as amp;
 use nonzero_ext::nonzero;
-use smol_str::SmolStr;
 
 #[derive(Eq, PartialEq, Hash, Debug, Clone, Copy)]
 pub(crate) struct ActorId(pub usize);
 
+/// The index of an interned map key in the `ActorMap`
+#[derive(Eq, PartialEq, Hash, Debug, Clone, Copy)]
+pub(crate) struct KeyId(pub u32);
+
 #[derive(Eq, PartialEq, Debug, Hash, Clone, Copy)]
 pub(crate) struct OpId(pub u64, pub ActorId);
 
@@ -20,9 +23,9 @@ pub(crate) enum ElementId {
     Id(OpId),
 }
 
-#[derive(PartialEq, Eq, Debug, Hash, Clone)]
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
 pub(crate) enum Key {
-    Map(SmolStr),
+    Map(KeyId),
     Seq(ElementId),
 }
 
@@ -64,7 +67,7 @@ impl Key {
         }
     }
 
-    pub fn to_opid(&self) -> Option<OpId> {
+    pub fn to_opid(self) -> Option<OpId> {
         match self.as_element_id()? {
             ElementId::Id(id) => Some(id),
             ElementId::Head => None,
@@ -34,6 +34,7 @@ mod actor_map;
 mod backend;
 mod change;
 mod columnar;
+mod compression;
 mod concurrent_operations;
 mod decoding;
 mod encoding;
@@ -41,6 +42,7 @@ mod error;
 mod event_handlers;
 mod expanded_op;
 mod internal;
+mod memory;
 mod object_store;
 mod op_handle;
 mod op_set;
@@ -49,13 +51,18 @@ mod patches;
 mod sync;
 mod vector_clock;
 
-pub use backend::Backend;
-pub use change::Change;
+pub use backend::{Authorship, Backend, Blame, BlameValue, Prop, QueuedChange, Value};
+pub use change::{Change, DocumentBlock};
+pub use compression::{Compression, CompressionPolicy};
 pub use decoding::Error as DecodingError;
 pub use encoding::Error as EncodingError;
 pub use error::AutomergeError;
 pub use event_handlers::{ChangeEventHandler, EventHandler, EventHandlerId};
-pub use sync::{BloomFilter, SyncHave, SyncMessage, SyncState};
+pub use memory::MemoryUsage;
+pub use sync::{
+    BloomFilter, EphemeralMessage, PeerMessage, Presence, PresenceStore, SyncHave, SyncMessage,
+    SyncState,
+};
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,122 @@
+use std::{
+    collections::{HashMap, HashSet},
+    mem::size_of,
+};
+
+/// An estimate of the memory used by a [`crate::Backend`], broken down by component. All sizes
+/// are in bytes.
+///
+/// The estimates count the space allocated by the collections making up each component, they do
+/// not include allocator overhead or the contents of long strings in values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MemoryUsage {
+    /// The encoded changes which have been applied and the indexes over them
+    pub history: usize,
+    /// Changes waiting for their dependencies
+    pub queue: usize,
+    /// The operations in the op set
+    pub ops: usize,
+    /// The state of each object, not including its operations or sequence indices
+    pub objects: usize,
+    /// The indices used to order the elements of lists and text
+    pub sequence_indices: usize,
+    /// Interned actor IDs and map keys
+    pub interned: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.history + self.queue + self.ops + self.objects + self.sequence_indices + self.interned
+    }
+}
+
+/// The heap space allocated by a `Vec`, not including anything its elements point to
+#[allow(clippy::ptr_arg)]
+pub(crate) fn vec_size<T>(vec: &Vec<T>) -> usize {
+    vec.capacity() * size_of::<T>()
+}
+
+/// The heap space allocated by a `HashMap`, not including anything its keys or values point to.
+///
+/// The standard library's hash map uses a control byte for each bucket on top of the entry.
+pub(crate) fn hash_map_size<K, V, S>(map: &HashMap<K, V, S>) -> usize {
+    map.capacity() * (size_of::<K>() + size_of::<V>() + 1)
+}
+
+/// The heap space allocated by a `HashSet`, not including anything its elements point to
+pub(crate) fn hash_set_size<T, S>(set: &HashSet<T, S>) -> usize {
+    set.capacity() * (size_of::<T>() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use amp::SortedVec;
+    use automerge_protocol as amp;
+
+    use crate::{Backend, Change};
+
+    /// A change by `actor` which sets `key0` to `key99` in the root object
+    fn set_keys(actor: &amp::ActorId, seq: u64, deps: Vec<amp::ChangeHash>) -> Change {
+        let start_op = (seq - 1) * 100 + 1;
+        amp::Change {
+            actor_id: actor.clone(),
+            seq,
+            start_op,
+            time: 0,
+            message: None,
+            hash: None,
+            deps,
+            operations: (0..100)
+                .map(|i| amp::Op {
+                    obj: amp::ObjectId::Root,
+                    action: amp::OpType::Set(amp::ScalarValue::Int(i as i64)),
+                    key: format!("key{}", i).as_str().into(),
+                    insert: false,
+                    // overwrite the value set by the previous change
+                    pred: if seq == 1 {
+                        SortedVec::new()
+                    } else {
+                        vec![amp::OpId(start_op - 100 + i, actor.clone())].into()
+                    },
+                })
+                .collect(),
+            extra_bytes: Vec::new(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn test_memory_usage_grows_with_ops() {
+        let actor = amp::ActorId::random();
+        let mut backend = Backend::new();
+        let empty = backend.memory_usage();
+        assert_eq!(empty.ops, 0);
+
+        backend
+            .apply_changes(vec![set_keys(&actor, 1, Vec::new())])
+            .unwrap();
+        let usage = backend.memory_usage();
+        assert!(usage.ops > 0);
+        assert!(usage.history > empty.history);
+        assert!(usage.interned > empty.interned);
+        assert_eq!(
+            usage.total(),
+            usage.history
+                + usage.queue
+                + usage.ops
+                + usage.objects
+                + usage.sequence_indices
+                + usage.interned
+        );
+
+        // overwriting the keys reuses the interned keys and replaces the ops they had
+        let deps = backend.get_heads();
+        backend
+            .apply_changes(vec![set_keys(&actor, 2, deps)])
+            .unwrap();
+        let overwritten = backend.memory_usage();
+        assert_eq!(overwritten.interned, usage.interned);
+        assert_eq!(overwritten.ops, usage.ops);
+        assert!(overwritten.history > usage.history);
+    }
+}
@@ -6,7 +6,8 @@ use fxhash::FxBuildHasher;
 use crate::{
     actor_map::ActorMap,
     concurrent_operations::ConcurrentOperations,
-    internal::{ElementId, Key, OpId},
+    internal::{ElementId, Key, ObjectId, OpId},
+    memory::{hash_map_size, vec_size, MemoryUsage},
     op_handle::OpHandle,
     ordered_set::{OrderedSet, SkipList},
 };
@@ -17,16 +18,27 @@ use crate::{
 /// respectively).
 
 /// Stores operations on map objects
+///
+/// Each operation is stored once, in `props`. The other fields only refer to operations by ID.
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct ObjState {
     pub props: HashMap<Key, ConcurrentOperations>,
     pub obj_type: amp::ObjType,
-    pub inbound: Option<OpHandle>,
+    /// The op which created this object, if it is still visible
+    pub inbound: Option<Inbound>,
     pub following: HashMap<ElementId, Vec<ElementId>, FxBuildHasher>,
-    pub insertions: HashMap<ElementId, OpHandle, FxBuildHasher>,
+    /// The element each element was inserted after
+    pub insertions: HashMap<ElementId, ElementId, FxBuildHasher>,
     pub seq: SkipList<OpId>,
 }
 
+/// Where the op which created an object is stored, i.e. its object and operation key
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Inbound {
+    pub obj: ObjectId,
+    pub key: Key,
+}
+
 impl ObjState {
     pub fn new(obj_type: amp::ObjType) -> ObjState {
         let mut following = HashMap::default();
@@ -45,8 +57,42 @@ impl ObjState {
         self.obj_type.is_sequence()
     }
 
+    pub fn add_memory_usage(&self, usage: &mut MemoryUsage) {
+        usage.objects += hash_map_size(&self.props);
+        for ops in self.props.values() {
+            usage.ops += vec_size(&ops.ops);
+            usage.ops += ops.iter().map(|op| vec_size(&op.pred)).sum::<usize>();
+        }
+        usage.sequence_indices += hash_map_size(&self.following)
+            + self.following.values().map(vec_size).sum::<usize>()
+            + hash_map_size(&self.insertions)
+            + self.seq.heap_size();
+    }
+
+    /// The number of visible elements in a sequence or keys in a map
+    pub fn len(&self) -> usize {
+        if self.is_seq() {
+            self.seq.len
+        } else {
+            self.props.values().filter(|ops| !ops.is_empty()).count()
+        }
+    }
+
+    /// The keys of a map which have a visible value
+    pub fn visible_keys(&self) -> impl Iterator<Item = &Key> {
+        self.props
+            .iter()
+            .filter(|(_, ops)| !ops.is_empty())
+            .map(|(key, _)| key)
+    }
+
+    /// The key of the visible element at `index` in a sequence
+    pub fn key_at(&self, index: usize) -> Option<Key> {
+        self.seq.key_of(index).map(|id| (*id).into())
+    }
+
     fn get_parent(&self, id: &ElementId) -> Option<ElementId> {
-        self.insertions.get(id).and_then(|i| i.key.as_element_id())
+        self.insertions.get(id).copied()
     }
 
     fn insertions_after(&self, parent: &ElementId) -> Vec<ElementId> {
@@ -59,6 +105,10 @@ impl ObjState {
 
     #[tracing::instrument(level = "debug", skip(self))]
     pub fn index_of(&self, id: OpId) -> Option<usize> {
+        // visible elements are in the skip list, which is much faster than walking the insertions
+        if let Some(index) = self.seq.index_of(&id) {
+            return Some(index);
+        }
         let mut prev_id = id.into();
         let mut index = None;
         // reverse walk through the following/insertions and looking for something that not deleted
@@ -101,9 +151,9 @@ impl ObjState {
         }
     }
 
-    pub fn insert_after(&mut self, elem: ElementId, op: OpHandle, actors: &ActorMap) {
+    pub fn insert_after(&mut self, elem: ElementId, op: &OpHandle, actors: &ActorMap) {
         let eid = op.id.into();
-        self.insertions.insert(eid, op);
+        self.insertions.insert(eid, elem);
         let following = self.following.entry(elem).or_default();
         following.push(eid);
         following.sort_unstable_by(|a, b| actors.cmp(b, a));
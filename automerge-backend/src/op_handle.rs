@@ -49,6 +49,22 @@ impl OpHandle {
         }
     }
 
+    /// A copy of this op to store in the op set. Once an op has been applied its `pred` is not
+    /// needed any more so we leave it out.
+    pub fn without_pred(&self) -> OpHandle {
+        OpHandle {
+            id: self.id,
+            op: InternalOp {
+                action: self.op.action.clone(),
+                obj: self.op.obj,
+                key: self.op.key,
+                pred: Vec::new(),
+                insert: self.op.insert,
+            },
+            delta: self.delta,
+        }
+    }
+
     pub fn child(&self) -> Option<ObjectId> {
         match &self.action {
             InternalOpType::Make(_) => Some(self.id.into()),
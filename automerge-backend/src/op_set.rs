@@ -6,7 +6,10 @@
 //! document::state) the implementation fetches the root object ID's history
 //! and then recursively walks through the tree of histories constructing the
 //! state. Obviously this is not very efficient.
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::OnceLock,
+};
 
 use automerge_protocol as amp;
 use fxhash::FxBuildHasher;
@@ -15,9 +18,11 @@ use tracing::instrument;
 
 use crate::{
     actor_map::ActorMap,
+    columnar::{DocOp, ObjectIndex},
     error::AutomergeError,
-    internal::{InternalOpType, Key, ObjectId},
-    object_store::ObjState,
+    internal::{ActorId, InternalOp, InternalOpType, Key, ObjectId, OpId},
+    memory::{hash_map_size, hash_set_size, vec_size, MemoryUsage},
+    object_store::{Inbound, ObjState},
     op_handle::OpHandle,
     ordered_set::OrderedSet,
     patches::{IncrementalPatch, PatchWorkshop},
@@ -45,6 +50,11 @@ pub(crate) struct OpSet {
     pub deps: HashSet<amp::ChangeHash>,
     pub max_op: u64,
     cursors: HashMap<ObjectId, Vec<CursorState>>,
+    /// The sequence object each element was inserted into, so that we can find the target of a
+    /// cursor without searching every object
+    element_objects: HashMap<OpId, ObjectId, FxBuildHasher>,
+    /// Objects from a loaded document which have not been changed since, see `load_document`
+    unloaded: HashMap<ObjectId, UnloadedObject, FxBuildHasher>,
 }
 
 impl Default for OpSet {
@@ -63,7 +73,86 @@ impl OpSet {
             max_op: 0,
             deps: HashSet::default(),
             cursors: HashMap::new(),
+            element_objects: HashMap::default(),
+            unloaded: HashMap::default(),
+        }
+    }
+
+    /// Set up the objects in a document chunk without building their state. The state of each
+    /// object is built from its ops the first time it is read or changed.
+    ///
+    /// This is only meant to be called on an empty op set. The ops of each object are checked
+    /// here so that building the state of an object later on cannot fail.
+    pub(crate) fn load_document(
+        &mut self,
+        index: ObjectIndex,
+        doc_actors: &[amp::ActorId],
+        actors: &mut ActorMap,
+    ) -> Result<(), AutomergeError> {
+        let doc_actors: Vec<ActorId> = doc_actors.iter().map(|a| actors.import_actor(a)).collect();
+        let mut obj_types: HashMap<ObjectId, amp::ObjType, FxBuildHasher> = HashMap::default();
+        obj_types.insert(ObjectId::Root, amp::ObjType::Map);
+        let mut inbound: HashMap<ObjectId, Inbound, FxBuildHasher> = HashMap::default();
+        let mut cursors = Vec::new();
+        let mut object_ops: HashMap<ObjectId, Vec<OpHandle>, FxBuildHasher> = HashMap::default();
+
+        for (obj, doc_ops) in index.into_objects() {
+            let obj = actors.import_obj(&obj);
+            let mut ops = Vec::with_capacity(doc_ops.len());
+            for doc_op in doc_ops {
+                let visible = doc_op.succ.is_empty();
+                let op = import_doc_op(doc_op, &doc_actors, actors);
+                if let (Some(child), Some(obj_type)) = (op.child(), op.obj_type()) {
+                    obj_types.insert(child, obj_type);
+                    if visible {
+                        inbound.insert(
+                            child,
+                            Inbound {
+                                obj,
+                                key: op.operation_key().into_owned(),
+                            },
+                        );
+                    }
+                }
+                if op.insert {
+                    self.element_objects.insert(op.id, obj);
+                }
+                if visible {
+                    if let InternalOpType::Set(amp::ScalarValue::Cursor(_)) = op.action {
+                        cursors.push(op.clone());
+                    }
+                }
+                ops.push(op);
+            }
+            // ops always have a greater counter than the ops they depend on
+            ops.sort_unstable_by_key(|op| (op.id.0, (op.id.1).0));
+            object_ops.insert(obj, ops);
+        }
+
+        self.objs.remove(&ObjectId::Root);
+        for (object_id, obj_type) in obj_types {
+            let ops = object_ops.remove(&object_id).unwrap_or_default();
+            for op in &ops {
+                check_loaded_op(&object_id, obj_type, op, actors)?;
+            }
+            self.unloaded.insert(
+                object_id,
+                UnloadedObject {
+                    obj_type,
+                    inbound: inbound.remove(&object_id),
+                    ops,
+                    state: OnceLock::new(),
+                },
+            );
+        }
+        if !object_ops.is_empty() {
+            return Err(AutomergeError::MissingObjectError);
+        }
+
+        for op in cursors {
+            self.add_cursor(&op, actors)?;
         }
+        Ok(())
     }
 
     pub(crate) fn apply_ops(
@@ -79,6 +168,25 @@ impl OpSet {
         Ok(())
     }
 
+    pub fn add_memory_usage(&self, usage: &mut MemoryUsage) {
+        usage.objects += hash_map_size(&self.objs)
+            + hash_set_size(&self.deps)
+            + hash_map_size(&self.cursors)
+            + self.cursors.values().map(vec_size).sum::<usize>();
+        usage.objects += hash_map_size(&self.unloaded);
+        usage.sequence_indices += hash_map_size(&self.element_objects);
+        for obj in self.objs.values() {
+            obj.add_memory_usage(usage);
+        }
+        for obj in self.unloaded.values() {
+            usage.ops += vec_size(&obj.ops);
+            usage.ops += obj.ops.iter().map(|op| vec_size(&op.pred)).sum::<usize>();
+            if let Some(state) = obj.state.get() {
+                state.add_memory_usage(usage);
+            }
+        }
+    }
+
     pub fn heads(&self) -> Vec<amp::ChangeHash> {
         let mut deps: Vec<_> = self.deps.iter().copied().collect();
         deps.sort_unstable();
@@ -97,99 +205,18 @@ impl OpSet {
             self.objs.insert(child, ObjState::new(obj_type));
         }
 
-        if let InternalOpType::Set(amp::ScalarValue::Cursor(ref oid)) = op.op.action {
-            tracing::debug!(referred_opid=?oid, "Adding cursor");
-            let internal_opid = actors.import_opid(oid);
-            let mut target_found = false;
-            for (obj_id, obj) in &self.objs {
-                if obj.insertions.contains_key(&internal_opid.into()) {
-                    target_found = true;
-                    self.cursors.entry(*obj_id).or_default().push(CursorState {
-                        referring_object_id: actors.export_obj(&op.obj),
-                        internal_referring_object_id: op.obj,
-                        key: op.key.clone(),
-                        element_opid: oid.clone(),
-                        internal_element_opid: internal_opid,
-                        index: obj.index_of(internal_opid).unwrap_or(0),
-                        referred_object_id: actors.export_obj(obj_id),
-                        internal_referred_object_id: *obj_id,
-                    });
-                }
-            }
-            if !target_found {
-                return Err(AutomergeError::InvalidCursor { opid: oid.clone() });
-            }
+        if let InternalOpType::Set(amp::ScalarValue::Cursor(_)) = op.op.action {
+            self.add_cursor(&op, actors)?;
         }
 
-        let object_id = op.obj;
-        let object = self.get_obj_mut(&object_id)?;
-
-        let overwritten = if object.is_seq() {
-            if op.insert {
-                object.insert_after(
-                    op.key.as_element_id().ok_or(AutomergeError::MapKeyInSeq)?,
-                    op.clone(),
-                    actors,
-                );
-            }
-
-            let ops = object
-                .props
-                .entry(op.operation_key().into_owned())
-                .or_default();
-            let before = !ops.is_empty();
-            let (op, overwritten_ops) = ops.incorporate_new_op(op);
-            let after = !ops.is_empty();
-
-            match (before, after) {
-                (true, true) => {
-                    tracing::debug!("updating existing element");
-                    let opid = op
-                        .operation_key()
-                        .to_opid()
-                        .ok_or(AutomergeError::HeadToOpId)?;
-                    let ops = ops.clone();
-                    let index = object.index_of(opid).unwrap_or(0);
-
-                    patch.record_seq_updates(&object_id, object, index, ops.iter(), actors);
-                }
-                (true, false) => {
-                    let opid = op
-                        .operation_key()
-                        .to_opid()
-                        .ok_or(AutomergeError::HeadToOpId)?;
-                    let index = object.seq.remove_key(&opid).unwrap();
-                    tracing::debug!(opid=?opid, index=%index, "deleting element");
-                    patch.record_seq_remove(&object_id, op.clone(), index);
-                }
-                (false, true) => {
-                    let id = op
-                        .operation_key()
-                        .to_opid()
-                        .ok_or(AutomergeError::HeadToOpId)?;
-                    let index = object.index_of(id).unwrap_or(0);
-                    tracing::debug!(new_id=?id, index=%index, after=?op.operation_key(), "inserting new element");
-                    object.seq.insert_index(index, id);
-                    patch.record_seq_insert(&object_id, op.clone(), index, op.id);
-                }
-                (false, false) => {}
-            };
-
-            self.unlink(&op, &overwritten_ops)?;
+        if op.insert {
+            self.element_objects.insert(op.id, op.obj);
+        }
 
-            overwritten_ops
-        } else {
-            let ops = object.props.entry(op.key.clone()).or_default();
-            let before = !ops.is_empty();
-            let (op, overwritten_ops) = ops.incorporate_new_op(op);
-            let after = !ops.is_empty();
-            self.unlink(&op, &overwritten_ops)?;
-
-            if before || after {
-                patch.record_set(&object_id, op);
-            }
-            overwritten_ops
-        };
+        let object_id = op.obj;
+        let object = self.get_obj_mut(&object_id, actors)?;
+        let (op, overwritten) = apply_op_to_object(&object_id, object, op, actors, patch)?;
+        self.unlink(&op, &overwritten)?;
 
         for op in overwritten {
             if let InternalOpType::Set(amp::ScalarValue::Cursor(ref oid)) = op.op.action {
@@ -201,26 +228,98 @@ impl OpSet {
         Ok(())
     }
 
+    fn add_cursor(&mut self, op: &OpHandle, actors: &mut ActorMap) -> Result<(), AutomergeError> {
+        if let InternalOpType::Set(amp::ScalarValue::Cursor(ref oid)) = op.op.action {
+            tracing::debug!(referred_opid=?oid, "Adding cursor");
+            let internal_opid = actors.import_opid(oid);
+            let obj_id = *self
+                .element_objects
+                .get(&internal_opid)
+                .ok_or_else(|| AutomergeError::InvalidCursor { opid: oid.clone() })?;
+            let index = self
+                .get_obj_mut(&obj_id, actors)?
+                .index_of(internal_opid)
+                .unwrap_or(0);
+            self.cursors.entry(obj_id).or_default().push(CursorState {
+                referring_object_id: actors.export_obj(&op.obj),
+                internal_referring_object_id: op.obj,
+                key: op.key,
+                element_opid: oid.clone(),
+                internal_element_opid: internal_opid,
+                index,
+                referred_object_id: actors.export_obj(&obj_id),
+                internal_referred_object_id: obj_id,
+            });
+        }
+        Ok(())
+    }
+
     fn unlink(&mut self, op: &OpHandle, overwritten: &[OpHandle]) -> Result<(), AutomergeError> {
         if let Some(child) = op.child() {
-            self.get_obj_mut(&child)?.inbound = Some(op.clone());
+            self.set_inbound(
+                &child,
+                Some(Inbound {
+                    obj: op.obj,
+                    key: op.operation_key().into_owned(),
+                }),
+            )?;
         }
 
         for old in overwritten.iter() {
             if let Some(child) = old.child() {
-                self.get_obj_mut(&child)?.inbound = None;
+                self.set_inbound(&child, None)?;
             }
         }
         Ok(())
     }
 
-    pub fn get_obj(&self, object_id: &ObjectId) -> Result<&ObjState, AutomergeError> {
-        self.objs
+    /// Update the inbound op of an object without building its state if it has not been built
+    fn set_inbound(
+        &mut self,
+        object_id: &ObjectId,
+        inbound: Option<Inbound>,
+    ) -> Result<(), AutomergeError> {
+        if let Some(obj) = self.objs.get_mut(object_id) {
+            obj.inbound = inbound;
+        } else {
+            let obj = self
+                .unloaded
+                .get_mut(object_id)
+                .ok_or(AutomergeError::MissingObjectError)?;
+            obj.inbound = inbound;
+            if let Some(state) = obj.state.get_mut() {
+                state.inbound = inbound;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get_obj(
+        &self,
+        object_id: &ObjectId,
+        actors: &ActorMap,
+    ) -> Result<&ObjState, AutomergeError> {
+        if let Some(obj) = self.objs.get(object_id) {
+            return Ok(obj);
+        }
+        self.unloaded
             .get(object_id)
+            .map(|obj| obj.state.get_or_init(|| obj.build(object_id, actors)))
             .ok_or(AutomergeError::MissingObjectError)
     }
 
-    fn get_obj_mut(&mut self, object_id: &ObjectId) -> Result<&mut ObjState, AutomergeError> {
+    fn get_obj_mut(
+        &mut self,
+        object_id: &ObjectId,
+        actors: &ActorMap,
+    ) -> Result<&mut ObjState, AutomergeError> {
+        if let Some(mut obj) = self.unloaded.remove(object_id) {
+            let state = obj
+                .state
+                .take()
+                .unwrap_or_else(|| obj.build(object_id, actors));
+            self.objs.insert(*object_id, state);
+        }
         self.objs
             .get_mut(object_id)
             .ok_or(AutomergeError::MissingObjectError)
@@ -244,7 +343,7 @@ impl OpSet {
                         cursor_changes
                             .entry(cursor.internal_referring_object_id)
                             .or_default()
-                            .push(cursor.key.clone());
+                            .push(cursor.key);
                     }
                 }
             }
@@ -273,6 +372,177 @@ impl OpSet {
     }
 }
 
+/// Apply `op` to `object`, returning the op as it was incorporated and the ops it overwrote. This
+/// only changes `object` so the caller is responsible for updating any objects created or
+/// overwritten by `op`.
+fn apply_op_to_object(
+    object_id: &ObjectId,
+    object: &mut ObjState,
+    op: OpHandle,
+    actors: &ActorMap,
+    patch: &mut IncrementalPatch,
+) -> Result<(OpHandle, Vec<OpHandle>), AutomergeError> {
+    if object.is_seq() {
+        if op.insert {
+            object.insert_after(
+                op.key.as_element_id().ok_or(AutomergeError::MapKeyInSeq)?,
+                &op,
+                actors,
+            );
+        }
+
+        let ops = object
+            .props
+            .entry(op.operation_key().into_owned())
+            .or_default();
+        let before = !ops.is_empty();
+        let (op, overwritten_ops) = ops.incorporate_new_op(op);
+        let after = !ops.is_empty();
+
+        match (before, after) {
+            (true, true) => {
+                tracing::debug!("updating existing element");
+                let opid = op
+                    .operation_key()
+                    .to_opid()
+                    .ok_or(AutomergeError::HeadToOpId)?;
+                let ops = ops.clone();
+                let index = object.index_of(opid).unwrap_or(0);
+
+                patch.record_seq_updates(object_id, object, index, ops.iter(), actors);
+            }
+            (true, false) => {
+                let opid = op
+                    .operation_key()
+                    .to_opid()
+                    .ok_or(AutomergeError::HeadToOpId)?;
+                let index = object.seq.remove_key(&opid).unwrap();
+                tracing::debug!(opid=?opid, index=%index, "deleting element");
+                patch.record_seq_remove(object_id, op.clone(), index);
+            }
+            (false, true) => {
+                let id = op
+                    .operation_key()
+                    .to_opid()
+                    .ok_or(AutomergeError::HeadToOpId)?;
+                let index = object.index_of(id).unwrap_or(0);
+                tracing::debug!(new_id=?id, index=%index, after=?op.operation_key(), "inserting new element");
+                object.seq.insert_index(index, id);
+                patch.record_seq_insert(object_id, op.clone(), index, op.id);
+            }
+            (false, false) => {}
+        };
+
+        Ok((op, overwritten_ops))
+    } else {
+        if object.obj_type == amp::ObjType::Set {
+            check_set_op(object_id, &op, actors)?;
+        }
+        let ops = object.props.entry(op.key).or_default();
+        let before = !ops.is_empty();
+        let (op, overwritten_ops) = ops.incorporate_new_op(op);
+        let after = !ops.is_empty();
+
+        if before || after {
+            patch.record_set(object_id, op.clone());
+        }
+        Ok((op, overwritten_ops))
+    }
+}
+
+/// Check that [`apply_op_to_object`] can apply `op` to an object of type `obj_type`. These are
+/// the only ways in which it can fail, so the state of a loaded object can be built from ops
+/// which pass this check without any errors.
+fn check_loaded_op(
+    object_id: &ObjectId,
+    obj_type: amp::ObjType,
+    op: &OpHandle,
+    actors: &ActorMap,
+) -> Result<(), AutomergeError> {
+    if obj_type.is_sequence() {
+        if op.insert && op.key.as_element_id().is_none() {
+            return Err(AutomergeError::MapKeyInSeq);
+        }
+        op.operation_key()
+            .to_opid()
+            .ok_or(AutomergeError::HeadToOpId)?;
+    } else if obj_type == amp::ObjType::Set {
+        check_set_op(object_id, op, actors)?;
+    }
+    Ok(())
+}
+
+/// Sets only contain scalar values, each stored under the key given by
+/// [`amp::ScalarValue::set_key`]
+fn check_set_op(
+    object_id: &ObjectId,
+    op: &OpHandle,
+    actors: &ActorMap,
+) -> Result<(), AutomergeError> {
+    let valid = match &op.action {
+        InternalOpType::Del => true,
+        InternalOpType::Set(value) => value
+            .set_key()
+            .is_some_and(|key| key == actors.key_to_string(&op.key)),
+        _ => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(AutomergeError::InvalidSetOp {
+            object_id: actors.export_obj(object_id),
+            opid: actors.export_opid(&op.id),
+        })
+    }
+}
+
+fn import_doc_op(op: DocOp, doc_actors: &[ActorId], actors: &mut ActorMap) -> OpHandle {
+    OpHandle {
+        id: OpId(op.ctr, doc_actors[op.actor]),
+        op: InternalOp {
+            action: op.action,
+            obj: actors.import_obj(&op.obj),
+            key: actors.import_key(&op.key),
+            pred: op
+                .pred
+                .iter()
+                .map(|(ctr, actor)| OpId(*ctr, doc_actors[*actor]))
+                .collect(),
+            insert: op.insert,
+        },
+        delta: 0,
+    }
+}
+
+/// An object from a loaded document whose state is only built when it is first needed.
+///
+/// Reads build the state in `state` and leave the object here, changes move the state into
+/// `OpSet::objs`.
+#[derive(Debug, PartialEq, Clone)]
+struct UnloadedObject {
+    obj_type: amp::ObjType,
+    inbound: Option<Inbound>,
+    /// The ops on this object in an order in which they can be applied
+    ops: Vec<OpHandle>,
+    state: OnceLock<ObjState>,
+}
+
+impl UnloadedObject {
+    fn build(&self, object_id: &ObjectId, actors: &ActorMap) -> ObjState {
+        let mut state = ObjState::new(self.obj_type);
+        // we never look at the patch, it is just somewhere for `apply_op_to_object` to write to
+        let mut patch = IncrementalPatch::new();
+        for op in &self.ops {
+            // `load_document` ran `check_loaded_op` on every op, which rejects all the ops this
+            // can fail on
+            apply_op_to_object(object_id, &mut state, op.clone(), actors, &mut patch)
+                .expect("ops were checked when the document was loaded");
+        }
+        state.inbound = self.inbound;
+        state
+    }
+}
+
 /// `CursorState` is the information we need to track in order to update cursors as changes come
 /// in. Cursors are created by `Set` operations and therefore live in a particular object (the
 /// "referring object") and point at an element in a sequence (the "referred" object). For example
@@ -323,7 +593,7 @@ struct PatchWorkshopImpl<'a> {
 
 impl<'a> PatchWorkshop for PatchWorkshopImpl<'a> {
     fn get_obj(&self, object_id: &ObjectId) -> Option<&ObjState> {
-        self.opset.get_obj(object_id).ok()
+        self.opset.get_obj(object_id, self.actors).ok()
     }
 
     fn find_cursor(&self, opid: &amp::OpId) -> Option<amp::CursorDiff> {
@@ -13,6 +13,8 @@ use std::{
 use fxhash::FxBuildHasher;
 use rand::{rngs::SmallRng, Rng, SeedableRng};
 
+use crate::memory::{hash_map_size, vec_size};
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 struct Link<K>
 where
@@ -345,6 +347,16 @@ impl<K> SkipList<K>
 where
     K: Copy + Clone + Debug + Hash + PartialEq + Eq,
 {
+    pub fn heap_size(&self) -> usize {
+        hash_map_size(&self.nodes)
+            + vec_size(&self.head.links)
+            + self
+                .nodes
+                .values()
+                .map(|node| vec_size(&node.links))
+                .sum::<usize>()
+    }
+
     pub fn new() -> SkipList<K> {
         let nodes = HashMap::default();
         let head = Node {
@@ -12,7 +12,9 @@ impl Edits {
 
     /// Append an edit to this sequence, collapsing it into the last edit if possible.
     ///
-    /// The collapsing handles conversion of a sequence of inserts to a multi-insert.
+    /// The collapsing handles conversion of a sequence of inserts to a multi-insert, and of
+    /// removes of adjacent elements to a single remove of a range, whether the elements were
+    /// removed going forwards (like the delete key) or backwards (like backspace).
     pub(crate) fn append_edit(&mut self, edit: amp::DiffEdit) {
         if let Some(mut last) = self.0.last_mut() {
             match (&mut last, edit) {
@@ -78,13 +80,46 @@ impl Edits {
                     //values.append(value).unwrap_none();
                     values.append(value);
                 }
+                (
+                    amp::DiffEdit::MultiElementInsert(amp::MultiElementInsert {
+                        index,
+                        elem_id,
+                        values,
+                    }),
+                    amp::DiffEdit::MultiElementInsert(amp::MultiElementInsert {
+                        index: next_index,
+                        elem_id: next_elem_id,
+                        values: next_values,
+                    }),
+                ) if *index + (values.len() as u64) == next_index
+                    // Ensure the values have a common type
+                    // `unwrap` is safe: multi inserts are never empty
+                    && std::mem::discriminant(values.get(0).unwrap())
+                        == std::mem::discriminant(next_values.get(0).unwrap())
+                    && next_elem_id.as_opid().is_some_and(|next_op_id| {
+                        elem_id
+                            .as_opid()
+                            .unwrap()
+                            .delta(next_op_id, values.len() as u64)
+                    }) =>
+                {
+                    for value in next_values.iter() {
+                        values.append(value.clone());
+                    }
+                }
+                // The new range of removed elements ends at or starts at (or both) the position
+                // of the last range, so together they remove one contiguous range of the
+                // original sequence
                 (
                     amp::DiffEdit::Remove { index, count },
                     amp::DiffEdit::Remove {
                         index: new_index,
                         count: new_count,
                     },
-                ) if *index == new_index => *count += new_count,
+                ) if new_index <= *index && *index <= new_index + new_count => {
+                    *index = new_index;
+                    *count += new_count;
+                }
                 (_, edit) => self.0.push(edit),
             }
         } else {
@@ -96,3 +131,97 @@ impl Edits {
         self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(index: u64, op_id: &amp::OpId, value: &str) -> amp::DiffEdit {
+        amp::DiffEdit::SingleElementInsert {
+            index,
+            elem_id: op_id.clone().into(),
+            op_id: op_id.clone(),
+            value: amp::Diff::Value(amp::ScalarValue::Str(value.into())),
+        }
+    }
+
+    fn coalesce(edits: Vec<amp::DiffEdit>) -> Vec<amp::DiffEdit> {
+        let mut coalesced = Edits::new();
+        for edit in edits {
+            coalesced.append_edit(edit);
+        }
+        coalesced.into_vec()
+    }
+
+    #[test]
+    fn consecutive_inserts_become_one_multi_insert() {
+        let actor = amp::ActorId::random();
+        let edits = (0..4)
+            .map(|i| insert(i + 2, &actor.op_id_at(i + 10), "a"))
+            .collect();
+        let expected_values: amp::ScalarValues = vec![amp::ScalarValue::Str("a".into()); 4]
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            coalesce(edits),
+            vec![amp::DiffEdit::MultiElementInsert(amp::MultiElementInsert {
+                index: 2,
+                elem_id: actor.op_id_at(10).into(),
+                values: expected_values,
+            })]
+        );
+    }
+
+    #[test]
+    fn adjacent_multi_inserts_are_joined() {
+        let actor = amp::ActorId::random();
+        let first = coalesce(vec![
+            insert(0, &actor.op_id_at(1), "a"),
+            insert(1, &actor.op_id_at(2), "b"),
+        ]);
+        let second = coalesce(vec![
+            insert(2, &actor.op_id_at(3), "c"),
+            insert(3, &actor.op_id_at(4), "d"),
+        ]);
+        let expected_values: amp::ScalarValues = vec!["a", "b", "c", "d"]
+            .into_iter()
+            .map(|s| amp::ScalarValue::Str(s.into()))
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            coalesce(first.into_iter().chain(second).collect()),
+            vec![amp::DiffEdit::MultiElementInsert(amp::MultiElementInsert {
+                index: 0,
+                elem_id: actor.op_id_at(1).into(),
+                values: expected_values,
+            })]
+        );
+    }
+
+    #[test]
+    fn inserts_from_different_actors_are_not_joined() {
+        let edits = vec![
+            insert(0, &amp::ActorId::random().op_id_at(1), "a"),
+            insert(1, &amp::ActorId::random().op_id_at(2), "b"),
+        ];
+        assert_eq!(coalesce(edits.clone()), edits);
+    }
+
+    #[test]
+    fn forward_and_backward_removes_become_a_range() {
+        let remove = |index, count| amp::DiffEdit::Remove { index, count };
+        // delete key at index 5 three times, then backspace twice
+        let edits = vec![
+            remove(5, 1),
+            remove(5, 1),
+            remove(5, 1),
+            remove(4, 1),
+            remove(3, 1),
+        ];
+        assert_eq!(coalesce(edits), vec![remove(3, 5)]);
+        // removes which are not adjacent are kept apart
+        let edits = vec![remove(5, 1), remove(3, 1)];
+        assert_eq!(coalesce(edits.clone()), edits);
+    }
+}
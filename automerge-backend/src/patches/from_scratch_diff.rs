@@ -197,5 +197,9 @@ fn construct_object(object_id: &ObjectId, workshop: &dyn PatchWorkshop) -> amp::
         amp::ObjType::Table => amp::Diff::Table(construct_table(object_id, object, workshop)),
         amp::ObjType::List => amp::Diff::List(construct_list(object_id, object, workshop)),
         amp::ObjType::Text => amp::Diff::Text(construct_text(object_id, object, workshop)),
+        amp::ObjType::Set => {
+            let amp::MapDiff { object_id, props } = construct_map(object_id, object, workshop);
+            amp::Diff::Set(amp::SetDiff { object_id, props })
+        }
     }
 }
@@ -88,22 +88,23 @@ impl IncrementalPatch {
         let diffs = self.0.entry(*oid).or_default();
         let mut new_diffs = Vec::new();
         'outer: for op in ops {
-            let i = op
-                .key
-                .to_opid()
+            // the key of an insert op is the element it was inserted after, the element itself
+            // is the operation key
+            let elem_opid = op.operation_key().to_opid();
+            let i = elem_opid
                 .and_then(|opid| object.index_of(opid))
                 .unwrap_or(0);
             if i == index {
                 // go through existing diffs and find an insert
                 for diff in diffs.iter_mut() {
                     match diff {
-                        // if this insert was for the index we are now updating, and it is from the
-                        // same actor,
-                        // then change the insert to just insert our data instead
-                        PendingDiff::SeqInsert(original_op, index, original_opid)
-                            if i == *index && original_op.id.1 == op.id.1 =>
+                        // if this insert was for the element we are now updating, and it is from
+                        // the same actor, then change the insert to just insert our data instead
+                        PendingDiff::SeqInsert(original_op, insert_index, original_opid)
+                            if Some(*original_opid) == elem_opid && original_op.id.1 == op.id.1 =>
                         {
-                            *diff = PendingDiff::SeqInsert(op.clone(), *index, *original_opid);
+                            *diff =
+                                PendingDiff::SeqInsert(op.clone(), *insert_index, *original_opid);
                             continue 'outer;
                         }
                         _ => {}
@@ -146,10 +147,16 @@ impl IncrementalPatch {
 
         let mut objs: Vec<_> = self.changed_object_ids().copied().collect();
         while let Some(obj_id) = objs.pop() {
-            if let Some(inbound) = workshop
+            let inbound = workshop
                 .get_obj(&obj_id)
-                .and_then(|obj| obj.inbound.as_ref())
-            {
+                .and_then(|obj| obj.inbound)
+                .and_then(|inbound| {
+                    workshop
+                        .get_obj(&inbound.obj)?
+                        .conflicts(&inbound.key)
+                        .find(|op| op.child() == Some(obj_id))
+                });
+            if let Some(inbound) = inbound {
                 if !self.0.contains_key(&inbound.obj) {
                     // our parent was not changed - walk up the tree and try them too
                     objs.push(inbound.obj);
@@ -205,6 +212,11 @@ impl IncrementalPatch {
                 amp::ObjType::Table => {
                     amp::Diff::Table(self.gen_table_diff(obj_id, obj, pending, workshop))
                 }
+                amp::ObjType::Set => {
+                    let amp::MapDiff { object_id, props } =
+                        self.gen_map_diff(obj_id, obj, pending, workshop);
+                    amp::Diff::Set(amp::SetDiff { object_id, props })
+                }
             }
         } else {
             // no changes so just return empty edits or props
@@ -225,6 +237,10 @@ impl IncrementalPatch {
                     object_id: workshop.make_external_objid(obj_id),
                     edits: Vec::new(),
                 }),
+                amp::ObjType::Set => amp::Diff::Set(amp::SetDiff {
+                    object_id: workshop.make_external_objid(obj_id),
+                    props: HashMap::new(),
+                }),
             }
         }
     }
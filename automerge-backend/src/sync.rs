@@ -13,13 +13,16 @@ use crate::{
 };
 
 mod bloom;
+mod presence;
 mod state;
 
 pub use bloom::BloomFilter;
+pub use presence::{EphemeralMessage, Presence, PresenceStore};
 pub use state::{SyncHave, SyncState};
 
 const HASH_SIZE: usize = 32; // 256 bits = 32 bytes
 const MESSAGE_TYPE_SYNC: u8 = 0x42; // first byte of a sync message, for identification
+const MESSAGE_TYPE_EPHEMERAL: u8 = 0x44; // first byte of an ephemeral message, for identification
 
 impl Backend {
     pub fn generate_sync_message(&self, sync_state: &mut SyncState) -> Option<SyncMessage> {
@@ -307,6 +310,49 @@ impl SyncMessage {
     }
 }
 
+/// Any of the messages which peers exchange, so that sync and ephemeral messages can be sent over
+/// the same connection and told apart by their first byte.
+#[derive(Debug, Clone)]
+pub enum PeerMessage {
+    Sync(SyncMessage),
+    Ephemeral(EphemeralMessage),
+}
+
+impl PeerMessage {
+    pub fn encode(self) -> Result<Vec<u8>, encoding::Error> {
+        match self {
+            PeerMessage::Sync(message) => message.encode(),
+            PeerMessage::Ephemeral(message) => message.encode(),
+        }
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<PeerMessage, decoding::Error> {
+        match bytes.first() {
+            Some(&MESSAGE_TYPE_SYNC) => SyncMessage::decode(bytes).map(PeerMessage::Sync),
+            Some(&MESSAGE_TYPE_EPHEMERAL) => {
+                EphemeralMessage::decode(bytes).map(PeerMessage::Ephemeral)
+            }
+            Some(&found) => Err(decoding::Error::WrongType {
+                expected_one_of: vec![MESSAGE_TYPE_SYNC, MESSAGE_TYPE_EPHEMERAL],
+                found,
+            }),
+            None => Err(decoding::Error::NotEnoughBytes),
+        }
+    }
+}
+
+impl From<SyncMessage> for PeerMessage {
+    fn from(message: SyncMessage) -> Self {
+        PeerMessage::Sync(message)
+    }
+}
+
+impl From<EphemeralMessage> for PeerMessage {
+    fn from(message: EphemeralMessage) -> Self {
+        PeerMessage::Ephemeral(message)
+    }
+}
+
 fn encode_hashes(buf: &mut Vec<u8>, hashes: &[ChangeHash]) -> Result<(), encoding::Error> {
     debug_assert!(
         hashes.windows(2).all(|h| h[0] <= h[1]),
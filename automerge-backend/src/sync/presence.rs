@@ -0,0 +1,248 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use automerge_protocol::ActorId;
+
+use super::MESSAGE_TYPE_EPHEMERAL;
+use crate::{decoding, decoding::Decoder, encoding, encoding::Encodable};
+
+/// The presence of one actor, e.g. their name and selection in an editor.
+///
+/// The payload is opaque to automerge, applications choose its format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Presence {
+    pub actor_id: ActorId,
+    /// Increases every time the actor updates their presence so that stale updates which arrive
+    /// late can be ignored
+    pub counter: u64,
+    /// How many milliseconds after it is received this presence expires, a presence with a TTL
+    /// of zero means the actor has left
+    pub ttl_ms: u64,
+    pub payload: Vec<u8>,
+}
+
+/// A message carrying presence which is not stored in the document.
+///
+/// These can be sent over the same connection as [`SyncMessage`](crate::SyncMessage)s, use
+/// [`PeerMessage`](crate::PeerMessage) to decode either kind.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EphemeralMessage {
+    pub presences: Vec<Presence>,
+}
+
+impl EphemeralMessage {
+    pub fn encode(self) -> Result<Vec<u8>, encoding::Error> {
+        let mut buf = vec![MESSAGE_TYPE_EPHEMERAL];
+        (self.presences.len() as u32).encode(&mut buf)?;
+        for presence in self.presences {
+            presence.actor_id.to_bytes().encode(&mut buf)?;
+            presence.counter.encode(&mut buf)?;
+            presence.ttl_ms.encode(&mut buf)?;
+            presence.payload.as_slice().encode(&mut buf)?;
+        }
+        Ok(buf)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<EphemeralMessage, decoding::Error> {
+        let mut decoder = Decoder::new(Cow::Borrowed(bytes));
+
+        let message_type = decoder.read::<u8>()?;
+        if message_type != MESSAGE_TYPE_EPHEMERAL {
+            return Err(decoding::Error::WrongType {
+                expected_one_of: vec![MESSAGE_TYPE_EPHEMERAL],
+                found: message_type,
+            });
+        }
+
+        // the count comes from the peer so it isn't trusted to size the vector
+        let presence_count = decoder.read::<u32>()?;
+        let mut presences = Vec::new();
+        for _ in 0..presence_count {
+            presences.push(Presence {
+                actor_id: decoder.read()?,
+                counter: decoder.read()?,
+                ttl_ms: decoder.read()?,
+                payload: decoder.read()?,
+            });
+        }
+        if !decoder.done() {
+            return Err(decoding::Error::TrailingBytes);
+        }
+        Ok(EphemeralMessage { presences })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PresenceEntry {
+    counter: u64,
+    expires_at: u64,
+    /// `None` once the actor has left. The entry is kept until their presence would have
+    /// expired so that an update sent before they left which arrives late is still ignored.
+    payload: Option<Vec<u8>>,
+}
+
+/// The presence of the local actor and the peers we have heard from.
+///
+/// Entries expire once their TTL has passed, and nothing here touches the document or its
+/// history. Times are milliseconds from any clock the caller likes, so long as it is the same
+/// one for every call; TTLs are relative so peers' clocks don't need to agree.
+#[derive(Debug, Clone)]
+pub struct PresenceStore {
+    actor_id: ActorId,
+    counter: u64,
+    peers: HashMap<ActorId, PresenceEntry>,
+}
+
+impl PresenceStore {
+    pub fn new(actor_id: ActorId) -> PresenceStore {
+        PresenceStore {
+            actor_id,
+            counter: 0,
+            peers: HashMap::new(),
+        }
+    }
+
+    pub fn actor_id(&self) -> &ActorId {
+        &self.actor_id
+    }
+
+    /// Set the local actor's presence, returning the message to send to peers.
+    ///
+    /// The counter starts from `now_ms` so that a peer which restarts isn't ignored until its
+    /// old presence expires.
+    pub fn set_local(&mut self, payload: Vec<u8>, ttl_ms: u64, now_ms: u64) -> EphemeralMessage {
+        self.counter = (self.counter + 1).max(now_ms);
+        if ttl_ms == 0 {
+            self.peers.remove(&self.actor_id);
+        } else {
+            self.peers.insert(
+                self.actor_id.clone(),
+                PresenceEntry {
+                    counter: self.counter,
+                    expires_at: now_ms.saturating_add(ttl_ms),
+                    payload: Some(payload.clone()),
+                },
+            );
+        }
+        EphemeralMessage {
+            presences: vec![Presence {
+                actor_id: self.actor_id.clone(),
+                counter: self.counter,
+                ttl_ms,
+                payload,
+            }],
+        }
+    }
+
+    /// Remove the local actor's presence, returning the message telling peers we have left.
+    pub fn clear_local(&mut self, now_ms: u64) -> EphemeralMessage {
+        self.set_local(Vec::new(), 0, now_ms)
+    }
+
+    fn presence(&self, actor_id: &ActorId, now_ms: u64) -> Option<Presence> {
+        self.peers
+            .get(actor_id)
+            .filter(|entry| entry.expires_at > now_ms)
+            .map(|entry| match &entry.payload {
+                Some(payload) => Presence {
+                    actor_id: actor_id.clone(),
+                    counter: entry.counter,
+                    ttl_ms: entry.expires_at - now_ms,
+                    payload: payload.clone(),
+                },
+                None => Presence {
+                    actor_id: actor_id.clone(),
+                    counter: entry.counter,
+                    ttl_ms: 0,
+                    payload: Vec::new(),
+                },
+            })
+    }
+
+    /// A message with every presence which has not expired, with their remaining TTLs, and the
+    /// actors who have recently left. This is what a peer which relays presence, e.g. a server,
+    /// should send on.
+    pub fn generate_message(&self, now_ms: u64) -> Option<EphemeralMessage> {
+        let mut presences: Vec<_> = self
+            .peers
+            .keys()
+            .filter_map(|actor_id| self.presence(actor_id, now_ms))
+            .collect();
+        if presences.is_empty() {
+            return None;
+        }
+        presences.sort_by(|a, b| a.actor_id.cmp(&b.actor_id));
+        Some(EphemeralMessage { presences })
+    }
+
+    /// Apply the presences in `message`, returning the actors whose presence changed.
+    ///
+    /// Presences for the local actor, or with a counter no greater than the one we already have
+    /// for the actor, are ignored.
+    pub fn receive_message(&mut self, message: EphemeralMessage, now_ms: u64) -> Vec<ActorId> {
+        let mut changed = Vec::new();
+        for presence in message.presences {
+            if presence.actor_id == self.actor_id {
+                continue;
+            }
+            let stale = matches!(
+                self.peers.get(&presence.actor_id),
+                Some(entry) if entry.counter >= presence.counter
+            );
+            if stale {
+                continue;
+            }
+            if presence.ttl_ms == 0 {
+                if let Some(entry) = self.peers.get_mut(&presence.actor_id) {
+                    entry.counter = presence.counter;
+                    if entry.payload.take().is_some() && entry.expires_at > now_ms {
+                        changed.push(presence.actor_id);
+                    }
+                }
+                continue;
+            }
+            self.peers.insert(
+                presence.actor_id.clone(),
+                PresenceEntry {
+                    counter: presence.counter,
+                    expires_at: now_ms.saturating_add(presence.ttl_ms),
+                    payload: Some(presence.payload),
+                },
+            );
+            changed.push(presence.actor_id);
+        }
+        changed
+    }
+
+    /// Drop every presence which has expired, returning the actors they belonged to. Actors who
+    /// left before their presence expired are not returned again.
+    pub fn remove_expired(&mut self, now_ms: u64) -> Vec<ActorId> {
+        let mut expired = Vec::new();
+        self.peers.retain(|actor_id, entry| {
+            if entry.expires_at > now_ms {
+                return true;
+            }
+            if entry.payload.is_some() {
+                expired.push(actor_id.clone());
+            }
+            false
+        });
+        expired
+    }
+
+    /// The payload of `actor_id`'s presence if it has not expired
+    pub fn get(&self, actor_id: &ActorId, now_ms: u64) -> Option<&[u8]> {
+        self.peers
+            .get(actor_id)
+            .filter(|entry| entry.expires_at > now_ms)
+            .and_then(|entry| entry.payload.as_deref())
+    }
+
+    /// The actors with a presence which has not expired and their payloads, including the
+    /// local actor
+    pub fn presences(&self, now_ms: u64) -> impl Iterator<Item = (&ActorId, &[u8])> {
+        self.peers
+            .iter()
+            .filter(move |(_, entry)| entry.expires_at > now_ms)
+            .filter_map(|(actor_id, entry)| Some((actor_id, entry.payload.as_deref()?)))
+    }
+}
@@ -1,8 +1,10 @@
 use core::cmp::max;
-use std::{collections::HashMap, ops::AddAssign};
+use std::{collections::HashMap, mem::size_of, ops::AddAssign};
 
 use automerge_protocol as amp;
 
+use crate::memory::{hash_map_size, vec_size};
+
 #[derive(Debug, Default, Clone)]
 pub struct VectorClock {
     clock: HashMap<amp::ActorId, u64>,
@@ -17,10 +19,6 @@ impl VectorClock {
         }
     }
 
-    pub fn len(&self) -> usize {
-        self.clock.len()
-    }
-
     pub fn get_seq(&self, actor: &amp::ActorId) -> Option<u64> {
         self.clock.get(actor).copied()
     }
@@ -49,3 +47,167 @@ impl AddAssign<&Self> for VectorClock {
         }
     }
 }
+
+/// The vector clock of every change in a backend's history, in the same order as the history.
+///
+/// Actors are numbered in the order they first appear in the history. Most clocks are stored
+/// as the seqs which differ from the clock of the change's first dependency, which in a linear
+/// history is just the change's own seq. Every `MAX_DEPTH` changes along a chain of first
+/// dependencies the whole clock is stored instead so that looking up a clock stays cheap.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct ClockIndex {
+    actor_numbers: HashMap<amp::ActorId, usize>,
+    actors: Vec<amp::ActorId>,
+    clocks: Vec<IndexedClock>,
+}
+
+#[derive(Debug, Clone)]
+struct IndexedClock {
+    /// The index of the change whose clock `seqs` is relative to, `None` if `seqs` is the whole
+    /// clock
+    base: Option<usize>,
+    /// How many bases have to be followed to reach a whole clock
+    depth: usize,
+    /// Actor numbers and seqs
+    seqs: Box<[(usize, u64)]>,
+}
+
+const MAX_DEPTH: usize = 32;
+
+impl ClockIndex {
+    /// Add the clock for the next change in the history, `deps` are the indices in the history of
+    /// the change's dependencies
+    pub(crate) fn push<I>(&mut self, actor: &amp::ActorId, seq: u64, deps: I)
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        let actor_number = if let Some(number) = self.actor_numbers.get(actor) {
+            *number
+        } else {
+            self.actor_numbers.insert(actor.clone(), self.actors.len());
+            self.actors.push(actor.clone());
+            self.actors.len() - 1
+        };
+
+        let mut deps = deps.into_iter();
+        let base = deps.next();
+        let mut base_clock = vec![0; self.actors.len()];
+        if let Some(base) = base {
+            self.merge_clock(base, &mut base_clock);
+        }
+        let mut clock = base_clock.clone();
+        for dep in deps {
+            self.merge_clock(dep, &mut clock);
+        }
+        clock[actor_number] = max(clock[actor_number], seq);
+
+        let indexed = match base {
+            Some(base) if self.clocks[base].depth < MAX_DEPTH => IndexedClock {
+                base: Some(base),
+                depth: self.clocks[base].depth + 1,
+                seqs: sparse(&clock, |number, seq| seq != base_clock[number]),
+            },
+            _ => IndexedClock {
+                base: None,
+                depth: 0,
+                seqs: sparse(&clock, |_, seq| seq > 0),
+            },
+        };
+        self.clocks.push(indexed);
+    }
+
+    /// Merge the clock of the change at `index` in the history into `clock`, which is indexed by
+    /// actor number
+    fn merge_clock(&self, index: usize, clock: &mut [u64]) {
+        // clocks only grow along a chain of bases so the entries can be merged in any order
+        let mut next = Some(index);
+        while let Some(index) = next {
+            let indexed = &self.clocks[index];
+            for (number, seq) in &*indexed.seqs {
+                clock[*number] = max(clock[*number], *seq);
+            }
+            next = indexed.base;
+        }
+    }
+
+    /// The clock of the graph whose heads are the changes at `indices` in the history
+    pub(crate) fn clock_at<I>(&self, indices: I) -> VectorClock
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        let mut seqs = vec![0; self.actors.len()];
+        for index in indices {
+            self.merge_clock(index, &mut seqs);
+        }
+        let mut clock = VectorClock::default();
+        for (actor, seq) in self.actors.iter().zip(seqs) {
+            if seq > 0 {
+                clock.update(actor, seq);
+            }
+        }
+        clock
+    }
+
+    pub(crate) fn heap_size(&self) -> usize {
+        hash_map_size(&self.actor_numbers)
+            + vec_size(&self.actors)
+            + vec_size(&self.clocks)
+            + self
+                .clocks
+                .iter()
+                .map(|clock| clock.seqs.len() * size_of::<(usize, u64)>())
+                .sum::<usize>()
+    }
+}
+
+/// The entries of `clock` which `keep` returns true for
+fn sparse<F>(clock: &[u64], keep: F) -> Box<[(usize, u64)]>
+where
+    F: Fn(usize, u64) -> bool,
+{
+    clock
+        .iter()
+        .enumerate()
+        .filter(|(number, seq)| keep(*number, **seq))
+        .map(|(number, seq)| (number, *seq))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clocks_are_stored_sparsely() {
+        let actors: Vec<_> = (0..10).map(|_| amp::ActorId::random()).collect();
+        let mut index = ClockIndex::default();
+        // a linear history where each actor makes a change in turn
+        for i in 0..1_000 {
+            let deps = if i == 0 { None } else { Some(i - 1) };
+            index.push(&actors[i % 10], (i / 10) as u64 + 1, deps);
+        }
+        // and a merge of the last change with a concurrent one by a new actor
+        let new_actor = amp::ActorId::random();
+        index.push(&new_actor, 1, Some(500));
+        index.push(&actors[0], 101, vec![999, 1_000]);
+
+        let clock = index.clock_at(Some(999));
+        for (number, actor) in actors.iter().enumerate() {
+            assert_eq!(clock.get_seq(actor), Some(100), "actor {}", number);
+        }
+        assert_eq!(clock.get_seq(&new_actor), None);
+
+        let clock = index.clock_at(Some(1_000));
+        assert_eq!(clock.get_seq(&actors[0]), Some(51));
+        assert_eq!(clock.get_seq(&actors[9]), Some(50));
+        assert_eq!(clock.get_seq(&new_actor), Some(1));
+
+        let clock = index.clock_at(Some(1_001));
+        assert_eq!(clock.get_seq(&actors[0]), Some(101));
+        assert_eq!(clock.get_seq(&actors[9]), Some(100));
+        assert_eq!(clock.get_seq(&new_actor), Some(1));
+
+        let stored: usize = index.clocks.iter().map(|c| c.seqs.len()).sum();
+        assert!(stored < 1_000 + (1_000 / MAX_DEPTH) * 10 + 20, "{}", stored);
+    }
+}
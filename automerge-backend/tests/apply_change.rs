@@ -745,6 +745,98 @@ fn test_handle_list_element_insertion_and_deletion_in_same_change() {
     assert_eq!(patch, expected_patch)
 }
 
+#[test]
+fn test_coalesce_edits_from_a_batch_of_changes() {
+    let actor: ActorId = "d5a2c9b4f0e34c1e8a2b6f4d3c2b1a09".try_into().unwrap();
+    let change = |seq: u64, deps: Vec<amp::ChangeHash>, op: Op| -> Change {
+        amp::Change {
+            actor_id: actor.clone(),
+            seq,
+            start_op: seq,
+            time: 0,
+            message: None,
+            hash: None,
+            deps,
+            operations: vec![op],
+            extra_bytes: Vec::new(),
+        }
+        .try_into()
+        .unwrap()
+    };
+    let list_id = ObjectId::from(actor.op_id_at(1));
+
+    let mut changes = vec![change(
+        1,
+        Vec::new(),
+        Op {
+            action: amp::OpType::Make(amp::ObjType::List),
+            obj: ObjectId::Root,
+            key: "birds".into(),
+            pred: SortedVec::new(),
+            insert: false,
+        },
+    )];
+    // insert three birds one after the other, each in its own change
+    for (seq, bird) in [(2, "chaffinch"), (3, "goldfinch"), (4, "greenfinch")] {
+        let key = if seq == 2 {
+            ElementId::Head.into()
+        } else {
+            actor.op_id_at(seq - 1).into()
+        };
+        let deps = vec![changes.last().unwrap().hash];
+        changes.push(change(
+            seq,
+            deps,
+            Op {
+                action: amp::OpType::Set(bird.into()),
+                obj: list_id.clone(),
+                key,
+                pred: SortedVec::new(),
+                insert: true,
+            },
+        ));
+    }
+    // then delete the last two, backwards
+    for (seq, deleted) in [(5, 4), (6, 3)] {
+        let deps = vec![changes.last().unwrap().hash];
+        changes.push(change(
+            seq,
+            deps,
+            Op {
+                action: amp::OpType::Del(NonZeroU32::new(1).unwrap()),
+                obj: list_id.clone(),
+                key: actor.op_id_at(deleted).into(),
+                pred: vec![actor.op_id_at(deleted)].into(),
+                insert: false,
+            },
+        ));
+    }
+
+    let mut backend = Backend::new();
+    let patch = backend.apply_changes(changes).unwrap();
+    let expected_edits = vec![
+        DiffEdit::MultiElementInsert(amp::MultiElementInsert {
+            index: 0,
+            elem_id: actor.op_id_at(2).into(),
+            values: vec![
+                amp::ScalarValue::from("chaffinch"),
+                amp::ScalarValue::from("goldfinch"),
+                amp::ScalarValue::from("greenfinch"),
+            ]
+            .try_into()
+            .unwrap(),
+        }),
+        DiffEdit::Remove { index: 1, count: 2 },
+    ];
+    assert_eq!(
+        patch.diffs.props["birds"][&actor.op_id_at(1)],
+        Diff::List(ListDiff {
+            object_id: list_id,
+            edits: expected_edits,
+        })
+    );
+}
+
 #[test]
 fn test_handle_changes_within_conflicted_objects() {
     let actor1: ActorId = "9f17517523e54ee888e9cd51dfd7a572".try_into().unwrap();
@@ -1246,6 +1338,68 @@ fn test_throws_on_attempt_to_create_missing_cursor() {
     }
 }
 
+#[test]
+fn test_throws_on_set_member_stored_under_the_wrong_key() {
+    let actor = ActorId::random();
+    let set_id = actor.op_id_at(1);
+    let member = ScalarValue::Str("magpie".into());
+    let make_change = |key: amp::Key| amp::Change {
+        actor_id: actor.clone(),
+        seq: 1,
+        start_op: 1,
+        time: 0,
+        deps: Vec::new(),
+        message: None,
+        hash: None,
+        operations: vec![
+            Op {
+                action: amp::OpType::Make(amp::ObjType::Set),
+                obj: ObjectId::Root,
+                key: "birds".into(),
+                insert: false,
+                pred: SortedVec::new(),
+            },
+            Op {
+                action: amp::OpType::Set(member.clone()),
+                obj: set_id.clone().into(),
+                key,
+                insert: false,
+                pred: SortedVec::new(),
+            },
+        ],
+        extra_bytes: Vec::new(),
+    };
+
+    let mut backend = Backend::new();
+    let patch = backend
+        .apply_changes(vec![Change::from(make_change(
+            member.set_key().unwrap().as_str().into(),
+        ))])
+        .unwrap();
+    assert_eq!(
+        patch.diffs.props["birds"][&set_id],
+        Diff::Set(amp::SetDiff {
+            object_id: set_id.clone().into(),
+            props: hashmap! {
+                member.set_key().unwrap() => hashmap!{
+                    actor.op_id_at(2) => Diff::Value(member.clone()),
+                }
+            },
+        })
+    );
+
+    let mut backend = Backend::new();
+    let err = backend
+        .apply_changes(vec![Change::from(make_change("magpie".into()))])
+        .expect_err("Should be an error");
+    if let AutomergeError::InvalidSetOp { object_id, opid } = err {
+        assert_eq!(object_id, set_id.into());
+        assert_eq!(opid, actor.op_id_at(2));
+    } else {
+        panic!("Expected InvalidSetOp error but found {:?}", err)
+    }
+}
+
 #[test]
 fn test_updating_sequences_updates_referring_cursors() {
     let actor = ActorId::random();
@@ -1,4 +1,6 @@
-use automerge_backend::Backend;
+use automerge_backend::{AutomergeError, Backend, Change};
+use automerge_protocol as amp;
+use sha2::{Digest, Sha256};
 
 #[test]
 fn test_load_index_out_of_bounds() {
@@ -50,3 +52,60 @@ fn test_load_overflowing_sub() {
     ];
     let _ = Backend::load(bytes);
 }
+
+#[test]
+fn test_load_set_member_stored_under_the_wrong_key() {
+    let actor = amp::ActorId::random();
+    let set_id = actor.op_id_at(1);
+    let member = amp::ScalarValue::Str("magpie".into());
+    let change = amp::Change {
+        actor_id: actor.clone(),
+        seq: 1,
+        start_op: 1,
+        time: 0,
+        deps: Vec::new(),
+        message: None,
+        hash: None,
+        operations: vec![
+            amp::Op {
+                action: amp::OpType::Make(amp::ObjType::Set),
+                obj: amp::ObjectId::Root,
+                key: "birds".into(),
+                insert: false,
+                pred: amp::SortedVec::new(),
+            },
+            amp::Op {
+                action: amp::OpType::Set(member.clone()),
+                obj: set_id.clone().into(),
+                key: member.set_key().unwrap().as_str().into(),
+                insert: false,
+                pred: amp::SortedVec::new(),
+            },
+        ],
+        extra_bytes: Vec::new(),
+    };
+    let mut backend = Backend::new();
+    backend.apply_changes(vec![Change::from(change)]).unwrap();
+    let mut bytes = backend.save().unwrap();
+
+    // change the member but not the key it is stored under, which contains it after "str:"
+    let start = bytes
+        .windows(6)
+        .enumerate()
+        .position(|(i, w)| w == b"magpie" && !bytes[..i].ends_with(b"str:"))
+        .unwrap();
+    bytes[start..start + 6].copy_from_slice(b"robins");
+    let checksum = Sha256::digest(&bytes[8..]);
+    bytes[4..8].copy_from_slice(&checksum[..4]);
+
+    match Backend::load_without_hash_verification(&bytes) {
+        Err(AutomergeError::InvalidSetOp { object_id, opid }) => {
+            assert_eq!(object_id, set_id.into());
+            assert_eq!(opid, actor.op_id_at(2));
+        }
+        other => panic!(
+            "Expected InvalidSetOp error but found {:?}",
+            other.map(|_| ())
+        ),
+    }
+}
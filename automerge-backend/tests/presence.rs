@@ -0,0 +1,152 @@
+use automerge_backend::{
+    Backend, EphemeralMessage, PeerMessage, Presence, PresenceStore, SyncMessage, SyncState,
+};
+use automerge_protocol as amp;
+
+fn round_trip(message: EphemeralMessage) -> EphemeralMessage {
+    match PeerMessage::decode(&message.encode().unwrap()).unwrap() {
+        PeerMessage::Ephemeral(message) => message,
+        other => panic!("expected an ephemeral message but got {:?}", other),
+    }
+}
+
+#[test]
+fn test_ephemeral_messages_round_trip() {
+    let message = EphemeralMessage {
+        presences: vec![
+            Presence {
+                actor_id: amp::ActorId::random(),
+                counter: 1_000,
+                ttl_ms: 30_000,
+                payload: br#"{"selection":[1,4]}"#.to_vec(),
+            },
+            Presence {
+                actor_id: amp::ActorId::random(),
+                counter: 7,
+                ttl_ms: 0,
+                payload: Vec::new(),
+            },
+        ],
+    };
+    let encoded = message.clone().encode().unwrap();
+    assert_eq!(EphemeralMessage::decode(&encoded).unwrap(), message);
+    assert_eq!(round_trip(message.clone()), message);
+    assert!(SyncMessage::decode(&encoded).is_err());
+
+    let mut trailing = encoded.clone();
+    trailing.push(0);
+    assert!(EphemeralMessage::decode(&trailing).is_err());
+    // a count larger than the number of presences in the message
+    let mut truncated = EphemeralMessage::default().encode().unwrap();
+    truncated.truncate(1);
+    truncated.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0x0f]);
+    assert!(EphemeralMessage::decode(&truncated).is_err());
+}
+
+#[test]
+fn test_peer_messages_tell_sync_and_ephemeral_messages_apart() {
+    let backend = Backend::new();
+    let sync_message = backend
+        .generate_sync_message(&mut SyncState::default())
+        .unwrap();
+    let encoded = PeerMessage::from(sync_message).encode().unwrap();
+    assert!(matches!(
+        PeerMessage::decode(&encoded).unwrap(),
+        PeerMessage::Sync(_)
+    ));
+    assert!(EphemeralMessage::decode(&encoded).is_err());
+
+    assert!(PeerMessage::decode(&[]).is_err());
+    assert!(PeerMessage::decode(&[0x43, 0]).is_err());
+}
+
+#[test]
+fn test_presence_is_shared_and_expires() {
+    let alice = amp::ActorId::random();
+    let bob = amp::ActorId::random();
+    let mut alice_store = PresenceStore::new(alice.clone());
+    let mut bob_store = PresenceStore::new(bob.clone());
+
+    let message = alice_store.set_local(b"alice".to_vec(), 1_000, 10);
+    assert_eq!(
+        bob_store.receive_message(round_trip(message), 20),
+        vec![alice.clone()]
+    );
+    assert_eq!(bob_store.get(&alice, 20), Some(&b"alice"[..]));
+    assert_eq!(alice_store.get(&alice, 20), Some(&b"alice"[..]));
+
+    // the TTL counts from when bob received the presence
+    assert_eq!(bob_store.get(&alice, 1_019), Some(&b"alice"[..]));
+    assert_eq!(bob_store.get(&alice, 1_020), None);
+    assert_eq!(bob_store.presences(1_020).count(), 0);
+    assert_eq!(bob_store.remove_expired(1_020), vec![alice.clone()]);
+    assert!(bob_store.remove_expired(1_020).is_empty());
+}
+
+#[test]
+fn test_stale_and_own_presences_are_ignored() {
+    let alice = amp::ActorId::random();
+    let bob = amp::ActorId::random();
+    let mut alice_store = PresenceStore::new(alice.clone());
+    let mut bob_store = PresenceStore::new(bob.clone());
+
+    let first = alice_store.set_local(b"first".to_vec(), 1_000, 0);
+    let second = alice_store.set_local(b"second".to_vec(), 1_000, 0);
+    assert_eq!(bob_store.receive_message(second, 0), vec![alice.clone()]);
+    assert!(bob_store.receive_message(first, 0).is_empty());
+    assert_eq!(bob_store.get(&alice, 0), Some(&b"second"[..]));
+
+    // bob's own presence relayed back to him doesn't replace his local one
+    let own = bob_store.set_local(b"bob".to_vec(), 1_000, 0);
+    let mut relayed = own.clone();
+    relayed.presences[0].counter += 1;
+    relayed.presences[0].payload = b"impostor".to_vec();
+    assert!(bob_store.receive_message(relayed, 0).is_empty());
+    assert_eq!(bob_store.get(&bob, 0), Some(&b"bob"[..]));
+}
+
+#[test]
+fn test_leaving_removes_presence() {
+    let alice = amp::ActorId::random();
+    let mut alice_store = PresenceStore::new(alice.clone());
+    let mut bob_store = PresenceStore::new(amp::ActorId::random());
+
+    let late = alice_store.set_local(b"late".to_vec(), 60_000, 0);
+    bob_store.receive_message(alice_store.set_local(b"here".to_vec(), 60_000, 5), 5);
+    assert!(bob_store.get(&alice, 10).is_some());
+    let goodbye = alice_store.clear_local(10);
+    assert_eq!(alice_store.get(&alice, 10), None);
+    assert_eq!(bob_store.receive_message(goodbye, 10), vec![alice.clone()]);
+    assert_eq!(bob_store.get(&alice, 10), None);
+    assert_eq!(bob_store.presences(10).count(), 0);
+
+    // an update sent before alice left which arrives afterwards doesn't bring her back
+    assert!(bob_store.receive_message(late, 20).is_empty());
+    assert_eq!(bob_store.get(&alice, 20), None);
+
+    // relays pass the leave on until alice's presence would have expired
+    let relayed = bob_store.generate_message(20).unwrap();
+    assert_eq!(relayed.presences[0].actor_id, alice);
+    assert_eq!(relayed.presences[0].ttl_ms, 0);
+    assert!(bob_store.remove_expired(60_005).is_empty());
+    assert!(bob_store.generate_message(60_005).is_none());
+}
+
+#[test]
+fn test_relayed_presence_keeps_its_remaining_ttl() {
+    let alice = amp::ActorId::random();
+    let bob = amp::ActorId::random();
+    let mut alice_store = PresenceStore::new(alice.clone());
+    let mut server = PresenceStore::new(amp::ActorId::random());
+    let mut bob_store = PresenceStore::new(bob);
+
+    server.receive_message(alice_store.set_local(b"alice".to_vec(), 1_000, 0), 0);
+    let relayed = server.generate_message(400).unwrap();
+    assert_eq!(relayed.presences.len(), 1);
+    assert_eq!(relayed.presences[0].ttl_ms, 600);
+
+    bob_store.receive_message(round_trip(relayed), 5_000);
+    assert!(bob_store.get(&alice, 5_599).is_some());
+    assert!(bob_store.get(&alice, 5_600).is_none());
+    assert!(server.generate_message(1_000).is_none());
+}
@@ -0,0 +1,110 @@
+use std::convert::TryInto;
+
+use amp::SortedVec;
+use automerge_backend::{Backend, Change};
+use automerge_protocol as amp;
+
+/// A chain of `len` changes by one actor, each setting the key "counter" and depending on the one
+/// before it
+fn chain(len: u64) -> Vec<Change> {
+    let actor = amp::ActorId::random();
+    let mut changes: Vec<Change> = Vec::new();
+    for seq in 1..=len {
+        let change = amp::Change {
+            actor_id: actor.clone(),
+            seq,
+            start_op: seq,
+            time: 0,
+            message: None,
+            hash: None,
+            deps: changes.last().map(|c| vec![c.hash]).unwrap_or_default(),
+            operations: vec![amp::Op {
+                action: amp::OpType::Set(amp::ScalarValue::Uint(seq)),
+                obj: amp::ObjectId::Root,
+                key: "counter".into(),
+                insert: false,
+                pred: if seq == 1 {
+                    SortedVec::new()
+                } else {
+                    vec![actor.op_id_at(seq - 1)].into()
+                },
+            }],
+            extra_bytes: Vec::new(),
+        };
+        changes.push(change.try_into().unwrap());
+    }
+    changes
+}
+
+#[test]
+fn test_queued_changes_list_their_missing_deps() {
+    let changes = chain(3);
+    let mut backend = Backend::new();
+    let patch = backend
+        .apply_changes(vec![changes[2].clone(), changes[1].clone()])
+        .unwrap();
+    assert_eq!(patch.pending_changes, 2);
+
+    let queued = backend.queued_changes();
+    assert_eq!(queued.len(), 2);
+    assert_eq!(queued[0].change.hash, changes[2].hash);
+    assert_eq!(queued[0].missing_deps, vec![changes[1].hash]);
+    assert_eq!(queued[1].change.hash, changes[1].hash);
+    assert_eq!(queued[1].missing_deps, vec![changes[0].hash]);
+    assert!(queued[0].queued_at < queued[1].queued_at);
+    assert_eq!(backend.get_missing_deps(&[]), vec![changes[0].hash]);
+
+    // applying the first change makes the rest ready
+    let patch = backend.apply_changes(vec![changes[0].clone()]).unwrap();
+    assert_eq!(patch.pending_changes, 0);
+    assert!(backend.queued_changes().is_empty());
+    assert_eq!(backend.get_heads(), vec![changes[2].hash]);
+}
+
+#[test]
+fn test_evict_queued_changes() {
+    let changes = chain(4);
+    let mut backend = Backend::new();
+    backend
+        .apply_changes(vec![
+            changes[3].clone(),
+            changes[2].clone(),
+            changes[1].clone(),
+        ])
+        .unwrap();
+
+    let evicted = backend.evict_queued_change(&changes[2].hash).unwrap();
+    assert_eq!(evicted.hash, changes[2].hash);
+    assert!(backend.evict_queued_change(&changes[2].hash).is_none());
+
+    // the change which depended on the evicted change is still waiting for it
+    backend.apply_changes(vec![changes[0].clone()]).unwrap();
+    let queued = backend.queued_changes();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].change.hash, changes[3].hash);
+    assert_eq!(backend.get_missing_deps(&[]), vec![changes[2].hash]);
+
+    let evicted = backend.evict_queued_changes_before(queued[0].queued_at + 1);
+    assert_eq!(evicted.len(), 1);
+    assert!(backend.queued_changes().is_empty());
+    assert_eq!(backend.get_heads(), vec![changes[1].hash]);
+}
+
+#[test]
+fn test_max_queue_len_drops_the_oldest_changes() {
+    let changes = chain(4);
+    let mut backend = Backend::new();
+    backend.set_max_queue_len(Some(2));
+    for change in changes[1..].iter().rev() {
+        backend.apply_changes(vec![change.clone()]).unwrap();
+    }
+    let queued: Vec<_> = backend
+        .queued_changes()
+        .iter()
+        .map(|q| q.change.hash)
+        .collect();
+    assert_eq!(queued, vec![changes[2].hash, changes[1].hash]);
+
+    backend.apply_changes(vec![changes[0].clone()]).unwrap();
+    assert_eq!(backend.get_heads(), vec![changes[2].hash]);
+}
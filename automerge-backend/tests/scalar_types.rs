@@ -0,0 +1,98 @@
+use std::convert::TryInto;
+
+use amp::SortedVec;
+use automerge_backend::{Backend, Change};
+use automerge_protocol as amp;
+
+fn values() -> Vec<(&'static str, amp::ScalarValue)> {
+    vec![
+        (
+            "id",
+            amp::ScalarValue::Uuid(
+                uuid::Uuid::parse_str("0f5a9a5e-1c3b-4a8e-9b6a-3d2f1e0c9b8a").unwrap(),
+            ),
+        ),
+        (
+            "price",
+            amp::ScalarValue::Decimal("-12.50".parse().unwrap()),
+        ),
+        (
+            "future",
+            amp::ScalarValue::Unknown(amp::UnknownValue::new(13, vec![1, 2, 3]).unwrap()),
+        ),
+        (
+            "empty_future",
+            amp::ScalarValue::Unknown(amp::UnknownValue::new(15, Vec::new()).unwrap()),
+        ),
+    ]
+}
+
+fn change_setting_values() -> amp::Change {
+    let actor = amp::ActorId::random();
+    amp::Change {
+        actor_id: actor.clone(),
+        seq: 1,
+        start_op: 1,
+        time: 0,
+        message: None,
+        hash: None,
+        deps: Vec::new(),
+        operations: values()
+            .into_iter()
+            .map(|(key, value)| amp::Op {
+                action: amp::OpType::Set(value),
+                obj: amp::ObjectId::Root,
+                key: key.into(),
+                insert: false,
+                pred: SortedVec::new(),
+            })
+            .collect(),
+        extra_bytes: Vec::new(),
+    }
+}
+
+fn root_values(backend: &Backend) -> Vec<(String, amp::ScalarValue)> {
+    let patch = backend.get_patch().unwrap();
+    let mut values: Vec<_> = patch
+        .diffs
+        .props
+        .into_iter()
+        .map(|(key, mut values)| match values.drain().next() {
+            Some((_, amp::Diff::Value(value))) => (key.to_string(), value),
+            other => panic!("expected a value but got {:?}", other),
+        })
+        .collect();
+    values.sort_by(|(a, _), (b, _)| a.cmp(b));
+    values
+}
+
+#[test]
+fn test_changes_round_trip_new_and_unknown_value_types() {
+    let change = change_setting_values();
+    let encoded: Change = change.clone().try_into().unwrap();
+    let decoded = encoded.decode();
+    assert_eq!(decoded.operations, change.operations);
+
+    let reloaded = Change::from_bytes(encoded.raw_bytes().to_vec()).unwrap();
+    assert_eq!(reloaded.hash, encoded.hash);
+    assert_eq!(reloaded.decode().operations, change.operations);
+}
+
+#[test]
+fn test_documents_round_trip_new_and_unknown_value_types() {
+    let change: Change = change_setting_values().try_into().unwrap();
+    let mut backend = Backend::new();
+    backend.apply_changes(vec![change]).unwrap();
+
+    let mut expected: Vec<_> = values()
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    expected.sort_by(|(a, _), (b, _)| a.cmp(b));
+    assert_eq!(root_values(&backend), expected);
+
+    let saved = backend.save().unwrap();
+    let loaded = Backend::load(saved.clone()).unwrap();
+    assert_eq!(root_values(&loaded), expected);
+    assert_eq!(loaded.save().unwrap(), saved);
+}
@@ -0,0 +1,105 @@
+use anyhow::Result;
+use automerge_backend as amb;
+
+fn blame_to_json(blame: &amb::Blame) -> serde_json::Value {
+    serde_json::json!({
+        "value": blame_value_to_json(&blame.value),
+        "actor": blame.author.actor.to_hex_string(),
+        "seq": blame.author.seq,
+        "hash": blame.author.change_hash,
+        "opid": blame.author.opid.to_string(),
+        "time": blame.author.time,
+        "message": blame.author.message,
+    })
+}
+
+fn blame_value_to_json(value: &amb::BlameValue) -> serde_json::Value {
+    match value {
+        amb::BlameValue::Map(props) | amb::BlameValue::Table(props) => props
+            .iter()
+            .map(|(k, v)| (k.to_string(), blame_to_json(v)))
+            .collect::<serde_json::Map<_, _>>()
+            .into(),
+        amb::BlameValue::List(elems)
+        | amb::BlameValue::Text(elems)
+        | amb::BlameValue::Set(elems) => elems.iter().map(blame_to_json).collect(),
+        // ScalarValue is serialized untagged so this can't fail
+        amb::BlameValue::Primitive(v) => serde_json::to_value(v).unwrap(),
+    }
+}
+
+fn get_blame_json(input_data: &[u8]) -> Result<serde_json::Value> {
+    let changes = amb::Change::load_document(input_data)?;
+    let mut backend = amb::Backend::new();
+    backend.load_changes(changes)?;
+    Ok(blame_value_to_json(&backend.blame()?))
+}
+
+pub fn blame(
+    mut reader: impl std::io::Read,
+    mut writer: impl std::io::Write,
+    is_tty: bool,
+) -> Result<()> {
+    let mut input_data = vec![];
+    reader.read_to_end(&mut input_data)?;
+
+    let blame_json = get_blame_json(&input_data)?;
+    if is_tty {
+        colored_json::write_colored_json(&blame_json, &mut writer)?;
+        writeln!(writer)?;
+    } else {
+        writeln!(writer, "{}", serde_json::to_string_pretty(&blame_json)?)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cli_blame_with_empty_input() {
+        assert_eq!(get_blame_json(&[]).unwrap(), serde_json::json!({}))
+    }
+
+    #[test]
+    fn cli_blame_annotates_values() {
+        let mut frontend = automerge_frontend::Frontend::new();
+        let actor = frontend.actor_id.clone();
+        let mut backend = amb::Backend::new();
+        let ((), change) = frontend
+            .change::<_, _, automerge_frontend::InvalidChangeRequest>(
+                Some("add birds".into()),
+                |d| {
+                    d.add_change(automerge_frontend::LocalChange::set(
+                        automerge_frontend::Path::root().key("birds"),
+                        automerge_frontend::Value::from(vec!["wren"]),
+                    ))
+                },
+            )
+            .unwrap();
+        let (_, change) = backend.apply_local_change(change.unwrap()).unwrap();
+        let hash = change.hash;
+        let time = change.time;
+
+        let author = |opid: u64| {
+            serde_json::json!({
+                "actor": actor.to_hex_string(),
+                "seq": 1,
+                "hash": hash,
+                "opid": actor.op_id_at(opid).to_string(),
+                "time": time,
+                "message": "add birds",
+            })
+        };
+        let mut expected_wren = author(2);
+        expected_wren["value"] = "wren".into();
+        let mut expected_birds = author(1);
+        expected_birds["value"] = serde_json::json!([expected_wren]);
+
+        assert_eq!(
+            get_blame_json(&backend.save().unwrap()).unwrap(),
+            serde_json::json!({ "birds": expected_birds })
+        );
+    }
+}
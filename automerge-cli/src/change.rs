@@ -1,7 +1,9 @@
 use automerge_backend as amb;
 use automerge_frontend as amf;
+use automerge_protocol as amp;
 use combine::{parser::char as charparser, EasyParser, ParseError, Parser};
 use thiserror::Error;
+use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Error, Debug)]
 pub enum ChangeError {
@@ -35,6 +37,7 @@ enum Op {
     Insert,
     Delete,
     Increment,
+    Splice,
 }
 
 fn case_insensitive_string<Input>(s: &'static str) -> impl Parser<Input, Output = String>
@@ -54,6 +57,7 @@ where
         combine::attempt(case_insensitive_string("insert")).map(|_| Op::Insert),
         combine::attempt(case_insensitive_string("delete")).map(|_| Op::Delete),
         combine::attempt(case_insensitive_string("increment")).map(|_| Op::Increment),
+        combine::attempt(case_insensitive_string("splice")).map(|_| Op::Splice),
     ))
 }
 
@@ -114,22 +118,64 @@ where
         //combine::position().and(combine::many1::<Vec<char>, _, _>(combine::any())).and_then(
         combine::position().and(combine::many1::<Vec<char>, _, _>(combine::any())).flat_map(
         |(position, chars): (Input::Position, Vec<char>)| -> Result<amf::Value, Input::Error> {
-            let json_str: String = chars.into_iter().collect();
-            let json: serde_json::Value = serde_json::from_str(json_str.as_str()).map_err(|e| {
+            let value_str: String = chars.into_iter().collect();
+            parse_value(value_str.as_str()).map_err(|e| {
+                // the most likely way to end up here is a JSON value spread over several lines
+                let e = if e.is_eof() {
+                    format!("{}, values must be on the same line as their command", e)
+                } else {
+                    e.to_string()
+                };
                 //let pe = <Input::Error as ParseError<_, _, _>>::StreamError::message::<combine::error::Format<String>>(combine::error::Format(e.to_string()));
                 //let pe = <Input::Error as ParseError<Input::Token, Input::Range, Input::Position>>::StreamError::message(e.to_string().into());
                 let mut pe = Input::Error::empty(position);
-                pe.add_message(combine::error::Format(e.to_string()));
+                pe.add_message(combine::error::Format(e));
                 //let pe = combine::ParseError:::wmpty(position);
                 pe
-            })?;
-            Ok(amf::Value::from_json(&json))
+            })
         },
         )
     ).boxed()
 }
 
-fn change_parser<'a, Input: 'a>() -> impl combine::Parser<Input, Output = amf::LocalChange> + 'a
+/// Values are JSON, except that `text <JSON string>` creates a text object
+fn parse_value(input: &str) -> Result<amf::Value, serde_json::Error> {
+    let input = input.trim();
+    match input.strip_prefix("text") {
+        Some(text) if text.starts_with(char::is_whitespace) => {
+            let text: String = serde_json::from_str(text)?;
+            Ok(amf::Value::Text(
+                text.graphemes(true).map(|g| g.into()).collect(),
+            ))
+        }
+        _ => {
+            let json: serde_json::Value = serde_json::from_str(input)?;
+            Ok(amf::Value::from_json(&json))
+        }
+    }
+}
+
+/// Delete `deletions` elements of a list or text object starting at `path` and then insert
+/// `values` there. A string is inserted as one element per character.
+fn splice(path: amf::Path, deletions: u32, values: Option<amf::Value>) -> Vec<amf::LocalChange> {
+    let mut changes: Vec<_> = (0..deletions)
+        .map(|_| amf::LocalChange::delete(path.clone()))
+        .collect();
+    let values = match values {
+        None => Vec::new(),
+        Some(amf::Value::List(values)) => values,
+        Some(amf::Value::Primitive(amf::Primitive::Str(s))) => {
+            s.graphemes(true).map(amf::Value::from).collect()
+        }
+        Some(value) => vec![value],
+    };
+    if !values.is_empty() {
+        changes.push(amf::LocalChange::insert_many(path, values));
+    }
+    changes
+}
+
+fn change_parser<'a, Input: 'a>() -> impl combine::Parser<Input, Output = Vec<amf::LocalChange>> + 'a
 where
     Input: 'a,
     Input: combine::stream::Stream<Token = char>,
@@ -145,35 +191,125 @@ where
         .skip(charparser::spaces())
         .then(|(operation, path)| {
             let onwards: Box<
-                dyn combine::Parser<Input, Output = amf::LocalChange, PartialState = _>,
+                dyn combine::Parser<Input, Output = Vec<amf::LocalChange>, PartialState = _>,
             > = match operation {
                 Op::Set => value_parser::<'a>()
-                    .map(move |value| amf::LocalChange::set(path.clone(), value))
+                    .map(move |value| vec![amf::LocalChange::set(path.clone(), value)])
                     .boxed(),
                 Op::Insert => value_parser::<'a>()
-                    .map(move |value| amf::LocalChange::insert(path.clone(), value))
+                    .map(move |value| vec![amf::LocalChange::insert(path.clone(), value)])
                     .boxed(),
-                Op::Delete => combine::value(amf::LocalChange::delete(path)).boxed(),
-                Op::Increment => combine::value(amf::LocalChange::increment(path)).boxed(),
+                Op::Delete => combine::value(vec![amf::LocalChange::delete(path)]).boxed(),
+                Op::Increment => combine::value(vec![amf::LocalChange::increment(path)]).boxed(),
+                Op::Splice => combine::parser::combinator::no_partial(
+                    index_parser()
+                        .skip(charparser::spaces())
+                        .and(combine::optional(value_parser::<'a>()))
+                        .map(move |(deletions, values)| splice(path.clone(), deletions, values)),
+                )
+                .boxed(),
             };
             onwards
         })
 }
 
-fn parse_change_script(input: &str) -> Result<amf::LocalChange, ChangeError> {
-    let (change, _) =
+pub(crate) fn parse_path(input: &str) -> Result<amf::Path, ChangeError> {
+    let (path, _) = charparser::spaces()
+        .skip(charparser::string("$"))
+        .with(path_segment_parser(amf::Path::root()))
+        .skip(charparser::spaces())
+        .skip(combine::eof())
+        .easy_parse(input)
+        .map_err(|e| ChangeError::InvalidChangeScript {
+            message: e.to_string(),
+        })?;
+    Ok(path)
+}
+
+pub(crate) fn parse_change_script(input: &str) -> Result<Vec<amf::LocalChange>, ChangeError> {
+    let (changes, _) =
         change_parser()
             .easy_parse(input)
             .map_err(|e| ChangeError::InvalidChangeScript {
                 message: e.to_string(),
             })?;
-    Ok(change)
+    Ok(changes)
+}
+
+/// Parse the arguments of a `commit` command, which are either empty or `-m <message>`, where
+/// the message may be a JSON string.
+pub(crate) fn parse_commit_args(args: &str) -> Result<Option<String>, ChangeError> {
+    let args = args.trim();
+    if args.is_empty() {
+        return Ok(None);
+    }
+    let message =
+        args.strip_prefix("-m")
+            .map(str::trim)
+            .ok_or_else(|| ChangeError::InvalidChangeScript {
+                message: "Usage: commit [-m <message>]".into(),
+            })?;
+    if message.starts_with('"') {
+        serde_json::from_str(message).map_err(|e| ChangeError::InvalidChangeScript {
+            message: e.to_string(),
+        })
+    } else {
+        Ok(Some(message.to_string()))
+    }
+}
+
+/// The operations between two `commit` lines of a script, which are applied as one change
+#[derive(Debug, PartialEq)]
+pub(crate) struct ScriptChange {
+    pub message: Option<String>,
+    pub ops: Vec<amf::LocalChange>,
+}
+
+/// Parse a script of change commands, one per line.
+///
+/// A command and its value must be on a single line, a JSON value cannot be spread over several
+/// lines. Blank lines and lines starting with `#` are ignored. A `commit [-m <message>]` line ends a
+/// change, any commands after the last `commit` form a final change without a message.
+pub(crate) fn parse_script(input: &str) -> Result<Vec<ScriptChange>, ChangeError> {
+    let mut changes = Vec::new();
+    let mut ops = Vec::new();
+    for (index, line) in input.lines().enumerate() {
+        let line = line.trim();
+        let with_line_number = |e| match e {
+            ChangeError::InvalidChangeScript { message } => ChangeError::InvalidChangeScript {
+                message: format!("line {}: {}", index + 1, message),
+            },
+            e => e,
+        };
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (command, args) = line.split_at(line.find(char::is_whitespace).unwrap_or(line.len()));
+        if command.eq_ignore_ascii_case("commit") {
+            changes.push(ScriptChange {
+                message: parse_commit_args(args).map_err(with_line_number)?,
+                ops: std::mem::take(&mut ops),
+            });
+        } else {
+            ops.extend(parse_change_script(line).map_err(with_line_number)?);
+        }
+    }
+    if !ops.is_empty() {
+        changes.push(ScriptChange { message: None, ops });
+    }
+    Ok(changes)
 }
 
+/// Apply a change script to the document in `reader` and write the result to `writer`.
+///
+/// The changes are made by `actor`, or a random actor, with timestamp `time`, or the current
+/// time. Passing both means the same script always produces the same document.
 pub fn change(
     mut reader: impl std::io::Read,
     mut writer: impl std::io::Write,
     script: &str,
+    actor: Option<amp::ActorId>,
+    time: Option<i64>,
 ) -> Result<(), ChangeError> {
     let mut backend = amb::Backend::new();
     let mut buf: Vec<u8> = Vec::new();
@@ -182,21 +318,33 @@ pub fn change(
         .map_err(|e| ChangeError::ErrReadingChanges { source: e })?;
     let changes = amb::Change::load_document(&buf)
         .map_err(|e| ChangeError::ErrApplyingInitialChanges { source: e })?;
-    let mut frontend = amf::Frontend::new();
+    let actor = actor.unwrap_or_else(amp::ActorId::random);
+    let mut frontend = match time {
+        Some(time) => amf::Frontend::new_with_timestamper_and_actor_id(
+            Box::new(move || Some(time)),
+            actor.to_bytes(),
+        ),
+        None => amf::Frontend::new_with_actor_id(actor.to_bytes()),
+    };
     let patch = backend
         .apply_changes(changes)
         .map_err(|e| ChangeError::ErrApplyingInitialChanges { source: e })?;
     // This unwrap should be fine, we've generated the patch ourselves, if it's invalid then
     // there's no way for the user to recover
     frontend.apply_patch(patch).unwrap();
-    let local_change = parse_change_script(script)?;
-    let ((), new_changes) = frontend.change::<_, _, amf::InvalidChangeRequest>(None, |d| {
-        d.add_change(local_change)?;
-        Ok(())
-    })?;
-    if let Some(c) = new_changes {
-        // The user can't do anything to recover if this fails so we unwrap
-        backend.apply_local_change(c).unwrap();
+    for ScriptChange { message, ops } in parse_script(script)? {
+        let ((), new_changes) =
+            frontend.change::<_, _, amf::InvalidChangeRequest>(message, |d| {
+                for local_change in ops {
+                    d.add_change(local_change)?;
+                }
+                Ok(())
+            })?;
+        if let Some(c) = new_changes {
+            // The user can't do anything to recover if this fails so we unwrap
+            let (patch, _) = backend.apply_local_change(c).unwrap();
+            frontend.apply_patch(patch).unwrap();
+        }
     }
     let change_bytes = backend.save().unwrap();
     writer
@@ -215,34 +363,62 @@ mod tests {
     fn test_parse_change_script() {
         struct Scenario {
             input: &'static str,
-            expected: amf::LocalChange,
+            expected: Vec<amf::LocalChange>,
         }
         let scenarios = vec![
             Scenario {
                 input: "set $[\"map\"][0] {\"some\": \"value\"}",
-                expected: amf::LocalChange::set(
+                expected: vec![amf::LocalChange::set(
                     amf::Path::root().key("map").index(0),
                     amf::Value::from(hashmap! {"some" => "value"}),
-                ),
+                )],
             },
             Scenario {
                 input: "insert $[\"map\"][0] {\"some\": \"value\"}",
-                expected: amf::LocalChange::insert(
+                expected: vec![amf::LocalChange::insert(
                     amf::Path::root().key("map").index(0),
                     hashmap! {"some" => "value"}.into(),
-                ),
+                )],
             },
             Scenario {
                 input: "delete $[\"map\"][0]",
-                expected: amf::LocalChange::delete(amf::Path::root().key("map").index(0)),
+                expected: vec![amf::LocalChange::delete(
+                    amf::Path::root().key("map").index(0),
+                )],
             },
             Scenario {
                 input: "increment $[\"map\"][0]",
-                expected: amf::LocalChange::increment(amf::Path::root().key("map").index(0)),
+                expected: vec![amf::LocalChange::increment(
+                    amf::Path::root().key("map").index(0),
+                )],
+            },
+            Scenario {
+                input: "set $[\"text\"] text \"hi\"",
+                expected: vec![amf::LocalChange::set(
+                    amf::Path::root().key("text"),
+                    amf::Value::Text(vec!["h".into(), "i".into()]),
+                )],
+            },
+            Scenario {
+                input: "splice $[\"text\"][1] 2 \"ab\"",
+                expected: vec![
+                    amf::LocalChange::delete(amf::Path::root().key("text").index(1)),
+                    amf::LocalChange::delete(amf::Path::root().key("text").index(1)),
+                    amf::LocalChange::insert_many(
+                        amf::Path::root().key("text").index(1),
+                        vec!["a".into(), "b".into()],
+                    ),
+                ],
+            },
+            Scenario {
+                input: "splice $[\"list\"][0] 1",
+                expected: vec![amf::LocalChange::delete(
+                    amf::Path::root().key("list").index(0),
+                )],
             },
         ];
         for (index, scenario) in scenarios.into_iter().enumerate() {
-            let result: Result<(amf::LocalChange, _), _> =
+            let result: Result<(Vec<amf::LocalChange>, _), _> =
                 change_parser().easy_parse(scenario.input);
             let change = result.unwrap().0;
             assert_eq!(
@@ -253,4 +429,95 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_parse_script() {
+        let script = r#"
+# make some birds
+set $["birds"] ["wren"]
+insert $["birds"][1] "magpie"
+commit -m "add birds"
+
+commit
+set $["count"] 1
+"#;
+        assert_eq!(
+            parse_script(script).unwrap(),
+            vec![
+                ScriptChange {
+                    message: Some("add birds".into()),
+                    ops: vec![
+                        amf::LocalChange::set(
+                            amf::Path::root().key("birds"),
+                            amf::Value::from(vec!["wren"]),
+                        ),
+                        amf::LocalChange::insert(
+                            amf::Path::root().key("birds").index(1),
+                            "magpie".into(),
+                        ),
+                    ],
+                },
+                ScriptChange {
+                    message: None,
+                    ops: vec![],
+                },
+                ScriptChange {
+                    message: None,
+                    ops: vec![amf::LocalChange::set(
+                        amf::Path::root().key("count"),
+                        amf::Value::Primitive(amf::Primitive::F64(1.0)),
+                    )],
+                },
+            ]
+        );
+
+        match parse_script("set $[\"a\"] 1\nfrobnicate $[\"a\"]") {
+            Err(ChangeError::InvalidChangeScript { message }) => {
+                assert!(message.starts_with("line 2:"), "{}", message)
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        match parse_script("set $[\"a\"] {\n  \"b\": 1\n}") {
+            Err(ChangeError::InvalidChangeScript { message }) => {
+                assert!(message.starts_with("line 1:"), "{}", message);
+                assert!(
+                    message.contains("values must be on the same line as their command"),
+                    "{}",
+                    message
+                );
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_change_script_is_reproducible() {
+        let script = r#"
+set $["doc"] {"title": "birds", "body": []}
+set $["doc"]["body"] text "hello world"
+commit -m "first draft"
+splice $["doc"]["body"][6] 5 "birds"
+commit -m "edit"
+"#;
+        let actor: amp::ActorId = "deadbeef".parse().unwrap();
+        let run = || {
+            let mut output = Vec::new();
+            change(&[][..], &mut output, script, Some(actor.clone()), Some(0)).unwrap();
+            output
+        };
+        let document = run();
+        assert_eq!(document, run());
+
+        let backend = amb::Backend::load(document).unwrap();
+        let changes = backend.get_changes(&[]);
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[1].message(), Some("edit".into()));
+        let mut frontend = amf::Frontend::new();
+        frontend.apply_patch(backend.get_patch().unwrap()).unwrap();
+        assert_eq!(
+            frontend.state().to_json(),
+            serde_json::json!({"doc": {"title": "birds", "body": "hello birds"}})
+        );
+    }
 }
@@ -35,7 +35,8 @@ pub fn examine(
     let uncompressed_changes: Vec<amp::Change> = changes.iter().map(|c| c.decode()).collect();
     if is_tty {
         let json_changes = serde_json::to_value(uncompressed_changes).unwrap();
-        colored_json::write_colored_json(&json_changes, &mut output).unwrap();
+        colored_json::write_colored_json(&json_changes, &mut output)
+            .map_err(|e| ExamineError::WritingToOutput { source: e.into() })?;
     } else {
         let json_changes = serde_json::to_string_pretty(&uncompressed_changes).unwrap();
         output
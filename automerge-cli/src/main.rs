@@ -1,13 +1,19 @@
-use std::{fs::File, path::PathBuf, str::FromStr};
+use std::{fs::File, io::Write, path::PathBuf, str::FromStr};
 
 use anyhow::{anyhow, Result};
+use automerge_protocol as amp;
 use clap::Clap;
 
+mod blame;
 mod change;
 mod examine;
 mod export;
 mod import;
 mod merge;
+mod shell;
+mod sync;
+mod table;
+mod verify;
 
 #[derive(Debug, Clap)]
 #[clap(about = "Automerge CLI")]
@@ -20,6 +26,8 @@ struct Opts {
 enum ExportFormat {
     Json,
     Toml,
+    Csv,
+    JsonLines,
 }
 
 impl FromStr for ExportFormat {
@@ -29,6 +37,8 @@ impl FromStr for ExportFormat {
         match input {
             "json" => Ok(ExportFormat::Json),
             "toml" => Ok(ExportFormat::Toml),
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" => Ok(ExportFormat::JsonLines),
             _ => Err(anyhow!("Invalid export format: {}", input)),
         }
     }
@@ -38,10 +48,15 @@ impl FromStr for ExportFormat {
 enum Command {
     /// Output current state of an Automerge document in a specified format
     Export {
-        /// Format for output: json, toml
+        /// Format for output: json, toml, csv, jsonl. The csv and jsonl formats write the rows of
+        /// a table, one per line
         #[clap(long, short, default_value = "json")]
         format: ExportFormat,
 
+        /// For csv and jsonl output, the key of the table in the root of the document
+        #[clap(long, default_value = "table")]
+        table: String,
+
         /// Path that contains Automerge changes
         #[clap(parse(from_os_str))]
         changes_file: Option<PathBuf>,
@@ -51,11 +66,27 @@ enum Command {
         output_file: Option<PathBuf>,
     },
 
+    /// Create an automerge document from a file in a specified format
     Import {
-        /// Format for input: json, toml
+        /// Format for input: json, toml, csv, jsonl. The csv and jsonl formats create a document
+        /// containing a table with one row per line of the input
         #[clap(long, short, default_value = "json")]
         format: ExportFormat,
 
+        /// For csv and jsonl input, the key in the root of the document to create the table at
+        #[clap(long, default_value = "table")]
+        table: String,
+
+        /// For csv and jsonl input, the column to use as the key of each row. If omitted rows are
+        /// keyed by random UUIDs
+        #[clap(long)]
+        primary_key: Option<String>,
+
+        /// For csv input, store every field as a string rather than inferring numbers, booleans
+        /// and nulls
+        #[clap(long)]
+        no_type_inference: bool,
+
         #[clap(parse(from_os_str))]
         input_file: Option<PathBuf>,
 
@@ -68,7 +99,15 @@ enum Command {
     /// document to stdout or the specified output file.
     Change {
         /// The change script to perform. Change scripts have the form <command> <path> [<JSON value>].
-        /// The possible commands are 'set', 'insert', 'delete', and 'increment'.
+        /// The possible commands are 'set', 'insert', 'delete', 'increment' and 'splice'.
+        ///
+        /// A script may contain many commands, one per line, so a JSON value must be written on
+        /// the same line as its command. Lines starting with '#' are ignored
+        /// and a 'commit [-m <message>]' line turns the commands before it into a change with
+        /// that message, otherwise the whole script is a single change. If the script starts
+        /// with '@' the rest of it is the path of a file to read the script from.
+        ///
+        /// Values are JSON, or `text <JSON string>` to create a text object.
         ///
         /// Paths look like this: $["mapkey"][0]. They always lways start with a '$', then each
         /// subsequent segment of the path is either a string in double quotes to index a key in a
@@ -91,6 +130,16 @@ enum Command {
         /// ## delete
         ///
         /// > automerge change 'delete $["someobject"]["items"]' somefile
+        ///
+        /// ## splice
+        ///
+        /// Delete 5 characters starting at index 6, then insert "birds" at index 6
+        ///
+        /// > automerge change 'splice $["sometext"][6] 5 "birds"' somefile
+        ///
+        /// ## a script file
+        ///
+        /// > automerge change @changes.txt somefile
         script: String,
 
         /// The file to change, if omitted will assume stdin
@@ -100,18 +149,112 @@ enum Command {
         /// Path to write Automerge changes to, if omitted will write to stdout
         #[clap(parse(from_os_str), long("out"), short('o'))]
         output_file: Option<PathBuf>,
+
+        /// The hex encoded actor ID to make the changes with, if omitted a random one is used
+        #[clap(long)]
+        actor: Option<amp::ActorId>,
+
+        /// The timestamp, in milliseconds since the epoch, to give the changes. If omitted the
+        /// current time is used. Together with --actor this makes the output reproducible
+        #[clap(long)]
+        time: Option<i64>,
     },
 
     /// Read an automerge document and print a JSON representation of the changes in it to stdout
     Examine { input_file: Option<PathBuf> },
 
-    /// Read one or more automerge documents and output a merged, compacted version of them
+    /// Read an automerge document and print it as JSON with each value annotated with the
+    /// actor, change hash, sequence number and time of the change which last wrote it
+    Blame {
+        /// The file to read, if omitted will assume stdin
+        #[clap(parse(from_os_str))]
+        input_file: Option<PathBuf>,
+    },
+
+    /// Check an automerge document for corruption and print a JSON report of any problems found.
+    /// Exits with a non-zero status if there are problems.
+    ///
+    /// The checks are: that every block decodes and has a valid checksum, that the heads of
+    /// compressed documents match their changes, that no two changes have the same actor and
+    /// sequence number, that every dependency of every change is present and that every object,
+    /// list element and predecessor an operation refers to exists.
+    Verify {
+        /// The file to check, if omitted will assume stdin
+        #[clap(parse(from_os_str))]
+        input_file: Option<PathBuf>,
+
+        /// Write a new document containing every change which is not part of a problem and all
+        /// of whose dependencies are present and not part of a problem
+        #[clap(parse(from_os_str), long)]
+        salvage: Option<PathBuf>,
+    },
+
+    /// Open a document in an interactive shell. Type `help` in the shell for a list of commands.
+    ///
+    /// Commands are read from stdin, one per line, so the shell can also run a script:
+    ///
+    /// > echo 'set $["bird"] "magpie"\ncommit\nsave' | automerge shell somefile
+    Shell {
+        /// The document to open, it will be created when the shell saves if it does not exist
+        #[clap(parse(from_os_str))]
+        file: Option<PathBuf>,
+    },
+
+    /// Read one or more automerge documents and output a merged, compacted version of them.
+    ///
+    /// Inputs which can't be loaded and changes whose dependencies are missing from every input
+    /// are listed on stderr. By default (--strict) this is an error and nothing is written, with
+    /// --best-effort the merged document is written anyway and the exit status is 2.
     Merge {
         /// The file to write to. If omitted assumes stdout
         #[clap(parse(from_os_str), long("out"), short('o'))]
         output_file: Option<PathBuf>,
         /// The file(s) to compact. If empty assumes stdin
         input: Vec<PathBuf>,
+        /// Fail without writing anything if any input can't be loaded or any change is missing
+        /// dependencies. This is the default.
+        #[clap(long, conflicts_with = "best-effort")]
+        strict: bool,
+        /// Write whatever could be merged even if some inputs can't be loaded or some changes
+        /// are missing dependencies, then exit with status 2
+        #[clap(long)]
+        best_effort: bool,
+    },
+
+    /// Synchronise a document with another document using the sync protocol and write the merged
+    /// result back to the document file(s). Statistics about the sync are printed to stderr.
+    ///
+    /// The other document can be a second file, or another `automerge sync` process which we talk
+    /// to over stdin/stdout (--stdio) or a Unix socket (--socket). When syncing with another
+    /// process exactly one of the two processes must be started with --listen.
+    ///
+    /// Examples
+    ///
+    /// > automerge sync a.mpl b.mpl
+    ///
+    /// > automerge sync --socket /tmp/doc.sock --listen a.mpl &
+    /// > automerge sync --socket /tmp/doc.sock b.mpl
+    Sync {
+        /// The document to sync, it will be created if it does not exist
+        #[clap(parse(from_os_str))]
+        file: PathBuf,
+
+        /// Another document to sync with, it will be created if it does not exist
+        #[clap(parse(from_os_str), conflicts_with_all(&["stdio", "socket"]))]
+        other_file: Option<PathBuf>,
+
+        /// Sync with another process over stdin and stdout
+        #[clap(long, conflicts_with("socket"))]
+        stdio: bool,
+
+        /// Sync with another process over the Unix socket at this path
+        #[clap(parse(from_os_str), long)]
+        socket: Option<PathBuf>,
+
+        /// Wait for the other process to start the sync. With --socket this binds the socket
+        /// rather than connecting to it
+        #[clap(long)]
+        listen: bool,
     },
 }
 
@@ -148,6 +291,7 @@ fn main() -> Result<()> {
         Command::Export {
             changes_file,
             format,
+            table,
             output_file,
         } => {
             let output: Box<dyn std::io::Write> = if let Some(output_file) = output_file {
@@ -161,49 +305,158 @@ fn main() -> Result<()> {
                     export::export_json(&mut in_buffer, output, atty::is(atty::Stream::Stdout))
                 }
                 ExportFormat::Toml => unimplemented!(),
+                ExportFormat::Csv => {
+                    let in_buffer = open_file_or_stdin(changes_file)?;
+                    table::export_csv(in_buffer, output, &table)
+                }
+                ExportFormat::JsonLines => {
+                    let in_buffer = open_file_or_stdin(changes_file)?;
+                    table::export_json_lines(in_buffer, output, &table)
+                }
             }
         }
         Command::Import {
             format,
+            table,
+            primary_key,
+            no_type_inference,
             input_file,
             changes_file,
-        } => match format {
-            ExportFormat::Json => {
-                let mut out_buffer = create_file_or_stdout(changes_file)?;
-                let mut in_buffer = open_file_or_stdin(input_file)?;
-                import::import_json(&mut in_buffer, &mut out_buffer)
+        } => {
+            let table_options = table::TableOptions {
+                table,
+                primary_key,
+                infer_types: !no_type_inference,
+            };
+            match format {
+                ExportFormat::Json => {
+                    let mut out_buffer = create_file_or_stdout(changes_file)?;
+                    let mut in_buffer = open_file_or_stdin(input_file)?;
+                    import::import_json(&mut in_buffer, &mut out_buffer)
+                }
+                ExportFormat::Toml => unimplemented!(),
+                ExportFormat::Csv => {
+                    let out_buffer = create_file_or_stdout(changes_file)?;
+                    let in_buffer = open_file_or_stdin(input_file)?;
+                    table::import_csv(in_buffer, out_buffer, &table_options)
+                }
+                ExportFormat::JsonLines => {
+                    let out_buffer = create_file_or_stdout(changes_file)?;
+                    let in_buffer = open_file_or_stdin(input_file)?;
+                    table::import_json_lines(
+                        std::io::BufReader::new(in_buffer),
+                        out_buffer,
+                        &table_options,
+                    )
+                }
             }
-            ExportFormat::Toml => unimplemented!(),
-        },
+        }
         Command::Change {
             input_file,
             output_file,
             script,
+            actor,
+            time,
         } => {
+            let script = match script.strip_prefix('@') {
+                Some(script_file) => std::fs::read_to_string(script_file)?,
+                None => script,
+            };
             let in_buffer = open_file_or_stdin(input_file)?;
             let mut out_buffer = create_file_or_stdout(output_file)?;
-            change::change(in_buffer, &mut out_buffer, script.as_str())
+            change::change(in_buffer, &mut out_buffer, script.as_str(), actor, time)
                 .map_err(|e| anyhow::format_err!("Unable to make changes: {:?}", e))
         }
         Command::Examine { input_file } => {
             let in_buffer = open_file_or_stdin(input_file)?;
             let out_buffer = std::io::stdout();
-            match examine::examine(in_buffer, out_buffer, atty::is(atty::Stream::Stdout)) {
-                Ok(()) => {}
-                Err(e) => {
-                    eprintln!("Error: {:?}", e);
-                }
+            examine::examine(in_buffer, out_buffer, atty::is(atty::Stream::Stdout))?;
+            Ok(())
+        }
+        Command::Blame { input_file } => {
+            let in_buffer = open_file_or_stdin(input_file)?;
+            blame::blame(in_buffer, std::io::stdout(), atty::is(atty::Stream::Stdout))
+        }
+        Command::Verify {
+            input_file,
+            salvage,
+        } => {
+            let in_buffer = open_file_or_stdin(input_file)?;
+            let report = verify::verify(in_buffer)?;
+            let mut out_buffer = std::io::stdout();
+            if atty::is(atty::Stream::Stdout) {
+                colored_json::write_colored_json(&report.to_json(), &mut out_buffer)?;
+                writeln!(out_buffer)?;
+            } else {
+                writeln!(
+                    out_buffer,
+                    "{}",
+                    serde_json::to_string_pretty(&report.to_json())?
+                )?;
+            }
+            if let Some(path) = salvage {
+                std::fs::write(path, report.salvage()?)?;
+            }
+            if report.is_ok() {
+                Ok(())
+            } else {
+                Err(anyhow!(
+                    "Found {} problem(s) in the document",
+                    report.problems.len()
+                ))
+            }
+        }
+        Command::Shell { file } => {
+            let stdin = std::io::stdin();
+            shell::shell(
+                file.as_deref(),
+                stdin.lock(),
+                std::io::stdout(),
+                atty::is(atty::Stream::Stdin),
+            )
+        }
+        Command::Merge {
+            input,
+            output_file,
+            strict,
+            best_effort,
+        } => {
+            let strict = strict || !best_effort;
+            let merged = merge::merge(input.into())?;
+            eprint!("{}", merged.report);
+            if !merged.report.is_clean() && strict {
+                return Err(anyhow!(
+                    "Merge failed, nothing was written. Use --best-effort to write the changes \
+                     which could be merged"
+                ));
+            }
+            let mut out_buffer = create_file_or_stdout(output_file)?;
+            out_buffer.write_all(&merged.document)?;
+            out_buffer.flush()?;
+            if !merged.report.is_clean() {
+                std::process::exit(2);
             }
             Ok(())
         }
-        Command::Merge { input, output_file } => {
-            let out_buffer = create_file_or_stdout(output_file)?;
-            match merge::merge(input.into(), out_buffer) {
-                Ok(()) => {}
-                Err(e) => {
-                    eprintln!("Failed to merge: {}", e);
+        Command::Sync {
+            file,
+            other_file,
+            stdio,
+            socket,
+            listen,
+        } => {
+            let peer = match (other_file, stdio, socket) {
+                (Some(other_file), _, _) => sync::Peer::File(other_file),
+                (None, true, _) => sync::Peer::Stdio { listen },
+                (None, false, Some(path)) => sync::Peer::Socket { path, listen },
+                (None, false, None) => {
+                    return Err(anyhow!(
+                        "Must provide another file, --stdio or --socket to sync with"
+                    ))
                 }
             };
+            let stats = sync::sync(&file, peer)?;
+            eprintln!("{}", stats);
             Ok(())
         }
     }
@@ -1,8 +1,12 @@
 use std::{
+    fmt,
     io::Read,
     path::{Path, PathBuf},
 };
 
+use automerge_backend as amb;
+use automerge_protocol as amp;
+
 pub(super) enum Inputs {
     Stdin,
     Paths(Vec<PathBuf>),
@@ -22,41 +26,224 @@ impl From<Vec<PathBuf>> for Inputs {
 pub(super) enum MergeError {
     #[error(transparent)]
     Io(#[from] std::io::Error),
-    #[error("failed to load {path}: {error}")]
-    FailedToLoad {
-        path: PathBuf,
-        error: Box<dyn std::error::Error>,
-    },
     #[error(transparent)]
-    Automerge(#[from] automerge_backend::AutomergeError),
+    Automerge(#[from] amb::AutomergeError),
+}
+
+/// An input which could not be loaded
+#[derive(Debug)]
+pub(super) struct InputFailure {
+    pub input: String,
+    pub error: Box<dyn std::error::Error>,
+}
+
+/// A change which could not be applied because some of its dependencies are missing
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct QueuedChange {
+    pub hash: amp::ChangeHash,
+    pub actor: amp::ActorId,
+    pub seq: u64,
+    pub missing_deps: Vec<amp::ChangeHash>,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct MergeReport {
+    pub failures: Vec<InputFailure>,
+    /// Changes which remained in the queue after loading every input, these are not in the
+    /// merged document
+    pub queued: Vec<QueuedChange>,
+}
+
+impl MergeReport {
+    pub fn is_clean(&self) -> bool {
+        self.failures.is_empty() && self.queued.is_empty()
+    }
 }
 
-pub(super) fn merge<W: std::io::Write>(inputs: Inputs, mut output: W) -> Result<(), MergeError> {
-    let mut backend = automerge_backend::Backend::new();
+impl fmt::Display for MergeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for failure in &self.failures {
+            writeln!(f, "failed to load {}: {}", failure.input, failure.error)?;
+        }
+        for queued in &self.queued {
+            let missing: Vec<_> = queued
+                .missing_deps
+                .iter()
+                .map(|h| hex::encode(h.0))
+                .collect();
+            writeln!(
+                f,
+                "change {} (actor {}, seq {}) is waiting for missing dependencies: {}",
+                hex::encode(queued.hash.0),
+                queued.actor,
+                queued.seq,
+                missing.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub(super) struct Merged {
+    /// The merged document, containing every change which could be applied
+    pub document: Vec<u8>,
+    pub report: MergeReport,
+}
+
+/// Load every input into one document.
+///
+/// Inputs which fail to load and changes which can't be applied are recorded in the report
+/// rather than stopping the merge, it's up to the caller to decide whether to use the result.
+pub(super) fn merge(inputs: Inputs) -> Result<Merged, MergeError> {
+    let mut backend = amb::Backend::new();
+    let mut report = MergeReport::default();
+    let mut loaded = Vec::new();
     match inputs {
         Inputs::Stdin => {
             let mut input = Vec::new();
             std::io::stdin().read_to_end(&mut input)?;
-            let changes = automerge_backend::Change::load_document(&input)?;
-            backend.load_changes(changes)?;
+            match load_bytes(&mut backend, &input) {
+                Ok(changes) => loaded.extend(changes),
+                Err(error) => report.failures.push(InputFailure {
+                    input: "stdin".into(),
+                    error,
+                }),
+            }
         }
         Inputs::Paths(paths) => {
             for path in paths {
-                load_path(&mut backend, &path)
-                    .map_err(|error| MergeError::FailedToLoad { path, error })?;
+                match load_path(&mut backend, &path) {
+                    Ok(changes) => loaded.extend(changes),
+                    Err(error) => report.failures.push(InputFailure {
+                        input: path.display().to_string(),
+                        error,
+                    }),
+                }
             }
         }
     }
-    output.write_all(&backend.save().unwrap())?;
-    Ok(())
+
+    let mut seen = std::collections::HashSet::new();
+    for change in loaded {
+        if seen.insert(change.hash) && backend.get_change_by_hash(&change.hash).is_none() {
+            let missing_deps = change
+                .deps
+                .iter()
+                .filter(|dep| backend.get_change_by_hash(dep).is_none())
+                .copied()
+                .collect();
+            report.queued.push(QueuedChange {
+                hash: change.hash,
+                actor: change.actor_id().clone(),
+                seq: change.seq,
+                missing_deps,
+            });
+        }
+    }
+
+    Ok(Merged {
+        document: backend.save()?,
+        report,
+    })
 }
 
 fn load_path(
-    backend: &mut automerge_backend::Backend,
+    backend: &mut amb::Backend,
     path: &Path,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<Vec<amb::Change>, Box<dyn std::error::Error>> {
     let input = std::fs::read(path).map_err(Box::new)?;
-    let changes = automerge_backend::Change::load_document(&input).map_err(Box::new)?;
-    backend.apply_changes(changes).map_err(Box::new)?;
-    Ok(())
+    load_bytes(backend, &input)
+}
+
+/// Apply the changes in `input` to `backend`, returning them
+fn load_bytes(
+    backend: &mut amb::Backend,
+    input: &[u8],
+) -> Result<Vec<amb::Change>, Box<dyn std::error::Error>> {
+    let changes = amb::Change::load_document(input).map_err(Box::new)?;
+    backend.apply_changes(changes.clone()).map_err(Box::new)?;
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(actor: &amp::ActorId, seq: u64, deps: Vec<amp::ChangeHash>) -> amb::Change {
+        amp::Change {
+            actor_id: actor.clone(),
+            seq,
+            start_op: seq,
+            time: 0,
+            message: None,
+            hash: None,
+            deps,
+            operations: vec![amp::Op {
+                action: amp::OpType::Set(amp::ScalarValue::Uint(seq)),
+                obj: amp::ObjectId::Root,
+                key: "seq".into(),
+                insert: false,
+                pred: amp::SortedVec::new(),
+            }],
+            extra_bytes: Vec::new(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn cli_merge_reports_failures_and_queued_changes() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("automerge-merge-{}", amp::ActorId::random()));
+        std::fs::create_dir(&dir).unwrap();
+
+        let actor = amp::ActorId::random();
+        let c1 = change(&actor, 1, vec![]);
+        let c2 = change(&actor, 2, vec![c1.hash]);
+        let c3 = change(&actor, 3, vec![c2.hash]);
+        let good = dir.join("good.mpl");
+        std::fs::write(&good, c1.raw_bytes()).unwrap();
+        // c3 depends on c2 which is in none of the inputs
+        let gap = dir.join("gap.mpl");
+        std::fs::write(&gap, c3.raw_bytes()).unwrap();
+        let corrupt = dir.join("corrupt.mpl");
+        let mut corrupt_bytes = c2.raw_bytes().to_vec();
+        corrupt_bytes[10] ^= 0xff;
+        std::fs::write(&corrupt, corrupt_bytes).unwrap();
+        let missing = dir.join("missing.mpl");
+
+        let merged = merge(Inputs::Paths(vec![
+            good,
+            gap,
+            corrupt.clone(),
+            missing.clone(),
+        ]))
+        .unwrap();
+
+        let failed: Vec<_> = merged
+            .report
+            .failures
+            .iter()
+            .map(|f| f.input.clone())
+            .collect();
+        assert_eq!(
+            failed,
+            vec![corrupt.display().to_string(), missing.display().to_string()]
+        );
+        assert_eq!(
+            merged.report.queued,
+            vec![QueuedChange {
+                hash: c3.hash,
+                actor: actor.clone(),
+                seq: 3,
+                missing_deps: vec![c2.hash],
+            }]
+        );
+        assert!(!merged.report.is_clean());
+
+        // The merged document contains everything which could be applied
+        let backend = amb::Backend::load(merged.document).unwrap();
+        assert_eq!(backend.get_heads(), vec![c1.hash]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
 }
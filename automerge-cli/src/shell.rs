@@ -0,0 +1,568 @@
+use std::{
+    collections::HashSet,
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use automerge_backend as amb;
+use automerge_frontend as amf;
+use automerge_protocol as amp;
+
+use crate::change::{parse_change_script, parse_commit_args, parse_path};
+
+const HELP: &str = r#"Change commands are added to the current transaction, which is turned into a single change by
+`commit`. Reads see the uncommitted operations.
+
+  set <path> <JSON value>        Set a value
+  insert <path> <JSON value>     Insert a value into a list
+  delete <path>                  Delete a value
+  increment <path>               Increment a counter
+  splice <path> <n> [<values>]   Delete n elements of a list or text object starting at an index
+                                 and insert a JSON array of values or a string there
+  commit [-m <message>]          Commit the current transaction
+  abort                          Discard the current transaction
+  get [<path>]                   Print the value at a path as JSON
+  ls [<path>]                    List the keys or indices of the object at a path
+  conflicts <path>               Print all the conflicting values at a path, keyed by op ID
+  heads                          Print the hashes of the heads of the document
+  undo                           Make a change which reverts the last change committed in this
+                                 session
+  save [<file>]                  Write the document to the file it was loaded from, or to <file>
+  help                           Print this message
+  quit                           Exit the shell
+
+Paths look like $["key"][0]. Values are JSON, or `text <JSON string>` to create a text object."#;
+
+enum Outcome {
+    Continue,
+    Quit,
+}
+
+/// An interactive session editing a single document
+struct Shell {
+    backend: amb::Backend,
+    /// All the changes made in this shell use the same actor so that the frontends we create
+    /// continue each other's sequence numbers
+    actor: amp::ActorId,
+    path: Option<PathBuf>,
+    pending: Vec<amf::LocalChange>,
+    /// The changes committed during this session, most recent last
+    commits: Vec<amp::ChangeHash>,
+    unsaved: bool,
+}
+
+impl Shell {
+    fn new(backend: amb::Backend, path: Option<PathBuf>) -> Shell {
+        Shell {
+            backend,
+            actor: amp::ActorId::random(),
+            path,
+            pending: Vec::new(),
+            commits: Vec::new(),
+            unsaved: false,
+        }
+    }
+
+    fn prompt(&self) -> String {
+        if self.pending.is_empty() {
+            "automerge> ".to_string()
+        } else {
+            format!("automerge ({} pending)> ", self.pending.len())
+        }
+    }
+
+    /// A frontend reflecting the committed state of the document with `pending` applied. Returns
+    /// the change which applying `pending` produced, if any.
+    fn frontend(
+        &self,
+        pending: &[amf::LocalChange],
+        message: Option<String>,
+    ) -> Result<(amf::Frontend, Option<amp::Change>)> {
+        let mut frontend = amf::Frontend::new_with_actor_id(self.actor.to_bytes());
+        frontend.apply_patch(self.backend.get_patch()?)?;
+        let ((), change) = frontend.change::<_, _, amf::InvalidChangeRequest>(message, |d| {
+            for local_change in pending {
+                d.add_change(local_change.clone())?;
+            }
+            Ok(())
+        })?;
+        Ok((frontend, change))
+    }
+
+    fn execute(&mut self, line: &str, writer: &mut impl Write) -> Result<Outcome> {
+        let line = line.trim();
+        let (command, args) = match line.find(char::is_whitespace) {
+            Some(i) => (&line[..i], line[i..].trim()),
+            None => (line, ""),
+        };
+        match command.to_lowercase().as_str() {
+            "" => {}
+            "set" | "insert" | "delete" | "increment" | "splice" => {
+                let mut pending = self.pending.clone();
+                pending.extend(parse_change_script(line)?);
+                // Check the operation applies before adding it to the transaction
+                self.frontend(&pending, None)?;
+                self.pending = pending;
+            }
+            "commit" => self.commit(args, writer)?,
+            "abort" => {
+                writeln!(writer, "discarded {} operation(s)", self.pending.len())?;
+                self.pending.clear();
+            }
+            "get" => {
+                let value = self.value_at(args)?;
+                writeln!(
+                    writer,
+                    "{}",
+                    serde_json::to_string_pretty(&value.to_json())?
+                )?;
+            }
+            "ls" => self.ls(args, writer)?,
+            "conflicts" => {
+                let path = parse_path(args)?;
+                let (frontend, _) = self.frontend(&self.pending, None)?;
+                let conflicts = frontend
+                    .get_conflicts(&path)
+                    .ok_or_else(|| anyhow!("Nothing at {}", args))?;
+                let json: serde_json::Map<_, _> = conflicts
+                    .into_iter()
+                    .map(|(opid, value)| (opid.to_string(), value.to_json()))
+                    .collect();
+                writeln!(writer, "{}", serde_json::to_string_pretty(&json)?)?;
+            }
+            "heads" => {
+                for head in self.backend.get_heads() {
+                    writeln!(writer, "{}", hex::encode(head.0))?;
+                }
+            }
+            "undo" => self.undo(writer)?,
+            "save" => {
+                let path = if args.is_empty() {
+                    self.path
+                        .clone()
+                        .ok_or_else(|| anyhow!("No file to save to, use `save <file>`"))?
+                } else {
+                    PathBuf::from(args)
+                };
+                std::fs::write(&path, self.backend.save()?)?;
+                self.unsaved = false;
+                writeln!(writer, "saved to {}", path.display())?;
+                if !self.pending.is_empty() {
+                    writeln!(
+                        writer,
+                        "{} uncommitted operation(s) were not saved",
+                        self.pending.len()
+                    )?;
+                }
+            }
+            "help" => writeln!(writer, "{}", HELP)?,
+            "quit" | "exit" => return Ok(Outcome::Quit),
+            other => return Err(anyhow!("Unknown command: {}, try `help`", other)),
+        }
+        Ok(Outcome::Continue)
+    }
+
+    fn commit(&mut self, args: &str, writer: &mut impl Write) -> Result<()> {
+        let message = parse_commit_args(args)?;
+        let (_, change) = self.frontend(&self.pending, message)?;
+        self.pending.clear();
+        match change {
+            Some(change) => {
+                let (_, change) = self.backend.apply_local_change(change)?;
+                self.commits.push(change.hash);
+                self.unsaved = true;
+                writeln!(writer, "{}", hex::encode(change.hash.0))?;
+            }
+            None => writeln!(writer, "nothing to commit")?,
+        }
+        Ok(())
+    }
+
+    fn undo(&mut self, writer: &mut impl Write) -> Result<()> {
+        if !self.pending.is_empty() {
+            return Err(anyhow!(
+                "There is an uncommitted transaction, commit or abort it first"
+            ));
+        }
+        let undone = *self
+            .commits
+            .last()
+            .ok_or_else(|| anyhow!("No changes have been committed in this session"))?;
+        // The change may already have been saved or synced, so rather than removing it we make a
+        // new change which puts back the values it overwrote. Changes are undone most recent
+        // first, so the document is as `undone` left it.
+        let before = self.value_before(&undone)?;
+        let (mut frontend, _) = self.frontend(&[], None)?;
+        let mut reverts = Vec::new();
+        revert_changes(&amf::Path::root(), frontend.state(), &before, &mut reverts);
+        let message = format!("Undo {}", hex::encode(undone.0));
+        let (_, change) = self.frontend(&reverts, Some(message))?;
+        self.commits.pop();
+        match change {
+            Some(change) => {
+                let (_, change) = self.backend.apply_local_change(change)?;
+                self.unsaved = true;
+                writeln!(
+                    writer,
+                    "undid {} with {}",
+                    hex::encode(undone.0),
+                    hex::encode(change.hash.0)
+                )?;
+            }
+            None => writeln!(writer, "undid {}, nothing changed", hex::encode(undone.0))?,
+        }
+        Ok(())
+    }
+
+    /// The document as it was before the change `hash` was made
+    fn value_before(&self, hash: &amp::ChangeHash) -> Result<amf::Value> {
+        let change = self
+            .backend
+            .get_change_by_hash(hash)
+            .ok_or_else(|| anyhow!("Missing change {}", hex::encode(hash.0)))?;
+        let mut ancestors = HashSet::new();
+        let mut to_visit = change.deps.clone();
+        while let Some(dep) = to_visit.pop() {
+            if ancestors.insert(dep) {
+                if let Some(dep) = self.backend.get_change_by_hash(&dep) {
+                    to_visit.extend(dep.deps.iter().copied());
+                }
+            }
+        }
+        let changes = self
+            .backend
+            .get_changes(&[])
+            .into_iter()
+            .filter(|c| ancestors.contains(&c.hash))
+            .cloned()
+            .collect();
+        let mut backend = amb::Backend::new();
+        backend.load_changes(changes)?;
+        let mut frontend = amf::Frontend::new();
+        frontend.apply_patch(backend.get_patch()?)?;
+        Ok(frontend.state().clone())
+    }
+
+    fn value_at(&self, path: &str) -> Result<amf::Value> {
+        let (mut frontend, _) = self.frontend(&self.pending, None)?;
+        if path.is_empty() {
+            Ok(frontend.state().clone())
+        } else {
+            frontend
+                .get_value(&parse_path(path)?)
+                .ok_or_else(|| anyhow!("Nothing at {}", path))
+        }
+    }
+
+    fn ls(&self, path: &str, writer: &mut impl Write) -> Result<()> {
+        match self.value_at(path)? {
+            amf::Value::Map(props) | amf::Value::Table(props) => {
+                let mut keys: Vec<_> = props.keys().collect();
+                keys.sort();
+                for key in keys {
+                    writeln!(writer, "{}\t{}", key, summary(&props[key]))?;
+                }
+            }
+            amf::Value::List(elems) => {
+                for (index, elem) in elems.iter().enumerate() {
+                    writeln!(writer, "{}\t{}", index, summary(elem))?;
+                }
+            }
+            amf::Value::Text(elements) => {
+                for (index, element) in elements.iter().enumerate() {
+                    match element {
+                        amf::TextElement::Grapheme(g) => {
+                            writeln!(writer, "{}\t{:?}", index, g.as_str())?
+                        }
+                        amf::TextElement::Embedded(v) => {
+                            writeln!(writer, "{}\t{}", index, summary(v))?
+                        }
+                    }
+                }
+            }
+            amf::Value::Set(members) => {
+                for member in members {
+                    writeln!(writer, "{}", amf::Value::Primitive(member).to_json())?;
+                }
+            }
+            amf::Value::Primitive(_) => return Err(anyhow!("{} is not an object", path)),
+        }
+        Ok(())
+    }
+}
+
+/// Add the changes which turn `current`, the value at `path`, into `target` to `changes`. Objects
+/// which are in both are changed in place rather than replaced.
+fn revert_changes(
+    path: &amf::Path,
+    current: &amf::Value,
+    target: &amf::Value,
+    changes: &mut Vec<amf::LocalChange>,
+) {
+    match (current, target) {
+        (amf::Value::Map(current), amf::Value::Map(target))
+        | (amf::Value::Table(current), amf::Value::Table(target)) => {
+            for key in current.keys().filter(|key| !target.contains_key(*key)) {
+                changes.push(amf::LocalChange::delete(path.clone().key(key.clone())));
+            }
+            for (key, value) in target {
+                let path = path.clone().key(key.clone());
+                match current.get(key) {
+                    Some(old) => revert_changes(&path, old, value, changes),
+                    None => changes.push(amf::LocalChange::set(path, value.clone())),
+                }
+            }
+        }
+        (amf::Value::List(current), amf::Value::List(target)) => {
+            revert_sequence(path, current, target, amf::Value::clone, changes)
+        }
+        (amf::Value::Text(current), amf::Value::Text(target)) => {
+            revert_sequence(path, current, target, amf::TextElement::to_value, changes)
+        }
+        (amf::Value::Set(current), amf::Value::Set(target)) => {
+            for member in current.iter().filter(|m| !target.contains(m)) {
+                changes.push(amf::LocalChange::remove_from_set(
+                    path.clone(),
+                    member.clone(),
+                ));
+            }
+            for member in target.iter().filter(|m| !current.contains(m)) {
+                changes.push(amf::LocalChange::add_to_set(path.clone(), member.clone()));
+            }
+        }
+        (
+            amf::Value::Primitive(amf::Primitive::Counter(current)),
+            amf::Value::Primitive(amf::Primitive::Counter(target)),
+        ) => {
+            if current != target {
+                changes.push(amf::LocalChange::increment_by(
+                    path.clone(),
+                    target - current,
+                ));
+            }
+        }
+        (current, target) => {
+            if current != target {
+                changes.push(amf::LocalChange::set(path.clone(), target.clone()));
+            }
+        }
+    }
+}
+
+/// Add the changes which turn the list or text `current` at `path` into `target` to `changes`,
+/// leaving the elements at either end which are the same alone
+fn revert_sequence<T: PartialEq>(
+    path: &amf::Path,
+    current: &[T],
+    target: &[T],
+    to_value: impl Fn(&T) -> amf::Value,
+    changes: &mut Vec<amf::LocalChange>,
+) {
+    let prefix = current
+        .iter()
+        .zip(target)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = current[prefix..]
+        .iter()
+        .rev()
+        .zip(target[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let current = &current[prefix..current.len() - suffix];
+    let target = &target[prefix..target.len() - suffix];
+    let common = current.len().min(target.len());
+    for (i, (old, new)) in current.iter().zip(target).enumerate() {
+        let path = path.clone().index((prefix + i) as u32);
+        revert_changes(&path, &to_value(old), &to_value(new), changes);
+    }
+    let index = path.clone().index((prefix + common) as u32);
+    for _ in common..current.len() {
+        changes.push(amf::LocalChange::delete(index.clone()));
+    }
+    if target.len() > common {
+        let values = target[common..].iter().map(to_value).collect();
+        changes.push(amf::LocalChange::insert_many(index, values));
+    }
+}
+
+/// A one line description of a value for `ls`
+fn summary(value: &amf::Value) -> String {
+    match value {
+        amf::Value::Map(props) => format!("map ({} keys)", props.len()),
+        amf::Value::Table(props) => format!("table ({} rows)", props.len()),
+        amf::Value::List(elems) => format!("list ({} elements)", elems.len()),
+        amf::Value::Text(elements) => format!("text ({} characters)", elements.len()),
+        amf::Value::Set(members) => format!("set ({} members)", members.len()),
+        amf::Value::Primitive(_) => value.to_json().to_string(),
+    }
+}
+
+/// Run a shell which reads commands from `reader`, one per line, and writes the results to
+/// `writer`. If `interactive` is true a prompt is written before reading each command.
+pub(super) fn shell(
+    path: Option<&Path>,
+    reader: impl BufRead,
+    mut writer: impl Write,
+    interactive: bool,
+) -> Result<()> {
+    let backend = match path {
+        Some(path) if path.exists() => amb::Backend::load(std::fs::read(path)?)?,
+        _ => amb::Backend::new(),
+    };
+    let mut shell = Shell::new(backend, path.map(Path::to_path_buf));
+    if interactive {
+        write!(writer, "{}", shell.prompt())?;
+        writer.flush()?;
+    }
+    for line in reader.lines() {
+        match shell.execute(&line?, &mut writer) {
+            Ok(Outcome::Continue) => {}
+            Ok(Outcome::Quit) => break,
+            Err(e) => writeln!(writer, "error: {}", e)?,
+        }
+        if interactive {
+            write!(writer, "{}", shell.prompt())?;
+            writer.flush()?;
+        }
+    }
+    if shell.unsaved || !shell.pending.is_empty() {
+        writeln!(writer, "warning: exiting with unsaved changes")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(shell: &mut Shell, line: &str) -> String {
+        let mut output = Vec::new();
+        if let Err(e) = shell.execute(line, &mut output) {
+            writeln!(output, "error: {}", e).unwrap();
+        }
+        String::from_utf8(output).unwrap()
+    }
+
+    fn get_json(shell: &mut Shell, path: &str) -> serde_json::Value {
+        serde_json::from_str(&run(shell, &format!("get {}", path))).unwrap()
+    }
+
+    #[test]
+    fn cli_shell_transactions_and_undo() {
+        let mut shell = Shell::new(amb::Backend::new(), None);
+        run(&mut shell, r#"set $["birds"] ["wren"]"#);
+        run(&mut shell, r#"insert $["birds"][1] "magpie""#);
+        // Reads see uncommitted operations
+        assert_eq!(
+            get_json(&mut shell, ""),
+            serde_json::json!({"birds": ["wren", "magpie"]})
+        );
+        assert_eq!(shell.prompt(), "automerge (2 pending)> ");
+
+        run(&mut shell, r#"commit -m "add birds""#);
+        assert!(shell.pending.is_empty());
+        let heads = shell.backend.get_heads();
+        assert_eq!(heads, shell.commits);
+        let change = shell.backend.get_change_by_hash(&heads[0]).unwrap();
+        assert_eq!(change.message(), Some("add birds".to_string()));
+        assert_eq!(change.iter_ops().count(), 3);
+
+        run(&mut shell, r#"set $["bugs"] 3"#);
+        run(&mut shell, r#"delete $["birds"][0]"#);
+        run(&mut shell, r#"insert $["birds"][1] "jay""#);
+        run(&mut shell, "commit");
+        assert_eq!(
+            run(&mut shell, "ls"),
+            "birds\tlist (2 elements)\nbugs\t3.0\n"
+        );
+        // a peer which already has the change we are about to undo
+        let mut peer = amb::Backend::new();
+        peer.load_changes(
+            shell
+                .backend
+                .get_changes(&[])
+                .into_iter()
+                .cloned()
+                .collect(),
+        )
+        .unwrap();
+
+        assert!(run(&mut shell, "undo").starts_with("undid "));
+        assert_eq!(
+            get_json(&mut shell, ""),
+            serde_json::json!({"birds": ["wren", "magpie"]})
+        );
+        // Undoing makes a new change rather than rewriting history
+        let undo_heads = shell.backend.get_heads();
+        assert_ne!(undo_heads, heads);
+        let undo = shell.backend.get_change_by_hash(&undo_heads[0]).unwrap();
+        assert_eq!(undo.seq, 3);
+        assert_eq!(shell.backend.get_changes(&[]).len(), 3);
+        peer.apply_changes(
+            shell
+                .backend
+                .get_changes(&[])
+                .into_iter()
+                .cloned()
+                .collect(),
+        )
+        .unwrap();
+        assert_eq!(peer.get_heads(), undo_heads);
+
+        run(&mut shell, r#"set $["bugs"] 4"#);
+        run(&mut shell, "commit");
+        assert_eq!(get_json(&mut shell, r#"$["bugs"]"#), serde_json::json!(4.0));
+
+        // Undoing the first change as well leaves an empty document
+        run(&mut shell, "undo");
+        run(&mut shell, "undo");
+        assert_eq!(get_json(&mut shell, ""), serde_json::json!({}));
+        assert!(run(&mut shell, "undo").starts_with("error:"));
+    }
+
+    #[test]
+    fn cli_shell_rejects_invalid_commands() {
+        let mut shell = Shell::new(amb::Backend::new(), None);
+        assert!(run(&mut shell, r#"insert $["nothing"][0] 1"#).starts_with("error:"));
+        assert!(shell.pending.is_empty());
+        assert!(run(&mut shell, "frobnicate").starts_with("error: Unknown command"));
+        assert!(run(&mut shell, "undo").starts_with("error:"));
+        assert!(run(&mut shell, "save").starts_with("error:"));
+        assert_eq!(run(&mut shell, "commit"), "nothing to commit\n");
+    }
+
+    #[test]
+    fn cli_shell_script() {
+        let mut dir = std::env::temp_dir();
+        dir.push(format!("automerge-shell-{}", amp::ActorId::random()));
+        std::fs::create_dir(&dir).unwrap();
+        let path = dir.join("doc.mpl");
+
+        let script = r#"set $["bird"] "magpie"
+commit -m first
+heads
+save
+quit
+set $["ignored"] true
+"#;
+        let mut output = Vec::new();
+        shell(Some(&path), script.as_bytes(), &mut output, false).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<_> = output.lines().collect();
+        // commit and heads both print the hash of the new change
+        assert_eq!(lines[0], lines[1]);
+        assert_eq!(lines[2], format!("saved to {}", path.display()));
+        assert_eq!(lines.len(), 3);
+
+        let backend = amb::Backend::load(std::fs::read(&path).unwrap()).unwrap();
+        let mut frontend = amf::Frontend::new();
+        frontend.apply_patch(backend.get_patch().unwrap()).unwrap();
+        assert_eq!(
+            frontend.state().to_json(),
+            serde_json::json!({"bird": "magpie"})
+        );
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,333 @@
+use std::{
+    fmt,
+    io::{Read, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use automerge_backend as amb;
+
+/// The maximum number of round trips we will make before assuming the peers will never converge
+const MAX_ROUND_TRIPS: usize = 100;
+
+/// The largest sync message we will send or accept, so a misbehaving peer can't make us allocate
+/// an arbitrary amount of memory by sending a large frame length
+const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+/// Who we are synchronising with
+pub(super) enum Peer {
+    /// Another document file which we load into this process
+    File(PathBuf),
+    /// Another `automerge sync` process connected to our stdin and stdout
+    Stdio { listen: bool },
+    /// Another `automerge sync` process at the other end of a Unix socket
+    Socket { path: PathBuf, listen: bool },
+}
+
+/// Statistics about a sync session, from the point of view of the local document
+#[derive(Debug, Default, Clone, PartialEq)]
+pub(super) struct SyncStats {
+    pub messages_sent: usize,
+    pub messages_received: usize,
+    pub bytes_sent: usize,
+    pub bytes_received: usize,
+    pub changes_sent: usize,
+    pub changes_received: usize,
+}
+
+impl SyncStats {
+    /// A round trip is one message in each direction
+    pub fn round_trips(&self) -> usize {
+        (self.messages_sent + self.messages_received).div_ceil(2)
+    }
+
+    fn record_sent(&mut self, message: &amb::SyncMessage, encoded: &[u8]) {
+        self.messages_sent += 1;
+        self.bytes_sent += encoded.len();
+        self.changes_sent += message.changes.len();
+    }
+
+    fn record_received(&mut self, message: &amb::SyncMessage, encoded: &[u8]) {
+        self.messages_received += 1;
+        self.bytes_received += encoded.len();
+        self.changes_received += message.changes.len();
+    }
+}
+
+impl fmt::Display for SyncStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "round trips: {}", self.round_trips())?;
+        writeln!(
+            f,
+            "sent: {} messages, {} bytes, {} changes",
+            self.messages_sent, self.bytes_sent, self.changes_sent
+        )?;
+        write!(
+            f,
+            "received: {} messages, {} bytes, {} changes",
+            self.messages_received, self.bytes_received, self.changes_received
+        )
+    }
+}
+
+pub(super) fn sync(path: &Path, peer: Peer) -> Result<SyncStats> {
+    let mut backend = load_or_create(path)?;
+    let stats = match peer {
+        Peer::File(other_path) => {
+            let mut other = load_or_create(&other_path)?;
+            let stats = sync_local(&mut backend, &mut other)?;
+            std::fs::write(&other_path, other.save()?)?;
+            stats
+        }
+        Peer::Stdio { listen } => sync_stream(
+            &mut backend,
+            std::io::stdin().lock(),
+            std::io::stdout().lock(),
+            !listen,
+        )?,
+        Peer::Socket { path, listen } => sync_socket(&mut backend, &path, listen)?,
+    };
+    std::fs::write(path, backend.save()?)?;
+    Ok(stats)
+}
+
+fn load_or_create(path: &Path) -> Result<amb::Backend> {
+    if path.exists() {
+        Ok(amb::Backend::load(std::fs::read(path)?)?)
+    } else {
+        Ok(amb::Backend::new())
+    }
+}
+
+#[cfg(unix)]
+fn sync_socket(backend: &mut amb::Backend, path: &Path, listen: bool) -> Result<SyncStats> {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    if listen {
+        let listener = UnixListener::bind(path)?;
+        let accepted = listener.accept();
+        // The socket file is only needed to establish the connection
+        std::fs::remove_file(path)?;
+        let (stream, _) = accepted?;
+        sync_stream(backend, &stream, &stream, false)
+    } else {
+        let stream = UnixStream::connect(path)?;
+        sync_stream(backend, &stream, &stream, true)
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_socket(_backend: &mut amb::Backend, _path: &Path, _listen: bool) -> Result<SyncStats> {
+    Err(anyhow!("Unix sockets are not supported on this platform"))
+}
+
+/// Synchronise two documents in this process.
+///
+/// The documents take turns to respond to each other's messages until one of them has nothing
+/// left to say.
+pub(super) fn sync_local(a: &mut amb::Backend, b: &mut amb::Backend) -> Result<SyncStats> {
+    let mut stats = SyncStats::default();
+    let mut a_state = amb::SyncState::default();
+    let mut b_state = amb::SyncState::default();
+    let mut message = a.generate_sync_message(&mut a_state);
+    while let Some(a_to_b) = message {
+        let encoded = a_to_b.clone().encode()?;
+        stats.record_sent(&a_to_b, &encoded);
+        b.receive_sync_message(&mut b_state, amb::SyncMessage::decode(&encoded)?)?;
+
+        message = match b.generate_sync_message(&mut b_state) {
+            Some(b_to_a) => {
+                let encoded = b_to_a.clone().encode()?;
+                stats.record_received(&b_to_a, &encoded);
+                a.receive_sync_message(&mut a_state, amb::SyncMessage::decode(&encoded)?)?;
+                a.generate_sync_message(&mut a_state)
+            }
+            None => None,
+        };
+        check_round_trips(&stats)?;
+    }
+    Ok(stats)
+}
+
+/// Synchronise with a peer at the other end of a byte stream.
+///
+/// The initiator sends the first message and from then on each peer replies to the message it
+/// has just received. A peer with nothing to reply sends an empty frame, at which point both
+/// peers stop.
+pub(super) fn sync_stream<R: Read, W: Write>(
+    backend: &mut amb::Backend,
+    mut reader: R,
+    mut writer: W,
+    initiator: bool,
+) -> Result<SyncStats> {
+    let mut stats = SyncStats::default();
+    let mut state = amb::SyncState::default();
+    if initiator && !send(backend, &mut state, &mut writer, &mut stats)? {
+        return Ok(stats);
+    }
+    while receive(backend, &mut state, &mut reader, &mut stats)? {
+        if !send(backend, &mut state, &mut writer, &mut stats)? {
+            break;
+        }
+        check_round_trips(&stats)?;
+    }
+    Ok(stats)
+}
+
+fn check_round_trips(stats: &SyncStats) -> Result<()> {
+    if stats.round_trips() > MAX_ROUND_TRIPS {
+        Err(anyhow!(
+            "Documents did not converge after {} round trips",
+            MAX_ROUND_TRIPS
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn send<W: Write>(
+    backend: &amb::Backend,
+    state: &mut amb::SyncState,
+    writer: &mut W,
+    stats: &mut SyncStats,
+) -> Result<bool> {
+    if let Some(message) = backend.generate_sync_message(state) {
+        let encoded = message.clone().encode()?;
+        stats.record_sent(&message, &encoded);
+        write_frame(writer, &encoded)?;
+        Ok(true)
+    } else {
+        write_frame(writer, &[])?;
+        Ok(false)
+    }
+}
+
+fn receive<R: Read>(
+    backend: &mut amb::Backend,
+    state: &mut amb::SyncState,
+    reader: &mut R,
+    stats: &mut SyncStats,
+) -> Result<bool> {
+    let encoded = read_frame(reader)?;
+    if encoded.is_empty() {
+        return Ok(false);
+    }
+    let message = amb::SyncMessage::decode(&encoded)?;
+    stats.record_received(&message, &encoded);
+    backend.receive_sync_message(state, message)?;
+    Ok(true)
+}
+
+/// Frames are a big endian u32 length followed by that many bytes. A zero length frame means the
+/// sender had no message to send. Frames are at most `MAX_FRAME_SIZE` bytes long.
+fn write_frame<W: Write>(writer: &mut W, bytes: &[u8]) -> Result<()> {
+    if bytes.len() > MAX_FRAME_SIZE {
+        return Err(anyhow!(
+            "Sync message of {} bytes is larger than the maximum of {} bytes",
+            bytes.len(),
+            MAX_FRAME_SIZE
+        ));
+    }
+    writer.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    writer.write_all(bytes)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn read_frame<R: Read>(reader: &mut R) -> Result<Vec<u8>> {
+    let mut len = [0; 4];
+    reader
+        .read_exact(&mut len)
+        .map_err(|e| anyhow!("Peer closed the connection: {}", e))?;
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(anyhow!(
+            "Peer sent a sync message of {} bytes, the maximum is {} bytes",
+            len,
+            MAX_FRAME_SIZE
+        ));
+    }
+    let mut bytes = vec![0; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use automerge_frontend as amf;
+
+    use super::*;
+
+    fn backend_with(key: &str, value: &str) -> amb::Backend {
+        let mut frontend = amf::Frontend::new();
+        let ((), change) = frontend
+            .change::<_, _, amf::InvalidChangeRequest>(None, |d| {
+                d.add_change(amf::LocalChange::set(
+                    amf::Path::root().key(key),
+                    amf::Value::from(value),
+                ))
+            })
+            .unwrap();
+        let mut backend = amb::Backend::new();
+        backend.apply_local_change(change.unwrap()).unwrap();
+        backend
+    }
+
+    fn state_json(backend: &amb::Backend) -> serde_json::Value {
+        let mut frontend = amf::Frontend::new();
+        frontend.apply_patch(backend.get_patch().unwrap()).unwrap();
+        frontend.state().to_json()
+    }
+
+    #[test]
+    fn cli_sync_local_documents() {
+        let mut a = backend_with("bird", "magpie");
+        let mut b = backend_with("bug", "ant");
+
+        let stats = sync_local(&mut a, &mut b).unwrap();
+
+        let expected = serde_json::json!({"bird": "magpie", "bug": "ant"});
+        assert_eq!(state_json(&a), expected);
+        assert_eq!(state_json(&b), expected);
+        assert_eq!(stats.changes_sent, 1);
+        assert_eq!(stats.changes_received, 1);
+
+        // Syncing again should not send any changes
+        let stats = sync_local(&mut a, &mut b).unwrap();
+        assert_eq!(stats.changes_sent, 0);
+        assert_eq!(stats.changes_received, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cli_sync_over_a_stream() {
+        let (left, right) = std::os::unix::net::UnixStream::pair().unwrap();
+        let listener = std::thread::spawn(move || {
+            let mut b = backend_with("bug", "ant");
+            let stats = sync_stream(&mut b, &right, &right, false).unwrap();
+            (state_json(&b), stats)
+        });
+        let mut a = backend_with("bird", "magpie");
+        let a_stats = sync_stream(&mut a, &left, &left, true).unwrap();
+        let (b_json, b_stats) = listener.join().unwrap();
+
+        let expected = serde_json::json!({"bird": "magpie", "bug": "ant"});
+        assert_eq!(state_json(&a), expected);
+        assert_eq!(b_json, expected);
+        assert_eq!(a_stats.messages_sent, b_stats.messages_received);
+        assert_eq!(a_stats.bytes_received, b_stats.bytes_sent);
+        assert_eq!(a_stats.round_trips(), b_stats.round_trips());
+    }
+
+    #[test]
+    fn cli_sync_rejects_oversized_frames() {
+        let mut frame = Vec::new();
+        write_frame(&mut frame, b"hello").unwrap();
+        assert_eq!(read_frame(&mut frame.as_slice()).unwrap(), b"hello");
+
+        // the length is checked before anything is allocated for the frame
+        let header = ((MAX_FRAME_SIZE + 1) as u32).to_be_bytes();
+        assert!(read_frame(&mut &header[..]).is_err());
+        assert!(write_frame(&mut Vec::new(), &vec![0; MAX_FRAME_SIZE + 1]).is_err());
+    }
+}
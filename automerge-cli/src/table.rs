@@ -0,0 +1,208 @@
+use std::collections::{BTreeSet, HashMap};
+
+use anyhow::{anyhow, Result};
+use automerge_backend as amb;
+use automerge_frontend as amf;
+use smol_str::SmolStr;
+
+/// How to build a table from records
+pub struct TableOptions {
+    /// The key in the root of the document to put the table at
+    pub table: String,
+    /// The column to key rows by, if `None` rows are keyed by a random UUID
+    pub primary_key: Option<String>,
+    /// Whether to infer the types of CSV fields or store them all as strings
+    pub infer_types: bool,
+}
+
+impl TableOptions {
+    fn builder(&self) -> amf::TableBuilder {
+        let builder = amf::TableBuilder::new().infer_types(self.infer_types);
+        match &self.primary_key {
+            Some(column) => builder.primary_key(column.as_str()),
+            None => builder,
+        }
+    }
+}
+
+fn initialize_from_table(table_key: &str, builder: amf::TableBuilder) -> Result<Vec<u8>> {
+    let mut root = HashMap::new();
+    root.insert(SmolStr::from(table_key), builder.build());
+    let (_, initial_change) = amf::Frontend::new_with_initial_state(amf::Value::Map(root))?;
+    let mut backend = amb::Backend::new();
+    backend.apply_local_change(initial_change)?;
+    Ok(backend.save()?)
+}
+
+/// Create a document with a table containing one row per record of the CSV in `reader`. The
+/// first line of the CSV is the column names.
+pub fn import_csv(
+    reader: impl std::io::Read,
+    mut writer: impl std::io::Write,
+    options: &TableOptions,
+) -> Result<()> {
+    let mut csv_reader = csv::Reader::from_reader(reader);
+    let headers = csv_reader.headers()?.clone();
+    let mut builder = options.builder();
+    for (index, record) in csv_reader.records().enumerate() {
+        let record = record?;
+        builder
+            .add_record(headers.iter().zip(record.iter()))
+            .map_err(|e| anyhow!("Invalid record {}: {}", index + 1, e))?;
+    }
+    writer.write_all(&initialize_from_table(&options.table, builder)?)?;
+    Ok(())
+}
+
+/// Create a document with a table containing one row per line of `reader`, each line must be a
+/// JSON object.
+pub fn import_json_lines(
+    reader: impl std::io::BufRead,
+    mut writer: impl std::io::Write,
+    options: &TableOptions,
+) -> Result<()> {
+    let mut builder = options.builder();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let row = match amf::Value::from_json(&serde_json::from_str(&line)?) {
+            amf::Value::Map(row) => row,
+            _ => return Err(anyhow!("Line {} is not a JSON object", index + 1)),
+        };
+        builder
+            .add_row(row)
+            .map_err(|e| anyhow!("Invalid row on line {}: {}", index + 1, e))?;
+    }
+    writer.write_all(&initialize_from_table(&options.table, builder)?)?;
+    Ok(())
+}
+
+/// The rows of the table at `table_key` in the root of the document, ordered by row key
+fn load_table_rows(
+    mut changes_reader: impl std::io::Read,
+    table_key: &str,
+) -> Result<Vec<HashMap<SmolStr, amf::Value>>> {
+    let mut input_data = vec![];
+    changes_reader.read_to_end(&mut input_data)?;
+    let backend = amb::Backend::load(input_data)?;
+    let mut frontend = amf::Frontend::new();
+    frontend.apply_patch(backend.get_patch()?)?;
+    let mut rows = match frontend.get_value(&amf::Path::root().key(table_key)) {
+        Some(amf::Value::Table(rows)) => rows.into_iter().collect::<Vec<_>>(),
+        Some(_) => return Err(anyhow!("{} is not a table", table_key)),
+        None => return Err(anyhow!("The document has no table at {}", table_key)),
+    };
+    rows.sort_by(|(a, _), (b, _)| a.cmp(b));
+    rows.into_iter()
+        .map(|(key, row)| match row {
+            amf::Value::Map(row) => Ok(row),
+            _ => Err(anyhow!("Row {} is not a map", key)),
+        })
+        .collect()
+}
+
+/// Strings are written as is, other primitives and objects as JSON
+fn csv_field(value: Option<&amf::Value>) -> String {
+    match value {
+        None | Some(amf::Value::Primitive(amf::Primitive::Null)) => String::new(),
+        Some(amf::Value::Primitive(amf::Primitive::Str(s))) => s.to_string(),
+        Some(value) => value.to_json().to_string(),
+    }
+}
+
+/// Write the table at `table_key` as CSV, with one column for every key used by any row
+pub fn export_csv(
+    changes_reader: impl std::io::Read,
+    writer: impl std::io::Write,
+    table_key: &str,
+) -> Result<()> {
+    let rows = load_table_rows(changes_reader, table_key)?;
+    let columns: BTreeSet<&SmolStr> = rows.iter().flat_map(|row| row.keys()).collect();
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    csv_writer.write_record(columns.iter().map(|c| c.as_str()))?;
+    for row in &rows {
+        csv_writer.write_record(columns.iter().map(|c| csv_field(row.get(*c))))?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+/// Write each row of the table at `table_key` as a JSON object on its own line
+pub fn export_json_lines(
+    changes_reader: impl std::io::Read,
+    mut writer: impl std::io::Write,
+    table_key: &str,
+) -> Result<()> {
+    for row in load_table_rows(changes_reader, table_key)? {
+        writeln!(writer, "{}", amf::Value::Map(row).to_json())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(primary_key: Option<&str>) -> TableOptions {
+        TableOptions {
+            table: "birds".into(),
+            primary_key: primary_key.map(String::from),
+            infer_types: true,
+        }
+    }
+
+    #[test]
+    fn cli_csv_round_trip() {
+        let csv = "id,name,count,seen\n2,magpie,1.5,\n1,\"wren, jenny\",3,true\n";
+        let mut document = Vec::new();
+        import_csv(csv.as_bytes(), &mut document, &options(Some("id"))).unwrap();
+
+        let mut exported = Vec::new();
+        export_csv(&document[..], &mut exported, "birds").unwrap();
+        assert_eq!(
+            String::from_utf8(exported).unwrap(),
+            "count,id,name,seen\n3,1,\"wren, jenny\",true\n1.5,2,magpie,\n"
+        );
+    }
+
+    #[test]
+    fn cli_json_lines_import() {
+        let json_lines = "{\"name\": \"wren\", \"tags\": [\"small\"]}\n\n{\"name\": \"magpie\"}\n";
+        let mut document = Vec::new();
+        import_json_lines(json_lines.as_bytes(), &mut document, &options(Some("name"))).unwrap();
+
+        let mut exported = Vec::new();
+        export_json_lines(&document[..], &mut exported, "birds").unwrap();
+        assert_eq!(
+            String::from_utf8(exported).unwrap(),
+            "{\"name\":\"magpie\"}\n{\"name\":\"wren\",\"tags\":[\"small\"]}\n"
+        );
+
+        let err = import_json_lines(
+            "{\"name\": \"wren\"}\n{\"name\": \"wren\"}\n".as_bytes(),
+            &mut Vec::new(),
+            &options(Some("name")),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid row on line 2: more than one row has the primary key wren"
+        );
+    }
+
+    #[test]
+    fn cli_csv_rows_without_primary_key() {
+        let mut document = Vec::new();
+        import_csv(
+            "name\nwren\nwren\n".as_bytes(),
+            &mut document,
+            &options(None),
+        )
+        .unwrap();
+        let rows = load_table_rows(&document[..], "birds").unwrap();
+        assert_eq!(rows.len(), 2);
+        assert!(export_csv(&document[..], std::io::sink(), "bugs").is_err());
+    }
+}
@@ -0,0 +1,465 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    ops::Range,
+};
+
+use anyhow::Result;
+use automerge_backend as amb;
+use automerge_protocol as amp;
+
+/// Something wrong with a document
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Problem {
+    /// A block which could not be decoded, e.g. because its checksum is wrong, its columns are
+    /// corrupt or the heads of a compressed document do not match its changes
+    CorruptBlock { range: Range<usize>, error: String },
+    /// Bytes after the last block which could not be parsed as a block
+    TrailingBytes { offset: usize, length: usize },
+    /// More than one change with the same actor and sequence number
+    DuplicateSeq {
+        actor: amp::ActorId,
+        seq: u64,
+        hashes: Vec<amp::ChangeHash>,
+    },
+    /// A change which depends on changes that are not in the document
+    MissingDeps {
+        hash: amp::ChangeHash,
+        missing: Vec<amp::ChangeHash>,
+    },
+    /// An operation which refers to an object, element or operation that is not in the document
+    InvalidOp {
+        hash: amp::ChangeHash,
+        opid: amp::OpId,
+        error: String,
+    },
+}
+
+impl Problem {
+    fn to_json(&self) -> serde_json::Value {
+        match self {
+            Problem::CorruptBlock { range, error } => serde_json::json!({
+                "kind": "corrupt_block",
+                "start": range.start,
+                "end": range.end,
+                "error": error,
+            }),
+            Problem::TrailingBytes { offset, length } => serde_json::json!({
+                "kind": "trailing_bytes",
+                "offset": offset,
+                "length": length,
+            }),
+            Problem::DuplicateSeq { actor, seq, hashes } => serde_json::json!({
+                "kind": "duplicate_seq",
+                "actor": actor.to_hex_string(),
+                "seq": seq,
+                "hashes": hashes,
+            }),
+            Problem::MissingDeps { hash, missing } => serde_json::json!({
+                "kind": "missing_deps",
+                "hash": hash,
+                "missing": missing,
+            }),
+            Problem::InvalidOp { hash, opid, error } => serde_json::json!({
+                "kind": "invalid_op",
+                "hash": hash,
+                "opid": opid.to_string(),
+                "error": error,
+            }),
+        }
+    }
+}
+
+/// The result of checking a document
+pub(super) struct Report {
+    pub blocks: usize,
+    pub problems: Vec<Problem>,
+    /// Every distinct change we could decode, in the order they appear in the document
+    changes: Vec<amb::Change>,
+    /// Indices into `changes` of the changes which are not part of a problem and whose
+    /// dependencies are all present and complete, in causal order
+    complete: Vec<usize>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        let complete: HashSet<usize> = self.complete.iter().copied().collect();
+        let incomplete: Vec<_> = (0..self.changes.len())
+            .filter(|i| !complete.contains(i))
+            .map(|i| self.changes[i].hash)
+            .collect();
+        serde_json::json!({
+            "ok": self.is_ok(),
+            "blocks": self.blocks,
+            "changes": self.changes.len(),
+            "complete_changes": self.complete.len(),
+            "incomplete_changes": incomplete,
+            "problems": self.problems.iter().map(Problem::to_json).collect::<Vec<_>>(),
+        })
+    }
+
+    /// Build a new document from all the causally complete changes
+    pub fn salvage(&self) -> Result<Vec<u8>> {
+        let mut backend = amb::Backend::new();
+        backend.load_changes(
+            self.complete
+                .iter()
+                .map(|i| self.changes[*i].clone())
+                .collect(),
+        )?;
+        Ok(backend.save()?)
+    }
+}
+
+pub(super) fn verify(mut input: impl std::io::Read) -> Result<Report> {
+    let mut buf = Vec::new();
+    input.read_to_end(&mut buf)?;
+    Ok(verify_bytes(&buf))
+}
+
+fn verify_bytes(bytes: &[u8]) -> Report {
+    let mut problems = Vec::new();
+
+    let (blocks, end) = amb::Change::load_document_blocks(bytes);
+    let num_blocks = blocks.len();
+    let mut changes = Vec::new();
+    let mut seen = HashSet::new();
+    for block in blocks {
+        if let Some(e) = block.error {
+            problems.push(Problem::CorruptBlock {
+                range: block.range,
+                error: e.to_string(),
+            });
+        }
+        // The same change may be in a document more than once, that's fine
+        changes.extend(block.changes.into_iter().filter(|c| seen.insert(c.hash)));
+    }
+    if end < bytes.len() {
+        problems.push(Problem::TrailingBytes {
+            offset: end,
+            length: bytes.len() - end,
+        });
+    }
+
+    let mut bad = HashSet::new();
+    for problem in check_seqs(&changes).into_iter().chain(check_ops(&changes)) {
+        match &problem {
+            Problem::DuplicateSeq { hashes, .. } => bad.extend(hashes.iter().copied()),
+            Problem::InvalidOp { hash, .. } => {
+                bad.insert(*hash);
+            }
+            _ => {}
+        }
+        problems.push(problem);
+    }
+
+    let index: HashMap<amp::ChangeHash, usize> = changes
+        .iter()
+        .enumerate()
+        .map(|(i, c)| (c.hash, i))
+        .collect();
+    for change in &changes {
+        let missing: Vec<_> = change
+            .deps
+            .iter()
+            .filter(|d| !index.contains_key(d))
+            .copied()
+            .collect();
+        if !missing.is_empty() {
+            problems.push(Problem::MissingDeps {
+                hash: change.hash,
+                missing,
+            });
+        }
+    }
+
+    let complete = complete_changes(&changes, &index, &bad);
+    Report {
+        blocks: num_blocks,
+        problems,
+        changes,
+        complete,
+    }
+}
+
+fn check_seqs(changes: &[amb::Change]) -> Vec<Problem> {
+    let mut by_seq: HashMap<(&amp::ActorId, u64), Vec<amp::ChangeHash>> = HashMap::new();
+    for change in changes {
+        by_seq
+            .entry((change.actor_id(), change.seq))
+            .or_default()
+            .push(change.hash);
+    }
+    let mut duplicates: Vec<_> = by_seq
+        .into_iter()
+        .filter(|(_, hashes)| hashes.len() > 1)
+        .collect();
+    duplicates.sort_by_key(|((actor, seq), _)| (actor.to_hex_string(), *seq));
+    duplicates
+        .into_iter()
+        .map(|((actor, seq), hashes)| Problem::DuplicateSeq {
+            actor: actor.clone(),
+            seq,
+            hashes,
+        })
+        .collect()
+}
+
+/// Check that every object, list element and predecessor referenced by an operation is created
+/// by some operation in the document
+fn check_ops(changes: &[amb::Change]) -> Vec<Problem> {
+    let decoded: Vec<amp::Change> = changes.iter().map(amb::Change::decode).collect();
+    let mut opids = HashSet::new();
+    let mut objects = HashSet::new();
+    let mut elements = HashSet::new();
+    for change in &decoded {
+        for (opid, op) in ops_with_ids(change) {
+            if let amp::OpType::Make(_) = op.action {
+                objects.insert(opid.clone());
+            }
+            if op.insert {
+                elements.insert(opid.clone());
+            }
+            opids.insert(opid);
+        }
+    }
+
+    let mut problems = Vec::new();
+    for (change, decoded) in changes.iter().zip(&decoded) {
+        for (opid, op) in ops_with_ids(decoded) {
+            let mut invalid = |error: String| {
+                problems.push(Problem::InvalidOp {
+                    hash: change.hash,
+                    opid: opid.clone(),
+                    error,
+                })
+            };
+            if let amp::ObjectId::Id(obj) = &op.obj {
+                if !objects.contains(obj) {
+                    invalid(format!("object {} does not exist", obj));
+                }
+            }
+            if let amp::Key::Seq(amp::ElementId::Id(elem)) = &op.key {
+                if !elements.contains(elem) {
+                    invalid(format!("list element {} does not exist", elem));
+                }
+            }
+            for pred in op.pred.iter() {
+                if !opids.contains(pred) {
+                    invalid(format!("predecessor {} does not exist", pred));
+                }
+            }
+        }
+    }
+    problems
+}
+
+fn ops_with_ids(change: &amp::Change) -> impl Iterator<Item = (amp::OpId, &amp::Op)> {
+    change
+        .operations
+        .iter()
+        .enumerate()
+        .map(move |(i, op)| (change.actor_id.op_id_at(change.start_op + i as u64), op))
+}
+
+/// Find the changes which are not `bad` and all of whose transitive dependencies are present
+/// and not `bad`, returning their indices in causal order
+fn complete_changes(
+    changes: &[amb::Change],
+    index: &HashMap<amp::ChangeHash, usize>,
+    bad: &HashSet<amp::ChangeHash>,
+) -> Vec<usize> {
+    let mut waiting_on: Vec<usize> = Vec::with_capacity(changes.len());
+    let mut dependents: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (i, change) in changes.iter().enumerate() {
+        let deps: HashSet<_> = change.deps.iter().collect();
+        for dep in &deps {
+            if let Some(d) = index.get(dep) {
+                dependents.entry(*d).or_default().push(i);
+            }
+        }
+        waiting_on.push(deps.len());
+    }
+
+    let mut ready: VecDeque<usize> = (0..changes.len())
+        .filter(|i| waiting_on[*i] == 0 && !bad.contains(&changes[*i].hash))
+        .collect();
+    let mut complete = Vec::new();
+    while let Some(i) = ready.pop_front() {
+        complete.push(i);
+        for dependent in dependents.get(&i).into_iter().flatten() {
+            waiting_on[*dependent] -= 1;
+            if waiting_on[*dependent] == 0 && !bad.contains(&changes[*dependent].hash) {
+                ready.push_back(*dependent);
+            }
+        }
+    }
+    complete
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set_op(key: &str, value: &str) -> amp::Op {
+        amp::Op {
+            action: amp::OpType::Set(value.into()),
+            obj: amp::ObjectId::Root,
+            key: key.into(),
+            insert: false,
+            pred: amp::SortedVec::new(),
+        }
+    }
+
+    fn change(
+        actor: &amp::ActorId,
+        seq: u64,
+        start_op: u64,
+        deps: Vec<amp::ChangeHash>,
+        operations: Vec<amp::Op>,
+    ) -> amb::Change {
+        amp::Change {
+            actor_id: actor.clone(),
+            seq,
+            start_op,
+            time: 0,
+            message: None,
+            hash: None,
+            deps,
+            operations,
+            extra_bytes: Vec::new(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn cli_verify_healthy_document() {
+        let actor = amp::ActorId::random();
+        let c1 = change(&actor, 1, 1, vec![], vec![set_op("bird", "magpie")]);
+        let c2 = change(&actor, 2, 2, vec![c1.hash], vec![set_op("bug", "ant")]);
+        let mut backend = amb::Backend::new();
+        backend.apply_changes(vec![c1, c2]).unwrap();
+
+        let report = verify_bytes(&backend.save().unwrap());
+        assert!(report.is_ok());
+        assert_eq!(report.blocks, 1);
+        assert_eq!(report.complete.len(), 2);
+        assert_eq!(
+            report.to_json()["incomplete_changes"],
+            serde_json::json!([])
+        );
+    }
+
+    #[test]
+    fn cli_verify_corrupt_block_and_trailing_bytes() {
+        let actor = amp::ActorId::random();
+        let c1 = change(&actor, 1, 1, vec![], vec![set_op("bird", "magpie")]);
+        let c2 = change(&actor, 2, 2, vec![c1.hash], vec![set_op("bug", "ant")]);
+        let mut bytes = c1.raw_bytes().to_vec();
+        let c2_start = bytes.len();
+        bytes.extend(c2.raw_bytes());
+        // corrupt the value of the op in the second change
+        let last = bytes.len() - 1;
+        bytes[last] ^= 0xff;
+        bytes.extend(&[0x85, 0x6f]);
+
+        let report = verify_bytes(&bytes);
+        assert_eq!(report.problems.len(), 2);
+        assert!(matches!(
+            &report.problems[0],
+            Problem::CorruptBlock { range, .. } if range.start == c2_start
+        ));
+        assert_eq!(
+            report.problems[1],
+            Problem::TrailingBytes {
+                offset: bytes.len() - 2,
+                length: 2
+            }
+        );
+
+        let salvaged = amb::Backend::load(report.salvage().unwrap()).unwrap();
+        assert_eq!(salvaged.get_heads(), vec![c1.hash]);
+    }
+
+    #[test]
+    fn cli_verify_missing_deps_duplicate_seqs_and_invalid_ops() {
+        let actor = amp::ActorId::random();
+        let other = amp::ActorId::random();
+        let c1 = change(&actor, 1, 1, vec![], vec![set_op("bird", "magpie")]);
+        let c2 = change(&actor, 2, 2, vec![c1.hash], vec![set_op("bug", "ant")]);
+        let c2_dup = change(&actor, 2, 2, vec![c1.hash], vec![set_op("bug", "bee")]);
+        let c3 = change(&other, 1, 3, vec![c2.hash], vec![set_op("fish", "cod")]);
+        let missing_obj = actor.op_id_at(100);
+        let c4 = change(
+            &other,
+            2,
+            4,
+            vec![c3.hash],
+            vec![amp::Op {
+                action: amp::OpType::Set("x".into()),
+                obj: missing_obj.clone().into(),
+                key: "y".into(),
+                insert: false,
+                pred: amp::SortedVec::new(),
+            }],
+        );
+        let orphan_dep = change(&other, 7, 50, vec![], vec![]);
+        let orphan = change(&other, 8, 51, vec![orphan_dep.hash], vec![]);
+
+        let mut bytes = Vec::new();
+        for c in &[&c1, &c2, &c2_dup, &c3, &c4, &orphan] {
+            bytes.extend(c.raw_bytes());
+        }
+        let report = verify_bytes(&bytes);
+
+        let mut dup_hashes = vec![c2.hash, c2_dup.hash];
+        dup_hashes.sort();
+        let problems: Vec<_> = report
+            .problems
+            .iter()
+            .cloned()
+            .map(|p| match p {
+                Problem::DuplicateSeq {
+                    actor,
+                    seq,
+                    mut hashes,
+                } => {
+                    hashes.sort();
+                    Problem::DuplicateSeq { actor, seq, hashes }
+                }
+                p => p,
+            })
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                Problem::DuplicateSeq {
+                    actor: actor.clone(),
+                    seq: 2,
+                    hashes: dup_hashes,
+                },
+                Problem::InvalidOp {
+                    hash: c4.hash,
+                    opid: other.op_id_at(4),
+                    error: format!("object {} does not exist", missing_obj),
+                },
+                Problem::MissingDeps {
+                    hash: orphan.hash,
+                    missing: vec![orphan_dep.hash],
+                },
+            ]
+        );
+
+        // Only the first change is causally complete, everything else depends on the
+        // duplicated seq or on a missing change
+        let complete: Vec<_> = report
+            .complete
+            .iter()
+            .map(|i| report.changes[*i].hash)
+            .collect();
+        assert_eq!(complete, vec![c1.hash]);
+    }
+}
@@ -89,3 +89,77 @@ fn import_change_export() {
     });
     assert_eq!(result, expected);
 }
+
+#[test]
+fn sync_two_files() {
+    let bin = env!("CARGO_BIN_EXE_automerge");
+    let dir = std::env::temp_dir().join(format!("automerge_sync_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let a = dir.join("a.mpl");
+    let b = dir.join("b.mpl");
+
+    cmd!(bin, "import")
+        .stdin_bytes(r#"{"wrens": 3.0}"#)
+        .stdout_path(&a)
+        .run()
+        .unwrap();
+    cmd!(bin, "import")
+        .stdin_bytes(r#"{"sparrows": 15.0}"#)
+        .stdout_path(&b)
+        .run()
+        .unwrap();
+
+    let stats = cmd!(bin, "sync", &a, &b).stderr_capture().run().unwrap();
+    assert!(String::from_utf8(stats.stderr)
+        .unwrap()
+        .contains("round trips"));
+
+    let expected = serde_json::json!({"wrens": 3.0, "sparrows": 15.0});
+    for file in &[&a, &b] {
+        let stdout = cmd!(bin, "export").stdin_path(file).read().unwrap();
+        let result: serde_json::Value = serde_json::from_str(stdout.as_str()).unwrap();
+        assert_eq!(result, expected);
+    }
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn merge_exit_codes() {
+    let bin = env!("CARGO_BIN_EXE_automerge");
+    let dir = std::env::temp_dir().join(format!("automerge_merge_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let a = dir.join("a.mpl");
+    let missing = dir.join("missing.mpl");
+    let out = dir.join("out.mpl");
+
+    cmd!(bin, "import")
+        .stdin_bytes(r#"{"wrens": 3.0}"#)
+        .stdout_path(&a)
+        .run()
+        .unwrap();
+
+    let strict = cmd!(bin, "merge", &a, &missing)
+        .stdout_capture()
+        .stderr_capture()
+        .unchecked()
+        .run()
+        .unwrap();
+    assert_eq!(strict.status.code(), Some(1));
+    assert!(String::from_utf8(strict.stderr)
+        .unwrap()
+        .contains("failed to load"));
+    assert!(strict.stdout.is_empty());
+
+    let best_effort = cmd!(bin, "merge", "--best-effort", &a, &missing)
+        .stdout_path(&out)
+        .stderr_null()
+        .unchecked()
+        .run()
+        .unwrap();
+    assert_eq!(best_effort.status.code(), Some(2));
+    let stdout = cmd!(bin, "export").stdin_path(&out).read().unwrap();
+    let result: serde_json::Value = serde_json::from_str(stdout.as_str()).unwrap();
+    assert_eq!(result, serde_json::json!({"wrens": 3.0}));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
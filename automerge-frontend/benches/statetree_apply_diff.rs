@@ -1,5 +1,6 @@
 use amp::RootDiff;
-use automerge_frontend::Frontend;
+use automerge_backend::{Backend, Change};
+use automerge_frontend::{Frontend, InvalidChangeRequest, LocalChange, Path, Value};
 use automerge_protocol as amp;
 use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
 use maplit::hashmap;
@@ -125,9 +126,91 @@ pub fn sequential_inserts_in_single_patch(c: &mut Criterion) {
     );
 }
 
+/// Changes which each type one character into a text object, with every tenth change instead
+/// deleting the character before the cursor
+fn typing_changes(count: usize) -> Vec<Change> {
+    let mut doc = Frontend::new();
+    let mut backend = Backend::new();
+    let mut changes = Vec::with_capacity(count + 1);
+    let mut apply = |doc: &mut Frontend, change: amp::Change| {
+        let (patch, change) = backend.apply_local_change(change).unwrap();
+        changes.push(change.clone());
+        doc.apply_patch(patch).unwrap();
+    };
+    let (_, change) = doc
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(
+                Path::root().key("text"),
+                Value::Text(Vec::new()),
+            ))
+        })
+        .unwrap();
+    apply(&mut doc, change.unwrap());
+    let mut cursor = 0;
+    for i in 0..count {
+        let (_, change) = doc
+            .change::<_, _, InvalidChangeRequest>(None, |d| {
+                if i % 10 == 9 {
+                    cursor -= 1;
+                    d.add_change(LocalChange::delete(Path::root().key("text").index(cursor)))
+                } else {
+                    cursor += 1;
+                    d.add_change(LocalChange::insert(
+                        Path::root().key("text").index(cursor - 1),
+                        "c".into(),
+                    ))
+                }
+            })
+            .unwrap();
+        apply(&mut doc, change.unwrap());
+    }
+    changes
+}
+
+pub fn changes_applied_in_one_batch(c: &mut Criterion) {
+    let changes = typing_changes(10_000);
+    let patch = Backend::new().apply_changes(changes.clone()).unwrap();
+    c.bench_function(
+        "StateTreeValue::apply_diff 10000 typing changes applied to the backend in one batch",
+        move |b| {
+            b.iter_batched(
+                || patch.clone(),
+                |patch| {
+                    let mut doc = Frontend::new();
+                    doc.apply_patch(patch).unwrap();
+                    black_box(doc)
+                },
+                BatchSize::SmallInput,
+            )
+        },
+    );
+    let mut backend = Backend::new();
+    let patches: Vec<amp::Patch> = changes
+        .into_iter()
+        .map(|change| backend.apply_changes(vec![change]).unwrap())
+        .collect();
+    c.bench_function(
+        "StateTreeValue::apply_diff 10000 typing changes applied to the backend one at a time",
+        move |b| {
+            b.iter_batched(
+                || patches.clone(),
+                |patches| {
+                    let mut doc = Frontend::new();
+                    for patch in patches {
+                        doc.apply_patch(patch).unwrap();
+                    }
+                    black_box(doc)
+                },
+                BatchSize::SmallInput,
+            )
+        },
+    );
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default().sample_size(10);
     targets = sequential_inserts_in_multiple_patches, sequential_inserts_in_single_patch,
+        changes_applied_in_one_batch,
 }
 criterion_main!(benches);
@@ -2,9 +2,14 @@ use std::{error::Error, fmt};
 
 use automerge_protocol as amp;
 use automerge_protocol::ObjectId;
+use smol_str::SmolStr;
 use thiserror::Error;
 
-use crate::{value::Value, Path};
+use crate::{
+    value::{Primitive, Value},
+    value_ref::TextUnit,
+    Path,
+};
 
 #[derive(Debug, PartialEq)]
 pub enum AutomergeFrontendError {
@@ -50,7 +55,7 @@ impl Error for InvalidInitialStateError {}
 pub enum InvalidPatch {
     #[error("Mismatched sequence number, expected: {expected} but got {actual}")]
     MismatchedSequenceNumber { expected: u64, actual: u64 },
-    #[error("Received a diff inserting a non text object in a text object. Target object id was {object_id}, diff was {diff:?}")]
+    #[error("Received a diff inserting a string which is not a single grapheme cluster in a text object. Target object id was {object_id}, diff was {diff:?}")]
     InsertNonTextInTextObject {
         object_id: ObjectId,
         diff: amp::Diff,
@@ -80,6 +85,11 @@ pub enum InvalidPatch {
     DiffEditWithHeadElemId,
     #[error("Value diff containing cursor")]
     ValueDiffContainedCursor,
+    #[error("Received a diff for set {object_id} which was not a member stored under its own key, diff was {diff:?}")]
+    InvalidSetMember {
+        object_id: ObjectId,
+        diff: amp::Diff,
+    },
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -98,8 +108,22 @@ pub enum InvalidChangeRequest {
     InsertForNonSequenceObject { path: Path },
     #[error("attempted to insert past the end of a sequence, path was {path:?}, max length of sequence is {sequence_length}")]
     InsertPastEndOfSequence { path: Path, sequence_length: u64 },
-    #[error("attempted to insert something into a text object which is not a character, object: {object:?}")]
+    #[error("attempted to insert a string which is not a single grapheme cluster into a text object, object: {object:?}")]
     InsertNonTextInTextObject { path: Path, object: Value },
+    #[error("attempted to splice an object which is not text at {path:?}")]
+    SpliceForNonTextObject { path: Path },
+    #[error("the offset {offset} ({unit:?}) in the text at {path:?} is past the end of the text or inside a grapheme")]
+    InvalidTextOffset {
+        path: Path,
+        offset: usize,
+        unit: TextUnit,
+    },
+    #[error("attempted to add to or remove from an object which is not a set at {path:?}")]
+    NotASet { path: Path },
+    #[error("attempted to add {value:?} to the set at {path:?}, counters and cursors cannot be members of a set")]
+    InvalidSetMember { path: Path, value: Primitive },
+    #[error("attempted to resolve the conflict at {path:?} in favour of {opid}, which did not set a value there")]
+    NoSuchConflictingValue { path: Path, opid: amp::OpId },
     #[error("attmpted to delete root object")]
     CannotDeleteRootObject,
     #[error("Attempted to access a missing index")]
@@ -109,6 +133,18 @@ pub enum InvalidChangeRequest {
     },
 }
 
+#[derive(Error, Debug, PartialEq)]
+pub enum InvalidTableRow {
+    #[error("row has no value for the primary key column {column}")]
+    MissingPrimaryKey { column: SmolStr },
+    #[error(
+        "the primary key column {column} must be a string, number or boolean but was {value:?}"
+    )]
+    InvalidPrimaryKey { column: SmolStr, value: Value },
+    #[error("more than one row has the primary key {key}")]
+    DuplicatePrimaryKey { key: SmolStr },
+}
+
 #[derive(Error, Debug, PartialEq)]
 #[error("Attempted to access index {missing_index} in a collection with max index: {size_of_collection}")]
 pub struct MissingIndexError {
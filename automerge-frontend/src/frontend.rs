@@ -6,12 +6,13 @@ use automerge_protocol::{ActorId, ObjectId, OpId, Patch};
 use crate::{
     error::{InvalidInitialStateError, InvalidPatch},
     mutation::{LocalChange, MutableDocument},
-    path::Path,
+    path::{Path, PathElement},
+    resolver::{ConflictResolver, PathPattern, Resolvers},
     state::FrontendState,
     state_tree::StateTree,
     value,
-    value::Value,
-    value_ref::RootRef,
+    value::{Gravity, Primitive, StickyCursor, Value},
+    value_ref::{RootRef, TextElementRef, ValueRef},
 };
 
 pub struct Frontend {
@@ -25,6 +26,8 @@ pub struct Frontend {
     cached_value: Option<Value>,
     /// A function for generating timestamps
     timestamper: Box<dyn Fn() -> Option<i64>>,
+    /// The resolvers which choose the values of conflicting keys
+    resolvers: Resolvers,
 }
 
 impl Debug for Frontend {
@@ -35,6 +38,7 @@ impl Debug for Frontend {
             state,
             cached_value,
             timestamper: _,
+            resolvers,
         } = self;
         {
             let mut builder = f.debug_struct("Frontend");
@@ -42,6 +46,7 @@ impl Debug for Frontend {
             let _ = builder.field("seq", &seq);
             let _ = builder.field("state", &state);
             let _ = builder.field("cached_value", &cached_value);
+            let _ = builder.field("resolvers", &resolvers);
             builder.finish()
         }
     }
@@ -96,6 +101,7 @@ impl Frontend {
             },
             cached_value: None,
             timestamper: t,
+            resolvers: Resolvers::default(),
         }
     }
 
@@ -149,14 +155,31 @@ impl Frontend {
         if let Some(ref v) = self.cached_value {
             v
         } else {
-            let value = self.state.value();
+            let value = if self.resolvers.is_empty() {
+                self.state.value()
+            } else {
+                self.value_ref().value()
+            };
             self.cached_value = Some(value);
             self.cached_value.as_ref().unwrap()
         }
     }
 
     pub fn value_ref(&self) -> RootRef {
-        self.state.value_ref()
+        self.state.value_ref(&self.resolvers)
+    }
+
+    /// Use `resolver` to choose the value of the paths which match `pattern` when they have
+    /// conflicting values. If several patterns match a path the resolver added last is used.
+    ///
+    /// This changes the values returned by [`state`](Self::state),
+    /// [`value_ref`](Self::value_ref) and [`get_value`](Self::get_value) but not the document.
+    pub fn add_resolver<R>(&mut self, pattern: PathPattern, resolver: R)
+    where
+        R: ConflictResolver + 'static,
+    {
+        self.resolvers.add(pattern, Box::new(resolver));
+        self.cached_value = None;
     }
 
     pub fn change<F, O, E>(
@@ -219,6 +242,66 @@ impl Frontend {
 
     /// Returns the value given by path, if it exists
     pub fn get_value(&self, path: &Path) -> Option<Value> {
-        self.state.get_value(path)
+        if self.resolvers.is_empty() {
+            return self.state.get_value(path);
+        }
+        let root = self.value_ref();
+        let mut elements = path.clone().elements().into_iter();
+        let mut value = match elements.next() {
+            Some(PathElement::Key(k)) => root.get(&k)?,
+            Some(PathElement::Index(_)) => return None,
+            None => return Some(root.value()),
+        };
+        while let Some(element) = elements.next() {
+            value = match (element, &value) {
+                (PathElement::Key(k), ValueRef::Map(map)) => map.get(&k)?,
+                (PathElement::Key(k), ValueRef::Table(table)) => table.get(&k)?,
+                (PathElement::Index(i), ValueRef::List(list)) => list.get(i as usize)?,
+                (PathElement::Index(i), ValueRef::Text(text)) => match text.get(i as usize)? {
+                    TextElementRef::Grapheme(g) if elements.len() == 0 => {
+                        return Some(Value::Primitive(Primitive::Str(g.clone())))
+                    }
+                    TextElementRef::Grapheme(_) => return None,
+                    TextElementRef::Embedded(v) => v,
+                },
+                _ => return None,
+            };
+        }
+        Some(value.value())
+    }
+
+    /// Create a cursor for the position before the index at the end of `path` in a list or
+    /// text, e.g. `Path::root().key("text").index(2)` is the position between the second and
+    /// third characters. The index may be the length of the sequence for the position at the
+    /// end.
+    ///
+    /// The cursor is not stored in the document, use [`resolve_cursor`](Self::resolve_cursor)
+    /// to find its index after the document changes. This frontend tracks the cursor until it
+    /// is passed to [`release_cursor`](Self::release_cursor). Returns `None` if `path` is not a
+    /// position in a list or text.
+    pub fn sticky_cursor(&mut self, path: &Path, gravity: Gravity) -> Option<StickyCursor> {
+        self.state.sticky_cursor(path, gravity)
+    }
+
+    /// Track a cursor created by another frontend, e.g. a peer's, so that it still resolves once
+    /// the element it is attached to is deleted. Returns false if the cursor's list or text, or
+    /// the element it is attached to, is not in this document.
+    pub fn track_cursor(&mut self, cursor: &StickyCursor) -> bool {
+        self.state.track_cursor(cursor)
+    }
+
+    /// Stop tracking a cursor returned by [`sticky_cursor`](Self::sticky_cursor) or passed to
+    /// [`track_cursor`](Self::track_cursor)
+    pub fn release_cursor(&mut self, cursor: &StickyCursor) {
+        self.state.release_cursor(cursor)
+    }
+
+    /// The current index of `cursor` in its list or text, or `None` if the cursor points into an
+    /// object which is no longer in the document, or if this frontend isn't tracking it and the
+    /// element it is attached to has been deleted.
+    pub fn resolve_cursor(&self, cursor: &StickyCursor) -> Option<u32> {
+        self.state
+            .resolve_cursor(cursor)
+            .and_then(|i| u32::try_from(i).ok())
     }
 }
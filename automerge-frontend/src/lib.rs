@@ -2,6 +2,7 @@ mod error;
 mod frontend;
 mod mutation;
 mod path;
+pub mod resolver;
 mod state;
 mod state_tree;
 mod value;
@@ -9,8 +10,14 @@ pub mod value_ref;
 
 pub use error::{
     AutomergeFrontendError, InvalidChangeRequest, InvalidInitialStateError, InvalidPatch,
+    InvalidTableRow,
 };
 pub use frontend::Frontend;
 pub use mutation::{LocalChange, MutableDocument};
 pub use path::Path;
-pub use value::{Conflicts, Cursor, Primitive, Value};
+pub use resolver::{ConflictResolver, PathPattern, Resolution};
+pub use value::{
+    infer_primitive, Conflicts, Cursor, Gravity, Primitive, StickyCursor, TableBuilder,
+    TextElement, Value,
+};
+pub use value_ref::TextUnit;
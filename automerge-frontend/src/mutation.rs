@@ -6,9 +6,10 @@ use crate::{
     path::PathElement,
     state_tree::{
         LocalOperationForRollback, LocalOperationResult, OptimisticStateTree, ResolvedPath,
-        ResolvedPathMut, SetOrInsertPayload,
+        ResolvedPathMut, SetOrInsertPayload, StateTreeSet,
     },
     value::{Cursor, Value},
+    value_ref::TextUnit,
     Path, Primitive,
 };
 
@@ -25,6 +26,15 @@ pub enum LocalOperation {
     Increment(i64),
     Insert(Value),
     InsertMany(Vec<Value>),
+    SpliceText {
+        start: usize,
+        delete: usize,
+        text: String,
+        unit: TextUnit,
+    },
+    AddToSet(Primitive),
+    RemoveFromSet(Primitive),
+    ResolveConflict(amp::OpId),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -69,6 +79,8 @@ impl LocalChange {
         }
     }
 
+    /// Insert `value` at `path` in a list or text. Strings inserted into text must be a single
+    /// grapheme cluster, any other value is embedded in the text.
     pub fn insert(path: Path, value: Value) -> LocalChange {
         LocalChange {
             path,
@@ -82,6 +94,64 @@ impl LocalChange {
             operation: LocalOperation::InsertMany(values),
         }
     }
+
+    /// Delete `delete` units of the text object at `path` starting at `start` and insert `text`
+    /// in their place, where `start` and `delete` are measured in `unit`s.
+    ///
+    /// Both ends of the deleted range must fall on grapheme boundaries.
+    pub fn splice_text<S>(
+        path: Path,
+        start: usize,
+        delete: usize,
+        text: S,
+        unit: TextUnit,
+    ) -> LocalChange
+    where
+        S: Into<String>,
+    {
+        LocalChange {
+            path,
+            operation: LocalOperation::SpliceText {
+                start,
+                delete,
+                text: text.into(),
+                unit,
+            },
+        }
+    }
+
+    /// Add `value` to the set at `path`. If another actor concurrently removes the same value
+    /// the add wins.
+    pub fn add_to_set<P>(path: Path, value: P) -> LocalChange
+    where
+        P: Into<Primitive>,
+    {
+        LocalChange {
+            path,
+            operation: LocalOperation::AddToSet(value.into()),
+        }
+    }
+
+    /// Remove `value` from the set at `path`, removing a value which is not in the set does
+    /// nothing
+    pub fn remove_from_set<P>(path: Path, value: P) -> LocalChange
+    where
+        P: Into<Primitive>,
+    {
+        LocalChange {
+            path,
+            operation: LocalOperation::RemoveFromSet(value.into()),
+        }
+    }
+
+    /// Resolve the conflict at `path` in favour of the value set by `opid`, deleting the values
+    /// it conflicts with. See [`MapRef::get_all`](crate::value_ref::MapRef::get_all) for the values at a path.
+    pub fn resolve_conflict(path: Path, opid: amp::OpId) -> LocalChange {
+        LocalChange {
+            path,
+            operation: LocalOperation::ResolveConflict(opid),
+        }
+    }
 }
 
 /// `MutationTracker` is used as the context in which a mutation closure is
@@ -175,33 +245,14 @@ impl<'a> MutationTracker<'a> {
                         self.apply_state_change(res);
                     }
                     ResolvedPathMut::Text(mut text_target) => {
-                        let mut chars = Vec::with_capacity(values.len());
-                        for value in values {
-                            match value {
-                                Value::Primitive(Primitive::Str(s)) => {
-                                    if s.graphemes(true).count() == 1 {
-                                        chars.push(s.clone())
-                                    } else {
-                                        return Err(
-                                            InvalidChangeRequest::InsertNonTextInTextObject {
-                                                path: path.clone(),
-                                                object: Value::Primitive(Primitive::Str(s)),
-                                            },
-                                        );
-                                    }
-                                }
-                                _ => {
-                                    return Err(InvalidChangeRequest::InsertNonTextInTextObject {
-                                        path: path.clone(),
-                                        object: value.clone(),
-                                    })
-                                }
-                            }
+                        let values: Vec<Value> = values.collect();
+                        for value in &values {
+                            check_text_element(path, value)?;
                         }
                         let payload = SetOrInsertPayload {
                             start_op: self.max_op + 1,
                             actor: &self.actor_id.clone(),
-                            value: chars.into_iter(),
+                            value: values.into_iter(),
                         };
                         let res = text_target.insert_many(*index, payload)?;
                         self.apply_state_change(res);
@@ -299,28 +350,14 @@ impl<'a> MutableDocument for MutationTracker<'a> {
                                 Ok((LocalOperationForRollback::SetList { old }, res))
                             }
                             (PathElement::Index(i), ResolvedPathMut::Text(ref mut text)) => {
-                                match value {
-                                    Value::Primitive(Primitive::Str(s)) => {
-                                        if s.graphemes(true).count() == 1 {
-                                            let payload = SetOrInsertPayload {
-                                                start_op: self.max_op + 1,
-                                                actor: &self.actor_id.clone(),
-                                                value: s,
-                                            };
-                                            let (old, res) = text.set(*i, payload)?;
-                                            Ok((LocalOperationForRollback::SetText { old }, res))
-                                        } else {
-                                            Err(InvalidChangeRequest::InsertNonTextInTextObject {
-                                                path: change.path.clone(),
-                                                object: Value::Primitive(Primitive::Str(s)),
-                                            })
-                                        }
-                                    }
-                                    _ => Err(InvalidChangeRequest::InsertNonTextInTextObject {
-                                        path: change.path.clone(),
-                                        object: value.clone(),
-                                    }),
-                                }
+                                check_text_element(&change.path, &value)?;
+                                let payload = SetOrInsertPayload {
+                                    start_op: self.max_op + 1,
+                                    actor: &self.actor_id.clone(),
+                                    value,
+                                };
+                                let (old, res) = text.set(*i, payload)?;
+                                Ok((LocalOperationForRollback::SetText { old }, res))
                             }
                             (PathElement::Index(_), _) => {
                                 Err(InvalidChangeRequest::InsertWithNonSequencePath {
@@ -405,7 +442,7 @@ impl<'a> MutableDocument for MutationTracker<'a> {
                                     })
                                 }
                             },
-                            ResolvedPathMut::Character(_) => {
+                            ResolvedPathMut::Character(_) | ResolvedPathMut::Set(_) => {
                                 return Err(InvalidChangeRequest::NoSuchPathError {
                                     path: change.path,
                                 })
@@ -472,6 +509,84 @@ impl<'a> MutableDocument for MutationTracker<'a> {
                     Err(e) => Err(e),
                 }
             }
+            LocalOperation::SpliceText {
+                start,
+                delete,
+                text,
+                unit,
+            } => {
+                let path = change.path;
+                let (start, end) = match self.state.resolve_path(&path) {
+                    Some(ResolvedPath::Text(text_target)) => {
+                        let index_at = |offset| {
+                            text_target.index_at_offset(offset, unit).ok_or_else(|| {
+                                InvalidChangeRequest::InvalidTextOffset {
+                                    path: path.clone(),
+                                    offset,
+                                    unit,
+                                }
+                            })
+                        };
+                        (index_at(start)?, index_at(start.saturating_add(delete))?)
+                    }
+                    Some(_) => return Err(InvalidChangeRequest::SpliceForNonTextObject { path }),
+                    None => return Err(InvalidChangeRequest::NoSuchPathError { path }),
+                };
+                let start_path = path.index(start as u32);
+                for _ in start..end {
+                    self.add_change(LocalChange::delete(start_path.clone()))?;
+                }
+                if !text.is_empty() {
+                    let graphemes = text
+                        .graphemes(true)
+                        .map(|g| Value::Primitive(Primitive::Str(g.into())))
+                        .collect();
+                    self.add_change(LocalChange::insert_many(start_path, graphemes))?;
+                }
+                Ok(())
+            }
+            LocalOperation::AddToSet(value) => {
+                let path = change.path;
+                let key = match StateTreeSet::key_of(&value) {
+                    Some(key) => key,
+                    None => return Err(InvalidChangeRequest::InvalidSetMember { path, value }),
+                };
+                match self.state.resolve_path_mut(&path) {
+                    Some(ResolvedPathMut::Set(mut set)) => {
+                        let payload = SetOrInsertPayload {
+                            start_op: self.max_op + 1,
+                            actor: &self.actor_id.clone(),
+                            value,
+                        };
+                        let (old, res) = set.add(key.clone(), payload);
+                        self.copies_for_rollback
+                            .push((path, LocalOperationForRollback::AddToSet { key, old }));
+                        self.apply_state_change(res);
+                        Ok(())
+                    }
+                    Some(_) => Err(InvalidChangeRequest::NotASet { path }),
+                    None => Err(InvalidChangeRequest::NoSuchPathError { path }),
+                }
+            }
+            LocalOperation::RemoveFromSet(value) => {
+                let path = change.path;
+                match self.state.resolve_path_mut(&path) {
+                    Some(ResolvedPathMut::Set(mut set)) => {
+                        let removed = StateTreeSet::key_of(&value)
+                            .and_then(|key| set.remove(&key).map(|removed| (key, removed)));
+                        if let Some((key, (old, res))) = removed {
+                            self.copies_for_rollback.push((
+                                path,
+                                LocalOperationForRollback::RemoveFromSet { key, old },
+                            ));
+                            self.apply_state_change(res);
+                        }
+                        Ok(())
+                    }
+                    Some(_) => Err(InvalidChangeRequest::NotASet { path }),
+                    None => Err(InvalidChangeRequest::NoSuchPathError { path }),
+                }
+            }
             LocalOperation::InsertMany(values) => {
                 let count = values.len();
                 match self.insert_helper(&change.path, values.into_iter()) {
@@ -483,6 +598,50 @@ impl<'a> MutableDocument for MutationTracker<'a> {
                     Err(e) => Err(e),
                 }
             }
+            LocalOperation::ResolveConflict(opid) => {
+                let path = change.path;
+                let name = match path.name() {
+                    Some(name) => name.clone(),
+                    None => return Err(InvalidChangeRequest::NoSuchPathError { path }),
+                };
+                let resolved = match (name, self.state.resolve_path_mut(&path.parent())) {
+                    (PathElement::Key(k), Some(ResolvedPathMut::Root(mut root))) => {
+                        root.resolve_conflict(&k, &opid)
+                    }
+                    (PathElement::Key(k), Some(ResolvedPathMut::Map(mut map))) => {
+                        map.resolve_conflict(&k, &opid)
+                    }
+                    (PathElement::Key(k), Some(ResolvedPathMut::Table(mut table))) => {
+                        table.resolve_conflict(&k, &opid)
+                    }
+                    (PathElement::Index(i), Some(ResolvedPathMut::List(mut list))) => {
+                        list.resolve_conflict(i, &opid)
+                    }
+                    _ => return Err(InvalidChangeRequest::NoSuchPathError { path }),
+                };
+                match resolved {
+                    Some((losers, res)) => {
+                        self.copies_for_rollback
+                            .push((path, LocalOperationForRollback::ResolveConflict { losers }));
+                        self.apply_state_change(res);
+                        Ok(())
+                    }
+                    None => Err(InvalidChangeRequest::NoSuchConflictingValue { path, opid }),
+                }
+            }
+        }
+    }
+}
+
+/// Strings in text must be a single grapheme cluster, any other value is embedded in the text
+fn check_text_element(path: &Path, value: &Value) -> Result<(), InvalidChangeRequest> {
+    match value {
+        Value::Primitive(Primitive::Str(s)) if s.graphemes(true).count() != 1 => {
+            Err(InvalidChangeRequest::InsertNonTextInTextObject {
+                path: path.clone(),
+                object: value.clone(),
+            })
         }
+        _ => Ok(()),
     }
 }
@@ -41,6 +41,10 @@ impl Path {
         self.0.last()
     }
 
+    pub(crate) fn as_elements(&self) -> &[PathElement] {
+        &self.0
+    }
+
     pub(crate) fn elements(self) -> Vec<PathElement> {
         self.0
     }
@@ -0,0 +1,277 @@
+//! Choosing the value of a key or list element which has conflicting values.
+//!
+//! When concurrent changes set the same key the document keeps every value and, by default, the
+//! value set by the op with the highest ID wins. A [`ConflictResolver`] registered for a
+//! [`PathPattern`] with [`Frontend::add_resolver`](crate::Frontend::add_resolver) chooses the
+//! value the frontend shows for the paths which match the pattern instead. Resolvers only change
+//! how the document is read, the conflicting values stay in the document and are still returned
+//! by `get_all` and [`Frontend::get_conflicts`](crate::Frontend::get_conflicts).
+
+use std::fmt;
+
+use automerge_protocol as amp;
+use smol_str::SmolStr;
+
+use crate::{
+    path::PathElement, state_tree::MultiValue, value_ref::ValueRef, Path, Primitive, Value,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum PatternElement {
+    Key(SmolStr),
+    Index(u32),
+    AnyKey,
+    AnyIndex,
+}
+
+/// A pattern which matches paths, built in the same way as a [`Path`]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PathPattern(Vec<PatternElement>);
+
+impl PathPattern {
+    pub fn root() -> PathPattern {
+        PathPattern(Vec::new())
+    }
+
+    pub fn key<S: Into<SmolStr>>(mut self, key: S) -> PathPattern {
+        self.0.push(PatternElement::Key(key.into()));
+        self
+    }
+
+    pub fn index(mut self, index: u32) -> PathPattern {
+        self.0.push(PatternElement::Index(index));
+        self
+    }
+
+    /// Match any key of a map or table
+    pub fn any_key(mut self) -> PathPattern {
+        self.0.push(PatternElement::AnyKey);
+        self
+    }
+
+    /// Match any index of a list
+    pub fn any_index(mut self) -> PathPattern {
+        self.0.push(PatternElement::AnyIndex);
+        self
+    }
+
+    pub fn matches(&self, path: &Path) -> bool {
+        let elements = path.as_elements();
+        elements.len() == self.0.len()
+            && self
+                .0
+                .iter()
+                .zip(elements)
+                .all(|(pattern, element)| match (pattern, element) {
+                    (PatternElement::Key(k), PathElement::Key(key)) => k == key,
+                    (PatternElement::Index(i), PathElement::Index(index)) => i == index,
+                    (PatternElement::AnyKey, PathElement::Key(_))
+                    | (PatternElement::AnyIndex, PathElement::Index(_)) => true,
+                    _ => false,
+                })
+    }
+}
+
+impl From<Path> for PathPattern {
+    fn from(path: Path) -> Self {
+        PathPattern(
+            path.elements()
+                .into_iter()
+                .map(|element| match element {
+                    PathElement::Key(k) => PatternElement::Key(k),
+                    PathElement::Index(i) => PatternElement::Index(i),
+                })
+                .collect(),
+        )
+    }
+}
+
+/// The value a [`ConflictResolver`] chose
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    /// Use the value set by this op
+    Pick(amp::OpId),
+    /// Use a value made from the conflicting values, e.g. the union of some lists
+    Merge(Value),
+}
+
+pub trait ConflictResolver {
+    /// Choose the value at `path` from `candidates`, the conflicting values at `path` by the ID
+    /// of the op which set them, ordered by op ID. The last candidate is the value which wins
+    /// when there is no resolver.
+    fn resolve(&self, path: &Path, candidates: &[(amp::OpId, ValueRef<'_>)]) -> Resolution;
+}
+
+/// The numbers win over other values and the largest number wins, ties and conflicts between
+/// values which are not numbers are won by the highest op ID
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MaxWins;
+
+impl ConflictResolver for MaxWins {
+    fn resolve(&self, _path: &Path, candidates: &[(amp::OpId, ValueRef<'_>)]) -> Resolution {
+        pick_max_by_key(candidates, |value| match value.primitive()? {
+            Primitive::Int(i) | Primitive::Counter(i) => Some(*i as f64),
+            Primitive::Uint(u) => Some(*u as f64),
+            Primitive::F64(f) => Some(*f),
+            Primitive::Decimal(d) => Some(d.to_f64()),
+            _ => None,
+        })
+    }
+}
+
+/// The largest [`Primitive::Timestamp`] value wins. Timestamps win over other values, ties and
+/// conflicts between values which are not timestamps are won by the highest op ID.
+///
+/// This compares the conflicting values, not the times of the changes which set them, so it is
+/// for values which are themselves timestamps, e.g. a "last seen" time.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MaxTimestamp;
+
+impl ConflictResolver for MaxTimestamp {
+    fn resolve(&self, _path: &Path, candidates: &[(amp::OpId, ValueRef<'_>)]) -> Resolution {
+        pick_max_by_key(candidates, |value| match value.primitive()? {
+            Primitive::Timestamp(t) => Some(*t as f64),
+            _ => None,
+        })
+    }
+}
+
+/// Conflicting lists are merged into a list of every value which is in any of them, in the order
+/// of the op IDs of the lists. Values which are not lists are ignored unless there are no lists,
+/// then the highest op ID wins.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ListUnion;
+
+impl ConflictResolver for ListUnion {
+    fn resolve(&self, _path: &Path, candidates: &[(amp::OpId, ValueRef<'_>)]) -> Resolution {
+        let mut lists = candidates
+            .iter()
+            .filter_map(|(_, value)| value.list())
+            .peekable();
+        if lists.peek().is_none() {
+            return highest_opid(candidates);
+        }
+        let mut union: Vec<Value> = Vec::new();
+        for list in lists {
+            for element in list.iter() {
+                let element = element.value();
+                if !union.contains(&element) {
+                    union.push(element);
+                }
+            }
+        }
+        Resolution::Merge(Value::List(union))
+    }
+}
+
+fn highest_opid(candidates: &[(amp::OpId, ValueRef<'_>)]) -> Resolution {
+    // candidates are never empty, they are the values of a key
+    Resolution::Pick(candidates.last().unwrap().0.clone())
+}
+
+fn pick_max_by_key<F>(candidates: &[(amp::OpId, ValueRef<'_>)], key: F) -> Resolution
+where
+    F: Fn(&ValueRef<'_>) -> Option<f64>,
+{
+    candidates
+        .iter()
+        .filter_map(|(opid, value)| key(value).map(|k| (k, opid)))
+        // `max_by` returns the last of equal elements, which has the highest op ID
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map_or_else(
+            || highest_opid(candidates),
+            |(_, opid)| Resolution::Pick(opid.clone()),
+        )
+}
+
+/// The resolvers registered with a frontend
+#[derive(Default)]
+pub(crate) struct Resolvers(Vec<(PathPattern, Box<dyn ConflictResolver>)>);
+
+impl fmt::Debug for Resolvers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|(pattern, _)| pattern))
+            .finish()
+    }
+}
+
+impl Resolvers {
+    pub(crate) fn add(&mut self, pattern: PathPattern, resolver: Box<dyn ConflictResolver>) {
+        self.0.push((pattern, resolver));
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// The resolver for `path`, the one added last if several patterns match it
+    fn get(&self, path: &Path) -> Option<&dyn ConflictResolver> {
+        self.0
+            .iter()
+            .rev()
+            .find(|(pattern, _)| pattern.matches(path))
+            .map(|(_, resolver)| resolver.as_ref())
+    }
+
+    /// The path of the child of the value at `parent`, only built if there are resolvers which
+    /// could match it
+    pub(crate) fn child_path(
+        &self,
+        parent: Option<&Path>,
+        child: impl FnOnce(Path) -> Path,
+    ) -> Option<Path> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(child(parent.cloned().unwrap_or_else(Path::root)))
+        }
+    }
+
+    /// The value at `path`, whose values are `mv`
+    ///
+    /// `path` is `None` when there are no resolvers, see [`Resolvers::child_path`].
+    pub(crate) fn resolve<'a>(&'a self, path: Option<Path>, mv: &'a MultiValue) -> ValueRef<'a> {
+        let resolver = match &path {
+            Some(path) if mv.has_conflicts() => self.get(path),
+            _ => None,
+        };
+        let (path, resolver) = match (path, resolver) {
+            (Some(path), Some(resolver)) => (path, resolver),
+            (path, _) => return ValueRef::new(mv.default_statetree_value(), self, path),
+        };
+        let mut candidates: Vec<_> = mv
+            .iter()
+            .map(|(opid, value)| (opid.clone(), ValueRef::new(value, self, Some(path.clone()))))
+            .collect();
+        candidates.sort_by(|(a, _), (b, _)| a.cmp(b));
+        match resolver.resolve(&path, &candidates) {
+            Resolution::Pick(opid) => candidates
+                .into_iter()
+                .find(|(candidate, _)| candidate == &opid)
+                .map_or_else(
+                    || ValueRef::new(mv.default_statetree_value(), self, Some(path)),
+                    |(_, value)| value,
+                ),
+            Resolution::Merge(value) => ValueRef::Merged(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_pattern_matches() {
+        let pattern = PathPattern::root().key("players").any_index().any_key();
+        assert!(pattern.matches(&Path::root().key("players").index(3).key("score")));
+        assert!(!pattern.matches(&Path::root().key("players").key("3").key("score")));
+        assert!(!pattern.matches(&Path::root().key("players").index(3)));
+        assert!(!pattern.matches(&Path::root().key("teams").index(3).key("score")));
+
+        let pattern: PathPattern = Path::root().key("players").index(0).into();
+        assert!(pattern.matches(&Path::root().key("players").index(0)));
+        assert!(!pattern.matches(&Path::root().key("players").index(1)));
+    }
+}
@@ -4,9 +4,11 @@ use automerge_protocol as amp;
 
 use crate::{
     mutation::MutationTracker,
+    path::PathElement,
+    resolver::Resolvers,
     state_tree::{OptimisticStateTree, ResolvedPath, StateTree},
     value_ref::RootRef,
-    InvalidPatch, MutableDocument, Path, Value,
+    Gravity, InvalidPatch, MutableDocument, Path, StickyCursor, Value,
 };
 
 /// Tracks the possible states of the frontend
@@ -175,7 +177,32 @@ impl FrontendState {
     }
 
     pub(crate) fn resolve_path(&self, path: &Path) -> Option<ResolvedPath> {
-        let root = match self {
+        self.root_state().resolve_path(path)
+    }
+
+    pub(crate) fn sticky_cursor(&mut self, path: &Path, gravity: Gravity) -> Option<StickyCursor> {
+        let index = match path.name() {
+            Some(PathElement::Index(i)) => *i as usize,
+            _ => return None,
+        };
+        self.root_state_mut()
+            .sticky_cursor(&path.parent(), index, gravity)
+    }
+
+    pub(crate) fn track_cursor(&mut self, cursor: &StickyCursor) -> bool {
+        self.root_state_mut().track_cursor(cursor)
+    }
+
+    pub(crate) fn release_cursor(&mut self, cursor: &StickyCursor) {
+        self.root_state_mut().release_cursor(cursor)
+    }
+
+    pub(crate) fn resolve_cursor(&self, cursor: &StickyCursor) -> Option<usize> {
+        self.root_state().resolve_cursor(cursor)
+    }
+
+    fn root_state(&self) -> &StateTree {
+        match self {
             FrontendState::WaitingForInFlightRequests {
                 optimistic_root_state,
                 ..
@@ -184,8 +211,20 @@ impl FrontendState {
                 reconciled_root_state,
                 ..
             } => reconciled_root_state,
-        };
-        root.resolve_path(path)
+        }
+    }
+
+    fn root_state_mut(&mut self) -> &mut StateTree {
+        match self {
+            FrontendState::WaitingForInFlightRequests {
+                optimistic_root_state,
+                ..
+            } => optimistic_root_state,
+            FrontendState::Reconciled {
+                reconciled_root_state,
+                ..
+            } => reconciled_root_state,
+        }
     }
 
     /// Apply a patch. The change closure will be passed a `MutableDocument`
@@ -314,16 +353,16 @@ impl FrontendState {
         }
     }
 
-    pub(crate) fn value_ref(&self) -> RootRef {
+    pub(crate) fn value_ref<'a>(&'a self, resolvers: &'a Resolvers) -> RootRef<'a> {
         match self {
             FrontendState::WaitingForInFlightRequests {
                 optimistic_root_state,
                 ..
-            } => optimistic_root_state.value_ref(),
+            } => optimistic_root_state.value_ref(resolvers),
             FrontendState::Reconciled {
                 reconciled_root_state,
                 ..
-            } => reconciled_root_state.value_ref(),
+            } => reconciled_root_state.value_ref(resolvers),
         }
     }
 }
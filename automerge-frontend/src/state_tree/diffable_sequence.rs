@@ -4,10 +4,17 @@ use amp::{ActorId, OpId};
 use automerge_protocol as amp;
 use smol_str::SmolStr;
 
-use super::{sequence_tree::SequenceTree, MultiGrapheme, MultiValue, StateTreeValue};
-use crate::error::InvalidPatch;
-
-pub(crate) trait DiffableValue: Sized {
+use super::{
+    sequence_tree::{SequenceTree, TextWeight, Weighted},
+    MultiGrapheme, MultiValue, StateTreeValue,
+};
+use crate::{
+    error::{InvalidPatch, MissingIndexError},
+    value::Gravity,
+    TextElement,
+};
+
+pub(crate) trait DiffableValue: Sized + Weighted {
     fn take(&mut self) -> Self;
 
     fn check_construct(
@@ -42,7 +49,7 @@ impl DiffableValue for MultiGrapheme {
     fn take(&mut self) -> Self {
         std::mem::replace(
             self,
-            Self {
+            Self::Grapheme {
                 winning_value: (amp::OpId(0, ActorId::from(&[][..])), SmolStr::default()),
                 conflicts: HashMap::default(),
             },
@@ -95,6 +102,18 @@ impl DiffableValue for MultiGrapheme {
     }
 }
 
+impl Weighted for MultiGrapheme {
+    fn weight(&self) -> TextWeight {
+        TextWeight::of(
+            self.default_grapheme()
+                .map(SmolStr::as_str)
+                .unwrap_or(TextElement::OBJECT_REPLACEMENT),
+        )
+    }
+}
+
+impl Weighted for MultiValue {}
+
 impl DiffableValue for MultiValue {
     fn take(&mut self) -> Self {
         std::mem::replace(
@@ -188,7 +207,23 @@ where
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+impl<T> Weighted for SequenceElement<T>
+where
+    T: Clone,
+    T: DiffableValue,
+    T: PartialEq,
+    T: Debug,
+{
+    fn weight(&self) -> TextWeight {
+        // Values which are part way through a diff are reweighed when the diff is finished
+        match &self.value {
+            SequenceValue::Original(v) | SequenceValue::New(v) => v.weight(),
+            SequenceValue::Updated { original, .. } => original.weight(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
 pub(crate) struct DiffableSequence<T>
 where
     T: DiffableValue,
@@ -198,6 +233,33 @@ where
 {
     // stores the opid that created the element and the diffable value
     underlying: Box<SequenceTree<SequenceElement<T>>>,
+    // the sticky cursors tracked in this sequence, by the element they were attached to when
+    // they were created, with positions which are kept up to date as elements are inserted and
+    // removed. Elements holding objects which contain tracked cursors are tracked as cursors
+    // with right gravity so they can be found without a search.
+    cursors: HashMap<(OpId, Gravity), TrackedCursor>,
+}
+
+#[derive(Clone, Debug)]
+struct TrackedCursor {
+    position: usize,
+    // whether the element the cursor was attached to is still in the sequence
+    attached: bool,
+    // how many times the cursor has been tracked and not yet released
+    count: usize,
+}
+
+// Tracked cursors are a view of the sequence rather than a part of it
+impl<T> PartialEq for DiffableSequence<T>
+where
+    T: DiffableValue,
+    T: Clone,
+    T: PartialEq,
+    T: Debug,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.underlying == other.underlying
+    }
 }
 
 impl<T> DiffableSequence<T>
@@ -210,6 +272,7 @@ where
     pub fn new() -> DiffableSequence<T> {
         DiffableSequence {
             underlying: Box::new(SequenceTree::new()),
+            cursors: HashMap::new(),
         }
     }
 
@@ -223,6 +286,7 @@ where
         }
         DiffableSequence {
             underlying: Box::new(s),
+            cursors: HashMap::new(),
         }
     }
 
@@ -318,6 +382,7 @@ where
                     for _ in index..(index + count) {
                         self.underlying.remove(index);
                     }
+                    self.move_cursors_for_remove(index, count);
 
                     let mut i = 0;
                     while i < changed_indices.len() {
@@ -340,6 +405,7 @@ where
                     value,
                 } => {
                     let node = T::construct(op_id, value);
+                    self.move_cursors_for_insert(index as usize, 1);
                     if (index as usize) == self.underlying.len() {
                         self.underlying
                             .push(node.default_opid(), SequenceElement::new(node));
@@ -375,6 +441,8 @@ where
                             .insert(index + i, opid, SequenceElement::new(mv))
                     }
 
+                    self.move_cursors_for_insert(index, values.len());
+
                     for changed_index in changed_indices.iter_mut() {
                         if *changed_index >= index as u64 {
                             *changed_index += values.len() as u64;
@@ -390,18 +458,15 @@ where
                     value,
                     op_id,
                 } => {
-                    if let Some((_opid, v)) = self.underlying.get_mut(index as usize) {
-                        v.value.apply_diff(op_id, value);
-                    }
+                    self.underlying
+                        .update(index as usize, |v| v.value.apply_diff(op_id, value));
                     changed_indices.push(index);
                 }
             };
         }
 
         for i in changed_indices {
-            if let Some((_, u)) = self.underlying.get_mut(i as usize) {
-                u.value.finish()
-            }
+            self.underlying.update(i as usize, |u| u.value.finish());
         }
 
         debug_assert!(
@@ -413,12 +478,156 @@ where
     }
 
     pub(super) fn remove(&mut self, index: usize) -> T {
-        match self.underlying.remove(index).value {
+        let removed = self.underlying.remove(index);
+        self.move_cursors_for_remove(index, 1);
+        match removed.value {
             SequenceValue::Original(t) => t,
             _ => unreachable!(),
         }
     }
 
+    fn move_cursors_for_insert(&mut self, index: usize, count: usize) {
+        for ((_, gravity), cursor) in &mut self.cursors {
+            // elements inserted at a cursor's position go after it if it has left gravity
+            if index < cursor.position || (index == cursor.position && *gravity == Gravity::Right) {
+                cursor.position += count;
+            }
+        }
+    }
+
+    fn move_cursors_for_remove(&mut self, index: usize, count: usize) {
+        let removed = index..(index + count);
+        for ((_, gravity), cursor) in &mut self.cursors {
+            let attached_to = match gravity {
+                Gravity::Left => cursor.position.checked_sub(1),
+                Gravity::Right => Some(cursor.position),
+            };
+            if matches!(attached_to, Some(i) if removed.contains(&i)) {
+                cursor.attached = false;
+            }
+            // a cursor whose element is removed ends up next to the surviving neighbour in the
+            // direction of its gravity, which is where the removed elements were
+            if cursor.position >= removed.end {
+                cursor.position -= count;
+            } else if cursor.position > index {
+                cursor.position = index;
+            }
+        }
+    }
+
+    /// The index of the element created by `opid` if it is in the sequence
+    fn index_of(&self, opid: &OpId) -> Option<usize> {
+        match self.cursors.get(&(opid.clone(), Gravity::Right)) {
+            Some(cursor) if cursor.attached => Some(cursor.position),
+            Some(_) => None,
+            None => self.underlying.iter().position(|e| &e.opid == opid),
+        }
+    }
+
+    /// The element created by `opid`, found without a search if it is tracked
+    pub(crate) fn get_by_opid(&self, opid: &OpId) -> Option<&T> {
+        self.index_of(opid)
+            .and_then(|i| self.get(i))
+            .map(|(_, value)| value)
+    }
+
+    pub(crate) fn get_by_opid_mut(&mut self, opid: &OpId) -> Option<&mut T> {
+        self.index_of(opid)
+            .and_then(move |i| self.get_mut(i))
+            .map(|(_, value)| value)
+    }
+
+    /// Track a new sticky cursor at the position before `index`, returning the element it is
+    /// attached to or `None` if that is the start or end of the sequence
+    pub(crate) fn add_cursor(
+        &mut self,
+        index: usize,
+        gravity: Gravity,
+    ) -> Result<Option<OpId>, MissingIndexError> {
+        if index > self.len() {
+            return Err(MissingIndexError {
+                missing_index: index,
+                size_of_collection: self.len(),
+            });
+        }
+        let anchor = match gravity {
+            Gravity::Left => index.checked_sub(1),
+            Gravity::Right => Some(index),
+        };
+        let elem = anchor
+            .and_then(|i| self.underlying.get(i))
+            .map(|(_, e)| e.opid.clone());
+        if let Some(elem) = &elem {
+            self.cursors
+                .entry((elem.clone(), gravity))
+                .or_insert(TrackedCursor {
+                    position: index,
+                    attached: true,
+                    count: 0,
+                })
+                .count += 1;
+        }
+        Ok(elem)
+    }
+
+    /// Track the sticky cursor attached to `elem`, returning false if it isn't already tracked
+    /// and `elem` is not in the sequence
+    pub(crate) fn track_cursor(&mut self, elem: &OpId, gravity: Gravity) -> bool {
+        let key = (elem.clone(), gravity);
+        if !self.cursors.contains_key(&key) {
+            let position = match self.index_of(elem) {
+                Some(index) if gravity == Gravity::Left => index + 1,
+                Some(index) => index,
+                None => return false,
+            };
+            self.cursors.insert(
+                key.clone(),
+                TrackedCursor {
+                    position,
+                    attached: true,
+                    count: 0,
+                },
+            );
+        }
+        self.cursors.get_mut(&key).unwrap().count += 1;
+        true
+    }
+
+    /// Stop tracking the sticky cursor attached to `elem` once every user of it has released it
+    pub(crate) fn release_cursor(&mut self, elem: &OpId, gravity: Gravity) {
+        let key = (elem.clone(), gravity);
+        if let Some(cursor) = self.cursors.get_mut(&key) {
+            cursor.count -= 1;
+            if cursor.count == 0 {
+                self.cursors.remove(&key);
+            }
+        }
+    }
+
+    /// The current index of the position attached to `elem` with `gravity`, see
+    /// [`StickyCursor`](crate::StickyCursor).
+    ///
+    /// Returns `None` if the cursor is not tracked and `elem` is not in the sequence.
+    pub(crate) fn resolve_cursor(&self, elem: Option<&OpId>, gravity: Gravity) -> Option<usize> {
+        let elem = match elem {
+            Some(elem) => elem,
+            None => {
+                return Some(match gravity {
+                    Gravity::Left => 0,
+                    Gravity::Right => self.len(),
+                })
+            }
+        };
+        if let Some(cursor) = self.cursors.get(&(elem.clone(), gravity)) {
+            return Some(cursor.position);
+        }
+        let index = self.underlying.iter().position(|e| &e.opid == elem)?;
+        Some(match gravity {
+            Gravity::Left => index + 1,
+            Gravity::Right => index,
+        })
+    }
+
     pub(crate) fn len(&self) -> usize {
         self.underlying.len()
     }
@@ -459,11 +668,42 @@ where
     }
 
     pub(super) fn insert(&mut self, index: usize, value: T) {
-        self.underlying.insert(
-            index,
-            value.default_opid(),
-            SequenceElement::original(value),
-        )
+        self.move_cursors_for_insert(index, 1);
+        let opid = value.default_opid();
+        self.reattach_cursors(&opid, index);
+        self.underlying
+            .insert(index, opid, SequenceElement::original(value))
+    }
+
+    /// Rolling back the removal of an element puts it back at `index`, so the cursors which were
+    /// attached to it are attached to it again
+    fn reattach_cursors(&mut self, opid: &OpId, index: usize) {
+        for (gravity, position) in [(Gravity::Left, index + 1), (Gravity::Right, index)] {
+            if let Some(cursor) = self.cursors.get_mut(&(opid.clone(), gravity)) {
+                cursor.attached = true;
+                cursor.position = position;
+            }
+        }
+    }
+
+    /// The total weight of the values in the sequence
+    pub(crate) fn weight(&self) -> TextWeight {
+        self.underlying.weight()
+    }
+
+    /// The total weight of the values before `index`
+    pub(crate) fn weight_before(&self, index: usize) -> TextWeight {
+        self.underlying.weight_before(index)
+    }
+
+    /// The index of the value containing the unit at `offset` and how far into that value
+    /// `offset` is
+    pub(crate) fn find_by_weight(
+        &self,
+        offset: usize,
+        unit: fn(&TextWeight) -> usize,
+    ) -> Option<(usize, usize)> {
+        self.underlying.find_by_weight(offset, unit)
     }
 
     pub(crate) fn iter(&self) -> impl std::iter::Iterator<Item = &T> {
@@ -649,4 +889,38 @@ mod tests {
             ],
         )
     }
+
+    #[test]
+    fn tracked_cursors_are_not_part_of_equality() {
+        let actor = ActorId::random();
+        let mut values = ScalarValues::new(amp::ScalarValueKind::Null);
+        values.append(ScalarValue::Null);
+        values.append(ScalarValue::Null);
+        let insert = DiffEdit::MultiElementInsert(MultiElementInsert {
+            index: 0,
+            elem_id: amp::ElementId::Id(OpId(1, actor)),
+            values,
+        });
+
+        let mut ds = DiffableSequence::<MultiValue>::new();
+        ds.apply_diff(&ObjectId::Root, vec![insert.clone()]);
+        let mut tracked = ds.clone();
+        let elem = tracked.add_cursor(1, Gravity::Left).unwrap().unwrap();
+        assert_eq!(ds, tracked);
+
+        ds.apply_diff(
+            &ObjectId::Root,
+            vec![DiffEdit::Remove { index: 0, count: 1 }],
+        );
+        tracked.apply_diff(
+            &ObjectId::Root,
+            vec![DiffEdit::Remove { index: 0, count: 1 }],
+        );
+        assert_eq!(ds, tracked);
+        assert_eq!(tracked.resolve_cursor(Some(&elem), Gravity::Left), Some(0));
+        assert_eq!(ds.resolve_cursor(Some(&elem), Gravity::Left), None);
+
+        tracked.release_cursor(&elem, Gravity::Left);
+        assert!(tracked.cursors.is_empty());
+    }
 }
@@ -5,9 +5,17 @@ use automerge_protocol as amp;
 use automerge_protocol::RootDiff;
 use diffable_sequence::DiffableSequence;
 use multivalue::NewValueRequest;
+use sequence_tree::TextWeight;
 use smol_str::SmolStr;
 
-use crate::{error, path::PathElement, value_ref::RootRef, Path, Primitive, Value};
+use crate::{
+    error,
+    path::PathElement,
+    resolver::Resolvers,
+    value::ChildKey,
+    value_ref::{RootRef, TextUnit},
+    Gravity, Path, Primitive, StickyCursor, Value,
+};
 
 mod diffable_sequence;
 mod multivalue;
@@ -80,6 +88,7 @@ impl StateTree {
 
     pub fn apply_diff(&mut self, diff: CheckedRootDiff) {
         for (prop, prop_diff) in diff.0.props {
+            let opids: Vec<_> = prop_diff.keys().cloned().collect();
             let mut diff_iter = prop_diff.into_iter();
             match diff_iter.next() {
                 None => {
@@ -93,10 +102,9 @@ impl StateTree {
                             self.root_props.insert(prop.clone(), value);
                         }
                     };
-                    self.root_props
-                        .get_mut(&prop)
-                        .unwrap()
-                        .apply_diff_iter(&mut diff_iter);
+                    let value = self.root_props.get_mut(&prop).unwrap();
+                    value.apply_diff_iter(&mut diff_iter);
+                    value.retain_opids(&opids);
                 }
             }
         }
@@ -156,8 +164,127 @@ impl StateTree {
         Value::Map(m)
     }
 
-    pub(crate) fn value_ref(&self) -> RootRef {
-        RootRef::new(self)
+    pub(crate) fn value_ref<'a>(&'a self, resolvers: &'a Resolvers) -> RootRef<'a> {
+        RootRef::new(self, resolvers)
+    }
+
+    /// The route from the root to the object at `path`, see [`StickyCursor`]
+    fn route(&self, path: &Path) -> Option<Vec<(ChildKey, amp::ObjectId)>> {
+        let mut elements = path.clone().elements().into_iter();
+        let key = match elements.next()? {
+            PathElement::Key(key) => key,
+            PathElement::Index(_) => return None,
+        };
+        let mut composite = self.root_props.get(&key)?.default_composite()?;
+        let mut route = vec![(ChildKey::Key(key), composite.object_id())];
+        for element in elements {
+            let (key, child) = composite.child_at(&element)?;
+            composite = child.default_composite()?;
+            route.push((key, composite.object_id()));
+        }
+        Some(route)
+    }
+
+    /// The object at the end of `route` if it is still in the tree
+    fn object(&self, route: &[(ChildKey, amp::ObjectId)]) -> Option<&StateTreeComposite> {
+        let ((key, object_id), rest) = route.split_first()?;
+        let mut composite = match key {
+            ChildKey::Key(key) => self.root_props.get(key)?.composite(object_id)?,
+            ChildKey::Elem(_) => return None,
+        };
+        for (key, object_id) in rest {
+            composite = composite.child(key)?.composite(object_id)?;
+        }
+        Some(composite)
+    }
+
+    /// Call `f` with the object at the end of `route`, tracking the elements along the way so
+    /// that it can be found again without a search
+    fn track_route<F, R>(&mut self, route: &[(ChildKey, amp::ObjectId)], f: F) -> Option<R>
+    where
+        F: FnOnce(&mut StateTreeComposite) -> R,
+    {
+        match route.split_first()? {
+            ((ChildKey::Key(key), object_id), rest) => self
+                .root_props
+                .get_mut(key)?
+                .composite_mut(object_id)?
+                .track_route(rest, f),
+            ((ChildKey::Elem(_), _), _) => None,
+        }
+    }
+
+    /// Undo [`track_route`](Self::track_route), calling `f` with the object at the end of
+    /// `route` if it is still in the tree
+    fn release_route<F>(&mut self, route: &[(ChildKey, amp::ObjectId)], f: F)
+    where
+        F: FnOnce(&mut StateTreeComposite),
+    {
+        if let Some(((ChildKey::Key(key), object_id), rest)) = route.split_first() {
+            if let Some(composite) = self
+                .root_props
+                .get_mut(key)
+                .and_then(|v| v.composite_mut(object_id))
+            {
+                composite.release_route(rest, f)
+            }
+        }
+    }
+
+    /// Create and track a sticky cursor for the position before `index` in the list or text
+    /// at `path`
+    pub(crate) fn sticky_cursor(
+        &mut self,
+        path: &Path,
+        index: usize,
+        gravity: Gravity,
+    ) -> Option<StickyCursor> {
+        let route = self.route(path)?;
+        // check the cursor can be created before tracking anything
+        match self.object(&route)? {
+            StateTreeComposite::List(list) if index <= list.elements.len() => {}
+            StateTreeComposite::Text(text) if index <= text.graphemes.len() => {}
+            _ => return None,
+        }
+        let elem_opid = self
+            .track_route(&route, |composite| composite.add_cursor(index, gravity))?
+            .ok()?;
+        Some(StickyCursor::new(route, elem_opid, gravity))
+    }
+
+    /// Track a sticky cursor created elsewhere, returning false if its object or the element
+    /// it is attached to is not in the tree
+    pub(crate) fn track_cursor(&mut self, cursor: &StickyCursor) -> bool {
+        if self.resolve_cursor(cursor).is_none() {
+            return false;
+        }
+        self.track_route(&cursor.path, |composite| match &cursor.elem_opid {
+            Some(elem) => composite.track_cursor(elem, cursor.gravity),
+            None => true,
+        })
+        .unwrap_or(false)
+    }
+
+    pub(crate) fn release_cursor(&mut self, cursor: &StickyCursor) {
+        self.release_route(&cursor.path, |composite| {
+            if let Some(elem) = &cursor.elem_opid {
+                composite.release_cursor(elem, cursor.gravity);
+            }
+        })
+    }
+
+    /// The current index of `cursor`, or `None` if the object it points into is not in the
+    /// tree
+    pub(crate) fn resolve_cursor(&self, cursor: &StickyCursor) -> Option<usize> {
+        match self.object(&cursor.path)? {
+            StateTreeComposite::List(StateTreeList { elements, .. }) => {
+                elements.resolve_cursor(cursor.elem_opid.as_ref(), cursor.gravity)
+            }
+            StateTreeComposite::Text(StateTreeText { graphemes, .. }) => {
+                graphemes.resolve_cursor(cursor.elem_opid.as_ref(), cursor.gravity)
+            }
+            _ => None,
+        }
     }
 }
 
@@ -181,6 +308,7 @@ pub(crate) enum StateTreeComposite {
     Table(StateTreeTable),
     Text(StateTreeText),
     List(StateTreeList),
+    Set(StateTreeSet),
 }
 
 impl StateTreeComposite {
@@ -220,6 +348,13 @@ impl StateTreeComposite {
                 }),
                 StateTreeComposite::Text(text),
             ) => text.check_diff(edits),
+            (
+                amp::Diff::Set(amp::SetDiff {
+                    props: member_diffs,
+                    object_id: _,
+                }),
+                StateTreeComposite::Set(set),
+            ) => set.check_diff(member_diffs),
             // TODO throw an error
             (amp::Diff::Value(..), _) => unreachable!(),
             // TODO throw an error
@@ -227,7 +362,8 @@ impl StateTreeComposite {
             (amp::Diff::Map(_), _)
             | (amp::Diff::Table(_), _)
             | (amp::Diff::List(_), _)
-            | (amp::Diff::Text(_), _) => Err(error::InvalidPatch::MismatchingObjectType {
+            | (amp::Diff::Text(_), _)
+            | (amp::Diff::Set(_), _) => Err(error::InvalidPatch::MismatchingObjectType {
                 object_id: self.object_id(),
                 patch_expected_type: diff.object_type(),
                 actual_type: Some(self.obj_type()),
@@ -265,6 +401,13 @@ impl StateTreeComposite {
                 }),
                 StateTreeComposite::Text(text),
             ) => text.apply_diff(edits),
+            (
+                amp::Diff::Set(amp::SetDiff {
+                    props: member_diffs,
+                    object_id: _,
+                }),
+                StateTreeComposite::Set(set),
+            ) => set.apply_diff(member_diffs),
             // TODO throw an error
             (amp::Diff::Value(..), _) => unreachable!(),
             // TODO throw an error
@@ -272,7 +415,8 @@ impl StateTreeComposite {
             (amp::Diff::Map(_), _)
             | (amp::Diff::Table(_), _)
             | (amp::Diff::List(_), _)
-            | (amp::Diff::Text(_), _) => unreachable!(),
+            | (amp::Diff::Text(_), _)
+            | (amp::Diff::Set(_), _) => unreachable!(),
         }
     }
 
@@ -282,6 +426,7 @@ impl StateTreeComposite {
             Self::Table(..) => amp::ObjType::Table,
             Self::Text(..) => amp::ObjType::Text,
             Self::List(..) => amp::ObjType::List,
+            Self::Set(..) => amp::ObjType::Set,
         }
     }
 
@@ -291,6 +436,7 @@ impl StateTreeComposite {
             Self::Table(StateTreeTable { object_id, .. }) => object_id.clone(),
             Self::Text(StateTreeText { object_id, .. }) => object_id.clone(),
             Self::List(StateTreeList { object_id, .. }) => object_id.clone(),
+            Self::Set(StateTreeSet { object_id, .. }) => object_id.clone(),
         }
     }
 
@@ -311,12 +457,134 @@ impl StateTreeComposite {
             Self::List(StateTreeList {
                 elements: elems, ..
             }) => Value::List(elems.iter().map(|e| e.default_value()).collect()),
-            Self::Text(StateTreeText { graphemes, .. }) => Value::Text(
-                graphemes
-                    .iter()
-                    .map(|c| c.default_grapheme().clone())
-                    .collect(),
-            ),
+            Self::Text(StateTreeText { graphemes, .. }) => {
+                Value::Text(graphemes.iter().map(|c| c.default_element()).collect())
+            }
+            Self::Set(set) => Value::Set(set.members().cloned().collect()),
+        }
+    }
+
+    /// The child at `element` of a path and where it is in this object
+    fn child_at(&self, element: &PathElement) -> Option<(ChildKey, &MultiValue)> {
+        match (self, element) {
+            (Self::Map(StateTreeMap { props, .. }), PathElement::Key(key))
+            | (Self::Table(StateTreeTable { props, .. }), PathElement::Key(key)) => {
+                Some((ChildKey::Key(key.clone()), props.get(key)?))
+            }
+            (Self::List(StateTreeList { elements, .. }), PathElement::Index(index)) => {
+                let (opid, value) = elements.get(*index as usize)?;
+                Some((ChildKey::Elem(opid.clone()), value))
+            }
+            (Self::Text(StateTreeText { graphemes, .. }), PathElement::Index(index)) => {
+                match graphemes.get(*index as usize)? {
+                    (opid, MultiGrapheme::Embedded(value)) => {
+                        Some((ChildKey::Elem(opid.clone()), value))
+                    }
+                    (_, MultiGrapheme::Grapheme { .. }) => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn child(&self, key: &ChildKey) -> Option<&MultiValue> {
+        match (self, key) {
+            (Self::Map(StateTreeMap { props, .. }), ChildKey::Key(key))
+            | (Self::Table(StateTreeTable { props, .. }), ChildKey::Key(key)) => props.get(key),
+            (Self::List(StateTreeList { elements, .. }), ChildKey::Elem(opid)) => {
+                elements.get_by_opid(opid)
+            }
+            (Self::Text(StateTreeText { graphemes, .. }), ChildKey::Elem(opid)) => {
+                match graphemes.get_by_opid(opid)? {
+                    MultiGrapheme::Embedded(value) => Some(value),
+                    MultiGrapheme::Grapheme { .. } => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn child_mut(&mut self, key: &ChildKey) -> Option<&mut MultiValue> {
+        match (self, key) {
+            (Self::Map(StateTreeMap { props, .. }), ChildKey::Key(key))
+            | (Self::Table(StateTreeTable { props, .. }), ChildKey::Key(key)) => props.get_mut(key),
+            (Self::List(StateTreeList { elements, .. }), ChildKey::Elem(opid)) => {
+                elements.get_by_opid_mut(opid)
+            }
+            (Self::Text(StateTreeText { graphemes, .. }), ChildKey::Elem(opid)) => {
+                match graphemes.get_by_opid_mut(opid)? {
+                    MultiGrapheme::Embedded(value) => Some(value),
+                    MultiGrapheme::Grapheme { .. } => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn track_route<F, R>(&mut self, route: &[(ChildKey, amp::ObjectId)], f: F) -> Option<R>
+    where
+        F: FnOnce(&mut StateTreeComposite) -> R,
+    {
+        match route.split_first() {
+            None => Some(f(self)),
+            Some(((key, object_id), rest)) => {
+                // elements holding an object with a tracked cursor are tracked like cursors with
+                // right gravity so their index is always known
+                if let ChildKey::Elem(opid) = key {
+                    self.track_cursor(opid, Gravity::Right);
+                }
+                self.child_mut(key)?
+                    .composite_mut(object_id)?
+                    .track_route(rest, f)
+            }
+        }
+    }
+
+    fn release_route<F>(&mut self, route: &[(ChildKey, amp::ObjectId)], f: F)
+    where
+        F: FnOnce(&mut StateTreeComposite),
+    {
+        match route.split_first() {
+            None => f(self),
+            Some(((key, object_id), rest)) => {
+                if let Some(child) = self.child_mut(key).and_then(|v| v.composite_mut(object_id)) {
+                    child.release_route(rest, f);
+                }
+                if let ChildKey::Elem(opid) = key {
+                    self.release_cursor(opid, Gravity::Right);
+                }
+            }
+        }
+    }
+
+    fn add_cursor(
+        &mut self,
+        index: usize,
+        gravity: Gravity,
+    ) -> Result<Option<amp::OpId>, error::MissingIndexError> {
+        match self {
+            Self::List(StateTreeList { elements, .. }) => elements.add_cursor(index, gravity),
+            Self::Text(StateTreeText { graphemes, .. }) => graphemes.add_cursor(index, gravity),
+            _ => Err(error::MissingIndexError {
+                missing_index: index,
+                size_of_collection: 0,
+            }),
+        }
+    }
+
+    fn track_cursor(&mut self, elem: &amp::OpId, gravity: Gravity) -> bool {
+        match self {
+            Self::List(StateTreeList { elements, .. }) => elements.track_cursor(elem, gravity),
+            Self::Text(StateTreeText { graphemes, .. }) => graphemes.track_cursor(elem, gravity),
+            _ => false,
+        }
+    }
+
+    fn release_cursor(&mut self, elem: &amp::OpId, gravity: Gravity) {
+        match self {
+            Self::List(StateTreeList { elements, .. }) => elements.release_cursor(elem, gravity),
+            Self::Text(StateTreeText { graphemes, .. }) => graphemes.release_cursor(elem, gravity),
+            _ => {}
         }
     }
 
@@ -326,6 +594,8 @@ impl StateTreeComposite {
             Self::Table(table) => table.resolve_path(path),
             Self::List(list) => list.resolve_path(path),
             Self::Text(text) => text.resolve_path(path),
+            // sets have no paths inside them
+            Self::Set(_) => None,
         }
     }
 
@@ -335,6 +605,7 @@ impl StateTreeComposite {
             Self::Table(table) => table.resolve_path_mut(path),
             Self::List(list) => list.resolve_path_mut(path),
             Self::Text(text) => text.resolve_path_mut(path),
+            Self::Set(_) => None,
         }
     }
 }
@@ -351,6 +622,9 @@ impl StateTreeValue {
                 | amp::ScalarValue::Counter(_)
                 | amp::ScalarValue::Timestamp(_)
                 | amp::ScalarValue::Boolean(_)
+                | amp::ScalarValue::Uuid(_)
+                | amp::ScalarValue::Decimal(_)
+                | amp::ScalarValue::Unknown(_)
                 | amp::ScalarValue::Null => Ok(()),
                 amp::ScalarValue::Cursor(..) => Err(error::InvalidPatch::ValueDiffContainedCursor),
             },
@@ -358,6 +632,7 @@ impl StateTreeValue {
             | amp::Diff::Table(_)
             | amp::Diff::List(_)
             | amp::Diff::Text(_)
+            | amp::Diff::Set(_)
             | amp::Diff::Cursor(_) => Ok(()),
         }
     }
@@ -375,6 +650,9 @@ impl StateTreeValue {
                     amp::ScalarValue::Timestamp(i) => Primitive::Timestamp(i),
                     amp::ScalarValue::Boolean(b) => Primitive::Boolean(b),
                     amp::ScalarValue::Null => Primitive::Null,
+                    amp::ScalarValue::Uuid(u) => Primitive::Uuid(u),
+                    amp::ScalarValue::Decimal(d) => Primitive::Decimal(d),
+                    amp::ScalarValue::Unknown(u) => Primitive::Unknown(u),
                     amp::ScalarValue::Cursor(..) => {
                         unreachable!("value diff contained a cursor")
                     }
@@ -413,6 +691,14 @@ impl StateTreeValue {
                 text.apply_diff(edits);
                 StateTreeValue::Composite(StateTreeComposite::Text(text))
             }
+            amp::Diff::Set(amp::SetDiff { object_id, props }) => {
+                let mut set = StateTreeSet {
+                    object_id,
+                    members: HashMap::new(),
+                };
+                set.apply_diff(props);
+                StateTreeValue::Composite(StateTreeComposite::Set(set))
+            }
 
             amp::Diff::Cursor(ref c) => StateTreeValue::Leaf(c.into()),
         }
@@ -461,6 +747,7 @@ impl StateTreeMap {
 
     fn apply_diff(&mut self, prop_diffs: HashMap<SmolStr, HashMap<amp::OpId, amp::Diff>>) {
         for (prop, prop_diff) in prop_diffs {
+            let opids: Vec<_> = prop_diff.keys().cloned().collect();
             let mut diff_iter = prop_diff.into_iter();
             match diff_iter.next() {
                 None => {
@@ -474,10 +761,9 @@ impl StateTreeMap {
                             self.props.insert(prop.clone(), value);
                         }
                     };
-                    self.props
-                        .get_mut(&prop)
-                        .unwrap()
-                        .apply_diff_iter(&mut diff_iter);
+                    let value = self.props.get_mut(&prop).unwrap();
+                    value.apply_diff_iter(&mut diff_iter);
+                    value.retain_opids(&opids);
                 }
             }
         }
@@ -551,6 +837,7 @@ impl StateTreeTable {
 
     fn apply_diff(&mut self, prop_diffs: HashMap<SmolStr, HashMap<amp::OpId, amp::Diff>>) {
         for (prop, prop_diff) in prop_diffs {
+            let opids: Vec<_> = prop_diff.keys().cloned().collect();
             let mut diff_iter = prop_diff.into_iter();
             match diff_iter.next() {
                 None => {
@@ -564,10 +851,9 @@ impl StateTreeTable {
                             self.props.insert(prop.clone(), value);
                         }
                     };
-                    self.props
-                        .get_mut(&prop)
-                        .unwrap()
-                        .apply_diff_iter(&mut diff_iter);
+                    let value = self.props.get_mut(&prop).unwrap();
+                    value.apply_diff_iter(&mut diff_iter);
+                    value.retain_opids(&opids);
                 }
             }
         }
@@ -606,6 +892,92 @@ impl StateTreeTable {
     }
 }
 
+/// An add-wins set. Each member is stored under its [`amp::ScalarValue::set_key`], with a value
+/// for every op which added it and has not been removed.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct StateTreeSet {
+    object_id: amp::ObjectId,
+    pub(crate) members: HashMap<SmolStr, MultiValue>,
+}
+
+impl StateTreeSet {
+    fn check_diff(
+        &self,
+        member_diffs: &HashMap<SmolStr, HashMap<amp::OpId, amp::Diff>>,
+    ) -> Result<(), error::InvalidPatch> {
+        for (key, member_diff) in member_diffs {
+            for diff in member_diff.values() {
+                let valid = match diff {
+                    amp::Diff::Value(value) => value.set_key().as_ref() == Some(key),
+                    _ => false,
+                };
+                if !valid {
+                    return Err(error::InvalidPatch::InvalidSetMember {
+                        object_id: self.object_id.clone(),
+                        diff: diff.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The diff for a member lists every op which added it, so it replaces what we had
+    fn apply_diff(&mut self, member_diffs: HashMap<SmolStr, HashMap<amp::OpId, amp::Diff>>) {
+        for (key, member_diff) in member_diffs {
+            let mut diff_iter = member_diff.into_iter();
+            match diff_iter.next() {
+                None => {
+                    self.members.remove(&key);
+                }
+                Some((opid, diff)) => {
+                    let mut member = MultiValue::new_from_diff(opid, diff);
+                    member.apply_diff_iter(&mut diff_iter);
+                    self.members.insert(key, member);
+                }
+            }
+        }
+    }
+
+    pub(crate) fn key_of(value: &Primitive) -> Option<SmolStr> {
+        amp::ScalarValue::from(value).set_key()
+    }
+
+    pub(crate) fn contains(&self, value: &Primitive) -> bool {
+        Self::key_of(value).is_some_and(|key| self.members.contains_key(&key))
+    }
+
+    /// The members of the set, ordered by their keys
+    pub(crate) fn members(&self) -> impl Iterator<Item = &Primitive> {
+        let mut members: Vec<_> = self.members.iter().collect();
+        members.sort_unstable_by_key(|(key, _)| *key);
+        members
+            .into_iter()
+            .filter_map(|(_, member)| match member.default_statetree_value() {
+                StateTreeValue::Leaf(p) => Some(p),
+                StateTreeValue::Composite(_) => None,
+            })
+    }
+
+    /// The ops a remove of `key` needs to overwrite, every add of the member this set has seen
+    pub fn pred_for_key(&self, key: &str) -> SortedVec<amp::OpId> {
+        self.members
+            .get(key)
+            .map(MultiValue::opids)
+            .unwrap_or_else(SortedVec::new)
+    }
+}
+
+/// How much of `unit` a run of graphemes weighs, or `None` for graphemes as each one is an index
+fn measure(unit: TextUnit) -> Option<fn(&TextWeight) -> usize> {
+    match unit {
+        TextUnit::Grapheme => None,
+        TextUnit::Utf8 => Some(|w| w.utf8),
+        TextUnit::Utf16 => Some(|w| w.utf16),
+        TextUnit::Scalar => Some(|w| w.chars),
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub(crate) struct StateTreeText {
     object_id: amp::ObjectId,
@@ -644,10 +1016,9 @@ impl StateTreeText {
     pub(crate) fn elem_at(
         &self,
         index: usize,
-    ) -> Result<(&amp::OpId, &SmolStr), error::MissingIndexError> {
+    ) -> Result<(&amp::OpId, &MultiGrapheme), error::MissingIndexError> {
         self.graphemes
             .get(index)
-            .map(|mc| (mc.0, mc.1.default_grapheme()))
             .ok_or_else(|| error::MissingIndexError {
                 missing_index: index,
                 size_of_collection: self.graphemes.len(),
@@ -662,6 +1033,52 @@ impl StateTreeText {
         self.insert_many(index, std::iter::once(value))
     }
 
+    pub(crate) fn len_in(&self, unit: TextUnit) -> usize {
+        match measure(unit) {
+            Some(measure) => measure(&self.graphemes.weight()),
+            None => self.graphemes.len(),
+        }
+    }
+
+    /// The index of the grapheme which starts at `offset`, or the length of the text if `offset`
+    /// is the end of the text. Returns `None` for offsets inside a grapheme or past the end.
+    pub(crate) fn index_at_offset(&self, offset: usize, unit: TextUnit) -> Option<usize> {
+        match measure(unit) {
+            Some(measure) => {
+                if offset == measure(&self.graphemes.weight()) {
+                    Some(self.graphemes.len())
+                } else {
+                    match self.graphemes.find_by_weight(offset, measure)? {
+                        (index, 0) => Some(index),
+                        _ => None,
+                    }
+                }
+            }
+            None => {
+                if offset <= self.graphemes.len() {
+                    Some(offset)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// The offset at which the grapheme at `index` starts
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the length of the text
+    pub(crate) fn offset_of_index(&self, index: usize, unit: TextUnit) -> usize {
+        match measure(unit) {
+            Some(measure) => measure(&self.graphemes.weight_before(index)),
+            None => {
+                assert!(index <= self.graphemes.len());
+                index
+            }
+        }
+    }
+
     fn insert_many<I>(&mut self, index: usize, values: I) -> Result<(), error::MissingIndexError>
     where
         I: IntoIterator<Item = MultiGrapheme>,
@@ -697,11 +1114,8 @@ impl StateTreeText {
 
     pub(crate) fn resolve_path(&self, mut path: Vec<PathElement>) -> Option<ResolvedPath> {
         if let Some(PathElement::Index(i)) = path.pop() {
-            if path.is_empty() {
-                self.graphemes.get(i as usize)?.1.resolve_path(path)
-            } else {
-                None
-            }
+            let (elem_id, element) = self.graphemes.get(i as usize)?;
+            element.resolve_path(path, self.object_id.clone(), elem_id.into())
         } else {
             None
         }
@@ -712,11 +1126,10 @@ impl StateTreeText {
         mut path: Vec<PathElement>,
     ) -> Option<ResolvedPathMut> {
         if let Some(PathElement::Index(i)) = path.pop() {
-            if path.is_empty() {
-                self.graphemes.get_mut(i as usize)?.1.resolve_path_mut(path)
-            } else {
-                None
-            }
+            let object_id = self.object_id.clone();
+            let (elem_id, element) = self.graphemes.get_mut(i as usize)?;
+            let elem_id = elem_id.clone().into();
+            element.resolve_path_mut(path, object_id, elem_id)
         } else {
             None
         }
@@ -7,12 +7,12 @@ use unicode_segmentation::UnicodeSegmentation;
 
 use super::{
     CursorState, Cursors, DiffableSequence, ResolvedPath, ResolvedPathMut, StateTreeComposite,
-    StateTreeList, StateTreeMap, StateTreeTable, StateTreeText, StateTreeValue,
+    StateTreeList, StateTreeMap, StateTreeSet, StateTreeTable, StateTreeText, StateTreeValue,
 };
 use crate::{
     error,
     path::PathElement,
-    value::{Primitive, Value},
+    value::{Primitive, TextElement, Value},
 };
 
 pub(crate) struct NewValueRequest<'a, 'c> {
@@ -172,6 +172,10 @@ impl MultiValue {
         }
     }
 
+    pub(crate) fn has_conflicts(&self) -> bool {
+        !self.conflicts.is_empty()
+    }
+
     pub(crate) fn default_statetree_value(&self) -> &StateTreeValue {
         &self.winning_value.1
     }
@@ -188,11 +192,81 @@ impl MultiValue {
         self.winning_value.0.clone()
     }
 
-    fn iter(&self) -> impl std::iter::Iterator<Item = (&amp::OpId, &StateTreeValue)> {
+    pub(crate) fn iter(&self) -> impl std::iter::Iterator<Item = (&amp::OpId, &StateTreeValue)> {
         std::iter::once((&(self.winning_value).0, &(self.winning_value.1)))
             .chain(self.conflicts.iter())
     }
 
+    /// Drop the values whose opids are not in `opids`. Patches list every value a key has, so a
+    /// value which is missing from a patch has been overwritten or deleted.
+    pub(super) fn retain_opids(&mut self, opids: &[amp::OpId]) {
+        self.conflicts.retain(|opid, _| opids.contains(opid));
+        if !opids.contains(&self.winning_value.0) {
+            if let Some(winner) = self.conflicts.keys().max().cloned() {
+                let value = self.conflicts.remove(&winner).unwrap();
+                self.winning_value = (winner, value);
+            }
+        }
+    }
+
+    /// Make the value set by `opid` the only value, returning the values it was in conflict
+    /// with, or `None` if no value was set by `opid`
+    pub(super) fn resolve_conflict(
+        &mut self,
+        opid: &amp::OpId,
+    ) -> Option<HashMap<amp::OpId, StateTreeValue>> {
+        if opid != &self.winning_value.0 {
+            let value = self.conflicts.remove(opid)?;
+            let (old_opid, old_value) =
+                std::mem::replace(&mut self.winning_value, (opid.clone(), value));
+            self.conflicts.insert(old_opid, old_value);
+        }
+        Some(std::mem::take(&mut self.conflicts))
+    }
+
+    pub(super) fn rollback_resolve_conflict(&mut self, losers: HashMap<amp::OpId, StateTreeValue>) {
+        for (opid, value) in losers {
+            self.update(&opid, value);
+        }
+    }
+
+    /// The winning value if it is an object
+    pub(super) fn default_composite(&self) -> Option<&StateTreeComposite> {
+        match &self.winning_value.1 {
+            StateTreeValue::Composite(composite) => Some(composite),
+            StateTreeValue::Leaf(_) => None,
+        }
+    }
+
+    /// The object with ID `object_id` if it is one of the values
+    pub(super) fn composite(&self, object_id: &amp::ObjectId) -> Option<&StateTreeComposite> {
+        self.iter().find_map(|(_, v)| match v {
+            StateTreeValue::Composite(composite) if composite.object_id() == *object_id => {
+                Some(composite)
+            }
+            _ => None,
+        })
+    }
+
+    pub(super) fn composite_mut(
+        &mut self,
+        object_id: &amp::ObjectId,
+    ) -> Option<&mut StateTreeComposite> {
+        std::iter::once(&mut self.winning_value.1)
+            .chain(self.conflicts.values_mut())
+            .find_map(|v| match v {
+                StateTreeValue::Composite(composite) if composite.object_id() == *object_id => {
+                    Some(composite)
+                }
+                _ => None,
+            })
+    }
+
+    /// The IDs of the winning op and of every conflicting op
+    pub(super) fn opids(&self) -> SortedVec<amp::OpId> {
+        self.iter().map(|(opid, _)| opid.clone()).collect()
+    }
+
     pub(super) fn realise_values(&self) -> std::collections::HashMap<amp::OpId, Value> {
         self.iter()
             .map(|(opid, v)| (opid.clone(), v.realise_value()))
@@ -226,6 +300,9 @@ impl MultiValue {
                     StateTreeComposite::List(list) => {
                         return Some(ResolvedPath::new_list(self, list.object_id.clone()))
                     }
+                    StateTreeComposite::Set(set) => {
+                        return Some(ResolvedPath::new_set(self, set.object_id.clone()))
+                    }
                 }
             }
         } else if let StateTreeValue::Composite(ref composite) = self.winning_value.1 {
@@ -265,6 +342,10 @@ impl MultiValue {
                         let oid = list.object_id.clone();
                         return Some(ResolvedPathMut::new_list(self, oid));
                     }
+                    StateTreeComposite::Set(set) => {
+                        let oid = set.object_id.clone();
+                        return Some(ResolvedPathMut::new_set(self, oid));
+                    }
                 }
             }
         } else if let StateTreeValue::Composite(ref mut composite) = self.winning_value.1 {
@@ -327,57 +408,70 @@ impl NewValue {
     }
 }
 
-/// This struct exists to constrain the values of a text type to just containing
-/// sequences of grapheme clusters
+/// The values of an element of a text object. Elements are grapheme clusters unless a value has
+/// been embedded in the text, then all the values of the element are kept as a `MultiValue` in
+/// which any graphemes are string primitives.
 #[derive(Debug, Clone, PartialEq)]
-pub struct MultiGrapheme {
-    pub(super) winning_value: (amp::OpId, SmolStr),
-    pub(super) conflicts: HashMap<amp::OpId, SmolStr>,
+pub enum MultiGrapheme {
+    Grapheme {
+        winning_value: (amp::OpId, SmolStr),
+        conflicts: HashMap<amp::OpId, SmolStr>,
+    },
+    Embedded(MultiValue),
 }
 
 impl MultiGrapheme {
     pub(super) fn new_from_grapheme_cluster(opid: amp::OpId, s: SmolStr) -> MultiGrapheme {
         debug_assert_eq!(s.graphemes(true).count(), 1);
-        MultiGrapheme {
+        MultiGrapheme::Grapheme {
             winning_value: (opid, s),
             conflicts: HashMap::new(),
         }
     }
 
-    pub(super) fn check_new_from_diff(
-        _opid: &amp::OpId,
+    /// The element holding the values in `value`, which is a plain grapheme if `value` is a
+    /// single string
+    pub(super) fn from_multivalue(value: MultiValue) -> MultiGrapheme {
+        if value.conflicts.is_empty() {
+            if let (opid, StateTreeValue::Leaf(Primitive::Str(s))) = &value.winning_value {
+                return MultiGrapheme::new_from_grapheme_cluster(opid.clone(), s.clone());
+            }
+        }
+        MultiGrapheme::Embedded(value)
+    }
+
+    /// Strings in text must be a single grapheme cluster, any other value is embedded
+    fn check_grapheme(
         diff: &amp::Diff,
         parent_object_id: &amp::ObjectId,
     ) -> Result<(), error::InvalidPatch> {
         match diff {
-            amp::Diff::Value(amp::ScalarValue::Str(s)) => {
-                if s.graphemes(true).count() != 1 {
-                    return Err(error::InvalidPatch::InsertNonTextInTextObject {
-                        object_id: parent_object_id.clone(),
-                        diff: diff.clone(),
-                    });
-                } else {
-                    s
-                }
-            }
-            _ => {
-                return Err(error::InvalidPatch::InsertNonTextInTextObject {
+            amp::Diff::Value(amp::ScalarValue::Str(s)) if s.graphemes(true).count() != 1 => {
+                Err(error::InvalidPatch::InsertNonTextInTextObject {
                     object_id: parent_object_id.clone(),
                     diff: diff.clone(),
-                });
+                })
             }
-        };
-        Ok(())
+            _ => Ok(()),
+        }
+    }
+
+    pub(super) fn check_new_from_diff(
+        _opid: &amp::OpId,
+        diff: &amp::Diff,
+        parent_object_id: &amp::ObjectId,
+    ) -> Result<(), error::InvalidPatch> {
+        Self::check_grapheme(diff, parent_object_id)?;
+        StateTreeValue::check_new_from_diff(diff)
     }
 
     pub(super) fn new_from_diff(opid: amp::OpId, diff: amp::Diff) -> MultiGrapheme {
-        let winning_value = match diff {
-            amp::Diff::Value(amp::ScalarValue::Str(s)) => s,
-            _ => unreachable!("insert non text in text object"),
-        };
-        MultiGrapheme {
-            winning_value: (opid, winning_value),
-            conflicts: HashMap::new(),
+        match diff {
+            amp::Diff::Value(amp::ScalarValue::Str(s)) => MultiGrapheme::Grapheme {
+                winning_value: (opid, s),
+                conflicts: HashMap::new(),
+            },
+            diff => MultiGrapheme::Embedded(MultiValue::new_from_diff(opid, diff)),
         }
     }
 
@@ -398,22 +492,11 @@ impl MultiGrapheme {
     where
         I: Iterator<Item = (&'a amp::OpId, &'b amp::Diff)>,
     {
-        for (_opid, subdiff) in diff {
-            match subdiff {
-                amp::Diff::Value(amp::ScalarValue::Str(s)) => {
-                    if s.graphemes(true).count() != 1 {
-                        return Err(error::InvalidPatch::InsertNonTextInTextObject {
-                            object_id: parent_object_id.clone(),
-                            diff: subdiff.clone(),
-                        });
-                    }
-                }
-                _ => {
-                    return Err(error::InvalidPatch::InsertNonTextInTextObject {
-                        object_id: parent_object_id.clone(),
-                        diff: subdiff.clone(),
-                    });
-                }
+        for (opid, subdiff) in diff {
+            Self::check_grapheme(subdiff, parent_object_id)?;
+            match self {
+                MultiGrapheme::Grapheme { .. } => StateTreeValue::check_new_from_diff(subdiff)?,
+                MultiGrapheme::Embedded(mv) => mv.check_diff(opid, subdiff)?,
             }
         }
         Ok(())
@@ -428,94 +511,186 @@ impl MultiGrapheme {
         I: Iterator<Item = (amp::OpId, amp::Diff)>,
     {
         for (opid, subdiff) in diff {
+            if let MultiGrapheme::Embedded(mv) = self {
+                mv.apply_diff(opid, subdiff);
+                continue;
+            }
             match subdiff {
                 amp::Diff::Value(amp::ScalarValue::Str(s)) => {
                     self.update(&opid, s);
                 }
-                _ => unreachable!("insert non text in text object"),
+                subdiff => {
+                    let mut mv = self.to_multivalue();
+                    mv.apply_diff(opid, subdiff);
+                    *self = MultiGrapheme::Embedded(mv);
+                }
             }
         }
     }
 
     fn update(&mut self, key: &amp::OpId, value: SmolStr) {
-        match key.cmp(&self.winning_value.0) {
+        let (winning_value, conflicts) = match self {
+            MultiGrapheme::Grapheme {
+                winning_value,
+                conflicts,
+            } => (winning_value, conflicts),
+            MultiGrapheme::Embedded(_) => unreachable!("grapheme update of an embedded value"),
+        };
+        match key.cmp(&winning_value.0) {
             Ordering::Equal => {
-                self.winning_value.1 = value;
+                winning_value.1 = value;
             }
             Ordering::Greater => {
-                self.conflicts
-                    .insert(self.winning_value.0.clone(), self.winning_value.1.clone());
-                self.winning_value.0 = key.clone();
-                self.winning_value.1 = value;
+                conflicts.insert(winning_value.0.clone(), winning_value.1.clone());
+                winning_value.0 = key.clone();
+                winning_value.1 = value;
             }
             Ordering::Less => {
-                self.conflicts.insert(key.clone(), value);
+                conflicts.insert(key.clone(), value);
             }
         }
     }
 
-    pub(crate) fn default_grapheme(&self) -> &SmolStr {
-        &self.winning_value.1
+    /// The values of this element with graphemes as string primitives
+    fn to_multivalue(&self) -> MultiValue {
+        match self {
+            MultiGrapheme::Grapheme {
+                winning_value,
+                conflicts,
+            } => {
+                let leaf = |s: &SmolStr| StateTreeValue::Leaf(Primitive::Str(s.clone()));
+                MultiValue {
+                    winning_value: (winning_value.0.clone(), leaf(&winning_value.1)),
+                    conflicts: conflicts
+                        .iter()
+                        .map(|(opid, s)| (opid.clone(), leaf(s)))
+                        .collect(),
+                }
+            }
+            MultiGrapheme::Embedded(mv) => mv.clone(),
+        }
     }
 
-    pub fn default_opid(&self) -> &amp::OpId {
-        &self.winning_value.0
+    /// The winning grapheme, or `None` if the winning value is embedded
+    pub(crate) fn default_grapheme(&self) -> Option<&SmolStr> {
+        match self {
+            MultiGrapheme::Grapheme { winning_value, .. } => Some(&winning_value.1),
+            MultiGrapheme::Embedded(mv) => match mv.default_statetree_value() {
+                StateTreeValue::Leaf(Primitive::Str(s)) => Some(s),
+                _ => None,
+            },
+        }
     }
 
-    fn iter(&self) -> impl std::iter::Iterator<Item = (&amp::OpId, &SmolStr)> {
-        std::iter::once((&(self.winning_value).0, &(self.winning_value.1)))
-            .chain(self.conflicts.iter())
+    pub(crate) fn default_element(&self) -> TextElement {
+        match self {
+            MultiGrapheme::Grapheme { winning_value, .. } => {
+                TextElement::Grapheme(winning_value.1.clone())
+            }
+            MultiGrapheme::Embedded(mv) => TextElement::from(mv.default_value()),
+        }
+    }
+
+    pub(crate) fn default_value(&self) -> Value {
+        match self {
+            MultiGrapheme::Grapheme { winning_value, .. } => {
+                Value::Primitive(Primitive::Str(winning_value.1.clone()))
+            }
+            MultiGrapheme::Embedded(mv) => mv.default_value(),
+        }
+    }
+
+    pub fn default_opid(&self) -> &amp::OpId {
+        match self {
+            MultiGrapheme::Grapheme { winning_value, .. } => &winning_value.0,
+            MultiGrapheme::Embedded(mv) => &mv.winning_value.0,
+        }
     }
 
     pub(super) fn realise_values(&self) -> std::collections::HashMap<amp::OpId, Value> {
-        self.iter()
-            .map(|(opid, v)| (opid.clone(), Value::Primitive(Primitive::Str(v.to_owned()))))
-            .collect()
+        match self {
+            MultiGrapheme::Grapheme {
+                winning_value,
+                conflicts,
+            } => std::iter::once((&winning_value.0, &winning_value.1))
+                .chain(conflicts.iter())
+                .map(|(opid, v)| (opid.clone(), Value::Primitive(Primitive::Str(v.to_owned()))))
+                .collect(),
+            MultiGrapheme::Embedded(mv) => mv.realise_values(),
+        }
     }
 
     pub(super) fn only_for_opid(&self, opid: amp::OpId) -> Option<MultiGrapheme> {
-        if opid == self.winning_value.0 {
-            Some(MultiGrapheme {
-                winning_value: self.winning_value.clone(),
-                conflicts: HashMap::new(),
-            })
-        } else {
-            self.conflicts.get(&opid).map(|value| MultiGrapheme {
-                winning_value: (opid, value.clone()),
-                conflicts: HashMap::new(),
-            })
+        match self {
+            MultiGrapheme::Grapheme {
+                winning_value,
+                conflicts,
+            } => {
+                if opid == winning_value.0 {
+                    Some(MultiGrapheme::new_from_grapheme_cluster(
+                        opid,
+                        winning_value.1.clone(),
+                    ))
+                } else {
+                    conflicts
+                        .get(&opid)
+                        .map(|value| MultiGrapheme::new_from_grapheme_cluster(opid, value.clone()))
+                }
+            }
+            MultiGrapheme::Embedded(mv) => mv.only_for_opid(opid).map(Self::from_multivalue),
         }
     }
 
     pub(super) fn add_values_from(&mut self, other: MultiGrapheme) {
-        for (opid, value) in other.iter() {
-            match opid.cmp(&self.winning_value.0) {
-                Ordering::Greater => {
-                    let mut temp = (opid.clone(), value.to_owned());
-                    std::mem::swap(&mut temp, &mut self.winning_value);
-                    self.conflicts.insert(temp.0, temp.1);
+        match other {
+            MultiGrapheme::Grapheme {
+                winning_value,
+                conflicts,
+            } if matches!(self, MultiGrapheme::Grapheme { .. }) => {
+                for (opid, value) in std::iter::once(winning_value).chain(conflicts) {
+                    self.update(&opid, value);
                 }
-                Ordering::Less => {
-                    self.conflicts.insert(opid.clone(), value.to_owned());
-                }
-                Ordering::Equal => {}
+            }
+            other => {
+                let mut mv = self.to_multivalue();
+                mv.add_values_from(other.to_multivalue());
+                *self = Self::from_multivalue(mv);
             }
         }
     }
 
-    pub(crate) fn resolve_path(&self, path: Vec<PathElement>) -> Option<ResolvedPath> {
-        if path.is_empty() {
-            Some(ResolvedPath::new_character(self))
-        } else {
-            None
+    /// Resolve `path` inside the element with ID `elem_id` of the text `parent_object_id`
+    pub(crate) fn resolve_path(
+        &self,
+        path: Vec<PathElement>,
+        parent_object_id: amp::ObjectId,
+        elem_id: amp::ElementId,
+    ) -> Option<ResolvedPath> {
+        match self {
+            MultiGrapheme::Grapheme { .. } if path.is_empty() => {
+                Some(ResolvedPath::new_character(self))
+            }
+            MultiGrapheme::Grapheme { .. } => None,
+            MultiGrapheme::Embedded(mv) => {
+                mv.resolve_path(path, parent_object_id, amp::Key::Seq(elem_id))
+            }
         }
     }
 
-    pub(crate) fn resolve_path_mut(&mut self, path: Vec<PathElement>) -> Option<ResolvedPathMut> {
-        if path.is_empty() {
-            Some(ResolvedPathMut::new_character(self))
-        } else {
-            None
+    pub(crate) fn resolve_path_mut(
+        &mut self,
+        path: Vec<PathElement>,
+        parent_object_id: amp::ObjectId,
+        elem_id: amp::ElementId,
+    ) -> Option<ResolvedPathMut> {
+        match self {
+            MultiGrapheme::Grapheme { .. } if path.is_empty() => {
+                Some(ResolvedPathMut::new_character(self))
+            }
+            MultiGrapheme::Grapheme { .. } => None,
+            MultiGrapheme::Embedded(mv) => {
+                mv.resolve_path_mut(path, parent_object_id, amp::Key::Seq(elem_id))
+            }
         }
     }
 }
@@ -544,7 +719,8 @@ where
             Value::Map(props) => self.new_map_or_table(props, amp::MapType::Map),
             Value::Table(props) => self.new_map_or_table(props, amp::MapType::Table),
             Value::List(values) => self.new_list(values),
-            Value::Text(graphemes) => self.new_text(graphemes),
+            Value::Text(elements) => self.new_text(elements),
+            Value::Set(members) => self.new_set(members),
             Value::Primitive(p) => self.new_primitive(p),
         }
     }
@@ -568,6 +744,9 @@ where
         let mut current_max_op = self.start_op;
         let mut cursors = Cursors::new();
         let mut result_props: HashMap<SmolStr, MultiValue> = HashMap::with_capacity(props.len());
+        // Create the props in key order so that the same value always produces the same ops
+        let mut props: Vec<_> = props.into_iter().collect();
+        props.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
         for (prop, value) in props {
             let context = NewValueContext {
                 actor: self.actor,
@@ -652,7 +831,7 @@ where
         }
     }
 
-    fn new_text(self, graphemes: Vec<SmolStr>) -> NewValue {
+    fn new_text(self, elements: Vec<TextElement>) -> NewValue {
         let make_text_opid = self.actor.op_id_at(self.start_op);
         let make_op = amp::Op {
             action: amp::OpType::Make(amp::ObjType::Text),
@@ -662,27 +841,47 @@ where
             pred: self.pred,
         };
         // for each value we add at least one op
-        let mut ops = Vec::with_capacity(graphemes.len() + 1);
+        let mut ops = Vec::with_capacity(elements.len() + 1);
         ops.push(make_op);
         let mut current_max_op = self.start_op;
+        let mut cursors = Cursors::new();
         let mut last_elemid = amp::ElementId::Head;
-        let mut multigraphemes: Vec<MultiGrapheme> = Vec::with_capacity(graphemes.len());
-        for grapheme in graphemes.iter() {
-            current_max_op += 1;
-            let opid = self.actor.op_id_at(current_max_op);
-            let op = amp::Op {
-                action: amp::OpType::Set(amp::ScalarValue::Str(grapheme.clone())),
-                obj: make_text_opid.clone().into(),
-                key: amp::Key::Seq(last_elemid),
-                insert: true,
-                pred: SortedVec::new(),
-            };
-            multigraphemes.push(MultiGrapheme::new_from_grapheme_cluster(
-                opid.clone(),
-                grapheme.clone(),
-            ));
-            ops.push(op);
-            last_elemid = opid.clone().into();
+        let mut multigraphemes: Vec<MultiGrapheme> = Vec::with_capacity(elements.len());
+        for element in elements {
+            let opid = self.actor.op_id_at(current_max_op + 1);
+            match element {
+                TextElement::Grapheme(grapheme) => {
+                    current_max_op += 1;
+                    ops.push(amp::Op {
+                        action: amp::OpType::Set(amp::ScalarValue::Str(grapheme.clone())),
+                        obj: make_text_opid.clone().into(),
+                        key: amp::Key::Seq(last_elemid),
+                        insert: true,
+                        pred: SortedVec::new(),
+                    });
+                    multigraphemes.push(MultiGrapheme::new_from_grapheme_cluster(
+                        opid.clone(),
+                        grapheme,
+                    ));
+                }
+                TextElement::Embedded(value) => {
+                    let context = NewValueContext {
+                        start_op: current_max_op + 1,
+                        pred: SortedVec::new(),
+                        insert: true,
+                        key: amp::Key::Seq(last_elemid),
+                        actor: self.actor,
+                        parent_obj: make_text_opid.clone(),
+                    };
+                    let next_value = context.create(value);
+                    current_max_op = next_value.max_op;
+                    let (multivalue, new_ops, new_cursors) = next_value.finish();
+                    cursors.extend(new_cursors);
+                    ops.extend(new_ops);
+                    multigraphemes.push(MultiGrapheme::from_multivalue(multivalue));
+                }
+            }
+            last_elemid = opid.into();
         }
         let seq = DiffableSequence::new_from(multigraphemes);
         let text = StateTreeComposite::Text(StateTreeText {
@@ -694,6 +893,52 @@ where
             value,
             opid: make_text_opid,
             ops,
+            new_cursors: cursors,
+            max_op: current_max_op,
+        }
+    }
+
+    fn new_set(self, members: Vec<Primitive>) -> NewValue {
+        let make_set_opid = self.actor.op_id_at(self.start_op);
+        let make_op = amp::Op {
+            action: amp::OpType::Make(amp::ObjType::Set),
+            obj: self.parent_obj.into(),
+            key: self.key.clone(),
+            insert: self.insert,
+            pred: self.pred,
+        };
+        let mut ops = Vec::with_capacity(members.len() + 1);
+        ops.push(make_op);
+        let mut current_max_op = self.start_op;
+        let mut result_members: HashMap<SmolStr, MultiValue> = HashMap::new();
+        for member in members {
+            let value = amp::ScalarValue::from(&member);
+            let key = match value.set_key() {
+                Some(key) if !result_members.contains_key(&key) => key,
+                _ => continue,
+            };
+            current_max_op += 1;
+            let opid = self.actor.op_id_at(current_max_op);
+            ops.push(amp::Op {
+                action: amp::OpType::Set(value),
+                obj: make_set_opid.clone().into(),
+                key: amp::Key::Map(key.clone()),
+                insert: false,
+                pred: SortedVec::new(),
+            });
+            result_members.insert(
+                key,
+                MultiValue::from_statetree_value(StateTreeValue::Leaf(member), opid),
+            );
+        }
+        let set = StateTreeComposite::Set(StateTreeSet {
+            object_id: make_set_opid.clone().into(),
+            members: result_members,
+        });
+        NewValue {
+            value: StateTreeValue::Composite(set),
+            opid: make_set_opid,
+            ops,
             new_cursors: Cursors::new(),
             max_op: current_max_op,
         }
@@ -710,18 +955,7 @@ where
             }),
             _ => Cursors::new(),
         };
-        let value = match &primitive {
-            Primitive::Bytes(b) => amp::ScalarValue::Bytes(b.clone()),
-            Primitive::Str(s) => amp::ScalarValue::Str(s.clone()),
-            Primitive::Int(i) => amp::ScalarValue::Int(*i),
-            Primitive::Uint(u) => amp::ScalarValue::Uint(*u),
-            Primitive::F64(f) => amp::ScalarValue::F64(*f),
-            Primitive::Counter(i) => amp::ScalarValue::Counter(*i),
-            Primitive::Timestamp(t) => amp::ScalarValue::Timestamp(*t),
-            Primitive::Boolean(b) => amp::ScalarValue::Boolean(*b),
-            Primitive::Cursor(c) => amp::ScalarValue::Cursor(c.elem_opid.clone()),
-            Primitive::Null => amp::ScalarValue::Null,
-        };
+        let value = amp::ScalarValue::from(&primitive);
         let opid = self.actor.op_id_at(self.start_op);
         NewValue {
             value: StateTreeValue::Leaf(primitive),
@@ -1,19 +1,50 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+};
 
-use super::{MultiGrapheme, MultiValue, ResolvedPathMut, StateTree};
+use automerge_protocol as amp;
+use smol_str::SmolStr;
+
+use super::{MultiGrapheme, MultiValue, ResolvedPathMut, StateTree, StateTreeValue};
 use crate::{path::PathElement, Path};
 
 /// Contains the required data to undo an operation on the state tree.
 #[derive(Clone, Debug)]
 pub(crate) enum LocalOperationForRollback {
-    Set { old: Option<MultiValue> },
-    SetList { old: MultiValue },
-    SetText { old: MultiGrapheme },
-    Delete { old: MultiValue },
-    DeleteText { old: MultiGrapheme },
+    Set {
+        old: Option<MultiValue>,
+    },
+    SetList {
+        old: MultiValue,
+    },
+    SetText {
+        old: MultiGrapheme,
+    },
+    Delete {
+        old: MultiValue,
+    },
+    DeleteText {
+        old: MultiGrapheme,
+    },
     Insert,
-    InsertMany { count: usize },
-    Increment { by: i64 },
+    InsertMany {
+        count: usize,
+    },
+    Increment {
+        by: i64,
+    },
+    AddToSet {
+        key: SmolStr,
+        old: Option<MultiValue>,
+    },
+    RemoveFromSet {
+        key: SmolStr,
+        old: MultiValue,
+    },
+    ResolveConflict {
+        losers: HashMap<amp::OpId, StateTreeValue>,
+    },
 }
 
 /// Keeps track of the changes made to a state tree and allows rolling back changes.
@@ -75,6 +106,7 @@ impl OptimisticStateTree {
                                 | (PathElement::Key(_), ResolvedPathMut::Text(_))
                                 | (PathElement::Key(_), ResolvedPathMut::Character(_))
                                 | (PathElement::Key(_), ResolvedPathMut::Counter(_))
+                                | (PathElement::Key(_), ResolvedPathMut::Set(_))
                                 | (PathElement::Key(_), ResolvedPathMut::Primitive(_)) => {
                                     unreachable!("found non object with key")
                                 }
@@ -85,6 +117,7 @@ impl OptimisticStateTree {
                                 | (PathElement::Index(_), ResolvedPathMut::Table(_))
                                 | (PathElement::Index(_), ResolvedPathMut::Character(_))
                                 | (PathElement::Index(_), ResolvedPathMut::Counter(_))
+                                | (PathElement::Index(_), ResolvedPathMut::Set(_))
                                 | (PathElement::Index(_), ResolvedPathMut::Primitive(_)) => {
                                     unreachable!("found index element while rolling back a set")
                                 }
@@ -108,6 +141,7 @@ impl OptimisticStateTree {
                                 | (PathElement::Index(_), ResolvedPathMut::Table(_))
                                 | (PathElement::Index(_), ResolvedPathMut::Character(_))
                                 | (PathElement::Index(_), ResolvedPathMut::Counter(_))
+                                | (PathElement::Index(_), ResolvedPathMut::Set(_))
                                 | (PathElement::Index(_), ResolvedPathMut::Primitive(_)) => {
                                     unreachable!("found non list with index")
                                 }
@@ -131,6 +165,7 @@ impl OptimisticStateTree {
                                 | (PathElement::Index(_), ResolvedPathMut::Table(_))
                                 | (PathElement::Index(_), ResolvedPathMut::Character(_))
                                 | (PathElement::Index(_), ResolvedPathMut::Counter(_))
+                                | (PathElement::Index(_), ResolvedPathMut::Set(_))
                                 | (PathElement::Index(_), ResolvedPathMut::Primitive(_)) => {
                                     unreachable!("found non text with index")
                                 }
@@ -155,6 +190,7 @@ impl OptimisticStateTree {
                                 | (PathElement::Key(_), ResolvedPathMut::Text(_))
                                 | (PathElement::Key(_), ResolvedPathMut::Character(_))
                                 | (PathElement::Key(_), ResolvedPathMut::Counter(_))
+                                | (PathElement::Key(_), ResolvedPathMut::Set(_))
                                 | (PathElement::Key(_), ResolvedPathMut::Primitive(_)) => {
                                     unreachable!("found non object with key")
                                 }
@@ -167,6 +203,7 @@ impl OptimisticStateTree {
                                 | (PathElement::Index(_), ResolvedPathMut::Table(_))
                                 | (PathElement::Index(_), ResolvedPathMut::Character(_))
                                 | (PathElement::Index(_), ResolvedPathMut::Counter(_))
+                                | (PathElement::Index(_), ResolvedPathMut::Set(_))
                                 | (PathElement::Index(_), ResolvedPathMut::Primitive(_)) => {
                                     unreachable!("found non list with index")
                                 }
@@ -185,6 +222,7 @@ impl OptimisticStateTree {
                                 | (PathElement::Key(_), ResolvedPathMut::Text(_))
                                 | (PathElement::Key(_), ResolvedPathMut::Character(_))
                                 | (PathElement::Key(_), ResolvedPathMut::Counter(_))
+                                | (PathElement::Key(_), ResolvedPathMut::Set(_))
                                 | (PathElement::Key(_), ResolvedPathMut::Primitive(_)) => {
                                     unreachable!("found key for SetText")
                                 }
@@ -197,6 +235,7 @@ impl OptimisticStateTree {
                                 | (PathElement::Index(_), ResolvedPathMut::Table(_))
                                 | (PathElement::Index(_), ResolvedPathMut::Character(_))
                                 | (PathElement::Index(_), ResolvedPathMut::Counter(_))
+                                | (PathElement::Index(_), ResolvedPathMut::Set(_))
                                 | (PathElement::Index(_), ResolvedPathMut::Primitive(_)) => {
                                     unreachable!("found non text with index")
                                 }
@@ -219,6 +258,7 @@ impl OptimisticStateTree {
                                 | ResolvedPathMut::Table(_)
                                 | ResolvedPathMut::Character(_)
                                 | ResolvedPathMut::Counter(_)
+                                | ResolvedPathMut::Set(_)
                                 | ResolvedPathMut::Primitive(_) => {
                                     unreachable!("Found non list object in rollback insert")
                                 }
@@ -245,6 +285,7 @@ impl OptimisticStateTree {
                                 | ResolvedPathMut::Table(_)
                                 | ResolvedPathMut::Character(_)
                                 | ResolvedPathMut::Counter(_)
+                                | ResolvedPathMut::Set(_)
                                 | ResolvedPathMut::Primitive(_) => {
                                     unreachable!("Found non list object in rollback insert")
                                 }
@@ -261,6 +302,27 @@ impl OptimisticStateTree {
                         }
                     }
                 }
+                LocalOperationForRollback::AddToSet { key, old } => {
+                    if let Some(ResolvedPathMut::Set(mut set)) = self.state.resolve_path_mut(&path)
+                    {
+                        set.rollback_add(key, old)
+                    }
+                }
+                LocalOperationForRollback::RemoveFromSet { key, old } => {
+                    if let Some(ResolvedPathMut::Set(mut set)) = self.state.resolve_path_mut(&path)
+                    {
+                        set.rollback_remove(key, old)
+                    }
+                }
+                LocalOperationForRollback::ResolveConflict { losers } => {
+                    if let Some(value) = self
+                        .state
+                        .resolve_path_mut(&path)
+                        .and_then(ResolvedPathMut::into_multivalue)
+                    {
+                        value.rollback_resolve_conflict(losers)
+                    }
+                }
             }
         }
     }
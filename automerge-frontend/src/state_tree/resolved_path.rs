@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     convert::TryInto,
     mem::{discriminant, Discriminant},
     num::NonZeroU32,
@@ -12,7 +13,7 @@ use super::{
     random_op_id, LocalOperationResult, MultiGrapheme, MultiValue, NewValueRequest, StateTree,
     StateTreeComposite, StateTreeValue,
 };
-use crate::{error, Cursor, Primitive, Value};
+use crate::{error, value_ref::TextUnit, Cursor, Primitive, Value};
 
 pub enum ResolvedPath<'a> {
     Root(ResolvedRoot<'a>),
@@ -20,6 +21,7 @@ pub enum ResolvedPath<'a> {
     Table(ResolvedTable<'a>),
     List(ResolvedList<'a>),
     Text(ResolvedText<'a>),
+    Set(ResolvedSet<'a>),
     Character(ResolvedChar<'a>),
     Counter(ResolvedCounter<'a>),
     Primitive(ResolvedPrimitive<'a>),
@@ -41,6 +43,9 @@ impl<'a> std::fmt::Debug for ResolvedPath<'a> {
             ResolvedPath::Text(texttarget) => {
                 write!(f, "text {:?}", texttarget.object_id)
             }
+            ResolvedPath::Set(settarget) => {
+                write!(f, "set {:?}", settarget.object_id)
+            }
             ResolvedPath::Counter(countertarget) => write!(
                 f,
                 "counter {0}:{1:?}",
@@ -85,6 +90,13 @@ impl<'a> ResolvedPath<'a> {
         })
     }
 
+    pub(super) fn new_set(value: &'a MultiValue, object_id: amp::ObjectId) -> ResolvedPath<'a> {
+        ResolvedPath::Set(ResolvedSet {
+            multivalue: value,
+            object_id,
+        })
+    }
+
     pub(super) fn new_counter(
         object_id: amp::ObjectId,
         key: amp::Key,
@@ -112,11 +124,10 @@ impl<'a> ResolvedPath<'a> {
             ResolvedPath::Table(tabletarget) => tabletarget.multivalue.default_value(),
             ResolvedPath::List(listtarget) => listtarget.multivalue.default_value(),
             ResolvedPath::Text(texttarget) => texttarget.multivalue.default_value(),
+            ResolvedPath::Set(settarget) => settarget.multivalue.default_value(),
             ResolvedPath::Counter(countertarget) => countertarget.multivalue.default_value(),
             ResolvedPath::Primitive(p) => p.multivalue.default_value(),
-            ResolvedPath::Character(ctarget) => Value::Primitive(Primitive::Str(
-                ctarget.multivalue.default_grapheme().clone(),
-            )),
+            ResolvedPath::Character(ctarget) => ctarget.multivalue.default_value(),
         }
     }
 
@@ -131,6 +142,7 @@ impl<'a> ResolvedPath<'a> {
             ResolvedPath::Table(tabletarget) => tabletarget.multivalue.realise_values(),
             ResolvedPath::List(listtarget) => listtarget.multivalue.realise_values(),
             ResolvedPath::Text(texttarget) => texttarget.multivalue.realise_values(),
+            ResolvedPath::Set(settarget) => settarget.multivalue.realise_values(),
             ResolvedPath::Counter(countertarget) => countertarget.multivalue.realise_values(),
             ResolvedPath::Primitive(p) => p.multivalue.realise_values(),
             ResolvedPath::Character(ctarget) => ctarget.multivalue.realise_values(),
@@ -144,6 +156,7 @@ impl<'a> ResolvedPath<'a> {
             ResolvedPath::Table(tabletarget) => Some(tabletarget.object_id.clone()),
             ResolvedPath::List(listtarget) => Some(listtarget.object_id.clone()),
             ResolvedPath::Text(texttarget) => Some(texttarget.object_id.clone()),
+            ResolvedPath::Set(settarget) => Some(settarget.object_id.clone()),
             ResolvedPath::Counter(_) | ResolvedPath::Primitive(_) | ResolvedPath::Character(_) => {
                 None
             }
@@ -157,6 +170,7 @@ pub enum ResolvedPathMut<'a> {
     Table(ResolvedTableMut<'a>),
     List(ResolvedListMut<'a>),
     Text(ResolvedTextMut<'a>),
+    Set(ResolvedSetMut<'a>),
     Character(ResolvedCharMut<'a>),
     Counter(ResolvedCounterMut<'a>),
     Primitive(ResolvedPrimitiveMut<'a>),
@@ -178,6 +192,9 @@ impl<'a> std::fmt::Debug for ResolvedPathMut<'a> {
             ResolvedPathMut::Text(texttarget) => {
                 write!(f, "text {:?}", texttarget.object_id)
             }
+            ResolvedPathMut::Set(settarget) => {
+                write!(f, "set {:?}", settarget.object_id)
+            }
             ResolvedPathMut::Counter(countertarget) => write!(
                 f,
                 "counter {0}:{1:?}",
@@ -234,6 +251,16 @@ impl<'a> ResolvedPathMut<'a> {
         })
     }
 
+    pub(super) fn new_set(
+        value: &'a mut MultiValue,
+        object_id: amp::ObjectId,
+    ) -> ResolvedPathMut<'a> {
+        ResolvedPathMut::Set(ResolvedSetMut {
+            multivalue: value,
+            object_id,
+        })
+    }
+
     pub(super) fn new_counter(
         object_id: amp::ObjectId,
         key: amp::Key,
@@ -253,6 +280,20 @@ impl<'a> ResolvedPathMut<'a> {
     pub(super) fn new_character(c: &'a mut MultiGrapheme) -> ResolvedPathMut<'a> {
         ResolvedPathMut::Character(ResolvedCharMut { multivalue: c })
     }
+
+    /// The values at this path, `None` for the root and for characters in text
+    pub(super) fn into_multivalue(self) -> Option<&'a mut MultiValue> {
+        match self {
+            ResolvedPathMut::Map(maptarget) => Some(maptarget.multivalue),
+            ResolvedPathMut::Table(tabletarget) => Some(tabletarget.multivalue),
+            ResolvedPathMut::List(listtarget) => Some(listtarget.multivalue),
+            ResolvedPathMut::Text(texttarget) => Some(texttarget.multivalue),
+            ResolvedPathMut::Set(settarget) => Some(settarget.multivalue),
+            ResolvedPathMut::Counter(countertarget) => Some(countertarget.multivalue),
+            ResolvedPathMut::Primitive(p) => Some(p.multivalue),
+            ResolvedPathMut::Root(_) | ResolvedPathMut::Character(_) => None,
+        }
+    }
 }
 
 pub(crate) struct SetOrInsertPayload<'a, T> {
@@ -261,6 +302,28 @@ pub(crate) struct SetOrInsertPayload<'a, T> {
     pub value: T,
 }
 
+/// The op which deletes `losers`, the values which were in conflict with the value left at `key`
+fn delete_conflicts(
+    obj: amp::ObjectId,
+    key: amp::Key,
+    losers: &HashMap<amp::OpId, StateTreeValue>,
+) -> LocalOperationResult {
+    if losers.is_empty() {
+        return LocalOperationResult {
+            new_ops: Vec::new(),
+        };
+    }
+    LocalOperationResult {
+        new_ops: vec![amp::Op {
+            action: amp::OpType::Del(NonZeroU32::new(1).unwrap()),
+            obj,
+            key,
+            insert: false,
+            pred: losers.keys().cloned().collect(),
+        }],
+    }
+}
+
 pub struct ResolvedRoot<'a> {
     pub(super) root: &'a StateTree,
 }
@@ -310,6 +373,16 @@ impl<'a> ResolvedRootMut<'a> {
         self.root.remove(key).map(|old| (old, op_result))
     }
 
+    pub(crate) fn resolve_conflict(
+        &mut self,
+        key: &str,
+        opid: &amp::OpId,
+    ) -> Option<(HashMap<amp::OpId, StateTreeValue>, LocalOperationResult)> {
+        let losers = self.root.root_props.get_mut(key)?.resolve_conflict(opid)?;
+        let op_result = delete_conflicts(amp::ObjectId::Root, key.into(), &losers);
+        Some((losers, op_result))
+    }
+
     pub(crate) fn rollback_set(&mut self, key: SmolStr, value: Option<MultiValue>) {
         match value {
             Some(old) => {
@@ -416,6 +489,20 @@ impl<'a> ResolvedMapMut<'a> {
         state_tree_map.props.remove(key).map(|old| (old, op_result))
     }
 
+    pub(crate) fn resolve_conflict(
+        &mut self,
+        key: &str,
+        opid: &amp::OpId,
+    ) -> Option<(HashMap<amp::OpId, StateTreeValue>, LocalOperationResult)> {
+        let state_tree_map = match self.multivalue.default_statetree_value_mut() {
+            StateTreeValue::Composite(StateTreeComposite::Map(map)) => map,
+            _ => unreachable!(),
+        };
+        let losers = state_tree_map.props.get_mut(key)?.resolve_conflict(opid)?;
+        let op_result = delete_conflicts(state_tree_map.object_id.clone(), key.into(), &losers);
+        Some((losers, op_result))
+    }
+
     pub(crate) fn rollback_set(&mut self, key: SmolStr, value: Option<MultiValue>) {
         let state_tree_map = match self.multivalue.default_statetree_value_mut() {
             StateTreeValue::Composite(StateTreeComposite::Map(map)) => map,
@@ -494,6 +581,23 @@ impl<'a> ResolvedTableMut<'a> {
             .map(|old| (old, op_result))
     }
 
+    pub(crate) fn resolve_conflict(
+        &mut self,
+        key: &str,
+        opid: &amp::OpId,
+    ) -> Option<(HashMap<amp::OpId, StateTreeValue>, LocalOperationResult)> {
+        let state_tree_table = match self.multivalue.default_statetree_value_mut() {
+            StateTreeValue::Composite(StateTreeComposite::Table(map)) => map,
+            _ => unreachable!(),
+        };
+        let losers = state_tree_table
+            .props
+            .get_mut(key)?
+            .resolve_conflict(opid)?;
+        let op_result = delete_conflicts(state_tree_table.object_id.clone(), key.into(), &losers);
+        Some((losers, op_result))
+    }
+
     pub(crate) fn rollback_set(&mut self, key: SmolStr, value: Option<MultiValue>) {
         let state_tree_map = match self.multivalue.default_statetree_value_mut() {
             StateTreeValue::Composite(StateTreeComposite::Table(map)) => map,
@@ -518,6 +622,86 @@ impl<'a> ResolvedTableMut<'a> {
     }
 }
 
+pub struct ResolvedSet<'a> {
+    object_id: amp::ObjectId,
+    pub(super) multivalue: &'a MultiValue,
+}
+
+pub struct ResolvedSetMut<'a> {
+    object_id: amp::ObjectId,
+    pub(super) multivalue: &'a mut MultiValue,
+}
+
+impl<'a> ResolvedSetMut<'a> {
+    /// Add `member` to the set, overwriting any adds of it this set has seen. Returns the key of
+    /// the member and the state it had before.
+    pub(crate) fn add(
+        &mut self,
+        key: SmolStr,
+        payload: SetOrInsertPayload<Primitive>,
+    ) -> (Option<MultiValue>, LocalOperationResult) {
+        let state_tree_set = match self.multivalue.default_statetree_value_mut() {
+            StateTreeValue::Composite(StateTreeComposite::Set(set)) => set,
+            _ => unreachable!(),
+        };
+        let opid = amp::OpId::new(payload.start_op, payload.actor);
+        let op = amp::Op {
+            action: amp::OpType::Set(amp::ScalarValue::from(&payload.value)),
+            obj: state_tree_set.object_id.clone(),
+            key: amp::Key::Map(key.clone()),
+            insert: false,
+            pred: state_tree_set.pred_for_key(&key),
+        };
+        let member = MultiValue::from_statetree_value(StateTreeValue::Leaf(payload.value), opid);
+        let old = state_tree_set.members.insert(key, member);
+        (old, LocalOperationResult { new_ops: vec![op] })
+    }
+
+    /// Remove the member stored under `key`, if there is one
+    pub(crate) fn remove(&mut self, key: &str) -> Option<(MultiValue, LocalOperationResult)> {
+        let state_tree_set = match self.multivalue.default_statetree_value_mut() {
+            StateTreeValue::Composite(StateTreeComposite::Set(set)) => set,
+            _ => unreachable!(),
+        };
+        let op_result = LocalOperationResult {
+            new_ops: vec![amp::Op {
+                action: amp::OpType::Del(NonZeroU32::new(1).unwrap()),
+                obj: state_tree_set.object_id.clone(),
+                key: key.into(),
+                insert: false,
+                pred: state_tree_set.pred_for_key(key),
+            }],
+        };
+        state_tree_set
+            .members
+            .remove(key)
+            .map(|old| (old, op_result))
+    }
+
+    pub(crate) fn rollback_add(&mut self, key: SmolStr, old: Option<MultiValue>) {
+        let state_tree_set = match self.multivalue.default_statetree_value_mut() {
+            StateTreeValue::Composite(StateTreeComposite::Set(set)) => set,
+            _ => unreachable!(),
+        };
+        match old {
+            Some(old) => {
+                state_tree_set.members.insert(key, old);
+            }
+            None => {
+                state_tree_set.members.remove(&key);
+            }
+        }
+    }
+
+    pub(crate) fn rollback_remove(&mut self, key: SmolStr, old: MultiValue) {
+        let state_tree_set = match self.multivalue.default_statetree_value_mut() {
+            StateTreeValue::Composite(StateTreeComposite::Set(set)) => set,
+            _ => unreachable!(),
+        };
+        state_tree_set.members.insert(key, old);
+    }
+}
+
 pub struct ResolvedText<'a> {
     object_id: amp::ObjectId,
     pub(super) multivalue: &'a MultiValue,
@@ -560,19 +744,21 @@ impl<'a> ResolvedTextMut<'a> {
         })
     }
 
+    /// Insert `payload`'s values at `index`. String values must be single grapheme clusters,
+    /// any other value is embedded in the text.
     pub(crate) fn insert_many<I>(
         &mut self,
         index: u32,
         payload: SetOrInsertPayload<I>,
     ) -> Result<LocalOperationResult, error::MissingIndexError>
     where
-        I: ExactSizeIterator<Item = SmolStr>,
+        I: ExactSizeIterator<Item = Value>,
     {
         let state_tree_text = match self.multivalue.default_statetree_value_mut() {
             StateTreeValue::Composite(StateTreeComposite::Text(text)) => text,
             _ => unreachable!(),
         };
-        let current_elemid = match index {
+        let mut last_elemid = match index {
             0 => amp::ElementId::Head,
             i => state_tree_text
                 .elem_at((i - 1).try_into().unwrap())?
@@ -580,34 +766,54 @@ impl<'a> ResolvedTextMut<'a> {
                 .into(),
         };
         let mut values = Vec::with_capacity(payload.value.len());
-        let mut chars: Vec<amp::ScalarValue> = Vec::with_capacity(payload.value.len());
-        for (i, c) in payload.value.enumerate() {
-            let insert_op = amp::OpId::new(payload.start_op + i as u64, payload.actor);
-            chars.push(amp::ScalarValue::Str(c.clone()));
-            let c = MultiGrapheme::new_from_grapheme_cluster(insert_op, c);
-            values.push(c)
+        let mut ops = Vec::with_capacity(payload.value.len());
+        let mut op_num = payload.start_op;
+        for value in payload.value {
+            let elem_opid = amp::OpId::new(op_num, payload.actor);
+            match value {
+                Value::Primitive(Primitive::Str(c)) => {
+                    ops.push(amp::Op {
+                        action: amp::OpType::Set(amp::ScalarValue::Str(c.clone())),
+                        obj: state_tree_text.object_id.clone(),
+                        key: last_elemid.into(),
+                        insert: true,
+                        pred: SortedVec::new(),
+                    });
+                    values.push(MultiGrapheme::new_from_grapheme_cluster(
+                        elem_opid.clone(),
+                        c,
+                    ));
+                    op_num += 1;
+                }
+                value => {
+                    let newvalue = MultiValue::new_from_value_2(NewValueRequest {
+                        actor: payload.actor,
+                        start_op: op_num,
+                        value,
+                        parent_obj: &state_tree_text.object_id,
+                        key: last_elemid.into(),
+                        insert: true,
+                        pred: SortedVec::new(),
+                    });
+                    op_num = newvalue.max_op() + 1;
+                    let (multivalue, new_ops, _new_cursors) = newvalue.finish();
+                    values.push(MultiGrapheme::from_multivalue(multivalue));
+                    ops.extend(new_ops);
+                }
+            }
+            last_elemid = elem_opid.into();
         }
         state_tree_text.insert_many(index.try_into().unwrap(), values)?;
-        let action = match chars.len() {
-            1 => amp::OpType::Set(chars[0].clone()),
-            // unwrap is safe here b/c we know all the `ScalarValue`s are of type `Str`
-            _ => amp::OpType::MultiSet(chars.try_into().unwrap()),
-        };
         Ok(LocalOperationResult {
-            new_ops: vec![amp::Op {
-                action,
-                obj: state_tree_text.object_id.clone(),
-                key: current_elemid.into(),
-                insert: true,
-                pred: SortedVec::new(),
-            }],
+            new_ops: condense_insert_ops(ops),
         })
     }
 
+    /// Set the element at `index` to `payload`'s value, which is embedded if it is not a string
     pub(crate) fn set(
         &mut self,
         index: u32,
-        payload: SetOrInsertPayload<SmolStr>,
+        payload: SetOrInsertPayload<Value>,
     ) -> Result<(MultiGrapheme, LocalOperationResult), error::MissingIndexError> {
         let state_tree_text = match self.multivalue.default_statetree_value_mut() {
             StateTreeValue::Composite(StateTreeComposite::Text(text)) => text,
@@ -617,21 +823,34 @@ impl<'a> ResolvedTextMut<'a> {
         let (current_elemid, _) = state_tree_text.elem_at(index)?;
         let current_elemid = current_elemid.clone();
         let update_op = amp::OpId::new(payload.start_op, payload.actor);
-        let c = MultiGrapheme::new_from_grapheme_cluster(update_op, payload.value.clone());
         let pred = state_tree_text.pred_for_index(index as u32);
-        let old = state_tree_text.set(index, c)?;
-        Ok((
-            old,
-            LocalOperationResult {
-                new_ops: vec![amp::Op {
-                    action: amp::OpType::Set(amp::ScalarValue::Str(payload.value)),
+        let (c, new_ops) = match payload.value {
+            Value::Primitive(Primitive::Str(s)) => (
+                MultiGrapheme::new_from_grapheme_cluster(update_op, s.clone()),
+                vec![amp::Op {
+                    action: amp::OpType::Set(amp::ScalarValue::Str(s)),
                     obj: state_tree_text.object_id.clone(),
                     key: current_elemid.into(),
                     pred,
                     insert: false,
                 }],
-            },
-        ))
+            ),
+            value => {
+                let newvalue = MultiValue::new_from_value_2(NewValueRequest {
+                    actor: payload.actor,
+                    start_op: payload.start_op,
+                    value,
+                    parent_obj: &state_tree_text.object_id,
+                    key: current_elemid.into(),
+                    insert: false,
+                    pred,
+                });
+                let (multivalue, new_ops, _new_cursors) = newvalue.finish();
+                (MultiGrapheme::from_multivalue(multivalue), new_ops)
+            }
+        };
+        let old = state_tree_text.set(index, c)?;
+        Ok((old, LocalOperationResult { new_ops }))
     }
 
     pub(crate) fn remove(
@@ -692,6 +911,15 @@ impl<'a> ResolvedTextMut<'a> {
 }
 
 impl<'a> ResolvedText<'a> {
+    pub(crate) fn index_at_offset(&self, offset: usize, unit: TextUnit) -> Option<usize> {
+        match self.multivalue.default_statetree_value() {
+            StateTreeValue::Composite(StateTreeComposite::Text(text)) => {
+                text.index_at_offset(offset, unit)
+            }
+            _ => unreachable!(),
+        }
+    }
+
     pub(crate) fn get_cursor(&self, index: u32) -> Result<Cursor, error::MissingIndexError> {
         let state_tree_text = match self.multivalue.default_statetree_value() {
             StateTreeValue::Composite(StateTreeComposite::Text(text)) => text,
@@ -844,6 +1072,25 @@ impl<'a> ResolvedListMut<'a> {
         ))
     }
 
+    pub(crate) fn resolve_conflict(
+        &mut self,
+        index: u32,
+        opid: &amp::OpId,
+    ) -> Option<(HashMap<amp::OpId, StateTreeValue>, LocalOperationResult)> {
+        let state_tree_list = match self.multivalue.default_statetree_value_mut() {
+            StateTreeValue::Composite(StateTreeComposite::List(list)) => list,
+            _ => unreachable!(),
+        };
+        let (elemid, value) = state_tree_list.elements.get_mut(index as usize)?;
+        let losers = value.resolve_conflict(opid)?;
+        let op_result = delete_conflicts(
+            state_tree_list.object_id.clone(),
+            elemid.clone().into(),
+            &losers,
+        );
+        Some((losers, op_result))
+    }
+
     pub(crate) fn rollback_set(&mut self, index: usize, value: MultiValue) {
         let state_tree_list = match self.multivalue.default_statetree_value_mut() {
             StateTreeValue::Composite(StateTreeComposite::List(list)) => list,
@@ -982,7 +1229,11 @@ fn condense_insert_ops(ops: Vec<amp::Op>) -> Vec<amp::Op> {
                     new_ops.push(v);
                 }
                 (Some(typ), Some(scalar)) => {
-                    if typ == discriminant(&scalar) && v.insert {
+                    if typ == discriminant(&scalar)
+                        && v.insert
+                        && insert_if_not_condensed
+                        && v.obj == obj
+                    {
                         // there is a multiset in progress & the current op could be part of it
                         cur_prim_vals.push(scalar)
                     } else {
@@ -1035,6 +1286,9 @@ fn prim_from_op_action(action: &amp::OpType) -> Option<amp::ScalarValue> {
             amp::ScalarValue::Cursor(_) => None,
             amp::ScalarValue::Boolean(_) => Some(v.clone()),
             amp::ScalarValue::Null => Some(v.clone()),
+            amp::ScalarValue::Uuid(_) => Some(v.clone()),
+            amp::ScalarValue::Decimal(_) => Some(v.clone()),
+            amp::ScalarValue::Unknown(_) => None,
         },
         _ => None,
     }
@@ -1074,4 +1328,51 @@ mod tests {
         let condensed_ops = condense_insert_ops(ops.clone());
         assert_eq!(ops, condensed_ops);
     }
+
+    #[test]
+    fn condense_does_not_join_inserts_to_the_props_of_an_inserted_map() {
+        let actor: amp::ActorId = "5449baf817ab4b76a0a6c5e3ab86e445".try_into().unwrap();
+        let text: amp::ObjectId = actor.op_id_at(1).into();
+        let map: amp::ObjectId = actor.op_id_at(2).into();
+        let ops = vec![
+            amp::Op {
+                action: amp::OpType::Make(amp::ObjType::Map),
+                obj: text.clone(),
+                key: amp::Key::Seq(amp::ElementId::Head),
+                pred: SortedVec::new(),
+                insert: true,
+            },
+            amp::Op {
+                action: amp::OpType::Set(amp::ScalarValue::Str("paragraph".into())),
+                obj: map,
+                key: amp::Key::Map("type".into()),
+                pred: SortedVec::new(),
+                insert: false,
+            },
+            amp::Op {
+                action: amp::OpType::Set(amp::ScalarValue::Str("a".into())),
+                obj: text.clone(),
+                key: actor.op_id_at(2).into(),
+                pred: SortedVec::new(),
+                insert: true,
+            },
+            amp::Op {
+                action: amp::OpType::Set(amp::ScalarValue::Str("b".into())),
+                obj: text.clone(),
+                key: actor.op_id_at(4).into(),
+                pred: SortedVec::new(),
+                insert: true,
+            },
+        ];
+        let mut expected = ops[..3].to_vec();
+        expected[2].action = amp::OpType::MultiSet(
+            vec![
+                amp::ScalarValue::Str("a".into()),
+                amp::ScalarValue::Str("b".into()),
+            ]
+            .try_into()
+            .unwrap(),
+        );
+        assert_eq!(condense_insert_ops(ops), expected);
+    }
 }
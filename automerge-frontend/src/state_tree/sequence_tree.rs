@@ -2,12 +2,85 @@ use std::{
     cmp::{min, Ordering},
     fmt::Debug,
     mem,
+    ops::{Add, AddAssign, Sub, SubAssign},
 };
 
 use automerge_protocol::OpId;
 
 pub type SequenceTree<T> = SequenceTreeInternal<T, 25>;
 
+/// The size of an element, or of a run of elements, in each of the units which text can be
+/// indexed by.
+///
+/// Every node of the tree stores the total weight of its subtree, which is what allows text
+/// offsets to be converted to and from element indices in `O(log n)`. Elements which are not text
+/// weigh nothing.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TextWeight {
+    pub utf8: usize,
+    pub utf16: usize,
+    pub chars: usize,
+}
+
+impl TextWeight {
+    pub fn of(s: &str) -> TextWeight {
+        TextWeight {
+            utf8: s.len(),
+            utf16: s.encode_utf16().count(),
+            chars: s.chars().count(),
+        }
+    }
+}
+
+impl Add for TextWeight {
+    type Output = TextWeight;
+
+    fn add(self, other: TextWeight) -> TextWeight {
+        TextWeight {
+            utf8: self.utf8 + other.utf8,
+            utf16: self.utf16 + other.utf16,
+            chars: self.chars + other.chars,
+        }
+    }
+}
+
+impl AddAssign for TextWeight {
+    fn add_assign(&mut self, other: TextWeight) {
+        *self = *self + other
+    }
+}
+
+impl Sub for TextWeight {
+    type Output = TextWeight;
+
+    fn sub(self, other: TextWeight) -> TextWeight {
+        TextWeight {
+            utf8: self.utf8 - other.utf8,
+            utf16: self.utf16 - other.utf16,
+            chars: self.chars - other.chars,
+        }
+    }
+}
+
+impl SubAssign for TextWeight {
+    fn sub_assign(&mut self, other: TextWeight) {
+        *self = *self - other
+    }
+}
+
+impl std::iter::Sum for TextWeight {
+    fn sum<I: Iterator<Item = TextWeight>>(iter: I) -> TextWeight {
+        iter.fold(TextWeight::default(), Add::add)
+    }
+}
+
+/// Elements of a `SequenceTree` which contribute to the text weight of the tree
+pub trait Weighted {
+    fn weight(&self) -> TextWeight {
+        TextWeight::default()
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct SequenceTreeInternal<T, const B: usize> {
     root_node: Option<SequenceTreeNode<T, B>>,
@@ -18,11 +91,12 @@ struct SequenceTreeNode<T, const B: usize> {
     elements: Vec<Box<(OpId, T)>>,
     children: Vec<SequenceTreeNode<T, B>>,
     length: usize,
+    weight: TextWeight,
 }
 
 impl<T, const B: usize> SequenceTreeInternal<T, B>
 where
-    T: Clone + Debug,
+    T: Clone + Debug + Weighted,
 {
     /// Construct a new, empty, sequence.
     pub fn new() -> Self {
@@ -39,6 +113,40 @@ where
         self.len() == 0
     }
 
+    /// Get the total weight of the elements in the sequence.
+    pub fn weight(&self) -> TextWeight {
+        self.root_node
+            .as_ref()
+            .map_or_else(TextWeight::default, |n| n.weight)
+    }
+
+    /// Get the total weight of the elements before `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn weight_before(&self, index: usize) -> TextWeight {
+        assert!(index <= self.len(), "index {} out of bounds", index);
+        self.root_node
+            .as_ref()
+            .map_or_else(TextWeight::default, |n| n.weight_before(index))
+    }
+
+    /// Find the element which contains the unit at `offset`, where `unit` picks the measure of
+    /// weight being used.
+    ///
+    /// Returns the index of the element and how far into the element `offset` is, or `None` if
+    /// `offset` is not less than the total weight of the sequence.
+    pub fn find_by_weight(
+        &self,
+        offset: usize,
+        unit: fn(&TextWeight) -> usize,
+    ) -> Option<(usize, usize)> {
+        self.root_node
+            .as_ref()
+            .and_then(|n| n.find_by_weight(offset, unit))
+    }
+
     /// Create an iterator through the sequence.
     pub fn iter(&self) -> Iter<'_, T, B> {
         Iter {
@@ -54,6 +162,7 @@ where
     /// Panics if `index > len`.
     pub fn insert(&mut self, index: usize, opid: OpId, element: T) {
         let old_len = self.len();
+        let weight = element.weight();
         if let Some(root) = self.root_node.as_mut() {
             #[cfg(debug_assertions)]
             root.check();
@@ -66,6 +175,7 @@ where
                 let old_root = mem::replace(root, new_root);
 
                 root.length += old_root.len();
+                root.weight += old_root.weight;
                 root.children.push(old_root);
                 root.split_child(0);
 
@@ -80,6 +190,7 @@ where
                     (&mut root.children[0], index)
                 };
                 root.length += 1;
+                root.weight += weight;
                 child.insert_into_non_full_node(insertion_index, opid, element)
             } else {
                 root.insert_into_non_full_node(index, opid, element)
@@ -89,6 +200,7 @@ where
                 elements: vec![Box::new((opid, element))],
                 children: Vec::new(),
                 length: 1,
+                weight,
             })
         }
         assert_eq!(self.len(), old_len + 1, "{:#?}", self);
@@ -106,10 +218,25 @@ where
     }
 
     /// Get the `OpId` and `element` at `index` in the sequence.
+    ///
+    /// The weight of the element must not be changed through the returned reference, use
+    /// `update` for changes which can alter it.
     pub fn get_mut(&mut self, index: usize) -> Option<(OpId, &mut T)> {
         self.root_node.as_mut().and_then(|n| n.get_mut(index))
     }
 
+    /// Apply `f` to the element at `index`, updating the weights of the tree to reflect any
+    /// change in the weight of the element.
+    pub fn update<F, R>(&mut self, index: usize, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.root_node
+            .as_mut()
+            .and_then(|n| n.update(index, f))
+            .map(|(_, _, r)| r)
+    }
+
     /// Removes the element at `index` from the sequence.
     ///
     /// # Panics
@@ -149,13 +276,14 @@ where
 
 impl<T, const B: usize> SequenceTreeNode<T, B>
 where
-    T: Clone + Debug,
+    T: Clone + Debug + Weighted,
 {
     fn new() -> Self {
         Self {
             elements: Vec::new(),
             children: Vec::new(),
             length: 0,
+            weight: TextWeight::default(),
         }
     }
 
@@ -185,8 +313,19 @@ where
         panic!("index not found in node")
     }
 
+    /// Recalculate the weight of this node from its elements and children
+    fn recalculate_weight(&mut self) {
+        self.weight = self
+            .elements
+            .iter()
+            .map(|e| e.1.weight())
+            .sum::<TextWeight>()
+            + self.children.iter().map(|c| c.weight).sum::<TextWeight>();
+    }
+
     fn insert_into_non_full_node(&mut self, index: usize, opid: OpId, element: T) {
         assert!(!self.is_full());
+        self.weight += element.weight();
         if self.is_leaf() {
             self.length += 1;
             self.elements.insert(index, Box::new((opid, element)));
@@ -239,6 +378,9 @@ where
                 .map(|c| c.len())
                 .sum::<usize>();
 
+        full_child.recalculate_weight();
+        successor_sibling.recalculate_weight();
+
         let z_len = successor_sibling.len();
 
         let full_child_len = full_child.len();
@@ -255,7 +397,9 @@ where
 
     fn remove_from_leaf(&mut self, index: usize) -> Box<(OpId, T)> {
         self.length -= 1;
-        self.elements.remove(index)
+        let removed = self.elements.remove(index);
+        self.weight -= removed.1.weight();
+        removed
     }
 
     fn remove_element_from_non_leaf(
@@ -264,7 +408,7 @@ where
         element_index: usize,
     ) -> Box<(OpId, T)> {
         self.length -= 1;
-        if self.children[element_index].elements.len() >= B {
+        let removed = if self.children[element_index].elements.len() >= B {
             let total_index = self.cumulative_index(element_index);
             // recursively delete index - 1 in predecessor_node
             let predecessor = self.children[element_index].remove(index - 1 - total_index);
@@ -283,7 +427,9 @@ where
 
             let total_index = self.cumulative_index(element_index);
             self.children[element_index].remove(index - total_index)
-        }
+        };
+        self.weight -= removed.1.weight();
+        removed
     }
 
     fn cumulative_index(&self, child_index: usize) -> usize {
@@ -354,6 +500,9 @@ where
                     self.children[child_index].length += last_child.len();
                     self.children[child_index].children.insert(0, last_child);
                 }
+
+                self.children[child_index - 1].recalculate_weight();
+                self.children[child_index].recalculate_weight();
             } else if self
                 .children
                 .get(child_index + 1)
@@ -376,16 +525,28 @@ where
 
                     self.children[child_index].children.push(first_child);
                 }
+
+                self.children[child_index].recalculate_weight();
+                self.children[child_index + 1].recalculate_weight();
             }
         }
         self.length -= 1;
         let total_index = self.cumulative_index(child_index);
-        self.children[child_index].remove(index - total_index)
+        let removed = self.children[child_index].remove(index - total_index);
+        self.weight -= removed.1.weight();
+        removed
     }
 
     fn check(&self) -> usize {
         let l = self.elements.len() + self.children.iter().map(|c| c.check()).sum::<usize>();
         assert_eq!(self.len(), l, "{:#?}", self);
+        let weight = self
+            .elements
+            .iter()
+            .map(|e| e.1.weight())
+            .sum::<TextWeight>()
+            + self.children.iter().map(|c| c.weight).sum::<TextWeight>();
+        assert_eq!(self.weight, weight, "{:#?}", self);
 
         l
     }
@@ -434,6 +595,7 @@ where
     }
 
     fn merge(&mut self, middle: Box<(OpId, T)>, successor_sibling: SequenceTreeNode<T, B>) {
+        self.weight += middle.1.weight() + successor_sibling.weight;
         self.elements.push(middle);
         self.elements.extend(successor_sibling.elements);
         self.children.extend(successor_sibling.children);
@@ -442,6 +604,13 @@ where
     }
 
     pub fn set(&mut self, index: usize, element: T) -> T {
+        let weight = element.weight();
+        let old = self.set_inner(index, element);
+        self.weight = self.weight - old.weight() + weight;
+        old
+    }
+
+    fn set_inner(&mut self, index: usize, element: T) -> T {
         if self.is_leaf() {
             let (_, old_element) = &mut **self.elements.get_mut(index).unwrap();
             mem::replace(old_element, element)
@@ -516,9 +685,101 @@ where
     }
 }
 
+impl<T, const B: usize> SequenceTreeNode<T, B>
+where
+    T: Clone + Debug + Weighted,
+{
+    /// Returns the weight of this node before the update, after it, and the result of `f`
+    fn update<F, R>(&mut self, index: usize, f: F) -> Option<(TextWeight, TextWeight, R)>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        let (old, new, r) = if self.is_leaf() {
+            let element = &mut self.elements.get_mut(index)?.1;
+            let old = element.weight();
+            let r = f(element);
+            (old, element.weight(), r)
+        } else {
+            let mut cumulative_len = 0;
+            let mut found = None;
+            for (child_index, child) in self.children.iter_mut().enumerate() {
+                match (cumulative_len + child.len()).cmp(&index) {
+                    Ordering::Less => {
+                        cumulative_len += child.len() + 1;
+                    }
+                    Ordering::Equal => {
+                        let element = &mut self.elements.get_mut(child_index)?.1;
+                        let old = element.weight();
+                        let r = f(element);
+                        found = Some((old, element.weight(), r));
+                        break;
+                    }
+                    Ordering::Greater => {
+                        found = child.update(index - cumulative_len, f);
+                        break;
+                    }
+                }
+            }
+            found?
+        };
+        self.weight = self.weight - old + new;
+        Some((old, new, r))
+    }
+
+    fn weight_before(&self, index: usize) -> TextWeight {
+        if self.is_leaf() {
+            return self.elements[..index].iter().map(|e| e.1.weight()).sum();
+        }
+        let mut weight = TextWeight::default();
+        let mut cumulative_len = 0;
+        for (child_index, child) in self.children.iter().enumerate() {
+            if index <= cumulative_len + child.len() {
+                return weight + child.weight_before(index - cumulative_len);
+            }
+            weight += child.weight + self.elements[child_index].1.weight();
+            cumulative_len += child.len() + 1;
+        }
+        weight
+    }
+
+    fn find_by_weight(
+        &self,
+        mut offset: usize,
+        unit: fn(&TextWeight) -> usize,
+    ) -> Option<(usize, usize)> {
+        if offset >= unit(&self.weight) {
+            return None;
+        }
+        let mut index = 0;
+        for (child_index, element) in self.elements.iter().enumerate() {
+            if let Some(child) = self.children.get(child_index) {
+                let child_weight = unit(&child.weight);
+                if offset < child_weight {
+                    return child
+                        .find_by_weight(offset, unit)
+                        .map(|(i, within)| (index + i, within));
+                }
+                offset -= child_weight;
+                index += child.len();
+            }
+            let element_weight = unit(&element.1.weight());
+            if offset < element_weight {
+                return Some((index, offset));
+            }
+            offset -= element_weight;
+            index += 1;
+        }
+        self.children.last().and_then(|child| {
+            child
+                .find_by_weight(offset, unit)
+                .map(|(i, within)| (index + i, within))
+        })
+    }
+}
+
 impl<T, const B: usize> Default for SequenceTreeInternal<T, B>
 where
-    T: Clone + Debug,
+    T: Clone + Debug + Weighted,
 {
     fn default() -> Self {
         Self::new()
@@ -527,7 +788,7 @@ where
 
 impl<T, const B: usize> PartialEq for SequenceTreeInternal<T, B>
 where
-    T: Clone + Debug + PartialEq,
+    T: Clone + Debug + PartialEq + Weighted,
 {
     fn eq(&self, other: &Self) -> bool {
         self.len() == other.len() && self.iter().zip(other.iter()).all(|(a, b)| a == b)
@@ -536,7 +797,7 @@ where
 
 impl<'a, T, const B: usize> IntoIterator for &'a SequenceTreeInternal<T, B>
 where
-    T: Clone + Debug,
+    T: Clone + Debug + Weighted,
 {
     type Item = &'a T;
 
@@ -557,7 +818,7 @@ pub struct Iter<'a, T, const B: usize> {
 
 impl<'a, T, const B: usize> Iterator for Iter<'a, T, B>
 where
-    T: Clone + Debug,
+    T: Clone + Debug + Weighted,
 {
     type Item = &'a T;
 
@@ -573,6 +834,16 @@ mod tests {
 
     use super::*;
 
+    impl Weighted for () {}
+
+    impl Weighted for usize {}
+
+    impl Weighted for String {
+        fn weight(&self) -> TextWeight {
+            TextWeight::of(self)
+        }
+    }
+
     #[test]
     fn push_back() {
         let mut t = SequenceTree::new();
@@ -695,4 +966,46 @@ mod tests {
         }
 
     }
+
+    fn arb_strings() -> impl Strategy<Value = Vec<(usize, String)>> {
+        proptest::collection::vec((any::<usize>(), "[a-z\u{e9}\u{1f600}]{1,3}"), 0..100)
+    }
+
+    proptest! {
+
+        #[test]
+        fn proptest_weights(inserts in arb_strings(), removes in arb_indices()) {
+            let mut t = SequenceTreeInternal::<String, 3>::new();
+            let actor = ActorId::random();
+            let mut v: Vec<String> = Vec::new();
+
+            for (i, s) in inserts {
+                let i = i % (v.len() + 1);
+                t.insert(i, actor.op_id_at(1), s.clone());
+                v.insert(i, s);
+            }
+            for i in removes {
+                if i < v.len() {
+                    t.remove(i);
+                    v.remove(i);
+                }
+            }
+
+            let units: [fn(&TextWeight) -> usize; 3] = [|w| w.utf8, |w| w.utf16, |w| w.chars];
+            assert_eq!(t.weight(), TextWeight::of(&v.concat()));
+            for index in 0..=v.len() {
+                let before = TextWeight::of(&v[..index].concat());
+                assert_eq!(t.weight_before(index), before);
+                if index < v.len() {
+                    for unit in units {
+                        assert_eq!(t.find_by_weight(unit(&before), unit), Some((index, 0)));
+                    }
+                }
+            }
+            for unit in units {
+                assert_eq!(t.find_by_weight(unit(&t.weight()), unit), None);
+            }
+        }
+
+    }
 }
@@ -1,16 +1,21 @@
 mod conflicts;
 mod cursor;
 mod primitive;
+mod table;
+mod text_element;
 
 use std::{borrow::Cow, collections::HashMap};
 
 use amp::SortedVec;
 use automerge_protocol as amp;
 pub use conflicts::Conflicts;
-pub use cursor::Cursor;
+pub(crate) use cursor::ChildKey;
+pub use cursor::{Cursor, Gravity, StickyCursor};
 pub use primitive::Primitive;
 use serde::Serialize;
 use smol_str::SmolStr;
+pub use table::{infer_primitive, TableBuilder};
+pub use text_element::TextElement;
 
 use crate::path::PathElement;
 
@@ -27,8 +32,11 @@ pub enum Value {
     Table(HashMap<SmolStr, Value>),
     /// An ordered sequence of values.
     List(Vec<Value>),
-    /// An ordered sequence of grapheme clusters.
-    Text(Vec<SmolStr>),
+    /// An ordered sequence of grapheme clusters and embedded values.
+    Text(Vec<TextElement>),
+    /// An unordered collection of distinct primitives. Counters and cursors cannot be members of
+    /// a set and are left out when a set is written to a document.
+    Set(Vec<Primitive>),
     /// A primitive value.
     Primitive(Primitive),
 }
@@ -78,14 +86,27 @@ impl Value {
         matches!(self, Self::Text(_))
     }
 
-    /// Extract the graphemes in this [`Value`] if it represents text.
-    pub fn text(&self) -> Option<&[SmolStr]> {
+    /// Extract the elements in this [`Value`] if it represents text.
+    pub fn text(&self) -> Option<&[TextElement]> {
         match self {
             Self::Text(m) => Some(m),
             _ => None,
         }
     }
 
+    /// Return whether the [`Value`] is a set.
+    pub fn is_set(&self) -> bool {
+        matches!(self, Self::Set(_))
+    }
+
+    /// Extract the members of this [`Value`] if it represents a set.
+    pub fn set(&self) -> Option<&[Primitive]> {
+        match self {
+            Self::Set(m) => Some(m),
+            _ => None,
+        }
+    }
+
     /// Return whether the [`Value`] is a primitive.
     pub fn is_primitive(&self) -> bool {
         matches!(self, Self::Primitive(_))
@@ -139,7 +160,21 @@ impl Value {
             Value::List(elements) => {
                 serde_json::Value::Array(elements.iter().map(|v| v.to_json()).collect())
             }
-            Value::Text(graphemes) => serde_json::Value::String(graphemes.join("")),
+            Value::Text(elements) => serde_json::Value::String(
+                elements
+                    .iter()
+                    .map(|e| {
+                        e.grapheme()
+                            .map_or(TextElement::OBJECT_REPLACEMENT, SmolStr::as_str)
+                    })
+                    .collect(),
+            ),
+            Value::Set(members) => serde_json::Value::Array(
+                members
+                    .iter()
+                    .map(|m| Value::Primitive(m.clone()).to_json())
+                    .collect(),
+            ),
             Value::Primitive(v) => match v {
                 Primitive::F64(n) => serde_json::Value::Number(
                     serde_json::Number::from_f64(*n).unwrap_or_else(|| serde_json::Number::from(0)),
@@ -159,6 +194,12 @@ impl Value {
                 Primitive::Cursor(c) => {
                     serde_json::Value::Number(serde_json::Number::from(c.index))
                 }
+                Primitive::Uuid(u) => serde_json::Value::String(u.to_string()),
+                Primitive::Decimal(d) => serde_json::Value::String(d.to_string()),
+                Primitive::Unknown(u) => serde_json::json!({
+                    "typeCode": u.type_code(),
+                    "bytes": u.bytes(),
+                }),
             },
         }
     }
@@ -182,13 +223,18 @@ impl Value {
                 (Value::List(s), PathElement::Index(i)) => s
                     .get(i as usize)
                     .and_then(|v| v.get_value_rev_path(rev_path)),
-                (Value::Text(t), PathElement::Index(i)) => t
-                    .get(i as usize)
-                    .map(|v| Cow::Owned(Value::Primitive(Primitive::Str(v.clone())))),
+                (Value::Text(t), PathElement::Index(i)) => match t.get(i as usize)? {
+                    TextElement::Grapheme(g) if rev_path.is_empty() => {
+                        Some(Cow::Owned(Value::Primitive(Primitive::Str(g.clone()))))
+                    }
+                    TextElement::Grapheme(_) => None,
+                    TextElement::Embedded(v) => v.get_value_rev_path(rev_path),
+                },
                 (Value::Map(_), PathElement::Index(_))
                 | (Value::Table(_), PathElement::Index(_))
                 | (Value::List(_), PathElement::Key(_))
                 | (Value::Text(_), PathElement::Key(_))
+                | (Value::Set(_), _)
                 | (Value::Primitive(_), PathElement::Key(_))
                 | (Value::Primitive(_), PathElement::Index(_)) => None,
             }
@@ -307,7 +353,7 @@ pub(crate) fn value_to_op_requests(
             }
             (result, op_num)
         }
-        Value::Text(chars) => {
+        Value::Text(elements) => {
             let make_text_op = amp::OpId(start_op, actor.clone());
             let make_op = amp::Op {
                 action: amp::OpType::Make(amp::ObjType::Text),
@@ -319,16 +365,33 @@ pub(crate) fn value_to_op_requests(
             let mut insert_ops: Vec<amp::Op> = Vec::new();
             let mut last_elemid = amp::ElementId::Head;
             let mut op_num = start_op + 1;
-            for c in chars.iter() {
-                insert_ops.push(amp::Op {
-                    action: amp::OpType::Set(amp::ScalarValue::Str(c.clone())),
-                    obj: amp::ObjectId::from(make_text_op.clone()),
-                    key: last_elemid.clone().into(),
-                    insert: true,
-                    pred: SortedVec::new(),
-                });
-                last_elemid = amp::OpId::new(op_num, actor).into();
-                op_num += 1;
+            for element in elements.iter() {
+                match element {
+                    TextElement::Grapheme(g) => {
+                        insert_ops.push(amp::Op {
+                            action: amp::OpType::Set(amp::ScalarValue::Str(g.clone())),
+                            obj: amp::ObjectId::from(make_text_op.clone()),
+                            key: last_elemid.clone().into(),
+                            insert: true,
+                            pred: SortedVec::new(),
+                        });
+                        last_elemid = amp::OpId::new(op_num, actor).into();
+                        op_num += 1;
+                    }
+                    TextElement::Embedded(v) => {
+                        let (child_requests, new_op_num) = value_to_op_requests(
+                            actor,
+                            op_num,
+                            amp::ObjectId::from(make_text_op.clone()),
+                            &last_elemid.clone().into(),
+                            v,
+                            true,
+                        );
+                        last_elemid = amp::OpId::new(op_num, actor).into();
+                        op_num = new_op_num;
+                        insert_ops.extend(child_requests);
+                    }
+                }
             }
             let mut ops = vec![make_op];
             ops.extend(insert_ops.into_iter());
@@ -384,6 +447,34 @@ pub(crate) fn value_to_op_requests(
             }
             (result, op_num)
         }
+        Value::Set(members) => {
+            let make_op_id = amp::OpId::new(start_op, actor);
+            let make_op = amp::Op {
+                action: amp::OpType::Make(amp::ObjType::Set),
+                obj: parent_object,
+                key: key.clone(),
+                insert,
+                pred: SortedVec::new(),
+            };
+            let mut result = vec![make_op];
+            let mut keys = std::collections::HashSet::new();
+            for member in members {
+                let value = amp::ScalarValue::from(member);
+                if let Some(member_key) = value.set_key() {
+                    if keys.insert(member_key.clone()) {
+                        result.push(amp::Op {
+                            action: amp::OpType::Set(value),
+                            obj: amp::ObjectId::from(make_op_id.clone()),
+                            key: amp::Key::Map(member_key),
+                            insert: false,
+                            pred: SortedVec::new(),
+                        });
+                    }
+                }
+            }
+            let op_num = start_op + result.len() as u64;
+            (result, op_num)
+        }
         Value::Primitive(prim_value) => {
             let ops = vec![amp::Op {
                 action: amp::OpType::Set(prim_value.into()),
@@ -1,5 +1,6 @@
 use automerge_protocol as amp;
 use serde::Serialize;
+use smol_str::SmolStr;
 
 #[derive(Serialize, Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "derive-arbitrary", derive(arbitrary::Arbitrary))]
@@ -18,3 +19,73 @@ impl Cursor {
         }
     }
 }
+
+/// Which neighbour of a position in a sequence a [`StickyCursor`] is attached to
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "derive-arbitrary", derive(arbitrary::Arbitrary))]
+pub enum Gravity {
+    /// Stay just after the element to the left of the position, elements inserted at the
+    /// position end up after the cursor
+    Left,
+    /// Stay just before the element to the right of the position, elements inserted at the
+    /// position end up before the cursor
+    Right,
+}
+
+/// Where an object is in its parent: the key for a map or table, or the ID of the element
+/// holding it for a list or text. Unlike an index this doesn't change as the document does.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "derive-arbitrary", derive(arbitrary::Arbitrary))]
+pub(crate) enum ChildKey {
+    Key(SmolStr),
+    Elem(amp::OpId),
+}
+
+/// A position between the elements of a list or text object which is not stored in the
+/// document.
+///
+/// Unlike a [`Cursor`] this doesn't hold an index, use
+/// [`Frontend::resolve_cursor`](crate::Frontend::resolve_cursor) to find where the position is
+/// now. If the element the cursor is attached to has been deleted the cursor resolves to the
+/// position next to the nearest surviving element in the direction of its gravity.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "derive-arbitrary", derive(arbitrary::Arbitrary))]
+pub struct StickyCursor {
+    /// The key and ID of every object from the root down to the list or text, so it can be
+    /// found without searching the document
+    pub(crate) path: Vec<(ChildKey, amp::ObjectId)>,
+    /// The element the cursor is attached to, `None` is the start of the sequence for a cursor
+    /// with left gravity and the end of it for one with right gravity
+    pub(crate) elem_opid: Option<amp::OpId>,
+    pub(crate) gravity: Gravity,
+}
+
+impl StickyCursor {
+    pub(crate) fn new(
+        path: Vec<(ChildKey, amp::ObjectId)>,
+        op: Option<amp::OpId>,
+        gravity: Gravity,
+    ) -> StickyCursor {
+        StickyCursor {
+            path,
+            elem_opid: op,
+            gravity,
+        }
+    }
+
+    /// The ID of the list or text object this cursor points into
+    pub fn object_id(&self) -> &amp::ObjectId {
+        self.path
+            .last()
+            .map_or(&amp::ObjectId::Root, |(_, object_id)| object_id)
+    }
+
+    /// The ID of the element this cursor is attached to
+    pub fn elem_opid(&self) -> Option<&amp::OpId> {
+        self.elem_opid.as_ref()
+    }
+
+    pub fn gravity(&self) -> Gravity {
+        self.gravity
+    }
+}
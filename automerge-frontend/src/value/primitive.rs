@@ -18,6 +18,15 @@ pub enum Primitive {
     Boolean(bool),
     Cursor(Cursor),
     Null,
+    Uuid(#[cfg_attr(feature = "derive-arbitrary", arbitrary(with = arbitrary_uuid))] uuid::Uuid),
+    Decimal(amp::Decimal),
+    /// A value of a type this version does not know about, see [`amp::UnknownValue`]
+    Unknown(amp::UnknownValue),
+}
+
+#[cfg(feature = "derive-arbitrary")]
+fn arbitrary_uuid(u: &mut arbitrary::Unstructured) -> arbitrary::Result<uuid::Uuid> {
+    Ok(uuid::Uuid::from_bytes(u.arbitrary()?))
 }
 
 impl Primitive {
@@ -138,6 +147,37 @@ impl Primitive {
         }
     }
 
+    /// Return whether the [`Primitive`] is a UUID.
+    pub fn is_uuid(&self) -> bool {
+        matches!(self, Self::Uuid(_))
+    }
+
+    /// Extract the [`uuid::Uuid`] in this [`Primitive`] if it represents a UUID.
+    pub fn uuid(&self) -> Option<uuid::Uuid> {
+        match self {
+            Self::Uuid(u) => Some(*u),
+            _ => None,
+        }
+    }
+
+    /// Return whether the [`Primitive`] is a decimal.
+    pub fn is_decimal(&self) -> bool {
+        matches!(self, Self::Decimal(_))
+    }
+
+    /// Extract the [`amp::Decimal`] in this [`Primitive`] if it represents a decimal.
+    pub fn decimal(&self) -> Option<amp::Decimal> {
+        match self {
+            Self::Decimal(d) => Some(*d),
+            _ => None,
+        }
+    }
+
+    /// Return whether the [`Primitive`] is a value of an unknown type.
+    pub fn is_unknown(&self) -> bool {
+        matches!(self, Self::Unknown(_))
+    }
+
     /// Return whether the [`Primitive`] is null.
     pub fn is_null(&self) -> bool {
         matches!(self, Self::Null)
@@ -167,6 +207,9 @@ impl From<&Primitive> for amp::ScalarValue {
             Primitive::Boolean(b) => amp::ScalarValue::Boolean(*b),
             Primitive::Null => amp::ScalarValue::Null,
             Primitive::Cursor(c) => amp::ScalarValue::Cursor(c.elem_opid.clone()),
+            Primitive::Uuid(u) => amp::ScalarValue::Uuid(*u),
+            Primitive::Decimal(d) => amp::ScalarValue::Decimal(*d),
+            Primitive::Unknown(u) => amp::ScalarValue::Unknown(u.clone()),
         }
     }
 }
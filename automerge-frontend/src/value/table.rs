@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+
+use smol_str::SmolStr;
+
+use super::{Primitive, Value};
+use crate::error::InvalidTableRow;
+
+/// Builds a [`Value::Table`] from records, one row per record, e.g. when importing a CSV file.
+///
+/// Each row is a map from column names to values. Rows are keyed by the value of the primary key
+/// column if there is one, otherwise by a random UUID.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableBuilder {
+    primary_key: Option<SmolStr>,
+    infer_types: bool,
+    rows: HashMap<SmolStr, Value>,
+}
+
+impl Default for TableBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TableBuilder {
+    pub fn new() -> Self {
+        TableBuilder {
+            primary_key: None,
+            infer_types: true,
+            rows: HashMap::new(),
+        }
+    }
+
+    /// Key each row by the value of `column`, which must be present and unique
+    pub fn primary_key<S: Into<SmolStr>>(mut self, column: S) -> Self {
+        self.primary_key = Some(column.into());
+        self
+    }
+
+    /// Whether [`TableBuilder::add_record`] should infer the types of fields (the default) or
+    /// store every field as a string
+    pub fn infer_types(mut self, infer_types: bool) -> Self {
+        self.infer_types = infer_types;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Add a row to the table, returning the key of the new row
+    pub fn add_row(&mut self, row: HashMap<SmolStr, Value>) -> Result<SmolStr, InvalidTableRow> {
+        let key = match &self.primary_key {
+            Some(column) => match row.get(column) {
+                Some(Value::Primitive(Primitive::Str(s))) => s.clone(),
+                Some(Value::Primitive(Primitive::Int(i))) => i.to_string().into(),
+                Some(Value::Primitive(Primitive::Uint(u))) => u.to_string().into(),
+                Some(Value::Primitive(Primitive::F64(f))) => f.to_string().into(),
+                Some(Value::Primitive(Primitive::Boolean(b))) => b.to_string().into(),
+                Some(value) => {
+                    return Err(InvalidTableRow::InvalidPrimaryKey {
+                        column: column.clone(),
+                        value: value.clone(),
+                    })
+                }
+                None => {
+                    return Err(InvalidTableRow::MissingPrimaryKey {
+                        column: column.clone(),
+                    })
+                }
+            },
+            None => uuid::Uuid::new_v4().to_string().into(),
+        };
+        if self.rows.contains_key(&key) {
+            return Err(InvalidTableRow::DuplicatePrimaryKey { key });
+        }
+        self.rows.insert(key.clone(), Value::Map(row));
+        Ok(key)
+    }
+
+    /// Add a row whose fields are all strings, such as a record from a CSV file. If type
+    /// inference is enabled each field is parsed with [`infer_primitive`].
+    pub fn add_record<K, V, I>(&mut self, record: I) -> Result<SmolStr, InvalidTableRow>
+    where
+        K: Into<SmolStr>,
+        V: AsRef<str>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let infer_types = self.infer_types;
+        let row = record
+            .into_iter()
+            .map(|(k, v)| {
+                let value = if infer_types {
+                    infer_primitive(v.as_ref())
+                } else {
+                    Primitive::Str(v.as_ref().into())
+                };
+                (k.into(), Value::Primitive(value))
+            })
+            .collect();
+        self.add_row(row)
+    }
+
+    pub fn build(self) -> Value {
+        Value::Table(self.rows)
+    }
+}
+
+/// Guess the type of a field from a text format such as CSV.
+///
+/// Empty fields are null, `true` and `false` are booleans, then we try integers and floats, and
+/// anything else is a string.
+pub fn infer_primitive(field: &str) -> Primitive {
+    if field.is_empty() {
+        Primitive::Null
+    } else if let Ok(b) = field.parse::<bool>() {
+        Primitive::Boolean(b)
+    } else if let Ok(i) = field.parse::<i64>() {
+        Primitive::Int(i)
+    } else if let Some(f) = field.parse::<f64>().ok().filter(|f| f.is_finite()) {
+        Primitive::F64(f)
+    } else {
+        Primitive::Str(field.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use maplit::hashmap;
+
+    use super::*;
+
+    #[test]
+    fn infer_primitive_types() {
+        assert_eq!(infer_primitive(""), Primitive::Null);
+        assert_eq!(infer_primitive("true"), Primitive::Boolean(true));
+        assert_eq!(infer_primitive("-12"), Primitive::Int(-12));
+        assert_eq!(infer_primitive("1.5"), Primitive::F64(1.5));
+        // "inf" and "NaN" parse as floats but are much more likely to be strings
+        assert_eq!(infer_primitive("NaN"), Primitive::Str("NaN".into()));
+        assert_eq!(infer_primitive("wren"), Primitive::Str("wren".into()));
+    }
+
+    #[test]
+    fn table_rows_are_keyed_by_primary_key() {
+        let mut builder = TableBuilder::new().primary_key("id");
+        assert_eq!(
+            builder.add_record(vec![("id", "7"), ("bird", "wren"), ("count", "3")]),
+            Ok("7".into())
+        );
+        assert_eq!(
+            builder.add_record(vec![("id", "7"), ("bird", "magpie")]),
+            Err(InvalidTableRow::DuplicatePrimaryKey { key: "7".into() })
+        );
+        assert_eq!(
+            builder.add_record(vec![("bird", "magpie")]),
+            Err(InvalidTableRow::MissingPrimaryKey {
+                column: "id".into()
+            })
+        );
+        assert_eq!(
+            builder.build(),
+            Value::Table(hashmap! {
+                "7".into() => Value::Map(hashmap! {
+                    "id".into() => Value::Primitive(Primitive::Int(7)),
+                    "bird".into() => "wren".into(),
+                    "count".into() => Value::Primitive(Primitive::Int(3)),
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn table_without_type_inference() {
+        let mut builder = TableBuilder::new().infer_types(false);
+        let key = builder.add_record(vec![("count", "3")]).unwrap();
+        assert_eq!(
+            builder.build(),
+            Value::Table(hashmap! {
+                key => Value::Map(hashmap! { "count".into() => "3".into() }),
+            })
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use serde::Serialize;
+use smol_str::SmolStr;
+
+use super::{Primitive, Value};
+
+/// An element of a text object.
+///
+/// Text is mostly made of grapheme clusters but other values can be embedded in it, e.g. a map
+/// marking the start of a paragraph. Embedded values are a single U+FFFC OBJECT REPLACEMENT
+/// CHARACTER in the text's string representation, both when counting offsets into the text and
+/// in its JSON string.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "derive-arbitrary", derive(arbitrary::Arbitrary))]
+#[serde(untagged)]
+pub enum TextElement {
+    /// A single grapheme cluster
+    Grapheme(SmolStr),
+    /// Any value other than a string
+    Embedded(Value),
+}
+
+impl TextElement {
+    /// The character which embedded values take the place of in the text's string representation
+    pub const OBJECT_REPLACEMENT: &'static str = "\u{fffc}";
+
+    /// Return whether this element is a grapheme cluster.
+    pub fn is_grapheme(&self) -> bool {
+        matches!(self, Self::Grapheme(_))
+    }
+
+    /// Extract the grapheme cluster in this element if it is one.
+    pub fn grapheme(&self) -> Option<&SmolStr> {
+        match self {
+            Self::Grapheme(g) => Some(g),
+            Self::Embedded(_) => None,
+        }
+    }
+
+    /// Return whether this element is an embedded value.
+    pub fn is_embedded(&self) -> bool {
+        matches!(self, Self::Embedded(_))
+    }
+
+    /// Extract the embedded value in this element if it is one.
+    pub fn embedded(&self) -> Option<&Value> {
+        match self {
+            Self::Grapheme(_) => None,
+            Self::Embedded(v) => Some(v),
+        }
+    }
+
+    /// This element as a value, graphemes become string primitives
+    pub fn to_value(&self) -> Value {
+        match self {
+            Self::Grapheme(g) => Value::Primitive(Primitive::Str(g.clone())),
+            Self::Embedded(v) => v.clone(),
+        }
+    }
+}
+
+impl From<&str> for TextElement {
+    fn from(s: &str) -> Self {
+        TextElement::Grapheme(SmolStr::new(s))
+    }
+}
+
+impl From<String> for TextElement {
+    fn from(s: String) -> Self {
+        TextElement::Grapheme(SmolStr::new(s))
+    }
+}
+
+impl From<SmolStr> for TextElement {
+    fn from(s: SmolStr) -> Self {
+        TextElement::Grapheme(s)
+    }
+}
+
+impl From<Value> for TextElement {
+    /// String primitives become graphemes, any other value is embedded
+    fn from(v: Value) -> Self {
+        match v {
+            Value::Primitive(Primitive::Str(s)) => TextElement::Grapheme(s),
+            other => TextElement::Embedded(other),
+        }
+    }
+}
@@ -1,18 +1,25 @@
 mod list;
 mod map;
 mod root;
+mod set;
 mod table;
 mod text;
 
 pub use list::ListRef;
 pub use map::MapRef;
 pub use root::RootRef;
+pub use set::SetRef;
 pub use table::TableRef;
-pub use text::TextRef;
+pub use text::{TextElementRef, TextRef, TextUnit};
+
+use std::collections::HashMap;
+
+use automerge_protocol as amp;
 
 use crate::{
-    state_tree::{StateTreeComposite, StateTreeValue},
-    Primitive, Value,
+    resolver::Resolvers,
+    state_tree::{MultiValue, StateTreeComposite, StateTreeValue},
+    Path, Primitive, Value,
 };
 
 /// A ValueRef represents a way to interact with the frontend's state lazily rather than creating
@@ -30,21 +37,47 @@ pub enum ValueRef<'a> {
     Table(TableRef<'a>),
     List(ListRef<'a>),
     Text(TextRef<'a>),
+    Set(SetRef<'a>),
+    /// A value which a [`ConflictResolver`](crate::ConflictResolver) made by merging conflicting
+    /// values
+    Merged(Value),
 }
 
 impl<'a> ValueRef<'a> {
-    pub(crate) fn new(stv: &'a StateTreeValue) -> Self {
+    pub(crate) fn new(
+        stv: &'a StateTreeValue,
+        resolvers: &'a Resolvers,
+        path: Option<Path>,
+    ) -> Self {
         match stv {
             StateTreeValue::Leaf(p) => Self::Primitive(p),
-            StateTreeValue::Composite(StateTreeComposite::Map(m)) => Self::Map(MapRef::new(m)),
+            StateTreeValue::Composite(StateTreeComposite::Map(m)) => {
+                Self::Map(MapRef::new(m, resolvers, path))
+            }
             StateTreeValue::Composite(StateTreeComposite::Table(t)) => {
-                Self::Table(TableRef::new(t))
+                Self::Table(TableRef::new(t, resolvers, path))
             }
-            StateTreeValue::Composite(StateTreeComposite::List(l)) => Self::List(ListRef::new(l)),
-            StateTreeValue::Composite(StateTreeComposite::Text(t)) => Self::Text(TextRef::new(t)),
+            StateTreeValue::Composite(StateTreeComposite::List(l)) => {
+                Self::List(ListRef::new(l, resolvers, path))
+            }
+            StateTreeValue::Composite(StateTreeComposite::Text(t)) => {
+                Self::Text(TextRef::new(t, resolvers, path))
+            }
+            StateTreeValue::Composite(StateTreeComposite::Set(s)) => Self::Set(SetRef::new(s)),
         }
     }
 
+    /// Every value in `mv`, which is at `path`, by the ID of the op which set it
+    pub(crate) fn all_from(
+        mv: &'a MultiValue,
+        resolvers: &'a Resolvers,
+        path: Option<Path>,
+    ) -> HashMap<amp::OpId, ValueRef<'a>> {
+        mv.iter()
+            .map(|(opid, value)| (opid.clone(), ValueRef::new(value, resolvers, path.clone())))
+            .collect()
+    }
+
     pub fn map(&self) -> Option<&MapRef<'a>> {
         match self {
             Self::Map(m) => Some(m),
@@ -73,6 +106,13 @@ impl<'a> ValueRef<'a> {
         }
     }
 
+    pub fn set(&self) -> Option<&SetRef<'a>> {
+        match self {
+            Self::Set(s) => Some(s),
+            _ => None,
+        }
+    }
+
     pub fn primitive(&self) -> Option<&Primitive> {
         match self {
             Self::Primitive(p) => Some(p),
@@ -87,6 +127,8 @@ impl<'a> ValueRef<'a> {
             ValueRef::Table(t) => t.value(),
             ValueRef::List(l) => l.value(),
             ValueRef::Text(t) => t.value(),
+            ValueRef::Set(s) => s.value(),
+            ValueRef::Merged(v) => v.clone(),
         }
     }
 }
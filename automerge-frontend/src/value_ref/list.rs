@@ -1,13 +1,28 @@
-use crate::{state_tree::StateTreeList, value_ref::ValueRef, Value};
+use std::collections::HashMap;
+
+use automerge_protocol as amp;
+
+use crate::{resolver::Resolvers, state_tree::StateTreeList, value_ref::ValueRef, Path, Value};
 
 #[derive(Clone, Debug)]
 pub struct ListRef<'a> {
     stl: &'a StateTreeList,
+    resolvers: &'a Resolvers,
+    /// Only tracked when there are resolvers, see [`Resolvers::child_path`]
+    path: Option<Path>,
 }
 
 impl<'a> ListRef<'a> {
-    pub(crate) fn new(stl: &'a StateTreeList) -> Self {
-        Self { stl }
+    pub(crate) fn new(
+        stl: &'a StateTreeList,
+        resolvers: &'a Resolvers,
+        path: Option<Path>,
+    ) -> Self {
+        Self {
+            stl,
+            resolvers,
+            path,
+        }
     }
 
     pub fn len(&self) -> usize {
@@ -19,24 +34,47 @@ impl<'a> ListRef<'a> {
     }
 
     pub fn get(&self, index: usize) -> Option<ValueRef<'a>> {
-        self.stl
-            .elements
-            .get(index)
-            .map(|(_, mv)| ValueRef::new(mv.default_statetree_value()))
+        self.stl.elements.get(index).map(|(_, mv)| {
+            self.resolvers.resolve(
+                self.resolvers
+                    .child_path(self.path.as_ref(), |p| p.index(index as u32)),
+                mv,
+            )
+        })
+    }
+
+    /// The element at `index` and every value it conflicts with, by the ID of the op which set them
+    pub fn get_all(&self, index: usize) -> Option<HashMap<amp::OpId, ValueRef<'a>>> {
+        self.stl.elements.get(index).map(|(_, mv)| {
+            ValueRef::all_from(
+                mv,
+                self.resolvers,
+                self.resolvers
+                    .child_path(self.path.as_ref(), |p| p.index(index as u32)),
+            )
+        })
     }
 
     pub fn iter(&self) -> impl Iterator<Item = ValueRef<'a>> {
-        self.stl
-            .elements
-            .iter()
-            .map(|mv| ValueRef::new(mv.default_statetree_value()))
+        let resolvers = self.resolvers;
+        let path = self.path.clone();
+        self.stl.elements.iter().enumerate().map(move |(i, mv)| {
+            resolvers.resolve(
+                resolvers.child_path(path.as_ref(), |p| p.index(i as u32)),
+                mv,
+            )
+        })
     }
 
     pub fn value(&self) -> Value {
-        let mut v = Vec::new();
-        for e in self.stl.elements.iter() {
-            v.push(e.default_value())
+        if self.resolvers.is_empty() {
+            let mut v = Vec::new();
+            for e in self.stl.elements.iter() {
+                v.push(e.default_value())
+            }
+            Value::List(v)
+        } else {
+            Value::List(self.iter().map(|v| v.value()).collect())
         }
-        Value::List(v)
     }
 }
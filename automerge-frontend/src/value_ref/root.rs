@@ -1,16 +1,20 @@
+use std::collections::HashMap;
+
+use automerge_protocol as amp;
 use smol_str::SmolStr;
 
 use super::ValueRef;
-use crate::{state_tree::StateTree, Value};
+use crate::{resolver::Resolvers, state_tree::StateTree, Value};
 
 #[derive(Clone, Debug)]
 pub struct RootRef<'a> {
     st: &'a StateTree,
+    resolvers: &'a Resolvers,
 }
 
 impl<'a> RootRef<'a> {
-    pub(crate) fn new(st: &'a StateTree) -> Self {
-        Self { st }
+    pub(crate) fn new(st: &'a StateTree, resolvers: &'a Resolvers) -> Self {
+        Self { st, resolvers }
     }
 
     pub fn contains_key(&self, key: &str) -> bool {
@@ -26,10 +30,21 @@ impl<'a> RootRef<'a> {
     }
 
     pub fn get(&self, key: &str) -> Option<ValueRef<'a>> {
-        self.st
-            .root_props
-            .get(key)
-            .map(|mv| ValueRef::new(mv.default_statetree_value()))
+        self.st.root_props.get(key).map(|mv| {
+            self.resolvers
+                .resolve(self.resolvers.child_path(None, |p| p.key(key)), mv)
+        })
+    }
+
+    /// The value of `key` and every value it conflicts with, by the ID of the op which set them
+    pub fn get_all(&self, key: &str) -> Option<HashMap<amp::OpId, ValueRef<'a>>> {
+        self.st.root_props.get(key).map(|mv| {
+            ValueRef::all_from(
+                mv,
+                self.resolvers,
+                self.resolvers.child_path(None, |p| p.key(key)),
+            )
+        })
     }
 
     pub fn keys(&self) -> impl Iterator<Item = &SmolStr> {
@@ -37,20 +52,27 @@ impl<'a> RootRef<'a> {
     }
 
     pub fn values(&self) -> impl Iterator<Item = ValueRef<'a>> {
-        self.st
-            .root_props
-            .values()
-            .map(|v| ValueRef::new(v.default_statetree_value()))
+        let resolvers = self.resolvers;
+        self.st.root_props.iter().map(move |(k, v)| {
+            resolvers.resolve(resolvers.child_path(None, |p| p.key(k.clone())), v)
+        })
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (&SmolStr, ValueRef<'a>)> {
-        self.st
-            .root_props
-            .iter()
-            .map(|(k, v)| (k, ValueRef::new(v.default_statetree_value())))
+        self.st.root_props.iter().map(move |(k, v)| {
+            (
+                k,
+                self.resolvers
+                    .resolve(self.resolvers.child_path(None, |p| p.key(k.clone())), v),
+            )
+        })
     }
 
     pub fn value(&self) -> Value {
-        self.st.value()
+        if self.resolvers.is_empty() {
+            self.st.value()
+        } else {
+            Value::Map(self.iter().map(|(k, v)| (k.clone(), v.value())).collect())
+        }
     }
 }
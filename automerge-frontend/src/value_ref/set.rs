@@ -0,0 +1,33 @@
+use crate::{state_tree::StateTreeSet, Primitive, Value};
+
+#[derive(Clone, Debug)]
+pub struct SetRef<'a> {
+    sts: &'a StateTreeSet,
+}
+
+impl<'a> SetRef<'a> {
+    pub(crate) fn new(sts: &'a StateTreeSet) -> Self {
+        Self { sts }
+    }
+
+    pub fn contains(&self, value: &Primitive) -> bool {
+        self.sts.contains(value)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sts.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sts.members.is_empty()
+    }
+
+    /// The members of the set, in the same order on every peer
+    pub fn iter(&self) -> impl Iterator<Item = &'a Primitive> {
+        self.sts.members()
+    }
+
+    pub fn value(&self) -> Value {
+        Value::Set(self.iter().cloned().collect())
+    }
+}
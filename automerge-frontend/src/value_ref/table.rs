@@ -1,17 +1,29 @@
 use std::collections::HashMap;
 
+use automerge_protocol as amp;
 use smol_str::SmolStr;
 
-use crate::{state_tree::StateTreeTable, value_ref::ValueRef, Value};
+use crate::{resolver::Resolvers, state_tree::StateTreeTable, value_ref::ValueRef, Path, Value};
 
 #[derive(Clone, Debug)]
 pub struct TableRef<'a> {
     stt: &'a StateTreeTable,
+    resolvers: &'a Resolvers,
+    /// Only tracked when there are resolvers, see [`Resolvers::child_path`]
+    path: Option<Path>,
 }
 
 impl<'a> TableRef<'a> {
-    pub(crate) fn new(stt: &'a StateTreeTable) -> Self {
-        Self { stt }
+    pub(crate) fn new(
+        stt: &'a StateTreeTable,
+        resolvers: &'a Resolvers,
+        path: Option<Path>,
+    ) -> Self {
+        Self {
+            stt,
+            resolvers,
+            path,
+        }
     }
 
     pub fn contains_key(&self, key: &str) -> bool {
@@ -27,10 +39,25 @@ impl<'a> TableRef<'a> {
     }
 
     pub fn get(&self, key: &str) -> Option<ValueRef<'a>> {
-        self.stt
-            .props
-            .get(key)
-            .map(|mv| ValueRef::new(mv.default_statetree_value()))
+        self.stt.props.get(key).map(|mv| {
+            self.resolvers.resolve(
+                self.resolvers
+                    .child_path(self.path.as_ref(), |p| p.key(key)),
+                mv,
+            )
+        })
+    }
+
+    /// The value of `key` and every value it conflicts with, by the ID of the op which set them
+    pub fn get_all(&self, key: &str) -> Option<HashMap<amp::OpId, ValueRef<'a>>> {
+        self.stt.props.get(key).map(|mv| {
+            ValueRef::all_from(
+                mv,
+                self.resolvers,
+                self.resolvers
+                    .child_path(self.path.as_ref(), |p| p.key(key)),
+            )
+        })
     }
 
     pub fn keys(&self) -> impl Iterator<Item = &SmolStr> {
@@ -38,24 +65,35 @@ impl<'a> TableRef<'a> {
     }
 
     pub fn values(&self) -> impl Iterator<Item = ValueRef<'a>> {
-        self.stt
-            .props
-            .values()
-            .map(|v| ValueRef::new(v.default_statetree_value()))
+        let resolvers = self.resolvers;
+        let path = self.path.clone();
+        self.stt.props.iter().map(move |(k, v)| {
+            resolvers.resolve(resolvers.child_path(path.as_ref(), |p| p.key(k.clone())), v)
+        })
     }
 
     pub fn iter(&self) -> impl Iterator<Item = (&SmolStr, ValueRef<'a>)> {
-        self.stt
-            .props
-            .iter()
-            .map(|(k, v)| (k, ValueRef::new(v.default_statetree_value())))
+        self.stt.props.iter().map(move |(k, v)| {
+            (
+                k,
+                self.resolvers.resolve(
+                    self.resolvers
+                        .child_path(self.path.as_ref(), |p| p.key(k.clone())),
+                    v,
+                ),
+            )
+        })
     }
 
     pub fn value(&self) -> Value {
-        let mut m = HashMap::new();
-        for (k, v) in &self.stt.props {
-            m.insert(k.clone(), v.default_value());
+        if self.resolvers.is_empty() {
+            let mut m = HashMap::new();
+            for (k, v) in &self.stt.props {
+                m.insert(k.clone(), v.default_value());
+            }
+            Value::Map(m)
+        } else {
+            Value::Map(self.iter().map(|(k, v)| (k.clone(), v.value())).collect())
         }
-        Value::Map(m)
     }
 }
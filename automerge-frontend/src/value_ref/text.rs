@@ -1,15 +1,78 @@
 use smol_str::SmolStr;
 
-use crate::{state_tree::StateTreeText, Value};
+use crate::{
+    resolver::Resolvers,
+    state_tree::{MultiGrapheme, StateTreeText},
+    value_ref::ValueRef,
+    Path, TextElement, Value,
+};
+
+/// The units which positions in text can be given in.
+///
+/// Each element of a text object is a grapheme cluster or an embedded value, so `Grapheme`
+/// offsets are indices into the text, while the other units count the bytes, code units or
+/// characters of the text's string representation in that encoding. Embedded values are a U+FFFC
+/// OBJECT REPLACEMENT CHARACTER in the string representation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextUnit {
+    Grapheme,
+    /// Bytes of the UTF-8 encoding, as used for offsets into a Rust `str`
+    Utf8,
+    /// Code units of the UTF-16 encoding, as used by JavaScript strings
+    Utf16,
+    /// Unicode scalar values, as yielded by `str::chars`
+    Scalar,
+}
+
+/// An element of a text object, see [`TextElement`](crate::TextElement)
+#[derive(Clone, Debug)]
+pub enum TextElementRef<'a> {
+    Grapheme(&'a SmolStr),
+    Embedded(ValueRef<'a>),
+}
+
+impl<'a> TextElementRef<'a> {
+    pub fn grapheme(&self) -> Option<&'a SmolStr> {
+        match self {
+            Self::Grapheme(g) => Some(g),
+            Self::Embedded(_) => None,
+        }
+    }
+
+    pub fn embedded(&self) -> Option<&ValueRef<'a>> {
+        match self {
+            Self::Grapheme(_) => None,
+            Self::Embedded(v) => Some(v),
+        }
+    }
+
+    pub fn value(&self) -> TextElement {
+        match self {
+            Self::Grapheme(g) => TextElement::Grapheme((*g).clone()),
+            Self::Embedded(v) => TextElement::from(v.value()),
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct TextRef<'a> {
     stt: &'a StateTreeText,
+    resolvers: &'a Resolvers,
+    /// Only tracked when there are resolvers, see [`Resolvers::child_path`]
+    path: Option<Path>,
 }
 
 impl<'a> TextRef<'a> {
-    pub(crate) fn new(stt: &'a StateTreeText) -> Self {
-        Self { stt }
+    pub(crate) fn new(
+        stt: &'a StateTreeText,
+        resolvers: &'a Resolvers,
+        path: Option<Path>,
+    ) -> Self {
+        Self {
+            stt,
+            resolvers,
+            path,
+        }
     }
 
     pub fn len(&self) -> usize {
@@ -20,22 +83,63 @@ impl<'a> TextRef<'a> {
         self.stt.graphemes.is_empty()
     }
 
-    pub fn get(&self, index: usize) -> Option<&SmolStr> {
+    /// The length of the text in `unit`s
+    pub fn len_in(&self, unit: TextUnit) -> usize {
+        self.stt.len_in(unit)
+    }
+
+    /// Convert `offset`, given in `from` units, to the same position in `to` units.
+    ///
+    /// Returns `None` if `offset` is past the end of the text or does not fall on a grapheme
+    /// boundary.
+    pub fn convert_offset(&self, offset: usize, from: TextUnit, to: TextUnit) -> Option<usize> {
+        let index = self.stt.index_at_offset(offset, from)?;
+        Some(self.stt.offset_of_index(index, to))
+    }
+
+    pub fn get(&self, index: usize) -> Option<TextElementRef<'a>> {
         self.stt
             .graphemes
             .get(index)
-            .map(|(_, mg)| mg.default_grapheme())
+            .map(|(_, mg)| element_ref(mg, self.resolvers, self.path.as_ref(), index))
     }
 
-    pub fn iter(&self) -> impl Iterator<Item = &SmolStr> {
-        self.stt.graphemes.iter().map(|mg| mg.default_grapheme())
+    pub fn iter(&self) -> impl Iterator<Item = TextElementRef<'a>> {
+        let resolvers = self.resolvers;
+        let path = self.path.clone();
+        self.stt
+            .graphemes
+            .iter()
+            .enumerate()
+            .map(move |(i, mg)| element_ref(mg, resolvers, path.as_ref(), i))
     }
 
     pub fn value(&self) -> Value {
-        let mut v = Vec::new();
-        for e in self.stt.graphemes.iter() {
-            v.push(e.default_grapheme().clone())
+        if self.resolvers.is_empty() {
+            let mut v = Vec::new();
+            for e in self.stt.graphemes.iter() {
+                v.push(e.default_element())
+            }
+            Value::Text(v)
+        } else {
+            Value::Text(self.iter().map(|e| e.value()).collect())
+        }
+    }
+}
+
+/// The element holding `mg`, which is at `index` in the text at `path`
+fn element_ref<'a>(
+    mg: &'a MultiGrapheme,
+    resolvers: &'a Resolvers,
+    path: Option<&Path>,
+    index: usize,
+) -> TextElementRef<'a> {
+    match (mg.default_grapheme(), mg) {
+        (Some(g), _) => TextElementRef::Grapheme(g),
+        (None, MultiGrapheme::Embedded(mv)) => {
+            let path = resolvers.child_path(path, |p| p.index(index as u32));
+            TextElementRef::Embedded(resolvers.resolve(path, mv))
         }
-        Value::Text(v)
+        (None, MultiGrapheme::Grapheme { .. }) => unreachable!("grapheme without a grapheme"),
     }
 }
@@ -1,7 +1,7 @@
 use std::{collections::HashMap, convert::TryInto};
 
 use amp::RootDiff;
-use automerge_frontend::{Frontend, Path, Primitive, Value};
+use automerge_frontend::{Frontend, InvalidChangeRequest, LocalChange, Path, Primitive, Value};
 use automerge_protocol as amp;
 use maplit::hashmap;
 use unicode_segmentation::UnicodeSegmentation;
@@ -510,6 +510,68 @@ fn apply_multi_insert_updates_inside_lists() {
     )
 }
 
+#[test]
+fn increment_counters_from_a_multi_insert() {
+    let actor = amp::ActorId::random();
+    let mut frontend = Frontend::new();
+    let patch = amp::Patch {
+        actor: None,
+        seq: None,
+        max_op: 4,
+        deps: Vec::new(),
+        clock: hashmap! {
+            actor.clone() => 1,
+        },
+        diffs: RootDiff {
+            props: hashmap! {
+                "counts".into() => hashmap!{
+                    actor.op_id_at(1) => amp::Diff::List(amp::ListDiff {
+                        object_id: actor.op_id_at(1).into(),
+                        edits: vec![amp::DiffEdit::MultiElementInsert(amp::MultiElementInsert {
+                            index: 0,
+                            elem_id: actor.op_id_at(2).into(),
+                            values: vec![
+                                amp::ScalarValue::Counter(1),
+                                amp::ScalarValue::Counter(2),
+                                amp::ScalarValue::Counter(3),
+                            ].try_into().unwrap()
+                        })],
+                    })
+                }
+            },
+        },
+        pending_changes: 0,
+    };
+    frontend.apply_patch(patch).unwrap();
+
+    let (_, change) = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::increment_by(
+                Path::root().key("counts").index(1),
+                5,
+            ))
+        })
+        .unwrap();
+    assert_eq!(
+        change.unwrap().operations,
+        vec![amp::Op {
+            action: amp::OpType::Inc(5),
+            obj: actor.op_id_at(1).into(),
+            key: actor.op_id_at(3).into(),
+            insert: false,
+            pred: vec![actor.op_id_at(3)].into(),
+        }]
+    );
+    assert_eq!(
+        frontend.get_value(&Path::root().key("counts")),
+        Some(Value::List(vec![
+            Value::Primitive(Primitive::Counter(1)),
+            Value::Primitive(Primitive::Counter(7)),
+            Value::Primitive(Primitive::Counter(3)),
+        ]))
+    );
+}
+
 #[test]
 fn apply_updates_inside_list_conflicts() {
     // We don't just use random actor IDs because we need to have a specific
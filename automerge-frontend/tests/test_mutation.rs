@@ -1,9 +1,12 @@
 use std::{collections::HashMap, convert::TryInto};
 
 use amp::SortedVec;
-use automerge_frontend::{Frontend, InvalidChangeRequest, LocalChange, Path, Value};
+use automerge_frontend::{
+    Frontend, InvalidChangeRequest, LocalChange, Path, Primitive, TextUnit, Value,
+};
 use automerge_protocol as amp;
 use maplit::hashmap;
+use unicode_segmentation::UnicodeSegmentation;
 
 #[test]
 fn test_delete_index_in_mutation() {
@@ -209,3 +212,257 @@ fn test_delete_non_existent_map_key() {
 
     assert_eq!(cr, InvalidChangeRequest::NoSuchPathError { path })
 }
+
+fn text_value(s: &str) -> Value {
+    Value::Text(s.graphemes(true).map(|g| g.into()).collect())
+}
+
+#[test]
+fn test_convert_text_offsets() {
+    let mut frontend = Frontend::new();
+    frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::set(
+                Path::root().key("text"),
+                text_value("h\u{e9}llo \u{1f44b}\u{1f3fd} world"),
+            ))?;
+            Ok(())
+        })
+        .unwrap();
+
+    let root = frontend.value_ref();
+    let text = root.get("text").unwrap();
+    let text = text.text().unwrap();
+    assert_eq!(text.len_in(TextUnit::Grapheme), 13);
+    assert_eq!(text.len_in(TextUnit::Utf8), 21);
+    assert_eq!(text.len_in(TextUnit::Utf16), 16);
+    assert_eq!(text.len_in(TextUnit::Scalar), 14);
+
+    // the grapheme after the waving hand
+    assert_eq!(
+        text.convert_offset(7, TextUnit::Grapheme, TextUnit::Utf8),
+        Some(15)
+    );
+    assert_eq!(
+        text.convert_offset(15, TextUnit::Utf8, TextUnit::Utf16),
+        Some(10)
+    );
+    assert_eq!(
+        text.convert_offset(10, TextUnit::Utf16, TextUnit::Scalar),
+        Some(8)
+    );
+    assert_eq!(
+        text.convert_offset(8, TextUnit::Scalar, TextUnit::Grapheme),
+        Some(7)
+    );
+    // the end of the text
+    assert_eq!(
+        text.convert_offset(16, TextUnit::Utf16, TextUnit::Utf8),
+        Some(21)
+    );
+    // inside the waving hand and past the end
+    assert_eq!(
+        text.convert_offset(8, TextUnit::Utf16, TextUnit::Grapheme),
+        None
+    );
+    assert_eq!(
+        text.convert_offset(14, TextUnit::Grapheme, TextUnit::Utf8),
+        None
+    );
+}
+
+#[test]
+fn test_splice_text() {
+    let mut frontend = Frontend::new();
+    frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::set(
+                Path::root().key("text"),
+                text_value("h\u{e9}llo \u{1f44b}\u{1f3fd} world"),
+            ))?;
+            Ok(())
+        })
+        .unwrap();
+
+    let path = Path::root().key("text");
+    frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            // replace the waving hand, which is four UTF-16 code units long
+            doc.add_change(LocalChange::splice_text(
+                path.clone(),
+                6,
+                4,
+                "there,",
+                TextUnit::Utf16,
+            ))?;
+            // delete the "h\u{e9}" at the start, which is three bytes long
+            doc.add_change(LocalChange::splice_text(
+                path.clone(),
+                0,
+                3,
+                "",
+                TextUnit::Utf8,
+            ))?;
+            doc.add_change(LocalChange::splice_text(
+                path.clone(),
+                0,
+                0,
+                "he",
+                TextUnit::Scalar,
+            ))?;
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(
+        frontend.get_value(&path),
+        Some(text_value("hello there, world"))
+    );
+
+    let err = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::splice_text(
+                path.clone(),
+                0,
+                100,
+                "",
+                TextUnit::Utf8,
+            ))?;
+            Ok(())
+        })
+        .unwrap_err();
+    assert_eq!(
+        err,
+        InvalidChangeRequest::InvalidTextOffset {
+            path,
+            offset: 100,
+            unit: TextUnit::Utf8
+        }
+    );
+}
+
+#[test]
+fn test_add_and_remove_set_members() {
+    let mut frontend = Frontend::new();
+    let birds = Path::root().key("birds");
+    frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::set(birds.clone(), Value::Set(Vec::new())))?;
+            doc.add_change(LocalChange::add_to_set(
+                birds.clone(),
+                Primitive::Str("magpie".into()),
+            ))?;
+            doc.add_change(LocalChange::add_to_set(
+                birds.clone(),
+                Primitive::Str("jay".into()),
+            ))?;
+            // adding a member twice does nothing
+            doc.add_change(LocalChange::add_to_set(
+                birds.clone(),
+                Primitive::Str("jay".into()),
+            ))?;
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(
+        frontend.get_value(&birds),
+        Some(Value::Set(vec![
+            Primitive::Str("jay".into()),
+            Primitive::Str("magpie".into()),
+        ]))
+    );
+
+    frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::remove_from_set(
+                birds.clone(),
+                Primitive::Str("jay".into()),
+            ))?;
+            // removing a value which is not a member does nothing
+            doc.add_change(LocalChange::remove_from_set(
+                birds.clone(),
+                Primitive::Str("wren".into()),
+            ))?;
+            Ok(())
+        })
+        .unwrap();
+    let root = frontend.value_ref();
+    let birds = root.get("birds").unwrap();
+    let set = birds.set().unwrap();
+    assert_eq!(set.len(), 1);
+    assert!(set.contains(&Primitive::Str("magpie".into())));
+    assert!(!set.contains(&Primitive::Str("jay".into())));
+}
+
+#[test]
+fn test_invalid_set_changes() {
+    let mut frontend = Frontend::new();
+    let birds = Path::root().key("birds");
+    frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::set(birds.clone(), Value::Set(Vec::new())))?;
+            doc.add_change(LocalChange::set(
+                Path::root().key("name"),
+                Value::Primitive(Primitive::Str("jay".into())),
+            ))?;
+            Ok(())
+        })
+        .unwrap();
+
+    let err = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::add_to_set(
+                Path::root().key("name"),
+                Primitive::Str("magpie".into()),
+            ))
+        })
+        .unwrap_err();
+    assert_eq!(
+        err,
+        InvalidChangeRequest::NotASet {
+            path: Path::root().key("name")
+        }
+    );
+
+    let err = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::add_to_set(
+                birds.clone(),
+                Primitive::Counter(1),
+            ))
+        })
+        .unwrap_err();
+    assert_eq!(
+        err,
+        InvalidChangeRequest::InvalidSetMember {
+            path: birds.clone(),
+            value: Primitive::Counter(1),
+        }
+    );
+}
+
+#[test]
+fn test_resolve_conflict_with_unknown_opid() {
+    let mut frontend = Frontend::new();
+    frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::set(Path::root().key("bird"), "magpie"))
+        })
+        .unwrap();
+    let opid = amp::ActorId::random().op_id_at(1);
+    let err = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::resolve_conflict(
+                Path::root().key("bird"),
+                opid.clone(),
+            ))
+        })
+        .unwrap_err();
+    assert_eq!(
+        err,
+        InvalidChangeRequest::NoSuchConflictingValue {
+            path: Path::root().key("bird"),
+            opid,
+        }
+    );
+    assert_eq!(frontend.value_ref().get_all("bird").unwrap().len(), 1);
+}
@@ -0,0 +1,159 @@
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+use crate::error::InvalidDecimal;
+
+/// A decimal number, `mantissa * 10^-scale`.
+///
+/// Decimals are compared by their mantissa and scale, so `1.5` and `1.50` are different values.
+/// The scale is at most [`MAX_SCALE`](Self::MAX_SCALE).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Decimal {
+    mantissa: i64,
+    scale: u32,
+}
+
+impl Decimal {
+    /// The largest scale, every digit of an `i64` mantissa can be after the decimal point
+    pub const MAX_SCALE: u32 = 18;
+
+    pub fn new(mantissa: i64, scale: u32) -> Result<Decimal, InvalidDecimal> {
+        if scale > Self::MAX_SCALE {
+            return Err(InvalidDecimal(format!(
+                "scale {} is larger than {}",
+                scale,
+                Self::MAX_SCALE
+            )));
+        }
+        Ok(Decimal { mantissa, scale })
+    }
+
+    pub fn mantissa(&self) -> i64 {
+        self.mantissa
+    }
+
+    /// The number of digits after the decimal point
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.mantissa as f64 / 10f64.powi(self.scale as i32)
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.mantissa.unsigned_abs().to_string();
+        let scale = self.scale as usize;
+        if self.mantissa < 0 {
+            write!(f, "-")?;
+        }
+        if scale == 0 {
+            write!(f, "{}", digits)
+        } else if digits.len() > scale {
+            let (int, frac) = digits.split_at(digits.len() - scale);
+            write!(f, "{}.{}", int, frac)
+        } else {
+            write!(f, "0.{}{}", "0".repeat(scale - digits.len()), digits)
+        }
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = InvalidDecimal;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidDecimal(s.to_string());
+        let (negative, unsigned) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.strip_prefix('+').unwrap_or(s)),
+        };
+        let (int, frac) = match unsigned.split_once('.') {
+            Some((int, frac)) => (int, frac),
+            None => (unsigned, ""),
+        };
+        if int.is_empty() || !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        if frac.len() > Decimal::MAX_SCALE as usize {
+            return Err(invalid());
+        }
+        let digits = format!("{}{}", int, frac);
+        // parse the magnitude as an i128 so that i64::MIN, which has no positive i64 counterpart,
+        // can be negated before the range check
+        let magnitude: i128 = digits.parse().map_err(|_| invalid())?;
+        let mantissa = if negative { -magnitude } else { magnitude };
+        Ok(Decimal {
+            mantissa: i64::try_from(mantissa).map_err(|_| invalid())?,
+            scale: frac.len() as u32,
+        })
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(i: i64) -> Self {
+        Decimal {
+            mantissa: i,
+            scale: 0,
+        }
+    }
+}
+
+#[cfg(feature = "derive-arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Decimal {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(Decimal {
+            mantissa: u.arbitrary()?,
+            scale: u.int_in_range(0..=Self::MAX_SCALE)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimal_string_round_trip() {
+        for (s, mantissa, scale) in [
+            ("0", 0, 0),
+            ("12.34", 1234, 2),
+            ("-12.34", -1234, 2),
+            ("0.005", 5, 3),
+            ("-0.50", -50, 2),
+            ("1500", 1500, 0),
+            ("-92233720368547758.08", i64::MIN, 2),
+            ("92233720368547758.07", i64::MAX, 2),
+        ] {
+            let decimal: Decimal = s.parse().unwrap();
+            assert_eq!(decimal, Decimal::new(mantissa, scale).unwrap());
+            assert_eq!(decimal.to_string(), s);
+        }
+        assert_eq!(
+            "+1.5".parse::<Decimal>().unwrap(),
+            Decimal::new(15, 1).unwrap()
+        );
+        for invalid in [
+            "",
+            "-",
+            ".5",
+            "1.2.3",
+            "1e5",
+            "abc",
+            "99999999999999999999",
+            "92233720368547758.08",
+        ] {
+            assert!(invalid.parse::<Decimal>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn decimal_scale_is_bounded() {
+        let smallest = Decimal::new(1, Decimal::MAX_SCALE).unwrap();
+        assert_eq!(smallest.to_string(), "0.000000000000000001");
+        assert_eq!(smallest.to_string().parse::<Decimal>().unwrap(), smallest);
+        assert!(Decimal::new(1, Decimal::MAX_SCALE + 1).is_err());
+        assert!(Decimal::new(1, u32::MAX).is_err());
+        assert!("0.0000000000000000001".parse::<Decimal>().is_err());
+    }
+}
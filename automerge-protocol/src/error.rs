@@ -22,6 +22,14 @@ pub struct InvalidActorId(pub String);
 #[error("Invalid change hash slice: {0:?}")]
 pub struct InvalidChangeHashSlice(pub Vec<u8>);
 
+#[derive(Error, Debug, PartialEq)]
+#[error("Invalid decimal: {0}")]
+pub struct InvalidDecimal(pub String);
+
+#[derive(Error, Debug, PartialEq)]
+#[error("Invalid type code for a value of an unknown type: {0}, expected one of 13, 14 or 15")]
+pub struct InvalidTypeCode(pub u8);
+
 #[derive(Error, Debug, PartialEq)]
 #[error("Invalid scalar value, expected {expected} but received {unexpected}")]
 pub struct InvalidScalarValue {
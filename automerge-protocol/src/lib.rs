@@ -1,5 +1,7 @@
+mod decimal;
 pub mod error;
 mod serde_impls;
+mod unknown_value;
 mod utility_impls;
 use std::{
     collections::HashMap,
@@ -11,6 +13,7 @@ use std::{
     str::FromStr,
 };
 
+pub use decimal::Decimal;
 use error::InvalidScalarValues;
 use serde::{
     de::{Error, MapAccess, Unexpected},
@@ -19,13 +22,14 @@ use serde::{
 use smol_str::SmolStr;
 use strum::EnumDiscriminants;
 use tinyvec::TinyVec;
+pub use unknown_value::UnknownValue;
 
 /// An actor id is a sequence of bytes. By default we use a uuid which can be nicely stack
 /// allocated.
 ///
 /// In the event that users want to use their own type of identifier that is longer than a uuid
 /// then they will likely end up pushing it onto the heap which is still fine.
-/// 
+///
 // Note that change encoding relies on the Ord implementation for the ActorId being implemented in
 // terms of the lexicographic ordering of the underlying bytes. Be aware of this if you are
 // changing the ActorId implementation in ways which might affect the Ord implementation
@@ -66,6 +70,8 @@ pub enum ObjType {
     Table,
     List,
     Text,
+    /// An add-wins set of scalar values
+    Set,
 }
 
 impl ObjType {
@@ -99,6 +105,7 @@ impl fmt::Display for ObjType {
             ObjType::Table => write!(f, "table"),
             ObjType::List => write!(f, "list"),
             ObjType::Text => write!(f, "text"),
+            ObjType::Set => write!(f, "set"),
         }
     }
 }
@@ -242,6 +249,12 @@ pub enum DataType {
     Int,
     #[serde(rename = "float64")]
     F64,
+    #[serde(rename = "uuid")]
+    Uuid,
+    #[serde(rename = "decimal")]
+    Decimal,
+    #[serde(rename = "unknown")]
+    Unknown,
     #[serde(rename = "undefined")]
     Undefined,
 }
@@ -344,6 +357,16 @@ impl ScalarValues {
             _ => None,
         }
     }
+
+    /// See [`ScalarValue::serialized_datatype`]
+    pub fn serialized_datatype(&self) -> Option<DataType> {
+        match self.kind {
+            ScalarValueKind::Uuid => Some(DataType::Uuid),
+            ScalarValueKind::Decimal => Some(DataType::Decimal),
+            ScalarValueKind::Unknown => Some(DataType::Unknown),
+            _ => self.as_numerical_datatype(),
+        }
+    }
 }
 
 #[derive(Serialize, PartialEq, Debug, Clone, EnumDiscriminants)]
@@ -360,6 +383,11 @@ pub enum ScalarValue {
     Cursor(OpId),
     Boolean(bool),
     Null,
+    #[serde(serialize_with = "serde_impls::serialize_display")]
+    Uuid(uuid::Uuid),
+    Decimal(Decimal),
+    /// A value of a type this version does not know about, see [`UnknownValue`]
+    Unknown(UnknownValue),
 }
 
 impl ScalarValue {
@@ -437,6 +465,46 @@ impl ScalarValue {
                     datatype,
                 },
             )?)),
+            (DataType::Uuid, ScalarValue::Uuid(u)) => Ok(ScalarValue::Uuid(*u)),
+            (DataType::Uuid, ScalarValue::Str(s)) => match uuid::Uuid::parse_str(s) {
+                Ok(u) => Ok(ScalarValue::Uuid(u)),
+                Err(_) => Err(error::InvalidScalarValue {
+                    raw_value: self.clone(),
+                    expected: "a UUID".to_string(),
+                    unexpected: format!("the string {}", s),
+                    datatype,
+                }),
+            },
+            (DataType::Uuid, v) => Err(error::InvalidScalarValue {
+                raw_value: self.clone(),
+                expected: "a UUID".to_string(),
+                unexpected: v.to_string(),
+                datatype,
+            }),
+            (DataType::Decimal, ScalarValue::Decimal(d)) => Ok(ScalarValue::Decimal(*d)),
+            (DataType::Decimal, ScalarValue::Str(s)) => match s.parse() {
+                Ok(d) => Ok(ScalarValue::Decimal(d)),
+                Err(_) => Err(error::InvalidScalarValue {
+                    raw_value: self.clone(),
+                    expected: "a decimal".to_string(),
+                    unexpected: format!("the string {}", s),
+                    datatype,
+                }),
+            },
+            (DataType::Decimal, ScalarValue::Int(i)) => Ok(ScalarValue::Decimal(Decimal::from(*i))),
+            (DataType::Decimal, v) => Err(error::InvalidScalarValue {
+                raw_value: self.clone(),
+                expected: "a decimal".to_string(),
+                unexpected: v.to_string(),
+                datatype,
+            }),
+            (DataType::Unknown, ScalarValue::Unknown(_)) => Ok(self.clone()),
+            (DataType::Unknown, v) => Err(error::InvalidScalarValue {
+                raw_value: self.clone(),
+                expected: "a value of an unknown type".to_string(),
+                unexpected: v.to_string(),
+                datatype,
+            }),
             (DataType::Undefined, _) => Ok(self.clone()),
         }
     }
@@ -456,6 +524,42 @@ impl ScalarValue {
         }
     }
 
+    /// Returns an Option containing the `DataType` which must be serialized alongside `self` for
+    /// it to be deserialized as the same value. This is the case for numbers, and for the values
+    /// which are serialized as strings (UUIDs and decimals) or maps (unknown values).
+    pub fn serialized_datatype(&self) -> Option<DataType> {
+        match self {
+            ScalarValue::Uuid(..) => Some(DataType::Uuid),
+            ScalarValue::Decimal(..) => Some(DataType::Decimal),
+            ScalarValue::Unknown(_) => Some(DataType::Unknown),
+            _ => self.as_numerical_datatype(),
+        }
+    }
+
+    /// The key under which this value is stored when it is a member of a set.
+    ///
+    /// Each value has exactly one key, so adding the same value twice refers to the same member.
+    /// Counters and cursors cannot be members of sets, for them this returns `None`.
+    pub fn set_key(&self) -> Option<SmolStr> {
+        let key = match self {
+            ScalarValue::Bytes(b) => format!("bytes:{}", hex::encode(b)),
+            ScalarValue::Str(s) => format!("str:{}", s),
+            ScalarValue::Int(i) => format!("int:{}", i),
+            ScalarValue::Uint(u) => format!("uint:{}", u),
+            ScalarValue::F64(f) => format!("f64:{:016x}", f.to_bits()),
+            ScalarValue::Timestamp(t) => format!("timestamp:{}", t),
+            ScalarValue::Boolean(b) => format!("bool:{}", b),
+            ScalarValue::Null => "null".to_string(),
+            ScalarValue::Uuid(u) => format!("uuid:{}", u),
+            ScalarValue::Decimal(d) => format!("decimal:{}", d),
+            ScalarValue::Unknown(u) => {
+                format!("unknown:{}:{}", u.type_code(), hex::encode(u.bytes()))
+            }
+            ScalarValue::Counter(_) | ScalarValue::Cursor(_) => return None,
+        };
+        Some(SmolStr::new(key))
+    }
+
     // TODO: Should this method be combined with as_numerical_datatype??
     pub fn datatype(&self) -> Option<DataType> {
         match self {
@@ -465,6 +569,9 @@ impl ScalarValue {
             ScalarValue::Uint(..) => Some(DataType::Uint),
             ScalarValue::F64(..) => Some(DataType::F64),
             ScalarValue::Cursor(..) => Some(DataType::Cursor),
+            ScalarValue::Uuid(..) => Some(DataType::Uuid),
+            ScalarValue::Decimal(..) => Some(DataType::Decimal),
+            ScalarValue::Unknown(_) => Some(DataType::Unknown),
             _ => None,
         }
     }
@@ -497,6 +604,7 @@ impl ScalarValue {
             ScalarValue::Int(n) => Some(*n as f64),
             ScalarValue::Uint(n) => Some(*n as f64),
             ScalarValue::F64(n) => Some(*n),
+            ScalarValue::Decimal(d) => Some(d.to_f64()),
             ScalarValue::Counter(n) => Some(*n as f64),
             ScalarValue::Timestamp(n) => Some(*n as f64),
             _ => None,
@@ -680,6 +788,7 @@ pub enum Diff {
     Table(TableDiff),
     List(ListDiff),
     Text(TextDiff),
+    Set(SetDiff),
     Value(ScalarValue),
     Cursor(CursorDiff),
 }
@@ -691,6 +800,7 @@ impl Diff {
             Diff::Table(_) => Some(ObjType::Table),
             Diff::List(_) => Some(ObjType::List),
             Diff::Text(_) => Some(ObjType::Text),
+            Diff::Set(_) => Some(ObjType::Set),
             Diff::Value(_) => None,
             Diff::Cursor(_) => None,
         }
@@ -702,6 +812,7 @@ impl Diff {
             Diff::Table(tablediff) => Some(tablediff.object_id.clone()),
             Diff::List(listdiff) => Some(listdiff.object_id.clone()),
             Diff::Text(textdiff) => Some(textdiff.object_id.clone()),
+            Diff::Set(setdiff) => Some(setdiff.object_id.clone()),
             Diff::Value(..) => None,
             Diff::Cursor(CursorDiff { object_id, .. }) => Some(object_id.clone()),
         }
@@ -722,6 +833,16 @@ pub struct TableDiff {
     pub props: HashMap<SmolStr, HashMap<OpId, Diff>>,
 }
 
+/// The changes to a set object. Each member is stored under the key returned by
+/// [`ScalarValue::set_key`] and the value under that key is the member itself. A member which was
+/// removed has no values under its key.
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SetDiff {
+    pub object_id: ObjectId,
+    pub props: HashMap<SmolStr, HashMap<OpId, Diff>>,
+}
+
 #[derive(Deserialize, Debug, PartialEq, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ListDiff {
@@ -2,7 +2,7 @@ use std::{collections::HashMap, fmt};
 
 use serde::{
     de,
-    de::{Error, MapAccess},
+    de::{Error, MapAccess, Unexpected},
     ser::SerializeStruct,
     Deserialize, Deserializer, Serialize, Serializer,
 };
@@ -10,8 +10,8 @@ use smol_str::SmolStr;
 
 use super::read_field;
 use crate::{
-    CursorDiff, DataType, Diff, DiffEdit, ListDiff, MapDiff, MapType, ObjType, ObjectId, OpId,
-    ScalarValue, SequenceType, TableDiff, TextDiff,
+    error::InvalidScalarValue, CursorDiff, DataType, Diff, DiffEdit, ListDiff, MapDiff, MapType,
+    ObjType, ObjectId, OpId, ScalarValue, SequenceType, SetDiff, TableDiff, TextDiff,
 };
 
 impl Serialize for Diff {
@@ -48,6 +48,13 @@ impl Serialize for Diff {
                 op.serialize_field("edits", &diff.edits)?;
                 op.end()
             }
+            Diff::Set(diff) => {
+                let mut op = serializer.serialize_struct("SetDiff", 3)?;
+                op.serialize_field("objectId", &diff.object_id)?;
+                op.serialize_field("type", "set")?;
+                op.serialize_field("props", &diff.props)?;
+                op.end()
+            }
             Diff::Value(val) => match val {
                 ScalarValue::Counter(_) => {
                     let mut op = serializer.serialize_struct("Value", 3)?;
@@ -84,6 +91,27 @@ impl Serialize for Diff {
                     op.serialize_field("datatype", "float64")?;
                     op.end()
                 }
+                ScalarValue::Uuid(_) => {
+                    let mut op = serializer.serialize_struct("Value", 3)?;
+                    op.serialize_field("value", &val)?;
+                    op.serialize_field("type", "value")?;
+                    op.serialize_field("datatype", "uuid")?;
+                    op.end()
+                }
+                ScalarValue::Decimal(_) => {
+                    let mut op = serializer.serialize_struct("Value", 3)?;
+                    op.serialize_field("value", &val)?;
+                    op.serialize_field("type", "value")?;
+                    op.serialize_field("datatype", "decimal")?;
+                    op.end()
+                }
+                ScalarValue::Unknown(_) => {
+                    let mut op = serializer.serialize_struct("Value", 3)?;
+                    op.serialize_field("value", &val)?;
+                    op.serialize_field("type", "value")?;
+                    op.serialize_field("datatype", "unknown")?;
+                    op.end()
+                }
                 _ => {
                     let mut op = serializer.serialize_struct("Value", 2)?;
                     op.serialize_field("value", &val)?;
@@ -102,6 +130,7 @@ enum RawDiffType {
     Text,
     List,
     Table,
+    Set,
 }
 
 // Same idea as RawOpType
@@ -110,7 +139,7 @@ impl<'de> Deserialize<'de> for RawDiffType {
     where
         D: Deserializer<'de>,
     {
-        const VARIANTS: &[&str] = &["value", "map", "text", "list", "table"];
+        const VARIANTS: &[&str] = &["value", "map", "text", "list", "table", "set"];
         // TODO: Probably more efficient to deserialize to a `&str`
         let raw_type = String::deserialize(deserializer)?;
         match raw_type.as_str() {
@@ -119,6 +148,7 @@ impl<'de> Deserialize<'de> for RawDiffType {
             "text" => Ok(RawDiffType::Text),
             "list" => Ok(RawDiffType::List),
             "table" => Ok(RawDiffType::Table),
+            "set" => Ok(RawDiffType::Set),
             other => Err(Error::unknown_variant(other, VARIANTS)),
         }
     }
@@ -131,6 +161,7 @@ impl RawDiffType {
             RawDiffType::Table => Some(ObjType::Table),
             RawDiffType::List => Some(ObjType::List),
             RawDiffType::Text => Some(ObjType::Text),
+            RawDiffType::Set => Some(ObjType::Set),
             RawDiffType::Value => None,
         }
     }
@@ -206,7 +237,13 @@ impl<'de> Deserialize<'de> for Diff {
                         }
                         _ => {
                             let value = value.ok_or_else(|| Error::missing_field("value"))?;
-                            let value_with_datatype = maybe_add_datatype_to_value(value, datatype);
+                            let value_with_datatype = maybe_add_datatype_to_value(value, datatype)
+                                .map_err(|e| {
+                                    Error::invalid_value(
+                                        Unexpected::Other(e.unexpected.as_str()),
+                                        &e.expected.as_str(),
+                                    )
+                                })?;
                             Ok(Diff::Value(value_with_datatype))
                         }
                     }
@@ -243,8 +280,15 @@ impl<'de> Deserialize<'de> for Diff {
                                     props,
                                 }))
                             },
+                            ObjType::Set => {
+                                let props = props.ok_or_else(|| Error::missing_field("props"))?;
+                                Ok(Diff::Set(SetDiff{
+                                    object_id,
+                                    props,
+                                }))
+                            },
                         }
-                        None => Err(Error::custom("'type' field must be one of ['list', 'text', 'table', 'map'] for an object diff"))
+                        None => Err(Error::custom("'type' field must be one of ['list', 'text', 'table', 'map', 'set'] for an object diff"))
                     }
 
                     //if let Some(props) = props {
@@ -281,23 +325,27 @@ impl<'de> Deserialize<'de> for Diff {
     }
 }
 
-fn maybe_add_datatype_to_value(value: ScalarValue, datatype: DataType) -> ScalarValue {
+fn maybe_add_datatype_to_value(
+    value: ScalarValue,
+    datatype: DataType,
+) -> Result<ScalarValue, InvalidScalarValue> {
     match datatype {
         DataType::Counter => {
             if let Some(n) = value.to_i64() {
-                ScalarValue::Counter(n)
+                Ok(ScalarValue::Counter(n))
             } else {
-                value
+                Ok(value)
             }
         }
         DataType::Timestamp => {
             if let Some(n) = value.to_i64() {
-                ScalarValue::Timestamp(n)
+                Ok(ScalarValue::Timestamp(n))
             } else {
-                value
+                Ok(value)
             }
         }
-        _ => value,
+        DataType::Uuid | DataType::Decimal => value.as_datatype(datatype),
+        _ => Ok(value),
     }
 }
 
@@ -414,4 +462,14 @@ mod tests {
         assert_eq!(json, serde_json::to_value(diff.clone()).unwrap());
         assert_eq!(serde_json::from_value::<Diff>(json).unwrap(), diff);
     }
+
+    #[test]
+    fn malformed_decimal_diff_is_an_error() {
+        let json = serde_json::json!({
+            "type": "value",
+            "datatype": "decimal",
+            "value": "twelve",
+        });
+        assert!(serde_json::from_value::<Diff>(json).is_err());
+    }
 }
@@ -1,11 +1,14 @@
+use std::fmt;
+
 use serde::{
     de::{Error, MapAccess},
-    Deserialize,
+    Deserialize, Serializer,
 };
 
 mod actor_id;
 mod change_hash;
 mod cursor_diff;
+mod decimal;
 mod diff;
 mod element_id;
 mod multi_element_insert;
@@ -33,3 +36,12 @@ where
         Ok(())
     }
 }
+
+// Helper for serializing values as their `Display` string, for use with `serialize_with`
+pub(crate) fn serialize_display<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: fmt::Display,
+    S: Serializer,
+{
+    serializer.collect_str(value)
+}
@@ -16,7 +16,7 @@ impl Serialize for MultiElementInsert {
         S: Serializer,
     {
         //serializer.serialize_newtype_variant("foo", 0, "bar", value)
-        let datatype = self.values.as_numerical_datatype();
+        let datatype = self.values.serialized_datatype();
         let mut ss =
             serializer.serialize_struct("MultiElementInsert", datatype.map_or(4, |_| 5))?;
         ss.serialize_field("index", &self.index)?;
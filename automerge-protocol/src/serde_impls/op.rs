@@ -22,13 +22,13 @@ impl Serialize for Op {
             fields += 1
         }
 
-        let numerical_datatype = match &self.action {
-            OpType::Set(value) => value.as_numerical_datatype(),
-            OpType::MultiSet(values) => values.as_numerical_datatype(),
+        let datatype = match &self.action {
+            OpType::Set(value) => value.serialized_datatype(),
+            OpType::MultiSet(values) => values.serialized_datatype(),
             _ => None,
         };
 
-        if numerical_datatype.is_some() {
+        if datatype.is_some() {
             fields += 2
         } else if !matches!(&self.action, OpType::Make(..)) {
             fields += 1
@@ -48,7 +48,7 @@ impl Serialize for Op {
         if self.insert {
             op.serialize_field("insert", &self.insert)?;
         }
-        if let Some(datatype) = numerical_datatype {
+        if let Some(datatype) = datatype {
             op.serialize_field("datatype", &datatype)?;
         }
         match &self.action {
@@ -74,6 +74,7 @@ pub enum RawOpType {
     MakeTable,
     MakeList,
     MakeText,
+    MakeSet,
     Del,
     Inc,
     Set,
@@ -89,6 +90,7 @@ impl Serialize for RawOpType {
             RawOpType::MakeTable => "makeTable",
             RawOpType::MakeList => "makeList",
             RawOpType::MakeText => "makeText",
+            RawOpType::MakeSet => "makeSet",
             RawOpType::Del => "del",
             RawOpType::Inc => "inc",
             RawOpType::Set => "set",
@@ -107,6 +109,7 @@ impl<'de> Deserialize<'de> for RawOpType {
             "makeTable",
             "makeList",
             "makeText",
+            "makeSet",
             "del",
             "inc",
             "set",
@@ -118,6 +121,7 @@ impl<'de> Deserialize<'de> for RawOpType {
             "makeTable" => Ok(RawOpType::MakeTable),
             "makeList" => Ok(RawOpType::MakeList),
             "makeText" => Ok(RawOpType::MakeText),
+            "makeSet" => Ok(RawOpType::MakeSet),
             "del" => Ok(RawOpType::Del),
             "inc" => Ok(RawOpType::Inc),
             "set" => Ok(RawOpType::Set),
@@ -192,6 +196,7 @@ impl<'de> Deserialize<'de> for Op {
                     RawOpType::MakeTable => OpType::Make(ObjType::Table),
                     RawOpType::MakeList => OpType::Make(ObjType::List),
                     RawOpType::MakeText => OpType::Make(ObjType::Text),
+                    RawOpType::MakeSet => OpType::Make(ObjType::Set),
                     RawOpType::Del => OpType::Del(
                         multi_op
                             .map(|i| NonZeroU32::new(i).unwrap())
@@ -251,6 +256,18 @@ impl<'de> Deserialize<'de> for Op {
                             Unexpected::Other("a cursor"),
                             &"a number",
                         )),
+                        Some(ScalarValue::Uuid(..)) => Err(Error::invalid_value(
+                            Unexpected::Other("a UUID"),
+                            &"a number",
+                        )),
+                        Some(ScalarValue::Decimal(..)) => Err(Error::invalid_value(
+                            Unexpected::Other("a decimal"),
+                            &"a number",
+                        )),
+                        Some(ScalarValue::Unknown(_)) => Err(Error::invalid_value(
+                            Unexpected::Other("a value of an unknown type"),
+                            &"a number",
+                        )),
                         None => Err(Error::missing_field("value")),
                     }?,
                 };
@@ -272,6 +289,7 @@ mod tests {
     use std::{convert::TryInto, str::FromStr};
 
     use super::*;
+    use crate::{Decimal, UnknownValue};
 
     #[test]
     fn test_deserialize_action() {
@@ -582,9 +600,110 @@ mod tests {
                     "pred": [],
                     "values": ["one",{"two": 2}],
                 }),
-                expected: Err(Error::invalid_type(
-                    Unexpected::Map,
-                    &"a number, string, bool, or null",
+                // maps are only accepted as values of unknown types
+                expected: Err(Error::unknown_field("two", &["typeCode", "bytes"])),
+            },
+            Scenario {
+                name: "Set with uuid",
+                json: serde_json::json!({
+                    "action": "set",
+                    "obj": "_root",
+                    "key": "somekey",
+                    "value": "0f5a9a5e-1c3b-4a8e-9b6a-3d2f1e0c9b8a",
+                    "datatype": "uuid",
+                    "pred": []
+                }),
+                expected: Ok(Op {
+                    action: OpType::Set(ScalarValue::Uuid(
+                        uuid::Uuid::parse_str("0f5a9a5e-1c3b-4a8e-9b6a-3d2f1e0c9b8a").unwrap(),
+                    )),
+                    obj: ObjectId::Root,
+                    key: "somekey".into(),
+                    insert: false,
+                    pred: SortedVec::new(),
+                }),
+            },
+            Scenario {
+                name: "Set with decimal",
+                json: serde_json::json!({
+                    "action": "set",
+                    "obj": "_root",
+                    "key": "somekey",
+                    "value": "-3.140",
+                    "datatype": "decimal",
+                    "pred": []
+                }),
+                expected: Ok(Op {
+                    action: OpType::Set(ScalarValue::Decimal(Decimal::new(-3140, 3).unwrap())),
+                    obj: ObjectId::Root,
+                    key: "somekey".into(),
+                    insert: false,
+                    pred: SortedVec::new(),
+                }),
+            },
+            Scenario {
+                name: "Set with decimal datatype and invalid string value",
+                json: serde_json::json!({
+                    "action": "set",
+                    "obj": "_root",
+                    "key": "somekey",
+                    "value": "three",
+                    "datatype": "decimal",
+                    "pred": []
+                }),
+                expected: Err(Error::invalid_value(
+                    Unexpected::Other("the string three"),
+                    &"a decimal",
+                )),
+            },
+            Scenario {
+                name: "Set with a value of an unknown type",
+                json: serde_json::json!({
+                    "action": "set",
+                    "obj": "_root",
+                    "key": "somekey",
+                    "value": {"typeCode": 14, "bytes": [1, 2]},
+                    "datatype": "unknown",
+                    "pred": []
+                }),
+                expected: Ok(Op {
+                    action: OpType::Set(ScalarValue::Unknown(
+                        UnknownValue::new(14, vec![1, 2]).unwrap(),
+                    )),
+                    obj: ObjectId::Root,
+                    key: "somekey".into(),
+                    insert: false,
+                    pred: SortedVec::new(),
+                }),
+            },
+            Scenario {
+                name: "Set with a value of an unknown type which uses a known type code",
+                json: serde_json::json!({
+                    "action": "set",
+                    "obj": "_root",
+                    "key": "somekey",
+                    "value": {"typeCode": 3, "bytes": [1, 2]},
+                    "datatype": "unknown",
+                    "pred": []
+                }),
+                expected: Err(Error::invalid_value(
+                    Unexpected::Unsigned(3),
+                    &"a type code between 13 and 15",
+                )),
+            },
+            Scenario {
+                name: "Set with a value of an unknown type whose type code overflows",
+                json: serde_json::json!({
+                    "action": "set",
+                    "obj": "_root",
+                    "key": "somekey",
+                    "value": {"typeCode": 16, "bytes": []},
+                    "datatype": "unknown",
+                    "pred": []
+                }),
+                expected: Err(Error::invalid_value(
+                    Unexpected::Unsigned(16),
+                    &"a type code between 13 and 15",
                 )),
             },
         ];
@@ -735,6 +854,38 @@ mod tests {
                 insert: true,
                 pred: SortedVec::new(),
             },
+            Op {
+                action: OpType::Set(ScalarValue::Uuid(uuid::Uuid::new_v4())),
+                obj: ObjectId::Root,
+                key: "somekey".into(),
+                insert: false,
+                pred: SortedVec::new(),
+            },
+            Op {
+                action: OpType::MultiSet(
+                    vec![
+                        ScalarValue::Decimal(Decimal::new(1050, 2).unwrap()),
+                        ScalarValue::Decimal(Decimal::new(-7, 0).unwrap()),
+                    ]
+                    .try_into()
+                    .unwrap(),
+                ),
+                obj: ObjectId::from_str("1@7ef48769b04d47e9a88e98a134d62716").unwrap(),
+                key: OpId::from_str("1@7ef48769b04d47e9a88e98a134d62716")
+                    .unwrap()
+                    .into(),
+                insert: true,
+                pred: SortedVec::new(),
+            },
+            Op {
+                action: OpType::Set(ScalarValue::Unknown(
+                    UnknownValue::new(13, vec![0, 255]).unwrap(),
+                )),
+                obj: ObjectId::Root,
+                key: "somekey".into(),
+                insert: false,
+                pred: SortedVec::new(),
+            },
         ];
         for (testcase_num, testcase) in testcases.iter().enumerate() {
             #[allow(clippy::expect_fun_call)]
@@ -1,7 +1,8 @@
 use serde::{de, Deserialize, Deserializer};
 use smol_str::SmolStr;
 
-use crate::ScalarValue;
+use super::read_field;
+use crate::{ScalarValue, UnknownValue};
 
 impl<'de> Deserialize<'de> for ScalarValue {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -13,7 +14,7 @@ impl<'de> Deserialize<'de> for ScalarValue {
             type Value = ScalarValue;
 
             fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                formatter.write_str("a number, string, bool, or null")
+                formatter.write_str("a number, string, bool, null, or value of an unknown type")
             }
 
             fn visit_bool<E>(self, value: bool) -> Result<ScalarValue, E>
@@ -57,6 +58,32 @@ impl<'de> Deserialize<'de> for ScalarValue {
             {
                 Ok(ScalarValue::Null)
             }
+
+            // Values of unknown types are serialized as `{"typeCode": .., "bytes": [..]}`
+            fn visit_map<V>(self, mut map: V) -> Result<ScalarValue, V::Error>
+            where
+                V: de::MapAccess<'de>,
+            {
+                let mut type_code: Option<u8> = None;
+                let mut bytes: Option<Vec<u8>> = None;
+                while let Some(field) = map.next_key::<String>()? {
+                    match field.as_ref() {
+                        "typeCode" => read_field("typeCode", &mut type_code, &mut map)?,
+                        "bytes" => read_field("bytes", &mut bytes, &mut map)?,
+                        _ => return Err(de::Error::unknown_field(&field, &["typeCode", "bytes"])),
+                    }
+                }
+                let type_code = type_code.ok_or_else(|| de::Error::missing_field("typeCode"))?;
+                let bytes = bytes.ok_or_else(|| de::Error::missing_field("bytes"))?;
+                UnknownValue::new(type_code, bytes)
+                    .map(ScalarValue::Unknown)
+                    .map_err(|_| {
+                        de::Error::invalid_value(
+                            de::Unexpected::Unsigned(u64::from(type_code)),
+                            &"a type code between 13 and 15",
+                        )
+                    })
+            }
         }
         deserializer.deserialize_any(ValueVisitor)
     }
@@ -0,0 +1,64 @@
+use serde::Serialize;
+
+use crate::error::InvalidTypeCode;
+
+/// A value of a type this version does not know about, loaded from a document written by a newer
+/// version.
+///
+/// `type_code` is the type from the binary encoding and `bytes` is the encoded value, which are
+/// written back unchanged when the document is saved. Only the type codes the encoding reserves
+/// for future types are allowed, anything else would be read back as a different type.
+#[derive(Serialize, PartialEq, Eq, Debug, Clone, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct UnknownValue {
+    type_code: u8,
+    bytes: Vec<u8>,
+}
+
+impl UnknownValue {
+    /// The type codes reserved for types this version does not know about
+    pub const TYPE_CODES: std::ops::RangeInclusive<u8> = 13..=15;
+
+    pub fn new(type_code: u8, bytes: Vec<u8>) -> Result<UnknownValue, InvalidTypeCode> {
+        if Self::TYPE_CODES.contains(&type_code) {
+            Ok(UnknownValue { type_code, bytes })
+        } else {
+            Err(InvalidTypeCode(type_code))
+        }
+    }
+
+    pub fn type_code(&self) -> u8 {
+        self.type_code
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+#[cfg(feature = "derive-arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for UnknownValue {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
+        Ok(UnknownValue {
+            type_code: u.int_in_range(Self::TYPE_CODES)?,
+            bytes: u.arbitrary()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_reserved_type_codes_are_allowed() {
+        for type_code in 0..=u8::MAX {
+            let value = UnknownValue::new(type_code, vec![1]);
+            if (13..=15).contains(&type_code) {
+                assert_eq!(value.unwrap().type_code(), type_code);
+            } else {
+                assert_eq!(value, Err(InvalidTypeCode(type_code)));
+            }
+        }
+    }
+}
@@ -2,7 +2,7 @@ use std::fmt;
 
 use smol_str::SmolStr;
 
-use crate::ScalarValue;
+use crate::{Decimal, ScalarValue};
 
 impl From<&str> for ScalarValue {
     fn from(s: &str) -> Self {
@@ -28,6 +28,18 @@ impl From<i32> for ScalarValue {
     }
 }
 
+impl From<uuid::Uuid> for ScalarValue {
+    fn from(u: uuid::Uuid) -> Self {
+        ScalarValue::Uuid(u)
+    }
+}
+
+impl From<Decimal> for ScalarValue {
+    fn from(d: Decimal) -> Self {
+        ScalarValue::Decimal(d)
+    }
+}
+
 impl From<bool> for ScalarValue {
     fn from(b: bool) -> Self {
         ScalarValue::Boolean(b)
@@ -53,6 +65,11 @@ impl fmt::Display for ScalarValue {
             ScalarValue::Boolean(b) => write!(f, "{}", b),
             ScalarValue::Null => write!(f, "null"),
             ScalarValue::Cursor(elemid) => write!(f, "Cursor: {}", elemid),
+            ScalarValue::Uuid(u) => write!(f, "Uuid: {}", u),
+            ScalarValue::Decimal(d) => write!(f, "Decimal: {}", d),
+            ScalarValue::Unknown(u) => {
+                write!(f, "Unknown({}): {:?}", u.type_code(), u.bytes())
+            }
         }
     }
 }
@@ -10,6 +10,7 @@ fn arb_objtype() -> impl Strategy<Value = amp::ObjType> {
         Just(amp::ObjType::Table),
         Just(amp::ObjType::List),
         Just(amp::ObjType::Text),
+        Just(amp::ObjType::Set),
     ]
 }
 
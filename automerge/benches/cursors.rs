@@ -0,0 +1,141 @@
+use automerge::{Backend, Frontend, InvalidChangeRequest, LocalChange, Path, Primitive, Value};
+use automerge_protocol as amp;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+fn apply_local(frontend: &mut Frontend, backend: &mut Backend, changes: Vec<LocalChange>) {
+    let change = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            for change in changes {
+                d.add_change(change)?;
+            }
+            Ok(())
+        })
+        .unwrap()
+        .1
+        .unwrap();
+    let (patch, _) = backend.apply_local_change(change).unwrap();
+    frontend.apply_patch(patch).unwrap();
+}
+
+/// A document with `num_objects` small lists and the change which adds a cursor pointing into
+/// the last of them
+fn many_objects(num_objects: usize) -> (Backend, amp::Change) {
+    let mut frontend = Frontend::new();
+    let mut backend = Backend::new();
+    apply_local(
+        &mut frontend,
+        &mut backend,
+        (0..num_objects)
+            .map(|i| {
+                LocalChange::set(
+                    Path::root().key(format!("list{}", i)),
+                    Value::List(vec![Value::Primitive(Primitive::Uint(i as u64))]),
+                )
+            })
+            .collect(),
+    );
+    let change = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            let cursor = d
+                .cursor_to_path(
+                    &Path::root()
+                        .key(format!("list{}", num_objects - 1))
+                        .index(0),
+                )
+                .unwrap();
+            d.add_change(LocalChange::set(Path::root().key("cursor"), cursor))
+        })
+        .unwrap()
+        .1
+        .unwrap();
+    (backend, change)
+}
+
+/// A document with a text object and `num_cursors` cursors into it, and the change which inserts
+/// a character at the start of the text, moving every cursor
+fn many_cursors(num_cursors: usize) -> (Backend, amp::Change) {
+    let mut frontend = Frontend::new();
+    let mut backend = Backend::new();
+    let text = Path::root().key("text");
+    apply_local(
+        &mut frontend,
+        &mut backend,
+        vec![LocalChange::set(
+            text.clone(),
+            Value::Text((0..num_cursors).map(|_| "a".into()).collect()),
+        )],
+    );
+    let cursors = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(
+                Path::root().key("cursors"),
+                Value::Map(Default::default()),
+            ))?;
+            for i in 0..num_cursors {
+                let cursor = d.cursor_to_path(&text.clone().index(i as u32)).unwrap();
+                d.add_change(LocalChange::set(
+                    Path::root().key("cursors").key(i.to_string()),
+                    cursor,
+                ))?;
+            }
+            Ok(())
+        })
+        .unwrap()
+        .1
+        .unwrap();
+    let (patch, _) = backend.apply_local_change(cursors).unwrap();
+    frontend.apply_patch(patch).unwrap();
+    let change = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::insert(text.index(0), "b".into()))
+        })
+        .unwrap()
+        .1
+        .unwrap();
+    (backend, change)
+}
+
+fn create_cursor(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Create a cursor");
+    for num_objects in [100, 1000, 10_000].iter() {
+        let (backend, change) = many_objects(*num_objects);
+        group.bench_with_input(
+            BenchmarkId::new("objects", num_objects),
+            num_objects,
+            |b, _| {
+                b.iter_batched(
+                    || (backend.clone(), change.clone()),
+                    |(mut backend, change)| {
+                        black_box(backend.apply_local_change(change).unwrap().0)
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+fn move_cursors(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Insert before cursors");
+    for num_cursors in [100, 1000, 10_000].iter() {
+        let (backend, change) = many_cursors(*num_cursors);
+        group.bench_with_input(
+            BenchmarkId::new("cursors", num_cursors),
+            num_cursors,
+            |b, _| {
+                b.iter_batched(
+                    || (backend.clone(), change.clone()),
+                    |(mut backend, change)| {
+                        black_box(backend.apply_local_change(change).unwrap().0)
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, create_cursor, move_cursors);
+criterion_main!(benches);
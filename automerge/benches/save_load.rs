@@ -1,4 +1,6 @@
-use automerge::{Backend, Frontend, InvalidChangeRequest, LocalChange, Path, Primitive, Value};
+use automerge::{
+    Backend, CompressionPolicy, Frontend, InvalidChangeRequest, LocalChange, Path, Primitive, Value,
+};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
 fn small_change_backend() -> Backend {
@@ -168,6 +170,38 @@ fn medium_change_backend() -> Backend {
     backend
 }
 
+/// A backend with `num_actors` actors each concurrently making `changes_per_actor` changes, this
+/// gives the loader lots of independent changes to decode and hash
+fn large_concurrent_backend(num_actors: usize, changes_per_actor: usize) -> Backend {
+    let mut backend = Backend::new();
+    for actor in 0..num_actors {
+        let mut actor_backend = Backend::new();
+        let mut frontend = Frontend::new();
+        for i in 0..changes_per_actor {
+            let (_, change) = frontend
+                .change::<_, _, InvalidChangeRequest>(None, |doc| {
+                    doc.add_change(LocalChange::set(
+                        Path::root().key(format!("{}-{}", actor, i % 10)),
+                        Value::Primitive(Primitive::Str("some text to hash".into())),
+                    ))
+                })
+                .unwrap();
+            let (patch, _) = actor_backend.apply_local_change(change.unwrap()).unwrap();
+            frontend.apply_patch(patch).unwrap();
+        }
+        backend
+            .apply_changes(
+                actor_backend
+                    .get_changes(&[])
+                    .into_iter()
+                    .cloned()
+                    .collect(),
+            )
+            .unwrap();
+    }
+    backend
+}
+
 fn save_empty(c: &mut Criterion) {
     c.bench_function("save an empty backend", |b| {
         b.iter_batched(
@@ -234,9 +268,72 @@ fn load_medium(c: &mut Criterion) {
     });
 }
 
+fn load_large(c: &mut Criterion) {
+    // Run with `--features rayon` to compare against parallel decoding
+    let backend = large_concurrent_backend(16, 200);
+    let document = backend.save().unwrap();
+    let archive: Vec<u8> = backend
+        .get_changes(&[])
+        .into_iter()
+        .flat_map(|change| change.raw_bytes().iter().copied())
+        .collect();
+
+    let mut group = c.benchmark_group("load a large concurrent history");
+    group.sample_size(10);
+    group.bench_function("as a document", |b| {
+        b.iter_batched(
+            || document.clone(),
+            |v| black_box(Backend::load(v).unwrap()),
+            criterion::BatchSize::LargeInput,
+        )
+    });
+    group.bench_function("as individual changes", |b| {
+        b.iter_batched(
+            || archive.clone(),
+            |v| black_box(Backend::load(v).unwrap()),
+            criterion::BatchSize::LargeInput,
+        )
+    });
+    group.finish();
+}
+
+fn load_compressed(c: &mut Criterion) {
+    // Run with `--features zstd` to include zstd
+    let backend = large_concurrent_backend(16, 200);
+    #[allow(unused_mut)]
+    let mut policies = vec![
+        ("uncompressed", CompressionPolicy::none()),
+        ("deflate level 1", CompressionPolicy::deflate(1)),
+        ("deflate level 6", CompressionPolicy::deflate(6)),
+        ("deflate level 9", CompressionPolicy::deflate(9)),
+    ];
+    #[cfg(feature = "zstd")]
+    policies.extend(vec![
+        ("zstd level 1", CompressionPolicy::zstd(1)),
+        ("zstd level 3", CompressionPolicy::zstd(3)),
+        ("zstd level 19", CompressionPolicy::zstd(19)),
+    ]);
+
+    let mut group = c.benchmark_group("load a document compressed with");
+    group.sample_size(10);
+    for (name, policy) in policies {
+        let document = backend.save_with(&policy).unwrap();
+        // criterion has nowhere to put sizes so we just print them
+        println!("{} document is {} bytes", name, document.len());
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || document.clone(),
+                |v| black_box(Backend::load(v).unwrap()),
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
 criterion_group! {
     name = benches;
     config = Criterion::default();
-    targets = save_empty, save_small, save_medium, load_empty, load_small, load_medium
+    targets = save_empty, save_small, save_medium, load_empty, load_small, load_medium, load_large, load_compressed
 }
 criterion_main!(benches);
@@ -1,7 +1,10 @@
 use std::collections::HashMap;
 
 use automerge_backend::{Backend, Change, SyncMessage, SyncState};
-use automerge_frontend::{value_ref::RootRef, Frontend, MutableDocument, Path, Value};
+use automerge_frontend::{
+    value_ref::RootRef, ConflictResolver, Frontend, Gravity, MutableDocument, Path, PathPattern,
+    StickyCursor, Value,
+};
 use automerge_protocol as amp;
 use automerge_protocol::OpId;
 use thiserror::Error;
@@ -142,6 +145,36 @@ impl Automerge {
         self.frontend.get_value(path)
     }
 
+    /// Create a cursor for a position in a list or text which is not stored in the document,
+    /// see [`Frontend::sticky_cursor`].
+    pub fn sticky_cursor(&mut self, path: &Path, gravity: Gravity) -> Option<StickyCursor> {
+        self.frontend.sticky_cursor(path, gravity)
+    }
+
+    /// Track a cursor created elsewhere, see [`Frontend::track_cursor`].
+    pub fn track_cursor(&mut self, cursor: &StickyCursor) -> bool {
+        self.frontend.track_cursor(cursor)
+    }
+
+    /// Stop tracking a cursor, see [`Frontend::release_cursor`].
+    pub fn release_cursor(&mut self, cursor: &StickyCursor) {
+        self.frontend.release_cursor(cursor)
+    }
+
+    /// The current index of `cursor`, see [`Frontend::resolve_cursor`].
+    pub fn resolve_cursor(&self, cursor: &StickyCursor) -> Option<u32> {
+        self.frontend.resolve_cursor(cursor)
+    }
+
+    /// Use `resolver` to choose the value of the paths which match `pattern` when they have
+    /// conflicting values, see [`Frontend::add_resolver`].
+    pub fn add_resolver<R>(&mut self, pattern: PathPattern, resolver: R)
+    where
+        R: ConflictResolver + 'static,
+    {
+        self.frontend.add_resolver(pattern, resolver)
+    }
+
     /// Load all of the changes from a previous document into this document.
     pub fn load_changes(&mut self, changes: Vec<Change>) -> Result<(), BackendError> {
         self.backend.load_changes(changes)
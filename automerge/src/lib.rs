@@ -1,9 +1,12 @@
-pub use automerge_backend::{AutomergeError as BackendError, Backend, Change};
+pub use automerge_backend::{
+    AutomergeError as BackendError, Backend, Change, Compression, CompressionPolicy,
+};
 pub use automerge_frontend::{
-    value_ref, AutomergeFrontendError as FrontendError, Frontend, InvalidChangeRequest,
-    InvalidPatch, LocalChange, MutableDocument, Path, Primitive, Value,
+    resolver, value_ref, AutomergeFrontendError as FrontendError, ConflictResolver, Frontend,
+    Gravity, InvalidChangeRequest, InvalidPatch, LocalChange, MutableDocument, Path, PathPattern,
+    Primitive, Resolution, StickyCursor, TextElement, Value,
 };
-pub use automerge_protocol::{ChangeHash, MapType, ObjType, ScalarValue, SequenceType};
+pub use automerge_protocol::{ChangeHash, Decimal, MapType, ObjType, ScalarValue, SequenceType};
 
 mod combined;
 
@@ -244,3 +244,169 @@ fn test_delete_key_in_map() {
     let val = f.get_value(&Path::root()).unwrap();
     assert_eq!(val, new);
 }
+
+#[test]
+fn test_concurrent_add_to_set_wins_over_remove() {
+    let birds = Path::root().key("birds");
+    let magpie = || Primitive::Str("magpie".into());
+
+    let mut backend_a = Backend::new();
+    let (mut frontend_a, change) =
+        automerge::Frontend::new_with_initial_state(Value::Map(hashmap! {
+            "birds".into() => Value::Set(vec![magpie()]),
+        }))
+        .unwrap();
+    let (patch, _) = backend_a.apply_local_change(change).unwrap();
+    frontend_a.apply_patch(patch).unwrap();
+
+    let mut backend_b = Backend::new();
+    backend_b
+        .apply_changes(backend_a.get_changes(&[]).into_iter().cloned().collect())
+        .unwrap();
+    let mut frontend_b = automerge::Frontend::new();
+    frontend_b
+        .apply_patch(backend_b.get_patch().unwrap())
+        .unwrap();
+
+    // a removes the magpie while b adds it again
+    let ((), change_a) = frontend_a
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::remove_from_set(birds.clone(), magpie()))
+        })
+        .unwrap();
+    let (patch, change_a) = backend_a.apply_local_change(change_a.unwrap()).unwrap();
+    let change_a = change_a.clone();
+    frontend_a.apply_patch(patch).unwrap();
+    assert_eq!(frontend_a.get_value(&birds), Some(Value::Set(Vec::new())));
+
+    let ((), change_b) = frontend_b
+        .change::<_, _, InvalidChangeRequest>(None, |doc| {
+            doc.add_change(LocalChange::add_to_set(birds.clone(), magpie()))
+        })
+        .unwrap();
+    let (patch, change_b) = backend_b.apply_local_change(change_b.unwrap()).unwrap();
+    let change_b = change_b.clone();
+    frontend_b.apply_patch(patch).unwrap();
+
+    frontend_a
+        .apply_patch(backend_a.apply_changes(vec![change_b]).unwrap())
+        .unwrap();
+    frontend_b
+        .apply_patch(backend_b.apply_changes(vec![change_a]).unwrap())
+        .unwrap();
+    assert_eq!(
+        frontend_a.get_value(&birds),
+        Some(Value::Set(vec![magpie()]))
+    );
+    assert_eq!(frontend_a.get_value(&birds), frontend_b.get_value(&birds));
+
+    let loaded = Backend::load(backend_a.save().unwrap()).unwrap();
+    let mut frontend = automerge::Frontend::new();
+    frontend.apply_patch(loaded.get_patch().unwrap()).unwrap();
+    assert_eq!(frontend.get_value(&birds), Some(Value::Set(vec![magpie()])));
+}
+
+#[test]
+fn test_resolve_conflicts() {
+    let mut backend_a = Backend::new();
+    let mut frontend_a = automerge::Frontend::new();
+    let mut backend_b = Backend::new();
+    let mut frontend_b = automerge::Frontend::new();
+
+    let apply_local =
+        |frontend: &mut automerge::Frontend, backend: &mut Backend, change: LocalChange| {
+            let ((), change) = frontend
+                .change::<_, _, InvalidChangeRequest>(None, |doc| doc.add_change(change))
+                .unwrap();
+            let (patch, change) = backend.apply_local_change(change.unwrap()).unwrap();
+            let change = change.clone();
+            frontend.apply_patch(patch).unwrap();
+            change
+        };
+
+    let change = apply_local(
+        &mut frontend_a,
+        &mut backend_a,
+        LocalChange::set(Path::root().key("birds"), Value::List(vec!["robin".into()])),
+    );
+    frontend_b
+        .apply_patch(backend_b.apply_changes(vec![change]).unwrap())
+        .unwrap();
+
+    // concurrently set the same key and the same list element
+    let changes_a = vec![
+        apply_local(
+            &mut frontend_a,
+            &mut backend_a,
+            LocalChange::set(Path::root().key("bird"), "magpie"),
+        ),
+        apply_local(
+            &mut frontend_a,
+            &mut backend_a,
+            LocalChange::set(Path::root().key("birds").index(0), "wren"),
+        ),
+    ];
+    let changes_b = vec![
+        apply_local(
+            &mut frontend_b,
+            &mut backend_b,
+            LocalChange::set(Path::root().key("bird"), "jay"),
+        ),
+        apply_local(
+            &mut frontend_b,
+            &mut backend_b,
+            LocalChange::set(Path::root().key("birds").index(0), "sparrow"),
+        ),
+    ];
+    frontend_a
+        .apply_patch(backend_a.apply_changes(changes_b).unwrap())
+        .unwrap();
+    frontend_b
+        .apply_patch(backend_b.apply_changes(changes_a).unwrap())
+        .unwrap();
+
+    let root = frontend_a.value_ref();
+    let bird = root.get_all("bird").unwrap();
+    assert_eq!(bird.len(), 2);
+    let default_bird = root.get("bird").unwrap().value();
+    let (losing_opid, losing_bird) = bird
+        .iter()
+        .find(|(_, value)| value.value() != default_bird)
+        .map(|(opid, value)| (opid.clone(), value.value()))
+        .unwrap();
+    let birds = root.get("birds").unwrap();
+    let elem = birds.list().unwrap().get_all(0).unwrap();
+    assert_eq!(elem.len(), 2);
+    let default_elem = birds.list().unwrap().get(0).unwrap().value();
+    let (losing_elem_opid, losing_elem) = elem
+        .iter()
+        .find(|(_, value)| value.value() != default_elem)
+        .map(|(opid, value)| (opid.clone(), value.value()))
+        .unwrap();
+
+    // resolve both conflicts in favour of the values which lost
+    let changes = vec![
+        apply_local(
+            &mut frontend_a,
+            &mut backend_a,
+            LocalChange::resolve_conflict(Path::root().key("bird"), losing_opid),
+        ),
+        apply_local(
+            &mut frontend_a,
+            &mut backend_a,
+            LocalChange::resolve_conflict(Path::root().key("birds").index(0), losing_elem_opid),
+        ),
+    ];
+    frontend_b
+        .apply_patch(backend_b.apply_changes(changes).unwrap())
+        .unwrap();
+
+    for frontend in [&frontend_a, &frontend_b] {
+        let root = frontend.value_ref();
+        assert_eq!(root.get_all("bird").unwrap().len(), 1);
+        assert_eq!(root.get("bird").unwrap().value(), losing_bird);
+        let birds = root.get("birds").unwrap();
+        assert_eq!(birds.list().unwrap().get_all(0).unwrap().len(), 1);
+        assert_eq!(birds.list().unwrap().get(0).unwrap().value(), losing_elem);
+    }
+}
@@ -0,0 +1,160 @@
+use automerge::{Automerge, InvalidChangeRequest, LocalChange, Path, Primitive, Value};
+
+fn change(doc: &mut Automerge, local_change: LocalChange) {
+    doc.change::<_, _, InvalidChangeRequest>(None, |d| d.add_change(local_change))
+        .unwrap();
+}
+
+fn merge(from: &Automerge, into: &mut Automerge) {
+    let changes = from.get_changes(&[]).into_iter().cloned().collect();
+    into.apply_changes(changes).unwrap();
+}
+
+fn counter(value: i64) -> Value {
+    Value::Primitive(Primitive::Counter(value))
+}
+
+fn counters(values: &[i64]) -> Value {
+    Value::List(values.iter().copied().map(counter).collect())
+}
+
+fn list(doc: &Automerge) -> Option<Value> {
+    doc.get_value(&Path::root().key("counters"))
+}
+
+#[test]
+fn test_increment_counters_in_a_list() {
+    let mut doc = Automerge::new();
+    change(
+        &mut doc,
+        LocalChange::set(Path::root().key("counters"), Value::List(Vec::new())),
+    );
+    change(
+        &mut doc,
+        LocalChange::insert(Path::root().key("counters").index(0), counter(1)),
+    );
+    change(
+        &mut doc,
+        LocalChange::insert(Path::root().key("counters").index(1), counter(10)),
+    );
+    change(
+        &mut doc,
+        LocalChange::increment(Path::root().key("counters").index(0)),
+    );
+    change(
+        &mut doc,
+        LocalChange::increment_by(Path::root().key("counters").index(1), -3),
+    );
+    assert_eq!(list(&doc), Some(counters(&[2, 7])));
+
+    let mut other = Automerge::new();
+    merge(&doc, &mut other);
+    assert_eq!(list(&other), Some(counters(&[2, 7])));
+
+    let loaded = Automerge::load(doc.save().unwrap()).unwrap();
+    assert_eq!(list(&loaded), Some(counters(&[2, 7])));
+}
+
+#[test]
+fn test_increment_counters_inserted_together() {
+    let mut doc = Automerge::new();
+    change(
+        &mut doc,
+        LocalChange::set(Path::root().key("counters"), counters(&[0, 0])),
+    );
+    change(
+        &mut doc,
+        LocalChange::insert_many(
+            Path::root().key("counters").index(2),
+            vec![counter(1), counter(2), counter(3)],
+        ),
+    );
+    change(
+        &mut doc,
+        LocalChange::increment_by(Path::root().key("counters").index(3), 5),
+    );
+    change(
+        &mut doc,
+        LocalChange::increment(Path::root().key("counters").index(0)),
+    );
+    let expected = counters(&[1, 0, 1, 7, 3]);
+    assert_eq!(list(&doc), Some(expected.clone()));
+
+    // the remote doc receives the counters as one multi-element insert
+    let mut other = Automerge::new();
+    merge(&doc, &mut other);
+    assert_eq!(list(&other), Some(expected.clone()));
+    change(
+        &mut other,
+        LocalChange::increment(Path::root().key("counters").index(4)),
+    );
+    merge(&other, &mut doc);
+    let expected = counters(&[1, 0, 1, 7, 4]);
+    assert_eq!(list(&doc), Some(expected.clone()));
+
+    let loaded = Automerge::load(doc.save().unwrap()).unwrap();
+    assert_eq!(list(&loaded), Some(expected));
+}
+
+#[test]
+fn test_concurrent_increment_and_delete_of_a_list_counter() {
+    let mut doc_a = Automerge::new();
+    change(
+        &mut doc_a,
+        LocalChange::set(Path::root().key("counters"), counters(&[1, 2, 3])),
+    );
+    let mut doc_b = Automerge::new();
+    merge(&doc_a, &mut doc_b);
+
+    change(
+        &mut doc_a,
+        LocalChange::increment(Path::root().key("counters").index(1)),
+    );
+    change(
+        &mut doc_b,
+        LocalChange::delete(Path::root().key("counters").index(1)),
+    );
+    merge(&doc_b, &mut doc_a);
+    merge(&doc_a, &mut doc_b);
+
+    assert_eq!(list(&doc_a), Some(counters(&[1, 3])));
+    assert_eq!(list(&doc_b), Some(counters(&[1, 3])));
+
+    // the counter after the deleted one can still be incremented
+    change(
+        &mut doc_b,
+        LocalChange::increment(Path::root().key("counters").index(1)),
+    );
+    merge(&doc_b, &mut doc_a);
+    assert_eq!(list(&doc_a), Some(counters(&[1, 4])));
+
+    let loaded = Automerge::load(doc_a.save().unwrap()).unwrap();
+    assert_eq!(list(&loaded), Some(counters(&[1, 4])));
+}
+
+#[test]
+fn test_concurrent_increments_of_a_list_counter() {
+    let mut doc_a = Automerge::new();
+    change(
+        &mut doc_a,
+        LocalChange::set(Path::root().key("counters"), counters(&[0])),
+    );
+    let mut doc_b = Automerge::new();
+    merge(&doc_a, &mut doc_b);
+
+    change(
+        &mut doc_a,
+        LocalChange::increment_by(Path::root().key("counters").index(0), 2),
+    );
+    change(
+        &mut doc_b,
+        LocalChange::increment_by(Path::root().key("counters").index(0), 3),
+    );
+    merge(&doc_b, &mut doc_a);
+    merge(&doc_a, &mut doc_b);
+
+    assert_eq!(list(&doc_a), Some(counters(&[5])));
+    assert_eq!(list(&doc_b), Some(counters(&[5])));
+    let loaded = Automerge::load(doc_b.save().unwrap()).unwrap();
+    assert_eq!(list(&loaded), Some(counters(&[5])));
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+
+use automerge::{
+    resolver::{ListUnion, MaxTimestamp, MaxWins},
+    value_ref::ValueRef,
+    Automerge, ConflictResolver, InvalidChangeRequest, LocalChange, Path, PathPattern, Primitive,
+    Resolution, Value,
+};
+use automerge_protocol as amp;
+use maplit::hashmap;
+
+/// Picks the longest string
+struct LongestString;
+
+impl ConflictResolver for LongestString {
+    fn resolve(&self, _path: &Path, candidates: &[(amp::OpId, ValueRef<'_>)]) -> Resolution {
+        let (opid, _) = candidates
+            .iter()
+            .max_by_key(|(_, value)| match value.primitive() {
+                Some(Primitive::Str(s)) => s.len(),
+                _ => 0,
+            })
+            .unwrap();
+        Resolution::Pick(opid.clone())
+    }
+}
+
+fn set(doc: &mut Automerge, path: Path, value: Value) {
+    doc.change::<_, _, InvalidChangeRequest>(None, |d| d.add_change(LocalChange::set(path, value)))
+        .unwrap();
+}
+
+fn merge(from: &Automerge, into: &mut Automerge) {
+    let changes = from.get_changes(&[]).into_iter().cloned().collect();
+    into.apply_changes(changes).unwrap();
+}
+
+fn conflicted_docs() -> (Automerge, Automerge) {
+    let mut doc_a = Automerge::new();
+    set(
+        &mut doc_a,
+        Path::root().key("scores"),
+        Value::Map(hashmap! {"alice".into() => Value::Primitive(Primitive::Int(1))}),
+    );
+    let mut doc_b = Automerge::new();
+    merge(&doc_a, &mut doc_b);
+
+    set(
+        &mut doc_a,
+        Path::root().key("score"),
+        Value::Primitive(Primitive::Int(10)),
+    );
+    set(
+        &mut doc_b,
+        Path::root().key("score"),
+        Value::Primitive(Primitive::Int(3)),
+    );
+    set(
+        &mut doc_a,
+        Path::root().key("scores").key("alice"),
+        Value::Primitive(Primitive::Int(7)),
+    );
+    set(
+        &mut doc_b,
+        Path::root().key("scores").key("alice"),
+        Value::Primitive(Primitive::Int(-2)),
+    );
+    set(&mut doc_a, Path::root().key("tags"), vec!["a", "b"].into());
+    set(&mut doc_b, Path::root().key("tags"), vec!["b", "c"].into());
+    set(&mut doc_a, Path::root().key("name"), "Alice".into());
+    set(&mut doc_b, Path::root().key("name"), "Al".into());
+
+    merge(&doc_b, &mut doc_a);
+    merge(&doc_a, &mut doc_b);
+    (doc_a, doc_b)
+}
+
+#[test]
+fn test_resolvers_choose_conflicting_values() {
+    let (mut doc_a, mut doc_b) = conflicted_docs();
+    for doc in [&mut doc_a, &mut doc_b] {
+        doc.add_resolver(PathPattern::root().key("score"), MaxWins);
+        doc.add_resolver(PathPattern::root().key("scores").any_key(), MaxWins);
+        doc.add_resolver(PathPattern::root().key("tags"), ListUnion);
+        doc.add_resolver(PathPattern::root().key("name"), LongestString);
+    }
+
+    let state_a = doc_a.state().clone();
+    assert_eq!(&state_a, doc_b.state());
+    let state: HashMap<_, _> = match state_a {
+        Value::Map(m) => m,
+        _ => panic!("root should be a map"),
+    };
+    assert_eq!(state["score"], Value::Primitive(Primitive::Int(10)));
+    assert_eq!(
+        state["scores"],
+        Value::Map(hashmap! {"alice".into() => Value::Primitive(Primitive::Int(7))})
+    );
+    assert_eq!(state["name"], "Alice".into());
+    let mut tags: Vec<_> = match &state["tags"] {
+        Value::List(tags) => tags.iter().map(|t| t.to_json().to_string()).collect(),
+        _ => panic!("tags should be a list"),
+    };
+    tags.sort();
+    assert_eq!(tags, vec!["\"a\"", "\"b\"", "\"c\""]);
+
+    assert_eq!(
+        doc_a.get_value(&Path::root().key("scores").key("alice")),
+        Some(Value::Primitive(Primitive::Int(7)))
+    );
+    let root = doc_a.value_ref();
+    assert_eq!(
+        root.get("score").unwrap().primitive(),
+        Some(&Primitive::Int(10))
+    );
+    assert!(matches!(root.get("tags"), Some(ValueRef::Merged(_))));
+
+    // the conflicting values are still in the document
+    assert_eq!(
+        doc_a
+            .get_conflicts(&Path::root().key("score"))
+            .unwrap()
+            .len(),
+        2
+    );
+    assert_eq!(root.get_all("tags").unwrap().len(), 2);
+}
+
+#[test]
+fn test_max_timestamp_picks_the_largest_timestamp_value() {
+    let mut doc_a = Automerge::new();
+    let mut doc_b = Automerge::new();
+    let mut doc_c = Automerge::new();
+    let seen = Path::root().key("seen");
+    set(
+        &mut doc_a,
+        seen.clone(),
+        Value::Primitive(Primitive::Timestamp(2000)),
+    );
+    set(
+        &mut doc_b,
+        seen.clone(),
+        Value::Primitive(Primitive::Timestamp(1000)),
+    );
+    set(&mut doc_c, seen.clone(), "never".into());
+    merge(&doc_b, &mut doc_a);
+    merge(&doc_c, &mut doc_a);
+    assert_eq!(doc_a.get_conflicts(&seen).unwrap().len(), 3);
+
+    doc_a.add_resolver(PathPattern::root().key("seen"), MaxTimestamp);
+    assert_eq!(
+        doc_a.get_value(&seen),
+        Some(Value::Primitive(Primitive::Timestamp(2000)))
+    );
+}
+
+#[test]
+fn test_the_last_matching_resolver_wins() {
+    let (mut doc, _) = conflicted_docs();
+    doc.add_resolver(PathPattern::root().any_key(), LongestString);
+    doc.add_resolver(PathPattern::root().key("score"), MaxWins);
+    assert_eq!(
+        doc.get_value(&Path::root().key("score")),
+        Some(Value::Primitive(Primitive::Int(10)))
+    );
+    assert_eq!(
+        doc.get_value(&Path::root().key("name")),
+        Some("Alice".into())
+    );
+}
@@ -692,3 +692,167 @@ fn missing_object_error_null_rle_decoding() {
         panic!("failed loading backend: {:?}", e)
     }
 }
+
+fn apply_local(frontend: &mut Frontend, backend: &mut Backend, changes: Vec<LocalChange>) {
+    let change = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            for change in changes {
+                d.add_change(change)?;
+            }
+            Ok(())
+        })
+        .unwrap()
+        .1
+        .unwrap();
+    let (patch, _) = backend.apply_local_change(change).unwrap();
+    frontend.apply_patch(patch).unwrap();
+}
+
+/// Load `saved` by applying every change in it, rather than building objects as they are used
+fn load_eagerly(saved: &[u8]) -> Backend {
+    let mut backend = Backend::new();
+    backend
+        .load_changes(automerge::Change::load_document(saved).unwrap())
+        .unwrap();
+    backend
+}
+
+#[test]
+fn lazily_loaded_objects_match_eagerly_loaded_objects() {
+    let mut frontend1 = Frontend::new();
+    let mut backend1 = Backend::new();
+    apply_local(
+        &mut frontend1,
+        &mut backend1,
+        vec![
+            LocalChange::set(
+                Path::root().key("text"),
+                Value::Text(
+                    "hello world"
+                        .chars()
+                        .map(|c| c.to_string().into())
+                        .collect(),
+                ),
+            ),
+            LocalChange::set(
+                Path::root().key("counters"),
+                Value::List(vec![Value::Primitive(Primitive::Counter(1))]),
+            ),
+            LocalChange::set(
+                Path::root().key("nested"),
+                Value::Map(
+                    vec![(
+                        "inner".into(),
+                        Value::Map(
+                            vec![("a".into(), Value::Primitive(Primitive::Int(1)))]
+                                .into_iter()
+                                .collect(),
+                        ),
+                    )]
+                    .into_iter()
+                    .collect(),
+                ),
+            ),
+        ],
+    );
+
+    let mut frontend2 = Frontend::new();
+    let mut backend2 = Backend::new();
+    let changes: Vec<_> = backend1.get_changes(&[]).into_iter().cloned().collect();
+    frontend2
+        .apply_patch(backend2.apply_changes(changes).unwrap())
+        .unwrap();
+
+    apply_local(
+        &mut frontend1,
+        &mut backend1,
+        vec![
+            LocalChange::delete(Path::root().key("text").index(5)),
+            LocalChange::increment_by(Path::root().key("counters").index(0), 2),
+            LocalChange::set(
+                Path::root().key("nested").key("inner").key("a"),
+                Value::Primitive(Primitive::Int(2)),
+            ),
+        ],
+    );
+    apply_local(
+        &mut frontend2,
+        &mut backend2,
+        vec![
+            LocalChange::insert(Path::root().key("text").index(0), "¡".into()),
+            LocalChange::increment_by(Path::root().key("counters").index(0), 3),
+            LocalChange::set(
+                Path::root().key("nested"),
+                Value::Primitive(Primitive::Str("overwritten".into())),
+            ),
+        ],
+    );
+    let changes: Vec<_> = backend2
+        .get_changes(&backend1.get_heads())
+        .into_iter()
+        .cloned()
+        .collect();
+    backend1.apply_changes(changes).unwrap();
+
+    let saved = backend1.save().unwrap();
+    let mut lazy = Backend::load(saved.clone()).unwrap();
+    let mut eager = load_eagerly(&saved);
+    assert_eq!(lazy.get_heads(), eager.get_heads());
+
+    // apply a change to objects which have not been read yet
+    let change = frontend2
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::insert(
+                Path::root().key("text").index(3),
+                "!".into(),
+            ))?;
+            d.add_change(LocalChange::increment(
+                Path::root().key("counters").index(0),
+            ))
+        })
+        .unwrap()
+        .1
+        .unwrap();
+    let change = automerge::Change::from(change);
+    assert_eq!(
+        lazy.apply_changes(vec![change.clone()]).unwrap(),
+        eager.apply_changes(vec![change]).unwrap()
+    );
+    assert_eq!(lazy.get_patch().unwrap(), eager.get_patch().unwrap());
+    assert_eq!(lazy.save().unwrap(), eager.save().unwrap());
+}
+
+#[test]
+fn save_and_load_uuids_and_decimals() {
+    let id = uuid::Uuid::new_v4();
+    let price: amp::Decimal = "19.990".parse().unwrap();
+    let mut doc = automerge::Automerge::new();
+    doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.add_change(LocalChange::set(
+            Path::root().key("id"),
+            Value::Primitive(Primitive::Uuid(id)),
+        ))?;
+        d.add_change(LocalChange::set(
+            Path::root().key("prices"),
+            Value::List(vec![
+                Value::Primitive(Primitive::Decimal(price)),
+                Value::Primitive(Primitive::Decimal(amp::Decimal::new(-5, 1).unwrap())),
+            ]),
+        ))
+    })
+    .unwrap();
+    let expected = doc.state().clone();
+
+    let mut other = automerge::Automerge::new();
+    other
+        .apply_changes(doc.get_changes(&[]).into_iter().cloned().collect())
+        .unwrap();
+    assert_eq!(other.state(), &expected);
+
+    let mut loaded = automerge::Automerge::load(doc.save().unwrap()).unwrap();
+    assert_eq!(loaded.state(), &expected);
+    assert_eq!(
+        loaded.get_value(&Path::root().key("prices").index(0)),
+        Some(Value::Primitive(Primitive::Decimal(price)))
+    );
+}
@@ -0,0 +1,301 @@
+use automerge::{
+    value_ref::TextUnit, Automerge, Backend, Frontend, Gravity, InvalidChangeRequest, LocalChange,
+    Path, Value,
+};
+
+fn change(doc: &mut Automerge, local_change: LocalChange) {
+    doc.change::<_, _, InvalidChangeRequest>(None, |d| d.add_change(local_change))
+        .unwrap();
+}
+
+fn merge(from: &Automerge, into: &mut Automerge) {
+    let changes = from.get_changes(&[]).into_iter().cloned().collect();
+    into.apply_changes(changes).unwrap();
+}
+
+fn text_path() -> Path {
+    Path::root().key("text")
+}
+
+fn splice(doc: &mut Automerge, start: usize, delete: usize, insert: &str) {
+    change(
+        doc,
+        LocalChange::splice_text(text_path(), start, delete, insert, TextUnit::Grapheme),
+    );
+}
+
+fn doc_with_text(text: &str) -> Automerge {
+    let mut doc = Automerge::new();
+    change(
+        &mut doc,
+        LocalChange::set(text_path(), Value::Text(Vec::new())),
+    );
+    splice(&mut doc, 0, 0, text);
+    doc
+}
+
+fn text(doc: &Automerge) -> String {
+    match doc.get_value(&text_path()) {
+        Some(Value::Text(elements)) => elements
+            .iter()
+            .filter_map(|e| e.grapheme().map(|g| g.to_string()))
+            .collect(),
+        other => panic!("expected text but got {:?}", other),
+    }
+}
+
+#[test]
+fn test_sticky_cursors_follow_inserts_and_deletes_around_them() {
+    let mut doc = doc_with_text("abcd");
+    let left = doc
+        .sticky_cursor(&text_path().index(2), Gravity::Left)
+        .unwrap();
+    let right = doc
+        .sticky_cursor(&text_path().index(2), Gravity::Right)
+        .unwrap();
+    assert_eq!(doc.resolve_cursor(&left), Some(2));
+    assert_eq!(doc.resolve_cursor(&right), Some(2));
+
+    splice(&mut doc, 0, 0, "xy");
+    assert_eq!(doc.resolve_cursor(&left), Some(4));
+    assert_eq!(doc.resolve_cursor(&right), Some(4));
+
+    change(&mut doc, LocalChange::delete(text_path().index(0)));
+    assert_eq!(doc.resolve_cursor(&left), Some(3));
+    assert_eq!(doc.resolve_cursor(&right), Some(3));
+
+    // text inserted at the cursors' position goes after a left cursor and before a right one
+    splice(&mut doc, 3, 0, "12");
+    assert_eq!(text(&doc), "yab12cd");
+    assert_eq!(doc.resolve_cursor(&left), Some(3));
+    assert_eq!(doc.resolve_cursor(&right), Some(5));
+}
+
+#[test]
+fn test_sticky_cursors_move_to_the_nearest_surviving_neighbour() {
+    let mut doc = doc_with_text("abcdef");
+    // between "c" and "d"
+    let left = doc
+        .sticky_cursor(&text_path().index(3), Gravity::Left)
+        .unwrap();
+    let right = doc
+        .sticky_cursor(&text_path().index(3), Gravity::Right)
+        .unwrap();
+
+    // delete "c" and "d", which are the elements the cursors are attached to
+    splice(&mut doc, 2, 2, "");
+    assert_eq!(text(&doc), "abef");
+    assert_eq!(doc.resolve_cursor(&left), Some(2));
+    assert_eq!(doc.resolve_cursor(&right), Some(2));
+
+    // the neighbours they moved to are deleted as well
+    change(&mut doc, LocalChange::delete(text_path().index(1)));
+    change(&mut doc, LocalChange::delete(text_path().index(1)));
+    assert_eq!(text(&doc), "af");
+    assert_eq!(doc.resolve_cursor(&left), Some(1));
+    assert_eq!(doc.resolve_cursor(&right), Some(1));
+
+    splice(&mut doc, 1, 0, "XY");
+    assert_eq!(text(&doc), "aXYf");
+    assert_eq!(doc.resolve_cursor(&left), Some(1));
+    assert_eq!(doc.resolve_cursor(&right), Some(3));
+}
+
+#[test]
+fn test_sticky_cursors_at_the_start_and_end() {
+    let mut doc = doc_with_text("abc");
+    let start = doc
+        .sticky_cursor(&text_path().index(0), Gravity::Left)
+        .unwrap();
+    let end = doc
+        .sticky_cursor(&text_path().index(3), Gravity::Right)
+        .unwrap();
+    let after_last = doc
+        .sticky_cursor(&text_path().index(3), Gravity::Left)
+        .unwrap();
+    assert!(doc
+        .sticky_cursor(&text_path().index(4), Gravity::Left)
+        .is_none());
+
+    splice(&mut doc, 0, 0, "<");
+    splice(&mut doc, 4, 0, ">");
+    assert_eq!(text(&doc), "<abc>");
+    assert_eq!(doc.resolve_cursor(&start), Some(0));
+    assert_eq!(doc.resolve_cursor(&end), Some(5));
+    assert_eq!(doc.resolve_cursor(&after_last), Some(4));
+
+    // deleting everything leaves every cursor at the start
+    splice(&mut doc, 0, 5, "");
+    assert_eq!(doc.resolve_cursor(&start), Some(0));
+    assert_eq!(doc.resolve_cursor(&end), Some(0));
+    assert_eq!(doc.resolve_cursor(&after_last), Some(0));
+}
+
+#[test]
+fn test_sticky_cursors_follow_remote_changes() {
+    let mut doc_a = doc_with_text("hello");
+    let mut doc_b = Automerge::new();
+    merge(&doc_a, &mut doc_b);
+
+    // a cursor created on one peer can be tracked and resolved on another
+    let cursor = doc_a
+        .sticky_cursor(&text_path().index(3), Gravity::Right)
+        .unwrap();
+    assert_eq!(doc_b.resolve_cursor(&cursor), Some(3));
+    assert!(doc_b.track_cursor(&cursor));
+
+    change(&mut doc_b, LocalChange::delete(text_path().index(3)));
+    change(
+        &mut doc_b,
+        LocalChange::insert(text_path().index(0), "¡".into()),
+    );
+    merge(&doc_b, &mut doc_a);
+    assert_eq!(text(&doc_a), "¡helo");
+    assert_eq!(doc_a.resolve_cursor(&cursor), Some(4));
+    assert_eq!(doc_b.resolve_cursor(&cursor), Some(4));
+}
+
+#[test]
+fn test_sticky_cursors_in_nested_lists() {
+    let mut doc = Automerge::new();
+    let list = Path::root().key("items").index(0).key("tags");
+    change(
+        &mut doc,
+        LocalChange::set(
+            Path::root().key("items"),
+            Value::List(vec![Value::from_json(
+                &serde_json::json!({"tags": ["a", "b", "c"]}),
+            )]),
+        ),
+    );
+    let cursor = doc
+        .sticky_cursor(&list.clone().index(2), Gravity::Left)
+        .unwrap();
+    assert_eq!(doc.resolve_cursor(&cursor), Some(2));
+    change(&mut doc, LocalChange::delete(list.clone().index(0)));
+    assert_eq!(doc.resolve_cursor(&cursor), Some(1));
+
+    // moving the list holding the object doesn't lose the cursor
+    change(
+        &mut doc,
+        LocalChange::insert(Path::root().key("items").index(0), "first".into()),
+    );
+    assert_eq!(doc.resolve_cursor(&cursor), Some(1));
+    let moved = Path::root().key("items").index(1).key("tags");
+    change(&mut doc, LocalChange::delete(moved.index(1)));
+    assert_eq!(doc.resolve_cursor(&cursor), Some(1));
+
+    // a cursor in an object which is no longer in the document doesn't resolve
+    change(&mut doc, LocalChange::delete(Path::root().key("items")));
+    assert_eq!(doc.resolve_cursor(&cursor), None);
+    assert!(doc.sticky_cursor(&list.index(0), Gravity::Left).is_none());
+}
+
+#[test]
+fn test_released_cursors_are_no_longer_tracked() {
+    let mut doc = doc_with_text("abc");
+    let cursor = doc
+        .sticky_cursor(&text_path().index(2), Gravity::Left)
+        .unwrap();
+    let other = doc
+        .sticky_cursor(&text_path().index(2), Gravity::Left)
+        .unwrap();
+    assert_eq!(cursor, other);
+
+    // the cursor is tracked until every copy of it is released
+    doc.release_cursor(&other);
+    change(&mut doc, LocalChange::delete(text_path().index(1)));
+    assert_eq!(doc.resolve_cursor(&cursor), Some(1));
+
+    doc.release_cursor(&cursor);
+    assert_eq!(doc.resolve_cursor(&cursor), None);
+    // a cursor attached to an element which has already been deleted can't be tracked
+    assert!(!doc.track_cursor(&cursor));
+}
+
+#[test]
+fn test_sticky_cursors_survive_cancelled_changes() {
+    let mut doc = Automerge::new();
+    let items = Path::root().key("items");
+    let tags = items.clone().index(1).key("tags");
+    change(
+        &mut doc,
+        LocalChange::set(
+            items.clone(),
+            Value::from_json(&serde_json::json!(["a", {"tags": ["x", "y"]}, "c"])),
+        ),
+    );
+    let cursor = doc
+        .sticky_cursor(&items.clone().index(1), Gravity::Right)
+        .unwrap();
+    let nested = doc
+        .sticky_cursor(&tags.clone().index(1), Gravity::Left)
+        .unwrap();
+
+    // delete the element the cursors are attached to or inside of and then give up
+    let result = doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.add_change(LocalChange::delete(items.clone().index(1)))?;
+        d.add_change(LocalChange::delete(items.clone().index(5)))
+    });
+    assert!(result.is_err());
+    assert_eq!(doc.resolve_cursor(&cursor), Some(1));
+    assert_eq!(doc.resolve_cursor(&nested), Some(1));
+
+    // the cursors are still attached
+    change(&mut doc, LocalChange::delete(items.clone().index(0)));
+    change(
+        &mut doc,
+        LocalChange::delete(items.index(0).key("tags").index(0)),
+    );
+    assert_eq!(doc.resolve_cursor(&cursor), Some(0));
+    assert_eq!(doc.resolve_cursor(&nested), Some(0));
+}
+
+#[test]
+fn test_sticky_cursors_survive_remote_patches_during_local_changes() {
+    let mut frontend = Frontend::new();
+    let mut backend = Backend::new();
+    let ((), request) = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::set(
+                text_path(),
+                Value::Text(vec!["a".into(), "b".into(), "c".into()]),
+            ))
+        })
+        .unwrap();
+    let (patch, _) = backend.apply_local_change(request.unwrap()).unwrap();
+    frontend.apply_patch(patch).unwrap();
+
+    let mut remote = Automerge::new();
+    remote
+        .apply_changes(backend.get_changes(&[]).into_iter().cloned().collect())
+        .unwrap();
+    change(
+        &mut remote,
+        LocalChange::insert(text_path().index(2), "y".into()),
+    );
+
+    // attached to "b"
+    let cursor = frontend
+        .sticky_cursor(&text_path().index(1), Gravity::Right)
+        .unwrap();
+    let ((), request) = frontend
+        .change::<_, _, InvalidChangeRequest>(None, |d| {
+            d.add_change(LocalChange::delete(text_path().index(1)))
+        })
+        .unwrap();
+
+    // the remote change arrives while the local one is in flight, so the local change is rolled
+    // back once the backend has applied it and then applied again in the right order
+    let remote_changes = remote.get_changes(&[]).into_iter().cloned().collect();
+    let patch = backend.apply_changes(remote_changes).unwrap();
+    frontend.apply_patch(patch).unwrap();
+    let (patch, _) = backend.apply_local_change(request.unwrap()).unwrap();
+    frontend.apply_patch(patch).unwrap();
+
+    assert_eq!(
+        frontend.get_value(&text_path()),
+        Some(Value::Text(vec!["a".into(), "y".into(), "c".into()]))
+    );
+    assert_eq!(frontend.resolve_cursor(&cursor), Some(1));
+}
@@ -0,0 +1,221 @@
+use automerge::{
+    value_ref::{TextElementRef, TextUnit},
+    Automerge, InvalidChangeRequest, LocalChange, Path, Primitive, TextElement, Value,
+};
+use maplit::hashmap;
+use unicode_segmentation::UnicodeSegmentation;
+
+fn change(doc: &mut Automerge, local_change: LocalChange) {
+    doc.change::<_, _, InvalidChangeRequest>(None, |d| d.add_change(local_change))
+        .unwrap();
+}
+
+fn merge(from: &Automerge, into: &mut Automerge) {
+    let changes = from.get_changes(&[]).into_iter().cloned().collect();
+    into.apply_changes(changes).unwrap();
+}
+
+fn text_path() -> Path {
+    Path::root().key("text")
+}
+
+fn paragraph(kind: &str) -> Value {
+    Value::Map(hashmap! {"type".into() => kind.into()})
+}
+
+fn text(doc: &Automerge) -> Option<Value> {
+    doc.get_value(&text_path())
+}
+
+/// "hi", a paragraph marker, "yo" and a number
+fn doc_with_embeds() -> Automerge {
+    let mut doc = Automerge::new();
+    change(
+        &mut doc,
+        LocalChange::set(text_path(), Value::Text(vec!["h".into(), "i".into()])),
+    );
+    change(
+        &mut doc,
+        LocalChange::insert_many(
+            text_path().index(2),
+            vec![
+                paragraph("heading"),
+                "y".into(),
+                "o".into(),
+                Value::Primitive(Primitive::Int(7)),
+            ],
+        ),
+    );
+    doc
+}
+
+fn expected_elements(kind: &str) -> Value {
+    Value::Text(vec![
+        "h".into(),
+        "i".into(),
+        TextElement::Embedded(paragraph(kind)),
+        "y".into(),
+        "o".into(),
+        TextElement::Embedded(Value::Primitive(Primitive::Int(7))),
+    ])
+}
+
+#[test]
+fn test_insert_embedded_values_into_text() {
+    let mut doc = doc_with_embeds();
+    assert_eq!(text(&doc), Some(expected_elements("heading")));
+    // embedded values are an object replacement character in the JSON string of the text
+    assert_eq!(
+        doc.state().to_json()["text"],
+        serde_json::Value::String("hi\u{fffc}yo\u{fffc}".into())
+    );
+    assert_eq!(
+        doc.get_value(&text_path().index(2).key("type")),
+        Some("heading".into())
+    );
+    assert_eq!(doc.get_value(&text_path().index(3)), Some("y".into()));
+
+    let root = doc.value_ref();
+    let text_ref = root.get("text").unwrap();
+    let text_ref = text_ref.text().unwrap();
+    assert_eq!(text_ref.len(), 6);
+    let elements: Vec<_> = text_ref.iter().collect();
+    assert_eq!(elements[1].grapheme().map(|g| g.as_str()), Some("i"));
+    match &elements[2] {
+        TextElementRef::Embedded(v) => assert_eq!(v.value(), paragraph("heading")),
+        other => panic!("expected an embedded map but got {:?}", other),
+    }
+    assert_eq!(
+        text_ref
+            .get(5)
+            .unwrap()
+            .embedded()
+            .and_then(|v| v.primitive().cloned()),
+        Some(Primitive::Int(7))
+    );
+}
+
+#[test]
+fn test_embedded_values_are_synced_and_saved() {
+    let mut doc = doc_with_embeds();
+    let mut other = Automerge::new();
+    merge(&doc, &mut other);
+    assert_eq!(text(&other), Some(expected_elements("heading")));
+
+    // update the embedded map on the other side
+    change(
+        &mut other,
+        LocalChange::set(text_path().index(2).key("type"), "quote"),
+    );
+    merge(&other, &mut doc);
+    assert_eq!(text(&doc), Some(expected_elements("quote")));
+
+    let loaded = Automerge::load(doc.save().unwrap()).unwrap();
+    assert_eq!(text(&loaded), Some(expected_elements("quote")));
+}
+
+#[test]
+fn test_create_text_with_embedded_values() {
+    let mut doc = Automerge::new();
+    change(
+        &mut doc,
+        LocalChange::set(text_path(), expected_elements("heading")),
+    );
+    assert_eq!(text(&doc), Some(expected_elements("heading")));
+    let loaded = Automerge::load(doc.save().unwrap()).unwrap();
+    assert_eq!(text(&loaded), Some(expected_elements("heading")));
+}
+
+#[test]
+fn test_replace_embedded_values_and_graphemes() {
+    let mut doc = doc_with_embeds();
+    change(
+        &mut doc,
+        LocalChange::set(text_path().index(0), Primitive::Boolean(true)),
+    );
+    change(&mut doc, LocalChange::set(text_path().index(2), "!"));
+    let expected = Value::Text(vec![
+        TextElement::Embedded(Value::Primitive(Primitive::Boolean(true))),
+        "i".into(),
+        "!".into(),
+        "y".into(),
+        "o".into(),
+        TextElement::Embedded(Value::Primitive(Primitive::Int(7))),
+    ]);
+    assert_eq!(text(&doc), Some(expected.clone()));
+
+    let mut other = Automerge::new();
+    merge(&doc, &mut other);
+    assert_eq!(text(&other), Some(expected));
+}
+
+#[test]
+fn test_embedded_values_count_as_one_character_in_offsets() {
+    let mut doc = doc_with_embeds();
+    let root = doc.value_ref();
+    let text_ref = root.get("text").unwrap();
+    let text_ref = text_ref.text().unwrap();
+    assert_eq!(text_ref.len_in(TextUnit::Utf16), 6);
+    assert_eq!(text_ref.len_in(TextUnit::Utf8), 10);
+    assert_eq!(
+        text_ref.convert_offset(3, TextUnit::Grapheme, TextUnit::Utf8),
+        Some(5)
+    );
+
+    // splicing around an embedded value leaves it in place
+    change(
+        &mut doc,
+        LocalChange::splice_text(text_path(), 3, 2, "hey", TextUnit::Utf16),
+    );
+    let mut expected: Vec<TextElement> = vec!["h".into(), "i".into()];
+    expected.push(TextElement::Embedded(paragraph("heading")));
+    expected.extend("hey".graphemes(true).map(TextElement::from));
+    expected.push(TextElement::Embedded(Value::Primitive(Primitive::Int(7))));
+    assert_eq!(text(&doc), Some(Value::Text(expected)));
+}
+
+#[test]
+fn test_strings_in_text_must_be_a_single_grapheme() {
+    let mut doc = doc_with_embeds();
+    let result = doc.change::<_, _, InvalidChangeRequest>(None, |d| {
+        d.add_change(LocalChange::insert(text_path().index(0), "ab".into()))
+    });
+    assert_eq!(
+        result.unwrap_err(),
+        InvalidChangeRequest::InsertNonTextInTextObject {
+            path: text_path().index(0),
+            object: "ab".into(),
+        }
+    );
+}
+
+#[test]
+fn test_concurrent_grapheme_and_embedded_value_at_one_index() {
+    let mut doc_a = doc_with_embeds();
+    let mut doc_b = Automerge::new();
+    merge(&doc_a, &mut doc_b);
+
+    change(&mut doc_a, LocalChange::set(text_path().index(0), "H"));
+    change(
+        &mut doc_b,
+        LocalChange::set(text_path().index(0), paragraph("title")),
+    );
+    merge(&doc_b, &mut doc_a);
+    merge(&doc_a, &mut doc_b);
+
+    assert_eq!(text(&doc_a), text(&doc_b));
+    let conflicts = doc_a.get_conflicts(&text_path().index(0)).unwrap();
+    let mut values: Vec<_> = conflicts.into_values().collect();
+    values.sort_by_key(|v| v.is_map());
+    assert_eq!(values, vec!["H".into(), paragraph("title")]);
+    assert_eq!(
+        doc_a
+            .value_ref()
+            .get("text")
+            .unwrap()
+            .text()
+            .unwrap()
+            .len_in(TextUnit::Grapheme),
+        6
+    );
+}